@@ -1,10 +1,21 @@
 //! Implements level.dat file loading.
 
-use libcraft_core::Biome;
+use libcraft_core::{Biome, GameRules};
 use libcraft_items::Item;
 use serde::{Deserialize, Serialize};
 use std::io::{Cursor, Read, Write};
-use std::{collections::HashMap, fs::File};
+use std::path::Path;
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+};
+
+/// The current level data.
+const LEVEL_FILE: &str = "level.dat";
+/// The level.dat being written.
+const NEW_LEVEL_FILE: &str = "level.dat_new";
+/// The level.dat before the last save.
+const OLD_LEVEL_FILE: &str = "level.dat_old";
 
 /// Root level tag
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -74,7 +85,47 @@ pub struct LevelData {
     #[serde(rename = "generatorName")]
     pub generator_name: String,
     #[serde(rename = "generatorOptions")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub generator_options: Option<SuperflatGeneratorOptions>,
+
+    #[serde(default)]
+    #[serde(rename = "GameRules")]
+    #[serde(with = "game_rules_as_strings")]
+    pub game_rules: GameRules,
+}
+
+/// level.dat stores game rules as a compound of strings,
+/// e.g. `{keepInventory: "false", randomTickSpeed: "3"}`.
+mod game_rules_as_strings {
+    use std::collections::HashMap;
+
+    use libcraft_core::GameRules;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S>(rules: &GameRules, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let map: HashMap<&str, String> = GameRules::NAMES
+            .iter()
+            .filter_map(|&name| rules.get(name).map(|value| (name, value)))
+            .collect();
+        map.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<GameRules, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let map = HashMap::<String, String>::deserialize(deserializer)?;
+        let mut rules = GameRules::default();
+        for (name, value) in map {
+            // Rules from other versions are dropped, and
+            // invalid values fall back to the default.
+            let _ = rules.set(&name, &value);
+        }
+        Ok(rules)
+    }
 }
 
 impl LevelData {
@@ -96,6 +147,67 @@ impl LevelData {
     }
 }
 
+/// Loads the level.dat file in the given world directory,
+/// or returns `None` if the world has no level.dat.
+///
+/// Falls back to level.dat_old, the previous save, if level.dat
+/// is missing or unreadable, e.g. after a crash while saving.
+pub fn load_level_data(world_dir: &Path) -> anyhow::Result<Option<LevelData>> {
+    let error = match load_level_file(&world_dir.join(LEVEL_FILE)) {
+        Ok(Some(level)) => return Ok(Some(level)),
+        Ok(None) => None,
+        Err(e) => Some(e),
+    };
+
+    match load_level_file(&world_dir.join(OLD_LEVEL_FILE)) {
+        Ok(Some(level)) => {
+            match &error {
+                Some(e) => log::warn!("Failed to load level.dat, using level.dat_old: {:?}", e),
+                None => log::warn!("level.dat is missing, using level.dat_old"),
+            }
+            Ok(Some(level))
+        }
+        Ok(None) | Err(_) => match error {
+            Some(e) => Err(e),
+            None => Ok(None),
+        },
+    }
+}
+
+fn load_level_file(path: &Path) -> anyhow::Result<Option<LevelData>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let mut file = File::open(path)?;
+    LevelData::load_from_file(&mut file).map(Some)
+}
+
+/// Writes the level.dat file in the given world directory.
+///
+/// Like vanilla, the data is first written to level.dat_new, then
+/// the current level.dat is kept as level.dat_old and replaced, so
+/// a crash while saving never leaves the world without a level.dat.
+pub fn save_level_data(world_dir: &Path, data: &LevelData) -> anyhow::Result<()> {
+    fs::create_dir_all(world_dir)?;
+    let path = world_dir.join(LEVEL_FILE);
+    let new_path = world_dir.join(NEW_LEVEL_FILE);
+    let old_path = world_dir.join(OLD_LEVEL_FILE);
+
+    let mut file = File::create(&new_path)?;
+    data.save_to_file(&mut file)?;
+    file.sync_all()?;
+    drop(file);
+
+    if path.exists() {
+        if old_path.exists() {
+            fs::remove_file(&old_path)?;
+        }
+        fs::rename(&path, &old_path)?;
+    }
+    fs::rename(&new_path, &path)?;
+    Ok(())
+}
+
 /// Represents level version data.
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct LevelVersion {
@@ -204,4 +316,50 @@ mod tests {
         assert_eq!(level.generator_name, "default");
         assert!(level.generator_options.is_none());
     }
+
+    #[test]
+    fn game_rules_roundtrip() {
+        let mut level = LevelData::default();
+        level.game_rules.keep_inventory = true;
+        level.game_rules.random_tick_speed = 10;
+
+        let mut buf = Vec::new();
+        nbt::to_gzip_writer(&mut buf, &Root { data: level }, None).unwrap();
+        let level = nbt::from_gzip_reader::<_, Root>(Cursor::new(buf))
+            .unwrap()
+            .data;
+
+        assert!(level.game_rules.keep_inventory);
+        assert_eq!(level.game_rules.random_tick_speed, 10);
+    }
+
+    fn temp_dir() -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("feather-level-{}", rand::random::<u64>()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn save_keeps_previous_level() {
+        let dir = temp_dir();
+        assert!(load_level_data(&dir).unwrap().is_none());
+
+        let mut level = LevelData::default();
+        level.day_time = 1;
+        save_level_data(&dir, &level).unwrap();
+        level.day_time = 2;
+        save_level_data(&dir, &level).unwrap();
+
+        assert!(!dir.join(NEW_LEVEL_FILE).exists());
+        assert_eq!(load_level_data(&dir).unwrap().unwrap().day_time, 2);
+
+        // A corrupted level.dat
+        fs::write(dir.join(LEVEL_FILE), b"").unwrap();
+        assert_eq!(load_level_data(&dir).unwrap().unwrap().day_time, 1);
+        // or a save interrupted between the two renames
+        fs::remove_file(dir.join(LEVEL_FILE)).unwrap();
+        assert_eq!(load_level_data(&dir).unwrap().unwrap().day_time, 1);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
 }
@@ -34,6 +34,13 @@ pub struct PlayerData {
     pub inventory: Vec<InventorySlot>,
     #[serde(rename = "SelectedItemSlot")]
     pub held_item: i32,
+    /// The position of the player's bed, if they have one.
+    #[serde(rename = "SpawnX", default, skip_serializing_if = "Option::is_none")]
+    pub spawn_x: Option<i32>,
+    #[serde(rename = "SpawnY", default, skip_serializing_if = "Option::is_none")]
+    pub spawn_y: Option<i32>,
+    #[serde(rename = "SpawnZ", default, skip_serializing_if = "Option::is_none")]
+    pub spawn_z: Option<i32>,
     pub abilities: PlayerAbilities,
 }
 
@@ -13,12 +13,14 @@ pub type ChunkHandle = Arc<ChunkLock>;
 #[derive(Debug)]
 pub struct ChunkLock {
     loaded: AtomicBool,
+    dirty: AtomicBool,
     lock: RwLock<Chunk>,
 }
 impl ChunkLock {
     pub fn new(chunk: Chunk, loaded: bool) -> Self {
         Self {
             loaded: AtomicBool::new(loaded),
+            dirty: AtomicBool::new(false),
             lock: RwLock::new(chunk),
         }
     }
@@ -42,6 +44,20 @@ impl ChunkLock {
         self.loaded.swap(true, Ordering::SeqCst)
     }
 
+    /// Returns whether the chunk may have changed since it was last saved.
+    /// Locking the chunk for writing marks it as dirty.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.load(Ordering::SeqCst)
+    }
+    /// Marks the chunk as needing to be saved.
+    pub fn mark_dirty(&self) {
+        self.dirty.store(true, Ordering::SeqCst);
+    }
+    /// Marks the chunk as saved and returns whether it was dirty.
+    pub fn take_dirty(&self) -> bool {
+        self.dirty.swap(false, Ordering::SeqCst)
+    }
+
     /// Locks this chunk with read acccess. Doesn't block.
     /// Returns None if the chunk is unloaded or locked for writing, Some otherwise.
     pub fn try_read(&self) -> Option<RwLockReadGuard<Chunk>> {
@@ -57,7 +73,11 @@ impl ChunkLock {
     /// Returns None if the chunk is unloaded or locked already, Some otherwise.
     pub fn try_write(&self) -> Option<RwLockWriteGuard<Chunk>> {
         if self.is_loaded() {
-            self.lock.try_write()
+            let guard = self.lock.try_write();
+            if guard.is_some() {
+                self.mark_dirty();
+            }
+            guard
         } else {
             None
         }
@@ -66,7 +86,9 @@ impl ChunkLock {
     /// Returns None if the chunk is unloaded, Some otherwise.
     pub fn write(&self) -> Option<RwLockWriteGuard<Chunk>> {
         if self.is_loaded() {
-            Some(self.lock.write())
+            let guard = self.lock.write();
+            self.mark_dirty();
+            Some(guard)
         } else {
             None
         }
@@ -113,6 +135,16 @@ mod tests {
         assert!(lock.try_read().is_some())
     }
     #[test]
+    fn writing_marks_dirty() {
+        let lock = empty_lock(0, 0, true);
+        assert!(!lock.is_dirty());
+        let _ = lock.try_read().unwrap();
+        assert!(!lock.is_dirty());
+        drop(lock.write().unwrap());
+        assert!(lock.take_dirty());
+        assert!(!lock.is_dirty());
+    }
+    #[test]
     fn multithreaded() {
         let lock = Arc::new(empty_lock(0, 0, true));
         let mut handles: Vec<JoinHandle<()>> = vec![];
@@ -24,6 +24,8 @@ pub const META_INDEX_POSE: u8 = 6;
 
 pub const META_INDEX_FALLING_BLOCK_SPAWN_POSITION: u8 = 7;
 
+pub const META_INDEX_ITEM: u8 = 7;
+
 bitflags! {
     pub struct EntityBitMask: u8 {
         const ON_FIRE = 0x01;
@@ -0,0 +1,134 @@
+//! Periodically saves the world while the server is running.
+//!
+//! Dirty chunks are saved incrementally, a few per tick,
+//! to avoid lag spikes on large worlds.
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use base::{anvil::level::LevelData, ChunkPosition, GameRules};
+use ecs::{SysResult, SystemExecutor};
+use quill_common::entities::Player;
+
+use crate::{entities::player::save_player, Game};
+
+/// Configures the autosave. Insert this resource
+/// before calling [`register`] to override the defaults.
+#[derive(Debug, Clone)]
+pub struct AutosaveOptions {
+    /// Time between the start of two autosaves,
+    /// or `None` to disable autosaving.
+    pub interval: Option<Duration>,
+    /// Maximum number of chunks to queue for saving each tick.
+    pub chunks_per_tick: usize,
+}
+
+impl Default for AutosaveOptions {
+    fn default() -> Self {
+        Self {
+            interval: Some(Duration::from_secs(5 * 60)),
+            chunks_per_tick: 32,
+        }
+    }
+}
+
+pub fn register(game: &mut Game, systems: &mut SystemExecutor<Game>) {
+    if game.resources.get::<AutosaveOptions>().is_err() {
+        game.insert_resource(AutosaveOptions::default());
+    }
+    game.insert_resource(AutosaveState::new());
+    systems.group::<AutosaveState>().add_system(autosave);
+}
+
+struct AutosaveState {
+    last_autosave: Instant,
+    /// Dirty chunks remaining to be saved in the
+    /// current autosave, if one is in progress.
+    queue: Option<VecDeque<ChunkPosition>>,
+    saved_chunks: usize,
+}
+
+impl AutosaveState {
+    pub fn new() -> Self {
+        Self {
+            last_autosave: Instant::now(),
+            queue: None,
+            saved_chunks: 0,
+        }
+    }
+}
+
+fn autosave(game: &mut Game, state: &mut AutosaveState) -> SysResult {
+    let options = game.resources.get::<AutosaveOptions>()?.clone();
+
+    if state.queue.is_none() {
+        match options.interval {
+            Some(interval) if state.last_autosave.elapsed() >= interval => (),
+            _ => return Ok(()),
+        }
+
+        let saved_players = save_players(game);
+        save_level(game)?;
+        let dirty_chunks = game.world.dirty_chunks();
+        log::info!(
+            "Autosave started: saved {} players, saving {} chunks",
+            saved_players,
+            dirty_chunks.len()
+        );
+
+        state.last_autosave = Instant::now();
+        state.saved_chunks = 0;
+        state.queue = Some(dirty_chunks.into());
+    }
+
+    let queue = match &mut state.queue {
+        Some(queue) => queue,
+        None => return Ok(()),
+    };
+    let count = options.chunks_per_tick.min(queue.len());
+    for pos in queue.drain(..count) {
+        // Chunks may have been unloaded (and saved)
+        // since the autosave started.
+        if game.world.save_chunk(pos) {
+            state.saved_chunks += 1;
+        }
+    }
+
+    if queue.is_empty() {
+        state.queue = None;
+        log::info!(
+            "Autosave finished: saved {} chunks in {:.1?}",
+            state.saved_chunks,
+            state.last_autosave.elapsed()
+        );
+    }
+
+    Ok(())
+}
+
+/// Saves the data of all online players,
+/// returning the number of players saved.
+pub fn save_players(game: &Game) -> usize {
+    let mut saved = 0;
+    for (player, _) in game.ecs.query::<&Player>().iter() {
+        match save_player(game, player) {
+            Ok(()) => saved += 1,
+            Err(e) => log::error!("Failed to save player data: {:?}", e),
+        }
+    }
+    saved
+}
+
+/// Writes the `LevelData` resource, if present, to level.dat.
+pub fn save_level(game: &Game) -> SysResult {
+    if let Ok(mut level) = game.resources.get_mut::<LevelData>() {
+        level.last_played = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as i64;
+        if let Ok(rules) = game.resources.get::<GameRules>() {
+            level.game_rules = rules.clone();
+        }
+        game.world.save_level_data(&level)?;
+    }
+    Ok(())
+}
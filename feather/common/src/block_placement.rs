@@ -0,0 +1,525 @@
+//! Places blocks when players use block items,
+//! following vanilla's orientation and replacement rules.
+
+use std::convert::TryFrom;
+
+use base::{
+    inventory::{SLOT_HOTBAR_OFFSET, SLOT_OFFHAND},
+    BlockId, BlockPosition, EntityKind, Gamemode, Position, ValidBlockPosition,
+};
+use blocks::{
+    categories::{PlacementType, SupportType},
+    Face, FacingCardinal, FacingCardinalAndDown, FacingCubic, HalfTopBottom, HalfUpperLower, Hinge,
+    Part, SlabKind,
+};
+use ecs::{Entity, SysResult, SystemExecutor};
+use libcraft_core::{BlockFace, Hand, Vec3f};
+use libcraft_items::Item;
+use quill_common::{components::CanBuild, events::BlockPlacementEvent};
+
+use crate::{
+    entities::player::HotbarSlot,
+    events::{BlockChangeEvent, InventoryUpdateEvent},
+    Game, Window,
+};
+
+/// Maximum squared distance between a player and
+/// the block they place against.
+const MAX_REACH_SQUARED: f64 = 8.0 * 8.0;
+
+pub fn register(systems: &mut SystemExecutor<Game>) {
+    systems.add_system(place_blocks);
+}
+
+fn place_blocks(game: &mut Game) -> SysResult {
+    let events: Vec<(Entity, BlockPlacementEvent)> = game
+        .ecs
+        .query::<&BlockPlacementEvent>()
+        .iter()
+        .map(|(player, event)| (player, event.clone()))
+        .collect();
+
+    for (player, event) in events {
+        let slot = match event.hand {
+            Hand::Main => SLOT_HOTBAR_OFFSET + game.ecs.get::<HotbarSlot>(player)?.get(),
+            Hand::Offhand => SLOT_OFFHAND,
+        };
+
+        match place_block(game, player, &event, slot)? {
+            Placement::Placed { consumed: false } => {}
+            Placement::Placed { consumed: true } => {
+                game.ecs
+                    .insert_entity_event(player, InventoryUpdateEvent(vec![slot]))?;
+            }
+            Placement::Rejected => {
+                // The client already shows the block and
+                // the consumed item, so correct it.
+                resend_blocks(game, &event);
+                game.ecs
+                    .insert_entity_event(player, InventoryUpdateEvent(vec![slot]))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+enum Placement {
+    Placed { consumed: bool },
+    Rejected,
+}
+
+fn place_block(
+    game: &mut Game,
+    player: Entity,
+    event: &BlockPlacementEvent,
+    slot: usize,
+) -> anyhow::Result<Placement> {
+    let (position, gamemode, item) = {
+        let player = game.ecs.entity(player)?;
+        if !player.get::<CanBuild>()?.0 {
+            return Ok(Placement::Rejected);
+        }
+        let window = player.get::<Window>()?;
+        let item = window.item(slot)?.item_kind();
+        (*player.get::<Position>()?, *player.get::<Gamemode>()?, item)
+    };
+
+    let block = match item.and_then(block_for_item) {
+        Some(block) => block,
+        None => return Ok(Placement::Rejected),
+    };
+    if position.distance_squared_to(event.location.position()) > MAX_REACH_SQUARED {
+        return Ok(Placement::Rejected);
+    }
+
+    let context = PlacementContext {
+        face: face_direction(&event.face),
+        cursor: event.cursor_position,
+        yaw: position.yaw,
+        pitch: position.pitch,
+    };
+    let blocks = match plan_placement(game, block, event.location, &context) {
+        Some(blocks) => blocks,
+        None => return Ok(Placement::Rejected),
+    };
+    if blocks
+        .iter()
+        .any(|&(pos, block)| block.is_solid() && collides_with_entity(game, pos))
+    {
+        return Ok(Placement::Rejected);
+    }
+
+    for (pos, block) in blocks {
+        game.set_block(pos, block);
+    }
+
+    let consumed = gamemode != Gamemode::Creative;
+    if consumed {
+        game.ecs.get::<Window>(player)?.item(slot)?.try_take(1);
+    }
+    Ok(Placement::Placed { consumed })
+}
+
+/// Sends the actual blocks at the clicked and adjacent
+/// positions to clients.
+fn resend_blocks(game: &mut Game, event: &BlockPlacementEvent) {
+    let adjacent = offset(event.location, face_direction(&event.face));
+    for pos in [event.location, adjacent].iter() {
+        if let Ok(pos) = ValidBlockPosition::try_from(*pos) {
+            game.ecs.insert_event(BlockChangeEvent::single(pos));
+        }
+    }
+}
+
+/// Returns the block placed by an item, if any.
+fn block_for_item(item: Item) -> Option<BlockId> {
+    // Items whose block has a different name.
+    let block = match item {
+        Item::Redstone => BlockId::redstone_wire(),
+        Item::String => BlockId::tripwire(),
+        Item::WheatSeeds => BlockId::wheat(),
+        Item::BeetrootSeeds => BlockId::beetroots(),
+        Item::Carrot => BlockId::carrots(),
+        Item::Potato => BlockId::potatoes(),
+        Item::MelonSeeds => BlockId::melon_stem(),
+        Item::PumpkinSeeds => BlockId::pumpkin_stem(),
+        Item::CocoaBeans => BlockId::cocoa(),
+        Item::SweetBerries => BlockId::sweet_berry_bush(),
+        Item::Air => return None,
+        _ => BlockId::from_identifier(&format!("minecraft:{}", item.name()))?,
+    };
+    Some(block)
+}
+
+/// The parts of a placement which determine a block's orientation.
+struct PlacementContext {
+    /// Direction from the clicked block to the new block.
+    face: FacingCubic,
+    /// Position on the clicked face, relative to the clicked block.
+    cursor: Vec3f,
+    yaw: f32,
+    pitch: f32,
+}
+
+impl PlacementContext {
+    /// Whether the block goes into the upper half of its
+    /// position, e.g. for upside-down stairs and top slabs.
+    fn upper_half(&self) -> bool {
+        match self.face {
+            FacingCubic::Down => true,
+            FacingCubic::Up => false,
+            _ => self.cursor.y > 0.5,
+        }
+    }
+}
+
+/// Determines which blocks to set for placing `block`
+/// against the block at `clicked`. Returns `None` if
+/// the block can't be placed.
+fn plan_placement(
+    game: &Game,
+    block: BlockId,
+    clicked: BlockPosition,
+    context: &PlacementContext,
+) -> Option<Vec<(ValidBlockPosition, BlockId)>> {
+    let clicked = ValidBlockPosition::try_from(clicked).ok()?;
+    let clicked_block = game.block(clicked)?;
+
+    // Clicking the top of a bottom slab or the
+    // bottom of a top slab turns it into a double slab.
+    let merges_into_clicked = matches!(
+        (clicked_block.slab_kind(), context.face),
+        (Some(SlabKind::Bottom), FacingCubic::Up) | (Some(SlabKind::Top), FacingCubic::Down)
+    );
+    if merges_into_clicked && clicked_block.kind() == block.kind() {
+        return Some(vec![(
+            clicked,
+            clicked_block.with_slab_kind(SlabKind::Double),
+        )]);
+    }
+
+    // Replaceable blocks like grass are replaced
+    // instead of placing the block next to them.
+    let (target, existing) = if clicked_block.is_replaceable() {
+        (clicked, clicked_block)
+    } else {
+        let target = ValidBlockPosition::try_from(offset(clicked.into(), context.face)).ok()?;
+        (target, game.block(target)?)
+    };
+
+    if existing.kind() == block.kind() {
+        let merged = match (existing.slab_kind(), context.upper_half()) {
+            (Some(SlabKind::Bottom), true) | (Some(SlabKind::Top), false) => {
+                Some(existing.with_slab_kind(SlabKind::Double))
+            }
+            _ => None,
+        };
+        if let Some(merged) = merged {
+            return Some(vec![(target, merged)]);
+        }
+    }
+    if !existing.is_replaceable() {
+        return None;
+    }
+
+    let block = orient(block, context)?;
+    let mut blocks = vec![(target, block)];
+
+    // Doors and tall plants take up two blocks,
+    // and beds extend in the direction they face.
+    let second = if block.half_upper_lower().is_some() {
+        blocks[0].1 = block.with_half_upper_lower(HalfUpperLower::Lower);
+        Some((
+            BlockPosition::from(target).up(),
+            block.with_half_upper_lower(HalfUpperLower::Upper),
+        ))
+    } else if block.part().is_some() {
+        let facing = block.facing_cardinal()?.to_facing_cubic();
+        blocks[0].1 = block.with_part(Part::Foot);
+        Some((offset(target.into(), facing), block.with_part(Part::Head)))
+    } else {
+        None
+    };
+    if let Some((pos, block)) = second {
+        let pos = ValidBlockPosition::try_from(pos).ok()?;
+        if !game.block(pos)?.is_replaceable() {
+            return None;
+        }
+        blocks.push((pos, block));
+    }
+
+    for (pos, block) in &mut blocks {
+        if let Some(existing) = game.block(*pos) {
+            if existing.kind() == blocks::BlockKind::Water && existing.water_level() == Some(0) {
+                block.set_waterlogged(true);
+            }
+        }
+    }
+
+    Some(blocks)
+}
+
+/// Orients a block based on the clicked face, the
+/// cursor position and the direction the player is looking.
+/// Returns `None` if the block can't be placed on the clicked face.
+fn orient(mut block: BlockId, context: &PlacementContext) -> Option<BlockId> {
+    let player_facing = horizontal_facing(context.yaw);
+    let face_cardinal = context.face.to_facing_cardinal();
+
+    // Torches, signs, banners and heads attach to walls
+    // when placed against the side of a block.
+    if let Some(wall_block) = block.to_wall_block() {
+        match face_cardinal {
+            Some(facing) => return Some(wall_block.with_facing_cardinal(facing)),
+            None if context.face == FacingCubic::Down => return None,
+            None => {}
+        }
+    }
+    if block.support_type() == Some(SupportType::FacingSolid) {
+        return Some(block.with_facing_cardinal(face_cardinal?));
+    }
+
+    if block.rotation().is_some() {
+        block.set_rotation(rotation(context.yaw));
+    }
+    if block.slab_kind().is_some() {
+        block.set_slab_kind(if context.upper_half() {
+            SlabKind::Top
+        } else {
+            SlabKind::Bottom
+        });
+    }
+    if let Some(axis) = block.axis_xyz().map(|_| context.face.axis()) {
+        block.set_axis_xyz(axis);
+    }
+
+    if block.face().is_some() {
+        // Buttons and levers
+        let (face, facing) = match face_cardinal {
+            Some(facing) => (Face::Wall, facing),
+            None if context.face == FacingCubic::Up => (Face::Floor, player_facing),
+            None => (Face::Ceiling, player_facing),
+        };
+        block.set_face(face);
+        block.set_facing_cardinal(facing);
+    } else if block.stairs_shape().is_some() {
+        block.set_facing_cardinal(player_facing);
+        block.set_half_top_bottom(half(context.upper_half()));
+    } else if block.half_top_bottom().is_some() && block.facing_cardinal().is_some() {
+        // Trapdoors
+        match face_cardinal {
+            Some(facing) => {
+                block.set_facing_cardinal(facing);
+                block.set_half_top_bottom(half(context.upper_half()));
+            }
+            None => {
+                block.set_facing_cardinal(player_facing.opposite());
+                block.set_half_top_bottom(half(context.face == FacingCubic::Down));
+            }
+        }
+    } else if block.facing_cardinal().is_some() {
+        let facing = match block.placement_type() {
+            Some(PlacementType::PlayerDirection) => player_facing,
+            Some(PlacementType::PlayerDirectionRightAngle) => player_facing.right(),
+            Some(PlacementType::TargetedFace) => face_cardinal.unwrap_or(player_facing),
+            // Furnaces, chests, pumpkins etc. face the player.
+            None => player_facing.opposite(),
+        };
+        block.set_facing_cardinal(facing);
+    }
+
+    if block.facing_cubic().is_some() {
+        let looking = looking_direction(context.yaw, context.pitch);
+        let facing = match block.placement_type() {
+            Some(PlacementType::TargetedFace) => context.face,
+            Some(PlacementType::PlayerDirection) => looking,
+            // Pistons, dispensers etc. face the player.
+            _ => looking.opposite(),
+        };
+        block.set_facing_cubic(facing);
+    }
+
+    if block.facing_cardinal_and_down().is_some() {
+        // Hoppers point into the clicked block.
+        let facing = face_cardinal
+            .map(|facing| facing.opposite().to_facing_cardinal_and_down())
+            .unwrap_or(FacingCardinalAndDown::Down);
+        block.set_facing_cardinal_and_down(facing);
+    }
+
+    if block.hinge().is_some() {
+        block.set_hinge(door_hinge(player_facing, context.cursor));
+    }
+
+    Some(block)
+}
+
+/// The horizontal direction a player with the given yaw is facing.
+fn horizontal_facing(yaw: f32) -> FacingCardinal {
+    match ((yaw / 90.0 + 0.5).floor() as i32).rem_euclid(4) {
+        0 => FacingCardinal::South,
+        1 => FacingCardinal::West,
+        2 => FacingCardinal::North,
+        _ => FacingCardinal::East,
+    }
+}
+
+/// The direction a player is looking, including up and down.
+fn looking_direction(yaw: f32, pitch: f32) -> FacingCubic {
+    if pitch > 45.0 {
+        FacingCubic::Down
+    } else if pitch < -45.0 {
+        FacingCubic::Up
+    } else {
+        horizontal_facing(yaw).to_facing_cubic()
+    }
+}
+
+/// The rotation of standing signs, banners and heads,
+/// in sixteenths of a full turn.
+fn rotation(yaw: f32) -> i32 {
+    ((yaw + 180.0) * 16.0 / 360.0 + 0.5).floor() as i32 & 15
+}
+
+fn half(upper: bool) -> HalfTopBottom {
+    if upper {
+        HalfTopBottom::Top
+    } else {
+        HalfTopBottom::Bottom
+    }
+}
+
+/// Chooses the side of a door's hinge from where
+/// on the block the player clicked.
+fn door_hinge(facing: FacingCardinal, cursor: Vec3f) -> Hinge {
+    let offset = facing.offset();
+    let (x, z) = (cursor.x, cursor.z);
+    let left = (offset.x >= 0 || z >= 0.5)
+        && (offset.x <= 0 || z <= 0.5)
+        && (offset.z >= 0 || x <= 0.5)
+        && (offset.z <= 0 || x >= 0.5);
+    if left {
+        Hinge::Left
+    } else {
+        Hinge::Right
+    }
+}
+
+fn face_direction(face: &BlockFace) -> FacingCubic {
+    match face {
+        BlockFace::Bottom => FacingCubic::Down,
+        BlockFace::Top => FacingCubic::Up,
+        BlockFace::North => FacingCubic::North,
+        BlockFace::South => FacingCubic::South,
+        BlockFace::West => FacingCubic::West,
+        BlockFace::East => FacingCubic::East,
+    }
+}
+
+fn offset(pos: BlockPosition, direction: FacingCubic) -> BlockPosition {
+    let offset = direction.offset();
+    BlockPosition::new(pos.x + offset.x, pos.y + offset.y, pos.z + offset.z)
+}
+
+/// Whether an entity which blocks building is inside the block at `pos`.
+fn collides_with_entity(game: &Game, pos: ValidBlockPosition) -> bool {
+    let (x, y, z) = (pos.x() as f64, pos.y() as f64, pos.z() as f64);
+    game.ecs
+        .query::<(&Position, &EntityKind)>()
+        .iter()
+        .filter(|(_, (_, kind))| !matches!(**kind, EntityKind::Item | EntityKind::ExperienceOrb))
+        .filter(|&(entity, _)| {
+            game.ecs
+                .get::<Gamemode>(entity)
+                .map_or(true, |gamemode| *gamemode != Gamemode::Spectator)
+        })
+        .any(|(_, (position, kind))| {
+            let size = kind.bounding_box().max;
+            let half_width = size.x / 2.0;
+            position.x - half_width < x + 1.0
+                && position.x + half_width > x
+                && position.y < y + 1.0
+                && position.y + size.y > y
+                && position.z - half_width < z + 1.0
+                && position.z + half_width > z
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use blocks::{AxisXyz, BlockKind};
+
+    use super::*;
+
+    fn context(face: FacingCubic, cursor_y: f32, yaw: f32) -> PlacementContext {
+        PlacementContext {
+            face,
+            cursor: Vec3f::new(0.5, cursor_y, 0.5),
+            yaw,
+            pitch: 0.0,
+        }
+    }
+
+    #[test]
+    fn facing_from_yaw() {
+        assert_eq!(horizontal_facing(0.0), FacingCardinal::South);
+        assert_eq!(horizontal_facing(90.0), FacingCardinal::West);
+        assert_eq!(horizontal_facing(-90.0), FacingCardinal::East);
+        assert_eq!(horizontal_facing(179.0), FacingCardinal::North);
+        assert_eq!(horizontal_facing(-530.0), FacingCardinal::North);
+    }
+
+    #[test]
+    fn stairs() {
+        let block = orient(BlockId::oak_stairs(), &context(FacingCubic::Up, 1.0, 90.0)).unwrap();
+        assert_eq!(block.facing_cardinal(), Some(FacingCardinal::West));
+        assert_eq!(block.half_top_bottom(), Some(HalfTopBottom::Bottom));
+
+        let block = orient(
+            BlockId::oak_stairs(),
+            &context(FacingCubic::North, 0.8, 0.0),
+        )
+        .unwrap();
+        assert_eq!(block.half_top_bottom(), Some(HalfTopBottom::Top));
+    }
+
+    #[test]
+    fn logs_and_slabs() {
+        let block = orient(BlockId::oak_log(), &context(FacingCubic::East, 0.5, 0.0)).unwrap();
+        assert_eq!(block.axis_xyz(), Some(AxisXyz::X));
+
+        let block = orient(BlockId::oak_slab(), &context(FacingCubic::Down, 0.0, 0.0)).unwrap();
+        assert_eq!(block.slab_kind(), Some(SlabKind::Top));
+    }
+
+    #[test]
+    fn wall_torches() {
+        let block = orient(BlockId::torch(), &context(FacingCubic::East, 0.5, 0.0)).unwrap();
+        assert_eq!(block.kind(), BlockKind::WallTorch);
+        assert_eq!(block.facing_cardinal(), Some(FacingCardinal::East));
+
+        let block = orient(BlockId::torch(), &context(FacingCubic::Up, 1.0, 0.0)).unwrap();
+        assert_eq!(block.kind(), BlockKind::Torch);
+
+        assert!(orient(BlockId::torch(), &context(FacingCubic::Down, 0.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn furnaces_face_the_player() {
+        let block = orient(BlockId::furnace(), &context(FacingCubic::Up, 1.0, 0.0)).unwrap();
+        assert_eq!(block.facing_cardinal(), Some(FacingCardinal::North));
+    }
+
+    #[test]
+    fn items_map_to_blocks() {
+        assert_eq!(
+            block_for_item(Item::Stone).map(BlockId::kind),
+            Some(BlockKind::Stone)
+        );
+        assert_eq!(
+            block_for_item(Item::WheatSeeds).map(BlockId::kind),
+            Some(BlockKind::Wheat)
+        );
+        assert_eq!(block_for_item(Item::Diamond), None);
+    }
+}
@@ -27,6 +27,20 @@ impl ChunkEntities {
             .unwrap_or_default()
     }
 
+    /// Returns the entities in the chunk containing `position`
+    /// and the eight chunks around it.
+    pub fn entities_near(&self, position: Position) -> Vec<Entity> {
+        let center = position.chunk();
+        let mut entities = Vec::new();
+        for x in -1..=1 {
+            for z in -1..=1 {
+                let chunk = ChunkPosition::new(center.x + x, center.z + z);
+                entities.extend_from_slice(self.entities_in_chunk(chunk));
+            }
+        }
+        entities
+    }
+
     fn update(
         &mut self,
         entity: Entity,
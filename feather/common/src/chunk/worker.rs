@@ -1,4 +1,4 @@
-use std::{path::PathBuf, sync::Arc};
+use std::{path::PathBuf, sync::Arc, thread::JoinHandle};
 
 use anyhow::bail;
 use base::{
@@ -18,6 +18,9 @@ pub struct LoadRequest {
 pub struct LoadedChunk {
     pub pos: ChunkPosition,
     pub chunk: Chunk,
+    /// Whether the chunk was newly generated
+    /// rather than loaded from the world save.
+    pub generated: bool,
 }
 
 #[derive(Debug)]
@@ -44,6 +47,9 @@ pub struct SaveRequest {
 pub enum WorkerRequest {
     Load(LoadRequest),
     Save(SaveRequest),
+    /// Stops the worker once all previous requests
+    /// have been handled.
+    Shutdown,
 }
 pub struct ChunkWorker {
     generator: Arc<dyn WorldGenerator>,
@@ -51,6 +57,7 @@ pub struct ChunkWorker {
     send_gen: Sender<LoadedChunk>,
     recv_gen: Receiver<LoadedChunk>, // Chunk generation should be infallible.
     recv_load: Receiver<ChunkLoadResult>,
+    region_worker: Option<JoinHandle<()>>,
 }
 
 impl ChunkWorker {
@@ -58,13 +65,14 @@ impl ChunkWorker {
         let (send_req, recv_req) = flume::unbounded();
         let (send_gen, recv_gen) = flume::unbounded();
         let (region_worker, recv_load) = RegionWorker::new(world_dir.into(), recv_req);
-        region_worker.start();
+        let region_worker = Some(region_worker.start());
         Self {
             generator,
             send_req,
             send_gen,
             recv_gen,
             recv_load,
+            region_worker,
         }
     }
     pub fn queue_load(&mut self, request: LoadRequest) {
@@ -94,7 +102,13 @@ impl ChunkWorker {
                         rayon::spawn(move || {
                             // spawn task to generate chunk
                             let chunk = gen.generate_chunk(pos);
-                            send_gen.send(LoadedChunk { pos, chunk }).unwrap()
+                            send_gen
+                                .send(LoadedChunk {
+                                    pos,
+                                    chunk,
+                                    generated: true,
+                                })
+                                .unwrap()
                         });
                         self.try_recv_gen() // check for generated chunks
                     }
@@ -109,7 +123,24 @@ impl ChunkWorker {
         }
     }
 
+    /// Returns the number of requests waiting to be handled.
+    pub fn queue_depth(&self) -> usize {
+        self.send_req.len()
+    }
+
     pub fn queue_chunk_save(&mut self, req: SaveRequest) {
         self.send_req.send(WorkerRequest::Save(req)).unwrap()
     }
+
+    /// Stops the region worker after it has handled all
+    /// queued requests, blocking until its thread exits.
+    pub fn shutdown(&mut self) -> anyhow::Result<()> {
+        if let Some(region_worker) = self.region_worker.take() {
+            self.send_req.send(WorkerRequest::Shutdown)?;
+            if region_worker.join().is_err() {
+                bail!("RegionWorker panicked");
+            }
+        }
+        Ok(())
+    }
 }
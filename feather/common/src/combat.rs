@@ -0,0 +1,568 @@
+//! Melee attacks of players against other entities.
+//!
+//! The damage of an attack depends on the held item, its
+//! enchantments and how far the attack cooldown recovered.
+//! Fully charged attacks land critical hits while falling
+//! and sweep across nearby entities when made with a sword.
+
+use base::{
+    inventory::{SLOT_ARMOR_MAX, SLOT_ARMOR_MIN, SLOT_HOTBAR_OFFSET},
+    EntityKind, Gamemode, Particle, ParticleKind, Position, Vec3d,
+};
+use ecs::{Entity, SysResult, SystemExecutor};
+use libcraft_core::InteractionType;
+use libcraft_items::{EnchantmentKind, InventorySlot, Item};
+use quill_common::{
+    components::{Health, OnGround, Sprinting},
+    events::InteractEntityEvent,
+};
+
+use crate::{
+    damage::{self, DamageSource, Dead, FallDistance},
+    digging::{enchantment_level, held_item},
+    entities::player::HotbarSlot,
+    events::{KnockbackEvent, MeleeHitEvent},
+    physics::{overlaps, Velocity},
+    Game, Window,
+};
+
+/// Players can't hit entities further away than this.
+const MAX_REACH: f64 = 6.0;
+
+/// Attacks per second with an empty hand or a non-weapon item.
+const DEFAULT_ATTACK_SPEED: f32 = 4.0;
+
+/// Knockback of every hit, away from the attacker.
+const BASE_KNOCKBACK: f64 = 0.4;
+
+/// Knockback of entities caught by a sweep attack.
+const SWEEP_KNOCKBACK: f64 = 0.4;
+
+/// Entities within this distance of the attacker are hit by sweep attacks.
+const SWEEP_RANGE: f64 = 3.0;
+
+/// Tracks the attack cooldown of a player: the ticks since
+/// they last attacked or switched to a different item.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct AttackCooldown {
+    pub ticks: u32,
+    held: Option<Item>,
+}
+
+impl AttackCooldown {
+    /// How far the cooldown recovered, from 0 to 1, for a
+    /// weapon attacking `attack_speed` times per second.
+    pub fn strength(&self, attack_speed: f32) -> f32 {
+        let period = 20.0 / attack_speed;
+        ((self.ticks as f32 + 0.5) / period).min(1.0)
+    }
+}
+
+pub fn register(systems: &mut SystemExecutor<Game>) {
+    systems
+        .add_system(tick_attack_cooldowns)
+        .add_system(handle_attacks);
+}
+
+/// Recovers attack cooldowns. Switching to a different
+/// item resets the cooldown, like in vanilla.
+fn tick_attack_cooldowns(game: &mut Game) -> SysResult {
+    for (_, (cooldown, hotbar_slot, window)) in game
+        .ecs
+        .query::<(&mut AttackCooldown, &HotbarSlot, &Window)>()
+        .iter()
+    {
+        let held = window
+            .item(SLOT_HOTBAR_OFFSET + hotbar_slot.get())?
+            .item_kind();
+        if held != cooldown.held {
+            cooldown.held = held;
+            cooldown.ticks = 0;
+        } else {
+            cooldown.ticks = cooldown.ticks.saturating_add(1);
+        }
+    }
+    Ok(())
+}
+
+fn handle_attacks(game: &mut Game) -> SysResult {
+    let attacks: Vec<(Entity, Entity)> = game
+        .ecs
+        .query::<&InteractEntityEvent>()
+        .iter()
+        .filter(|(_, event)| matches!(event.ty, InteractionType::Attack))
+        .map(|(player, event)| (player, Entity::from_bits(event.target.0)))
+        .collect();
+
+    for (player, target) in attacks {
+        if can_attack(game, player, target)? {
+            attack(game, player, target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether a player is able to hit `target`. Targets out of
+/// reach were most likely hit before they moved away.
+fn can_attack(game: &Game, player: Entity, target: Entity) -> anyhow::Result<bool> {
+    if player == target
+        || game.ecs.get::<Dead>(player).is_ok()
+        || *game.ecs.get::<Gamemode>(player)? == Gamemode::Spectator
+    {
+        return Ok(false);
+    }
+    let target = match game.ecs.get::<Position>(target) {
+        Ok(position) => *position,
+        Err(_) => return Ok(false),
+    };
+    let position = *game.ecs.get::<Position>(player)?;
+    Ok(position.distance_squared_to(target) < MAX_REACH * MAX_REACH)
+}
+
+/// Makes a player hit an entity with their held item.
+pub fn attack(game: &mut Game, player: Entity, target: Entity) -> SysResult {
+    let item = held_item(game, player)?;
+    let strength = {
+        let mut cooldown = game.ecs.get_mut::<AttackCooldown>(player)?;
+        let strength = cooldown.strength(attack_speed(item.item_kind()));
+        cooldown.ticks = 0;
+        strength
+    };
+    let charged = strength > 0.9;
+
+    let (position, on_ground, sprinting, falling) = {
+        let attacker = game.ecs.entity(player)?;
+        (
+            *attacker.get::<Position>()?,
+            attacker.get::<OnGround>()?.0,
+            attacker.get::<Sprinting>()?.0,
+            attacker.get::<FallDistance>()?.distance > 0.0,
+        )
+    };
+    let kind = *game.ecs.get::<EntityKind>(target)?;
+
+    let mut amount = attack_damage(item.item_kind()) * (0.2 + strength * strength * 0.8);
+    let bonus = enchantment_damage(&item, kind) * strength;
+
+    let mut knockback = enchantment_level(&item, EnchantmentKind::Knockback);
+    let sprint_hit = sprinting && charged;
+    if sprint_hit {
+        knockback += 1;
+    }
+    let critical = charged && falling && !on_ground && !sprinting;
+    if critical {
+        amount *= 1.5;
+    }
+    amount += bonus;
+    let sweep = charged && !critical && !sprint_hit && on_ground && is_sword(item.item_kind());
+
+    if !hit(game, player, target, amount)? {
+        return Ok(());
+    }
+
+    let (facing_x, facing_z) = facing(position);
+    if knockback > 0 {
+        knock_back(game, target, f64::from(knockback) * 0.5, facing_x, facing_z)?;
+    }
+    if sweep {
+        sweep_attack(game, player, target, amount, &item)?;
+    }
+
+    if critical || bonus > 0.0 {
+        game.ecs.insert_entity_event(
+            target,
+            MeleeHitEvent {
+                critical,
+                enchanted: bonus > 0.0,
+            },
+        )?;
+    }
+    Ok(())
+}
+
+/// Damages an entity hit by a player and knocks it away.
+/// Returns whether the entity took damage.
+fn hit(game: &mut Game, player: Entity, target: Entity, amount: f32) -> anyhow::Result<bool> {
+    let source = DamageSource::Attack { attacker: player };
+    if !damage::damage(game, target, source, amount)? {
+        return Ok(false);
+    }
+
+    let attacker = *game.ecs.get::<Position>(player)?;
+    let position = *game.ecs.get::<Position>(target)?;
+    knock_back(
+        game,
+        target,
+        BASE_KNOCKBACK,
+        position.x - attacker.x,
+        position.z - attacker.z,
+    )?;
+    Ok(true)
+}
+
+/// Hits the entities around the target of a sword attack.
+fn sweep_attack(
+    game: &mut Game,
+    player: Entity,
+    target: Entity,
+    amount: f32,
+    sword: &InventorySlot,
+) -> SysResult {
+    let level = enchantment_level(sword, EnchantmentKind::SweepingEdge) as f32;
+    let sweep_damage = 1.0 + amount * level / (level + 1.0);
+
+    let attacker = *game.ecs.get::<Position>(player)?;
+    let target_box = (
+        *game.ecs.get::<Position>(target)?,
+        *game.ecs.get::<EntityKind>(target)?,
+    );
+    let victims: Vec<Entity> = game
+        .chunk_entities
+        .entities_near(target_box.0)
+        .into_iter()
+        .filter(|&entity| entity != player && entity != target)
+        .filter(|&entity| {
+            let entity = match game.ecs.entity(entity) {
+                Ok(entity) => entity,
+                Err(_) => return false,
+            };
+            match (
+                entity.get::<Health>(),
+                entity.get::<Position>(),
+                entity.get::<EntityKind>(),
+            ) {
+                (Ok(_), Ok(position), Ok(kind)) => {
+                    attacker.distance_squared_to(*position) < SWEEP_RANGE * SWEEP_RANGE
+                        && overlaps(target_box, (*position, *kind), Vec3d::new(1.0, 0.25, 1.0))
+                }
+                _ => false,
+            }
+        })
+        .collect();
+
+    let (facing_x, facing_z) = facing(attacker);
+    for victim in victims {
+        knock_back(game, victim, SWEEP_KNOCKBACK, facing_x, facing_z)?;
+        hit(game, player, victim, sweep_damage)?;
+    }
+
+    let height = EntityKind::Player.bounding_box().max.y;
+    let mut builder = game.create_empty_entity_builder();
+    builder
+        .add(Position {
+            x: attacker.x + facing_x,
+            y: attacker.y + height * 0.5,
+            z: attacker.z + facing_z,
+            ..attacker
+        })
+        .add(Particle {
+            kind: ParticleKind::SweepAttack,
+            offset_x: 0.0,
+            offset_y: 0.0,
+            offset_z: 0.0,
+            count: 0,
+        });
+    game.spawn_entity(builder);
+    Ok(())
+}
+
+/// Returns the horizontal direction an entity looks in.
+fn facing(position: Position) -> (f64, f64) {
+    let yaw = f64::from(position.yaw).to_radians();
+    (-yaw.sin(), yaw.cos())
+}
+
+/// Pushes an entity in the direction of `(x, z)`,
+/// reduced by its knockback resistance.
+pub fn knock_back(game: &mut Game, entity: Entity, strength: f64, x: f64, z: f64) -> SysResult {
+    let strength = strength * (1.0 - knockback_resistance(game, entity)?);
+    if strength <= 0.0 {
+        return Ok(());
+    }
+
+    // Players move themselves, so an earlier knockback
+    // this tick is the only velocity known for them.
+    let velocity = match game.ecs.get::<Velocity>(entity) {
+        Ok(velocity) => velocity.0,
+        Err(_) => game
+            .ecs
+            .get::<KnockbackEvent>(entity)
+            .map(|event| event.0)
+            .unwrap_or_default(),
+    };
+    let on_ground = game.ecs.get::<OnGround>(entity)?.0;
+    let velocity = knockback_velocity(velocity, on_ground, strength, x, z);
+
+    if let Ok(mut current) = game.ecs.get_mut::<Velocity>(entity) {
+        current.0 = velocity;
+    }
+    game.ecs
+        .insert_entity_event(entity, KnockbackEvent(velocity))?;
+    Ok(())
+}
+
+/// Vanilla's knockback: the entity keeps half of its
+/// horizontal velocity and jumps up if on the ground.
+fn knockback_velocity(velocity: Vec3d, on_ground: bool, strength: f64, x: f64, z: f64) -> Vec3d {
+    let length = (x * x + z * z).sqrt();
+    let (push_x, push_z) = if length < 1.0e-4 {
+        (0.0, 0.0)
+    } else {
+        (x / length * strength, z / length * strength)
+    };
+    Vec3d::new(
+        velocity.x / 2.0 + push_x,
+        if on_ground {
+            (velocity.y / 2.0 + strength).min(0.4)
+        } else {
+            velocity.y
+        },
+        velocity.z / 2.0 + push_z,
+    )
+}
+
+/// Each piece of netherite armor resists 10% of knockback.
+fn knockback_resistance(game: &Game, entity: Entity) -> anyhow::Result<f64> {
+    let window = match game.ecs.get::<Window>(entity) {
+        Ok(window) => window,
+        Err(_) => return Ok(0.0),
+    };
+    let mut resistance = 0.0;
+    for slot in SLOT_ARMOR_MIN..=SLOT_ARMOR_MAX {
+        if matches!(
+            window.item(slot)?.item_kind(),
+            Some(Item::NetheriteHelmet)
+                | Some(Item::NetheriteChestplate)
+                | Some(Item::NetheriteLeggings)
+                | Some(Item::NetheriteBoots)
+        ) {
+            resistance += 0.1;
+        }
+    }
+    Ok(resistance)
+}
+
+/// The damage of a fully charged attack with an item.
+fn attack_damage(item: Option<Item>) -> f32 {
+    match item {
+        Some(Item::WoodenSword) | Some(Item::GoldenSword) => 4.0,
+        Some(Item::StoneSword) => 5.0,
+        Some(Item::IronSword) => 6.0,
+        Some(Item::DiamondSword) => 7.0,
+        Some(Item::NetheriteSword) => 8.0,
+        Some(Item::WoodenAxe) | Some(Item::GoldenAxe) => 7.0,
+        Some(Item::StoneAxe) | Some(Item::IronAxe) | Some(Item::DiamondAxe) => 9.0,
+        Some(Item::NetheriteAxe) => 10.0,
+        Some(Item::WoodenPickaxe) | Some(Item::GoldenPickaxe) => 2.0,
+        Some(Item::StonePickaxe) => 3.0,
+        Some(Item::IronPickaxe) => 4.0,
+        Some(Item::DiamondPickaxe) => 5.0,
+        Some(Item::NetheritePickaxe) => 6.0,
+        Some(Item::WoodenShovel) | Some(Item::GoldenShovel) => 2.5,
+        Some(Item::StoneShovel) => 3.5,
+        Some(Item::IronShovel) => 4.5,
+        Some(Item::DiamondShovel) => 5.5,
+        Some(Item::NetheriteShovel) => 6.5,
+        Some(Item::Trident) => 9.0,
+        _ => 1.0,
+    }
+}
+
+/// The number of fully charged attacks per second with an item.
+fn attack_speed(item: Option<Item>) -> f32 {
+    match item {
+        Some(Item::WoodenSword)
+        | Some(Item::StoneSword)
+        | Some(Item::IronSword)
+        | Some(Item::GoldenSword)
+        | Some(Item::DiamondSword)
+        | Some(Item::NetheriteSword) => 1.6,
+        Some(Item::WoodenAxe) | Some(Item::StoneAxe) => 0.8,
+        Some(Item::IronAxe) => 0.9,
+        Some(Item::GoldenAxe) | Some(Item::DiamondAxe) | Some(Item::NetheriteAxe) => 1.0,
+        Some(Item::WoodenPickaxe)
+        | Some(Item::StonePickaxe)
+        | Some(Item::IronPickaxe)
+        | Some(Item::GoldenPickaxe)
+        | Some(Item::DiamondPickaxe)
+        | Some(Item::NetheritePickaxe) => 1.2,
+        Some(Item::WoodenShovel)
+        | Some(Item::StoneShovel)
+        | Some(Item::IronShovel)
+        | Some(Item::GoldenShovel)
+        | Some(Item::DiamondShovel)
+        | Some(Item::NetheriteShovel) => 1.0,
+        Some(Item::WoodenHoe) | Some(Item::GoldenHoe) => 1.0,
+        Some(Item::StoneHoe) => 2.0,
+        Some(Item::IronHoe) => 3.0,
+        Some(Item::DiamondHoe) | Some(Item::NetheriteHoe) => 4.0,
+        Some(Item::Trident) => 1.1,
+        _ => DEFAULT_ATTACK_SPEED,
+    }
+}
+
+fn is_sword(item: Option<Item>) -> bool {
+    matches!(
+        item,
+        Some(Item::WoodenSword)
+            | Some(Item::StoneSword)
+            | Some(Item::IronSword)
+            | Some(Item::GoldenSword)
+            | Some(Item::DiamondSword)
+            | Some(Item::NetheriteSword)
+    )
+}
+
+/// Extra damage of a fully charged attack from the
+/// enchantments of a weapon against an entity.
+fn enchantment_damage(item: &InventorySlot, target: EntityKind) -> f32 {
+    let mut bonus = 0.0;
+    let sharpness = enchantment_level(item, EnchantmentKind::Sharpness);
+    if sharpness > 0 {
+        bonus += 0.5 * sharpness as f32 + 0.5;
+    }
+    if is_undead(target) {
+        bonus += 2.5 * enchantment_level(item, EnchantmentKind::Smite) as f32;
+    }
+    if is_arthropod(target) {
+        bonus += 2.5 * enchantment_level(item, EnchantmentKind::BaneOfArthropods) as f32;
+    }
+    bonus
+}
+
+fn is_undead(kind: EntityKind) -> bool {
+    matches!(
+        kind,
+        EntityKind::Zombie
+            | EntityKind::Husk
+            | EntityKind::Drowned
+            | EntityKind::ZombieVillager
+            | EntityKind::ZombifiedPiglin
+            | EntityKind::ZombieHorse
+            | EntityKind::Zoglin
+            | EntityKind::Skeleton
+            | EntityKind::Stray
+            | EntityKind::WitherSkeleton
+            | EntityKind::SkeletonHorse
+            | EntityKind::Wither
+            | EntityKind::Phantom
+    )
+}
+
+fn is_arthropod(kind: EntityKind) -> bool {
+    matches!(
+        kind,
+        EntityKind::Spider
+            | EntityKind::CaveSpider
+            | EntityKind::Bee
+            | EntityKind::Silverfish
+            | EntityKind::Endermite
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use base::{position, GameRules, Inventory};
+    use libcraft_items::ItemStackBuilder;
+
+    use super::*;
+    use crate::window::BackingWindow;
+
+    #[test]
+    fn cooldown_strength() {
+        let cooldown = AttackCooldown {
+            ticks: 0,
+            held: None,
+        };
+        assert!((cooldown.strength(4.0) - 0.1).abs() < 1e-4);
+
+        // A sword recovers after 12.5 ticks
+        let cooldown = AttackCooldown {
+            ticks: 6,
+            held: None,
+        };
+        assert!((cooldown.strength(1.6) - 0.52).abs() < 1e-4);
+        let cooldown = AttackCooldown {
+            ticks: 40,
+            held: None,
+        };
+        assert_eq!(cooldown.strength(1.6), 1.0);
+    }
+
+    #[test]
+    fn sharpness_and_smite() {
+        let sword = InventorySlot::Filled(
+            ItemStackBuilder::with_item(Item::DiamondSword)
+                .enchantment(EnchantmentKind::Sharpness, 5)
+                .into(),
+        );
+        assert_eq!(enchantment_damage(&sword, EntityKind::Cow), 3.0);
+
+        let sword = InventorySlot::Filled(
+            ItemStackBuilder::with_item(Item::DiamondSword)
+                .enchantment(EnchantmentKind::Smite, 2)
+                .into(),
+        );
+        assert_eq!(enchantment_damage(&sword, EntityKind::Zombie), 5.0);
+        assert_eq!(enchantment_damage(&sword, EntityKind::Cow), 0.0);
+    }
+
+    #[test]
+    fn knockback_pushes_away_and_up() {
+        let velocity = knockback_velocity(Vec3d::new(0.2, 0.0, 0.0), true, 0.4, 0.0, -3.0);
+        assert!((velocity.x - 0.1).abs() < 1e-9);
+        assert!((velocity.y - 0.4).abs() < 1e-9);
+        assert!((velocity.z + 0.4).abs() < 1e-9);
+
+        // Airborne entities keep their vertical velocity
+        let velocity = knockback_velocity(Vec3d::new(0.0, -0.5, 0.0), false, 0.4, 1.0, 0.0);
+        assert_eq!(velocity.y, -0.5);
+        assert!((velocity.x - 0.4).abs() < 1e-9);
+    }
+
+    #[test]
+    fn attack_damages_and_knocks_back() {
+        let mut game = Game::new();
+        game.insert_resource(GameRules::default());
+
+        let window = Window::new(BackingWindow::Player {
+            player: Inventory::player(),
+        });
+        window
+            .set_item(
+                SLOT_HOTBAR_OFFSET,
+                InventorySlot::Filled(ItemStackBuilder::with_item(Item::IronAxe).into()),
+            )
+            .unwrap();
+        let player = game.ecs.spawn((
+            position!(0.0, 64.0, 0.0),
+            OnGround(true),
+            Sprinting(false),
+            FallDistance::default(),
+            Gamemode::Survival,
+            HotbarSlot::new(0),
+            window,
+            AttackCooldown {
+                ticks: 40,
+                held: Some(Item::IronAxe),
+            },
+        ));
+        let target = game.ecs.spawn((
+            position!(0.0, 64.0, 2.0),
+            EntityKind::Zombie,
+            Health(20.0),
+            OnGround(true),
+            Velocity::default(),
+        ));
+
+        assert!(can_attack(&game, player, target).unwrap());
+        attack(&mut game, player, target).unwrap();
+
+        assert_eq!(game.ecs.get::<Health>(target).unwrap().0, 11.0);
+        assert_eq!(game.ecs.get::<AttackCooldown>(player).unwrap().ticks, 0);
+        let knockback = game.ecs.get::<KnockbackEvent>(target).unwrap().0;
+        assert!((knockback.z - BASE_KNOCKBACK).abs() < 1e-9);
+        assert!(knockback.y > 0.0);
+        assert_eq!(game.ecs.get::<Velocity>(target).unwrap().0, knockback);
+    }
+}
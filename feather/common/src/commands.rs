@@ -0,0 +1,831 @@
+//! A command dispatcher modeled after Mojang's
+//! [Brigadier](https://github.com/Mojang/brigadier).
+//!
+//! Commands form a tree of nodes. Each node is either
+//! a literal (`gamemode`) or a typed argument (`<target>`).
+//! The same tree is used to parse and execute commands,
+//! to answer tab completion requests, and to tell
+//! clients which commands exist (so they can highlight
+//! and complete input locally.)
+
+use std::{collections::HashMap, rc::Rc, sync::Arc};
+
+use anyhow::bail;
+use base::{BlockId, Gamemode, Position, Text};
+use ecs::{Entity, SysResult};
+use libcraft_text::TextComponentBuilder;
+use quill_common::components::Name;
+
+use crate::{
+    chat::{ChatKind, ChatMessage},
+    Game,
+};
+
+pub mod arguments;
+mod builtin;
+pub mod reader;
+pub mod selector;
+mod snbt;
+
+pub use arguments::{ArgumentKind, ArgumentValue, Coordinates, StringKind};
+pub use reader::{StringReader, SyntaxError};
+pub use selector::EntitySelector;
+
+/// The index of a node in a [`CommandDispatcher`].
+pub type NodeId = usize;
+
+/// The function called to execute a command.
+pub type CommandExecutor = Rc<dyn Fn(&mut Game, &CommandContext) -> SysResult>;
+
+/// The kind of a [`CommandNode`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum NodeKind {
+    /// The root node. There is exactly one per dispatcher.
+    Root,
+    /// Matches a fixed word.
+    Literal(String),
+    /// Parses a typed value.
+    Argument { name: String, kind: ArgumentKind },
+}
+
+/// The highest permission level, held by the console.
+pub const MAX_PERMISSION_LEVEL: u8 = 4;
+
+/// The operator level of an entity, from 0 (no
+/// permissions) to 4 (full control of the server.)
+///
+/// Entities without this component have level 0.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PermissionLevel(pub u8);
+
+/// Returns the permission level of `entity`.
+pub fn permission_level(game: &Game, entity: Entity) -> u8 {
+    game.ecs
+        .get::<PermissionLevel>(entity)
+        .map_or(0, |level| level.0)
+}
+
+/// A node in the command tree.
+#[derive(Clone)]
+pub struct CommandNode {
+    kind: NodeKind,
+    children: Vec<NodeId>,
+    redirect: Option<NodeId>,
+    executor: Option<CommandExecutor>,
+    permission_level: u8,
+}
+
+impl CommandNode {
+    pub fn kind(&self) -> &NodeKind {
+        &self.kind
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+
+    /// A node which continues parsing at the children of another node.
+    /// Used for aliases.
+    pub fn redirect(&self) -> Option<NodeId> {
+        self.redirect
+    }
+
+    pub fn is_executable(&self) -> bool {
+        self.executor.is_some()
+    }
+
+    /// The permission level required to use this node.
+    pub fn permission_level(&self) -> u8 {
+        self.permission_level
+    }
+
+    fn name(&self) -> &str {
+        match &self.kind {
+            NodeKind::Root => "",
+            NodeKind::Literal(name) => name,
+            NodeKind::Argument { name, .. } => name,
+        }
+    }
+}
+
+/// Builder for a command subtree, passed to [`CommandDispatcher::register`].
+pub struct CommandBuilder {
+    kind: NodeKind,
+    children: Vec<CommandBuilder>,
+    executor: Option<CommandExecutor>,
+    permission_level: u8,
+}
+
+/// Creates a builder for a literal node.
+pub fn literal(name: &str) -> CommandBuilder {
+    CommandBuilder {
+        kind: NodeKind::Literal(name.to_owned()),
+        children: Vec::new(),
+        executor: None,
+        permission_level: 0,
+    }
+}
+
+/// Creates a builder for an argument node.
+pub fn argument(name: &str, kind: ArgumentKind) -> CommandBuilder {
+    CommandBuilder {
+        kind: NodeKind::Argument {
+            name: name.to_owned(),
+            kind,
+        },
+        children: Vec::new(),
+        executor: None,
+        permission_level: 0,
+    }
+}
+
+impl CommandBuilder {
+    /// Adds a child node.
+    pub fn then(mut self, child: CommandBuilder) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    /// Makes the command executable when input ends at this node.
+    pub fn executes(
+        mut self,
+        executor: impl Fn(&mut Game, &CommandContext) -> SysResult + 'static,
+    ) -> Self {
+        self.executor = Some(Rc::new(executor));
+        self
+    }
+
+    /// Restricts this node and its children to senders
+    /// with at least the given [`PermissionLevel`].
+    pub fn requires(mut self, permission_level: u8) -> Self {
+        self.permission_level = permission_level;
+        self
+    }
+}
+
+/// State passed to a [`CommandExecutor`].
+#[derive(Clone)]
+pub struct CommandContext {
+    /// The entity which ran the command. This is a player
+    /// or the console; commands should not assume either.
+    pub sender: Entity,
+    /// The full command, without the leading slash.
+    pub input: String,
+    arguments: HashMap<String, ArgumentValue>,
+}
+
+impl CommandContext {
+    fn argument(&self, name: &str) -> anyhow::Result<&ArgumentValue> {
+        match self.arguments.get(name) {
+            Some(value) => Ok(value),
+            None => bail!("missing argument '{}'", name),
+        }
+    }
+
+    pub fn has_argument(&self, name: &str) -> bool {
+        self.arguments.contains_key(name)
+    }
+
+    pub fn bool(&self, name: &str) -> anyhow::Result<bool> {
+        match self.argument(name)? {
+            ArgumentValue::Bool(value) => Ok(*value),
+            value => bail!("argument '{}' is not a bool: {:?}", name, value),
+        }
+    }
+
+    pub fn integer(&self, name: &str) -> anyhow::Result<i32> {
+        match self.argument(name)? {
+            ArgumentValue::Integer(value) => Ok(*value),
+            value => bail!("argument '{}' is not an integer: {:?}", name, value),
+        }
+    }
+
+    pub fn double(&self, name: &str) -> anyhow::Result<f64> {
+        match self.argument(name)? {
+            ArgumentValue::Double(value) => Ok(*value),
+            value => bail!("argument '{}' is not a double: {:?}", name, value),
+        }
+    }
+
+    pub fn string(&self, name: &str) -> anyhow::Result<&str> {
+        match self.argument(name)? {
+            ArgumentValue::String(value) => Ok(value),
+            value => bail!("argument '{}' is not a string: {:?}", name, value),
+        }
+    }
+
+    /// Resolves an entity argument to the selected entities.
+    pub fn entities(&self, game: &Game, name: &str) -> anyhow::Result<Vec<Entity>> {
+        match self.argument(name)? {
+            ArgumentValue::Entity(selector) => Ok(selector.select(game, self.sender)),
+            value => bail!("argument '{}' is not an entity selector: {:?}", name, value),
+        }
+    }
+
+    /// Resolves a coordinates argument relative to the sender.
+    pub fn position(&self, game: &Game, name: &str) -> anyhow::Result<Position> {
+        match self.argument(name)? {
+            ArgumentValue::Coordinates(coordinates) => {
+                Ok(coordinates.resolve(self.sender_position(game)))
+            }
+            value => bail!("argument '{}' is not coordinates: {:?}", name, value),
+        }
+    }
+
+    pub fn gamemode(&self, name: &str) -> anyhow::Result<Gamemode> {
+        match self.argument(name)? {
+            ArgumentValue::Gamemode(gamemode) => Ok(*gamemode),
+            value => bail!("argument '{}' is not a gamemode: {:?}", name, value),
+        }
+    }
+
+    pub fn block_state(&self, name: &str) -> anyhow::Result<BlockId> {
+        match self.argument(name)? {
+            ArgumentValue::BlockState(block) => Ok(*block),
+            value => bail!("argument '{}' is not a block state: {:?}", name, value),
+        }
+    }
+
+    pub fn nbt(&self, name: &str) -> anyhow::Result<&nbt::Value> {
+        match self.argument(name)? {
+            ArgumentValue::Nbt(value) => Ok(value),
+            value => bail!("argument '{}' is not NBT: {:?}", name, value),
+        }
+    }
+
+    /// The position of the sender, used as the origin for
+    /// relative coordinates. Defaults to the world origin
+    /// for senders without a position (e.g. the console.)
+    pub fn sender_position(&self, game: &Game) -> Position {
+        game.ecs
+            .get::<Position>(self.sender)
+            .map(|pos| *pos)
+            .unwrap_or_default()
+    }
+
+    /// The display name of the sender.
+    pub fn sender_name(&self, game: &Game) -> String {
+        game.ecs
+            .get::<Name>(self.sender)
+            .map(|name| name.to_string())
+            .unwrap_or_else(|_| "Server".to_owned())
+    }
+
+    /// Determines whether the sender has a permission node.
+    pub fn has_permission(&self, game: &Game, node: &str) -> bool {
+        crate::permissions::has_permission(game, self.sender, node)
+    }
+
+    /// Sends feedback to the sender.
+    pub fn reply(&self, game: &mut Game, message: impl Into<Text>) -> SysResult {
+        game.send_message(
+            self.sender,
+            ChatMessage::new(ChatKind::System, message.into()),
+        )
+    }
+}
+
+/// The result of parsing a command: the node
+/// at which parsing ended and the parsed arguments.
+struct Parsed {
+    node: NodeId,
+    arguments: HashMap<String, ArgumentValue>,
+}
+
+/// Completions for a partially typed command.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suggestions {
+    /// Byte offset of the text being completed.
+    pub start: usize,
+    /// Length of the text being completed, in bytes.
+    pub length: usize,
+    pub matches: Vec<String>,
+}
+
+impl Suggestions {
+    /// Returns the start and length of the text being completed
+    /// in UTF-16 code units, which is how the client counts them.
+    /// `input` is the text the suggestions were computed for.
+    pub fn utf16_range(&self, input: &str) -> (usize, usize) {
+        let start = input[..self.start].encode_utf16().count();
+        let length = input[self.start..self.start + self.length]
+            .encode_utf16()
+            .count();
+        (start, length)
+    }
+}
+
+/// The command tree. Stored as a resource.
+pub struct CommandDispatcher {
+    nodes: Vec<CommandNode>,
+}
+
+impl Default for CommandDispatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CommandDispatcher {
+    const ROOT: NodeId = 0;
+
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![CommandNode {
+                kind: NodeKind::Root,
+                children: Vec::new(),
+                redirect: None,
+                executor: None,
+                permission_level: 0,
+            }],
+        }
+    }
+
+    /// Returns all nodes. The root node has index 0.
+    pub fn nodes(&self) -> &[CommandNode] {
+        &self.nodes
+    }
+
+    pub fn root(&self) -> NodeId {
+        Self::ROOT
+    }
+
+    /// Adds a command to the tree, merging it with
+    /// existing nodes of the same name.
+    ///
+    /// Returns the ID of the command's top-level node.
+    pub fn register(&mut self, command: CommandBuilder) -> NodeId {
+        self.insert(Self::ROOT, command)
+    }
+
+    /// Registers `alias` as another name for the top-level node `target`.
+    pub fn register_alias(&mut self, alias: &str, target: NodeId) -> NodeId {
+        let executor = self.nodes[target].executor.clone();
+        let permission_level = self.nodes[target].permission_level;
+        let id = self.insert(Self::ROOT, literal(alias).requires(permission_level));
+        self.nodes[id].redirect = Some(target);
+        self.nodes[id].executor = executor;
+        id
+    }
+
+    fn insert(&mut self, parent: NodeId, builder: CommandBuilder) -> NodeId {
+        let existing = self.nodes[parent]
+            .children
+            .iter()
+            .copied()
+            .find(|&child| self.nodes[child].kind == builder.kind);
+
+        let id = match existing {
+            Some(id) => {
+                if builder.executor.is_some() {
+                    self.nodes[id].executor = builder.executor;
+                }
+                if builder.permission_level > 0 {
+                    self.nodes[id].permission_level = builder.permission_level;
+                }
+                id
+            }
+            None => {
+                let id = self.nodes.len();
+                self.nodes.push(CommandNode {
+                    kind: builder.kind,
+                    children: Vec::new(),
+                    redirect: None,
+                    executor: builder.executor,
+                    permission_level: builder.permission_level,
+                });
+                self.nodes[parent].children.push(id);
+                id
+            }
+        };
+
+        for child in builder.children {
+            self.insert(id, child);
+        }
+
+        id
+    }
+
+    /// Returns whether a sender with the given
+    /// permission level may use `node`.
+    pub fn can_use(&self, node: NodeId, permission_level: u8) -> bool {
+        self.nodes[node].permission_level <= permission_level
+    }
+
+    /// Returns the children of `node` usable at `permission_level`
+    /// with literals first, following redirects.
+    fn children_of(&self, node: NodeId, permission_level: u8) -> Vec<NodeId> {
+        let node = self.nodes[node].redirect.unwrap_or(node);
+        let mut children: Vec<NodeId> = self.nodes[node]
+            .children
+            .iter()
+            .copied()
+            .filter(|&child| self.can_use(child, permission_level))
+            .collect();
+        children.sort_by_key(|&child| !matches!(self.nodes[child].kind, NodeKind::Literal(_)));
+        children
+    }
+
+    /// Parses `input` (without a leading slash) into an executable node,
+    /// ignoring nodes which require more than `permission_level`.
+    fn parse(&self, input: &str, permission_level: u8) -> Result<Parsed, SyntaxError> {
+        let mut reader = StringReader::new(input);
+        let mut arguments = HashMap::new();
+        let node = self.parse_node(Self::ROOT, &mut reader, &mut arguments, permission_level)?;
+        Ok(Parsed { node, arguments })
+    }
+
+    /// Recursively parses children of `node`, backtracking
+    /// when a branch fails. Returns the final node.
+    fn parse_node(
+        &self,
+        node: NodeId,
+        reader: &mut StringReader,
+        arguments: &mut HashMap<String, ArgumentValue>,
+        permission_level: u8,
+    ) -> Result<NodeId, SyntaxError> {
+        if !reader.can_read() {
+            return if self.nodes[node].is_executable() {
+                Ok(node)
+            } else {
+                Err(reader.error("Unknown or incomplete command, see below for error"))
+            };
+        }
+
+        if node != Self::ROOT {
+            reader.expect(' ').map_err(|_| {
+                reader.error("Expected whitespace to end one argument, but found trailing data")
+            })?;
+        }
+
+        let start = reader.cursor();
+        let mut best_error: Option<SyntaxError> = None;
+        for child in self.children_of(node, permission_level) {
+            reader.set_cursor(start);
+            let result = match &self.nodes[child].kind {
+                NodeKind::Root => continue,
+                NodeKind::Literal(name) => {
+                    let word = reader.read_word();
+                    if word != name {
+                        continue;
+                    }
+                    self.parse_node(child, reader, arguments, permission_level)
+                }
+                NodeKind::Argument { name, kind } => {
+                    match kind.parse(reader).and_then(|value| {
+                        if reader.can_read() && reader.peek() != Some(' ') {
+                            Err(reader.error(
+                                "Expected whitespace to end one argument, but found trailing data",
+                            ))
+                        } else {
+                            Ok(value)
+                        }
+                    }) {
+                        Ok(value) => {
+                            arguments.insert(name.clone(), value);
+                            let result =
+                                self.parse_node(child, reader, arguments, permission_level);
+                            if result.is_err() {
+                                arguments.remove(name);
+                            }
+                            result
+                        }
+                        Err(e) => Err(e),
+                    }
+                }
+            };
+
+            match result {
+                Ok(node) => return Ok(node),
+                Err(e) => {
+                    if best_error
+                        .as_ref()
+                        .map_or(true, |best| e.cursor > best.cursor)
+                    {
+                        best_error = Some(e);
+                    }
+                }
+            }
+        }
+
+        reader.set_cursor(start);
+        Err(best_error.unwrap_or_else(|| {
+            if node == Self::ROOT {
+                SyntaxError::new("Unknown command", start)
+            } else {
+                SyntaxError::new("Incorrect argument for command", start)
+            }
+        }))
+    }
+
+    /// Computes tab completions for `input` (without a leading slash.)
+    pub fn suggest(&self, game: &Game, input: &str, permission_level: u8) -> Suggestions {
+        let mut node = Self::ROOT;
+        let mut reader = StringReader::new(input);
+
+        // Walk as many complete words as possible.
+        'outer: loop {
+            let start = reader.cursor();
+            if node != Self::ROOT {
+                if reader.peek() != Some(' ') {
+                    break;
+                }
+                reader.skip();
+            }
+            let word_start = reader.cursor();
+            for child in self.children_of(node, permission_level) {
+                reader.set_cursor(word_start);
+                let parsed = match &self.nodes[child].kind {
+                    NodeKind::Root => false,
+                    NodeKind::Literal(name) => reader.read_word() == name,
+                    NodeKind::Argument { kind, .. } => kind.parse(&mut reader).is_ok(),
+                };
+                // Only descend if the word is followed by a space,
+                // otherwise it is still being typed.
+                if parsed && reader.peek() == Some(' ') {
+                    node = child;
+                    continue 'outer;
+                }
+            }
+            reader.set_cursor(if node == Self::ROOT {
+                start
+            } else {
+                word_start
+            });
+            break;
+        }
+
+        let start = reader.cursor();
+        let partial = reader.remaining();
+        let mut matches = Vec::new();
+        for child in self.children_of(node, permission_level) {
+            match &self.nodes[child].kind {
+                NodeKind::Root => (),
+                NodeKind::Literal(name) => {
+                    if name.starts_with(partial) {
+                        matches.push(name.clone());
+                    }
+                }
+                NodeKind::Argument { kind, .. } => matches.extend(kind.suggest(game, partial)),
+            }
+        }
+        matches.sort();
+        matches.dedup();
+
+        Suggestions {
+            start,
+            length: partial.len(),
+            matches,
+        }
+    }
+
+    /// Returns the usage strings of the command rooted at `node`,
+    /// e.g. `gamemode <gamemode> [<target>]`.
+    pub fn usage(&self, node: NodeId) -> Vec<String> {
+        let mut usages = Vec::new();
+        self.collect_usage(node, String::new(), &mut usages);
+        usages
+    }
+
+    fn collect_usage(&self, node: NodeId, prefix: String, usages: &mut Vec<String>) {
+        let this = &self.nodes[node];
+        let part = match &this.kind {
+            NodeKind::Root => String::new(),
+            NodeKind::Literal(name) => name.clone(),
+            NodeKind::Argument { name, .. } => format!("<{}>", name),
+        };
+        let prefix = if prefix.is_empty() {
+            part
+        } else {
+            format!("{} {}", prefix, part)
+        };
+
+        if let Some(target) = this.redirect {
+            usages.push(format!("{} -> {}", prefix, self.nodes[target].name()));
+            return;
+        }
+        if this.is_executable() || this.children.is_empty() {
+            usages.push(prefix.clone());
+        }
+        for &child in &this.children {
+            self.collect_usage(child, prefix.clone(), usages);
+        }
+    }
+}
+
+/// Parses and executes a command on behalf of `sender`.
+///
+/// `command` should not include the leading slash. Syntax
+/// and execution errors are reported to the sender's `ChatBox`.
+pub fn execute(game: &mut Game, sender: Entity, command: &str) -> SysResult {
+    let resources = Arc::clone(&game.resources);
+    let permission_level = permission_level(game, sender);
+    let parsed = {
+        let dispatcher = resources.get::<CommandDispatcher>()?;
+        dispatcher
+            .parse(command, permission_level)
+            .map(|parsed| (dispatcher.nodes[parsed.node].executor.clone(), parsed))
+    };
+
+    match parsed {
+        Ok((Some(executor), parsed)) => {
+            let context = CommandContext {
+                sender,
+                input: command.to_owned(),
+                arguments: parsed.arguments,
+            };
+            if let Err(e) = executor(game, &context) {
+                log::debug!("Command '{}' failed: {:?}", command, e);
+                context.reply(game, Text::from(e.to_string()).red())?;
+            }
+        }
+        Ok((None, _)) => unreachable!("parse only returns executable nodes"),
+        Err(e) => {
+            let mut message = Text::from(e.message.clone()).red();
+            if !command.is_empty() {
+                let context_start = command[..e.cursor.min(command.len())]
+                    .char_indices()
+                    .rev()
+                    .nth(9)
+                    .map_or(0, |(i, _)| i);
+                let before = &command[context_start..e.cursor.min(command.len())];
+                let after = &command[e.cursor.min(command.len())..];
+                message = message
+                    + Text::from(format!(
+                        "\n{}{}",
+                        if context_start > 0 { "..." } else { "" },
+                        before
+                    ))
+                    .gray()
+                    + Text::from(after.to_owned()).red().underlined()
+                    + Text::from("<--[HERE]").red().italic();
+            }
+            game.send_message(sender, ChatMessage::new(ChatKind::System, message))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Registers the command dispatcher with built-in commands.
+pub fn register(game: &mut Game) {
+    let mut dispatcher = CommandDispatcher::new();
+    builtin::register(&mut dispatcher);
+    game.insert_resource(dispatcher);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noop(_: &mut Game, _: &CommandContext) -> SysResult {
+        Ok(())
+    }
+
+    fn dispatcher() -> CommandDispatcher {
+        let mut dispatcher = CommandDispatcher::new();
+        let gamemode = dispatcher.register(
+            literal("gamemode").then(
+                argument("gamemode", ArgumentKind::Gamemode)
+                    .executes(noop)
+                    .then(
+                        argument(
+                            "target",
+                            ArgumentKind::Entity {
+                                single: false,
+                                players_only: true,
+                            },
+                        )
+                        .executes(noop),
+                    ),
+            ),
+        );
+        dispatcher.register_alias("gm", gamemode);
+        dispatcher.register(
+            literal("say").then(argument("message", ArgumentKind::Message).executes(noop)),
+        );
+        dispatcher
+    }
+
+    #[test]
+    fn parse_commands() {
+        let dispatcher = dispatcher();
+        let parsed = dispatcher.parse("gamemode creative @a", 0).unwrap();
+        assert_eq!(
+            parsed.arguments["gamemode"],
+            ArgumentValue::Gamemode(Gamemode::Creative)
+        );
+        assert!(parsed.arguments.contains_key("target"));
+
+        let parsed = dispatcher.parse("gm survival", 0).unwrap();
+        assert!(!parsed.arguments.contains_key("target"));
+
+        let parsed = dispatcher.parse("say hello world", 0).unwrap();
+        assert_eq!(
+            parsed.arguments["message"],
+            ArgumentValue::String("hello world".to_owned())
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        let dispatcher = dispatcher();
+        assert_eq!(dispatcher.parse("foo", 0).unwrap_err().cursor, 0);
+        assert!(dispatcher.parse("gamemode", 0).is_err());
+        assert_eq!(
+            dispatcher.parse("gamemode hardcore", 0).unwrap_err().cursor,
+            9
+        );
+        assert!(dispatcher.parse("gamemode creative @e", 0).is_err());
+        assert!(dispatcher.parse("gamemode creative @a extra", 0).is_err());
+    }
+
+    #[test]
+    fn register_merges_literals() {
+        let mut dispatcher = CommandDispatcher::new();
+        let a = dispatcher.register(literal("time").then(literal("set").executes(noop)));
+        let b = dispatcher.register(literal("time").then(literal("add").executes(noop)));
+        assert_eq!(a, b);
+        assert_eq!(dispatcher.nodes()[a].children().len(), 2);
+    }
+
+    #[test]
+    fn suggest_literals_and_arguments() {
+        let game = Game::new();
+        let dispatcher = dispatcher();
+
+        let suggestions = dispatcher.suggest(&game, "g", 0);
+        assert_eq!(suggestions.start, 0);
+        assert_eq!(suggestions.matches, vec!["gamemode", "gm"]);
+
+        let suggestions = dispatcher.suggest(&game, "gamemode cr", 0);
+        assert_eq!(suggestions.start, 9);
+        assert_eq!(suggestions.length, 2);
+        assert_eq!(suggestions.matches, vec!["creative"]);
+
+        let suggestions = dispatcher.suggest(&game, "gm s", 0);
+        assert_eq!(suggestions.matches, vec!["spectator", "survival"]);
+    }
+
+    #[test]
+    fn suggestion_ranges_count_utf16() {
+        let mut dispatcher = CommandDispatcher::new();
+        dispatcher.register(
+            literal("tell").then(
+                argument("name", ArgumentKind::String(StringKind::Quotable))
+                    .then(literal("loudly").executes(noop)),
+            ),
+        );
+        let game = Game::new();
+
+        let input = "tell \"José\" lo";
+        let suggestions = dispatcher.suggest(&game, input, 0);
+        assert_eq!(suggestions.matches, vec!["loudly"]);
+        assert_eq!(suggestions.start, 13);
+        assert_eq!(suggestions.utf16_range(input), (12, 2));
+
+        let input = "tell \"José\" ü";
+        let suggestions = dispatcher.suggest(&game, input, 0);
+        assert_eq!(suggestions.utf16_range(input), (12, 1));
+    }
+
+    #[test]
+    fn parse_gamerule_values() {
+        let mut dispatcher = CommandDispatcher::new();
+        builtin::register(&mut dispatcher);
+        let parsed = dispatcher
+            .parse("gamerule keepInventory true", MAX_PERMISSION_LEVEL)
+            .unwrap();
+        assert_eq!(parsed.arguments["value"], ArgumentValue::Bool(true));
+        let parsed = dispatcher
+            .parse("gamerule randomTickSpeed 10", MAX_PERMISSION_LEVEL)
+            .unwrap();
+        assert_eq!(parsed.arguments["value"], ArgumentValue::Integer(10));
+        assert!(dispatcher
+            .parse("gamerule keepInventory 3", MAX_PERMISSION_LEVEL)
+            .is_err());
+        assert!(dispatcher
+            .parse("gamerule randomTickSpeed -1", MAX_PERMISSION_LEVEL)
+            .is_err());
+    }
+
+    #[test]
+    fn permission_levels() {
+        let mut dispatcher = CommandDispatcher::new();
+        dispatcher.register(literal("help").executes(noop));
+        let stop = dispatcher.register(literal("stop").requires(4).executes(noop));
+        dispatcher.register_alias("halt", stop);
+
+        assert!(dispatcher.parse("help", 0).is_ok());
+        assert!(dispatcher.parse("stop", 0).is_err());
+        assert!(dispatcher.parse("halt", 3).is_err());
+        assert!(dispatcher.parse("halt", 4).is_ok());
+
+        let game = Game::new();
+        assert_eq!(dispatcher.suggest(&game, "", 0).matches, vec!["help"]);
+        assert_eq!(
+            dispatcher.suggest(&game, "", 4).matches,
+            vec!["halt", "help", "stop"]
+        );
+    }
+}
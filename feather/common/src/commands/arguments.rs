@@ -0,0 +1,494 @@
+//! Typed command arguments.
+
+use std::collections::BTreeMap;
+
+use base::{BlockId, BlockPosition, Gamemode, Position};
+
+use crate::Game;
+
+use super::{
+    reader::{StringReader, SyntaxError},
+    selector::EntitySelector,
+};
+
+/// How a string argument consumes input.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StringKind {
+    /// A single word.
+    Word,
+    /// A single word, or a quoted string which may contain spaces.
+    Quotable,
+    /// The rest of the input.
+    Greedy,
+}
+
+/// The type of an argument node. Determines how
+/// input is parsed and which parser the client uses
+/// to highlight and complete the argument.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ArgumentKind {
+    Bool,
+    Integer {
+        min: Option<i32>,
+        max: Option<i32>,
+    },
+    Double {
+        min: Option<f64>,
+        max: Option<f64>,
+    },
+    String(StringKind),
+    /// An entity selector or player name.
+    Entity {
+        single: bool,
+        players_only: bool,
+    },
+    /// Integer block coordinates, possibly relative (`~`) or local (`^`).
+    BlockPos,
+    /// Coordinates, possibly relative (`~`) or local (`^`).
+    Vec3,
+    /// The name of a player, who need not be online.
+    GameProfile,
+    /// A gamemode name, e.g. `creative`.
+    Gamemode,
+    /// A block with optional properties, e.g. `minecraft:oak_stairs[facing=east]`.
+    BlockState,
+    /// An SNBT compound tag.
+    Nbt,
+    /// A chat message which consumes the rest of the input.
+    Message,
+}
+
+impl ArgumentKind {
+    /// Parses a value of this kind.
+    pub fn parse(&self, reader: &mut StringReader) -> Result<ArgumentValue, SyntaxError> {
+        let start = reader.cursor();
+        match self {
+            ArgumentKind::Bool => reader.read_bool().map(ArgumentValue::Bool),
+            ArgumentKind::Integer { min, max } => {
+                let value = reader.read_int()?;
+                check_bounds(value, *min, *max, start).map(ArgumentValue::Integer)
+            }
+            ArgumentKind::Double { min, max } => {
+                let value = reader.read_double()?;
+                check_bounds(value, *min, *max, start).map(ArgumentValue::Double)
+            }
+            ArgumentKind::String(StringKind::Word) => Ok(ArgumentValue::String(
+                reader.read_unquoted_string().to_owned(),
+            )),
+            ArgumentKind::String(StringKind::Quotable) => {
+                reader.read_string().map(ArgumentValue::String)
+            }
+            ArgumentKind::String(StringKind::Greedy) | ArgumentKind::Message => {
+                let rest = reader.remaining().to_owned();
+                reader.set_cursor(reader.input().len());
+                Ok(ArgumentValue::String(rest))
+            }
+            ArgumentKind::Entity {
+                single,
+                players_only,
+            } => {
+                let selector = EntitySelector::parse(reader)?;
+                if *single && selector.may_select_multiple() {
+                    return Err(SyntaxError::new(
+                        "Only one entity is allowed, but the provided selector allows more than one",
+                        start,
+                    ));
+                }
+                if *players_only && selector.may_select_non_players() {
+                    return Err(SyntaxError::new(
+                        "Only players may be affected by this command, but the provided selector includes entities",
+                        start,
+                    ));
+                }
+                Ok(ArgumentValue::Entity(selector))
+            }
+            ArgumentKind::BlockPos => {
+                Coordinates::parse(reader, true).map(ArgumentValue::Coordinates)
+            }
+            ArgumentKind::Vec3 => Coordinates::parse(reader, false).map(ArgumentValue::Coordinates),
+            ArgumentKind::GameProfile => {
+                let name = reader.read_unquoted_string();
+                if name.is_empty() {
+                    return Err(SyntaxError::new("Expected a player name", start));
+                }
+                Ok(ArgumentValue::String(name.to_owned()))
+            }
+            ArgumentKind::Gamemode => {
+                let name = reader.read_unquoted_string();
+                parse_gamemode(name)
+                    .map(ArgumentValue::Gamemode)
+                    .ok_or_else(|| SyntaxError::new(format!("Unknown gamemode '{}'", name), start))
+            }
+            ArgumentKind::BlockState => parse_block_state(reader).map(ArgumentValue::BlockState),
+            ArgumentKind::Nbt => super::snbt::parse_compound(reader).map(ArgumentValue::Nbt),
+        }
+    }
+
+    /// Returns completions for a partially typed argument.
+    ///
+    /// Only argument kinds without client-side completion
+    /// need to return anything here.
+    pub fn suggest(&self, game: &Game, partial: &str) -> Vec<String> {
+        let candidates: Vec<String> = match self {
+            ArgumentKind::Bool => vec!["true".to_owned(), "false".to_owned()],
+            ArgumentKind::Gamemode => GAMEMODE_NAMES.iter().map(|&s| s.to_owned()).collect(),
+            ArgumentKind::Entity { .. } | ArgumentKind::GameProfile => game
+                .ecs
+                .query::<(
+                    &quill_common::entities::Player,
+                    &quill_common::components::Name,
+                )>()
+                .iter()
+                .map(|(_, (_, name))| name.to_string())
+                .collect(),
+            _ => Vec::new(),
+        };
+        candidates
+            .into_iter()
+            .filter(|candidate| candidate.starts_with(partial))
+            .collect()
+    }
+}
+
+fn check_bounds<T: PartialOrd + std::fmt::Display>(
+    value: T,
+    min: Option<T>,
+    max: Option<T>,
+    cursor: usize,
+) -> Result<T, SyntaxError> {
+    if let Some(min) = min {
+        if value < min {
+            return Err(SyntaxError::new(
+                format!("Value must not be less than {}, found {}", min, value),
+                cursor,
+            ));
+        }
+    }
+    if let Some(max) = max {
+        if value > max {
+            return Err(SyntaxError::new(
+                format!("Value must not be more than {}, found {}", max, value),
+                cursor,
+            ));
+        }
+    }
+    Ok(value)
+}
+
+/// A parsed argument value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ArgumentValue {
+    Bool(bool),
+    Integer(i32),
+    Double(f64),
+    String(String),
+    Entity(EntitySelector),
+    Coordinates(Coordinates),
+    Gamemode(Gamemode),
+    BlockState(BlockId),
+    Nbt(nbt::Value),
+}
+
+const GAMEMODE_NAMES: [&str; 4] = ["survival", "creative", "adventure", "spectator"];
+
+fn parse_gamemode(name: &str) -> Option<Gamemode> {
+    GAMEMODE_NAMES
+        .iter()
+        .position(|&gamemode| gamemode == name)
+        .and_then(|id| Gamemode::from_id(id as u8))
+}
+
+/// Returns the name used for a gamemode in commands.
+pub fn gamemode_name(gamemode: Gamemode) -> &'static str {
+    GAMEMODE_NAMES[gamemode as usize]
+}
+
+/// A single coordinate.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Coordinate {
+    Absolute(f64),
+    /// `~offset`, relative to the sender's position.
+    Relative(f64),
+    /// `^offset`, relative to the sender's position and rotation.
+    Local(f64),
+}
+
+impl Coordinate {
+    fn parse(reader: &mut StringReader, integer: bool) -> Result<Self, SyntaxError> {
+        let at_end = |reader: &StringReader| !reader.can_read() || reader.peek() == Some(' ');
+
+        let prefix = reader.peek();
+        if prefix == Some('~') || prefix == Some('^') {
+            reader.skip();
+            let offset = if at_end(reader) {
+                0.0
+            } else {
+                reader.read_double()?
+            };
+            return Ok(if prefix == Some('~') {
+                Coordinate::Relative(offset)
+            } else {
+                Coordinate::Local(offset)
+            });
+        }
+
+        if integer {
+            if at_end(reader) {
+                return Err(reader.error("Expected integer"));
+            }
+            Ok(Coordinate::Absolute(f64::from(reader.read_int()?)))
+        } else {
+            if at_end(reader) {
+                return Err(reader.error("Expected double"));
+            }
+            Ok(Coordinate::Absolute(reader.read_double()?))
+        }
+    }
+
+    fn is_local(self) -> bool {
+        matches!(self, Coordinate::Local(_))
+    }
+
+    fn resolve(self, origin: f64) -> f64 {
+        match self {
+            Coordinate::Absolute(value) => value,
+            Coordinate::Relative(offset) | Coordinate::Local(offset) => origin + offset,
+        }
+    }
+
+    fn value(self) -> f64 {
+        match self {
+            Coordinate::Absolute(value)
+            | Coordinate::Relative(value)
+            | Coordinate::Local(value) => value,
+        }
+    }
+}
+
+/// Three coordinates, as used by the `BlockPos` and `Vec3` argument kinds.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Coordinates {
+    pub x: Coordinate,
+    pub y: Coordinate,
+    pub z: Coordinate,
+}
+
+impl Coordinates {
+    fn parse(reader: &mut StringReader, integer: bool) -> Result<Self, SyntaxError> {
+        let start = reader.cursor();
+        let x = Coordinate::parse(reader, integer)?;
+        reader
+            .expect(' ')
+            .map_err(|_| reader.error("Incomplete (expected 3 coordinates)"))?;
+        let y = Coordinate::parse(reader, integer)?;
+        reader
+            .expect(' ')
+            .map_err(|_| reader.error("Incomplete (expected 3 coordinates)"))?;
+        let z = Coordinate::parse(reader, integer)?;
+
+        let local = [x, y, z].iter().filter(|c| c.is_local()).count();
+        if local != 0 && local != 3 {
+            return Err(SyntaxError::new(
+                "Cannot mix world & local coordinates (everything must either use ^ or not)",
+                start,
+            ));
+        }
+
+        Ok(Self { x, y, z })
+    }
+
+    /// Resolves these coordinates relative to `origin`.
+    pub fn resolve(&self, origin: Position) -> Position {
+        if self.x.is_local() {
+            return self.resolve_local(origin);
+        }
+        Position {
+            x: self.x.resolve(origin.x),
+            y: self.y.resolve(origin.y),
+            z: self.z.resolve(origin.z),
+            ..origin
+        }
+    }
+
+    /// Resolves these coordinates to a block position relative to `origin`.
+    pub fn resolve_block(&self, origin: Position) -> BlockPosition {
+        self.resolve(origin).block()
+    }
+
+    /// Resolves the coordinates along the left, up and forward
+    /// axes of `origin`'s rotation, matching vanilla behavior.
+    fn resolve_local(&self, origin: Position) -> Position {
+        let yaw = f64::from(origin.yaw + 90.0).to_radians();
+        let pitch = f64::from(-origin.pitch).to_radians();
+        let pitch_up = f64::from(-origin.pitch + 90.0).to_radians();
+
+        let forward = [
+            yaw.cos() * pitch.cos(),
+            pitch.sin(),
+            yaw.sin() * pitch.cos(),
+        ];
+        let up = [
+            yaw.cos() * pitch_up.cos(),
+            pitch_up.sin(),
+            yaw.sin() * pitch_up.cos(),
+        ];
+        // left = -(forward x up)
+        let left = [
+            -(forward[1] * up[2] - forward[2] * up[1]),
+            -(forward[2] * up[0] - forward[0] * up[2]),
+            -(forward[0] * up[1] - forward[1] * up[0]),
+        ];
+
+        let (l, u, f) = (self.x.value(), self.y.value(), self.z.value());
+        Position {
+            x: origin.x + forward[0] * f + up[0] * u + left[0] * l,
+            y: origin.y + forward[1] * f + up[1] * u + left[1] * l,
+            z: origin.z + forward[2] * f + up[2] * u + left[2] * l,
+            ..origin
+        }
+    }
+}
+
+/// Parses a block state like `stone` or `minecraft:oak_log[axis=x]`.
+fn parse_block_state(reader: &mut StringReader) -> Result<BlockId, SyntaxError> {
+    let start = reader.cursor();
+    let name = reader
+        .read_while(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | ':' | '/' | '.' | '-'));
+    if name.is_empty() {
+        return Err(reader.error("Expected block"));
+    }
+    let identifier = if name.contains(':') {
+        name.to_owned()
+    } else {
+        format!("minecraft:{}", name)
+    };
+
+    let block = match BlockId::from_identifier(&identifier) {
+        Some(block) => block,
+        None => {
+            return Err(SyntaxError::new(
+                format!("Unknown block type '{}'", name),
+                start,
+            ))
+        }
+    };
+
+    if reader.peek() != Some('[') {
+        return Ok(block);
+    }
+    reader.skip();
+
+    let mut properties: BTreeMap<String, String> = block
+        .to_properties_map()
+        .into_iter()
+        .map(|(key, value)| (key.to_owned(), value.to_owned()))
+        .collect();
+
+    reader.skip_whitespace();
+    while reader.peek() != Some(']') {
+        let key_start = reader.cursor();
+        let key = reader.read_unquoted_string();
+        if !properties.contains_key(key) {
+            return Err(SyntaxError::new(
+                format!("Block {} does not have property '{}'", identifier, key),
+                key_start,
+            ));
+        }
+        reader.skip_whitespace();
+        reader.expect('=')?;
+        reader.skip_whitespace();
+        let value = reader.read_unquoted_string();
+        properties.insert(key.to_owned(), value.to_owned());
+
+        reader.skip_whitespace();
+        if reader.peek() == Some(',') {
+            reader.skip();
+            reader.skip_whitespace();
+        } else if reader.peek() != Some(']') {
+            return Err(reader.error("Expected end of properties"));
+        }
+    }
+    reader.expect(']')?;
+
+    BlockId::from_identifier_and_properties(&identifier, &properties).ok_or_else(|| {
+        SyntaxError::new(
+            format!("Invalid properties for block {}", identifier),
+            start,
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use base::BlockKind;
+
+    use super::*;
+
+    fn parse(kind: ArgumentKind, input: &str) -> Result<ArgumentValue, SyntaxError> {
+        kind.parse(&mut StringReader::new(input))
+    }
+
+    #[test]
+    fn parse_integer_bounds() {
+        let kind = ArgumentKind::Integer {
+            min: Some(0),
+            max: Some(10),
+        };
+        assert_eq!(parse(kind.clone(), "5"), Ok(ArgumentValue::Integer(5)));
+        assert!(parse(kind.clone(), "11").is_err());
+        assert!(parse(kind, "-1").is_err());
+    }
+
+    #[test]
+    fn parse_coordinates() {
+        let value = parse(ArgumentKind::BlockPos, "~ ~1 5").unwrap();
+        let coordinates = match value {
+            ArgumentValue::Coordinates(c) => c,
+            v => panic!("{:?}", v),
+        };
+        assert_eq!(
+            coordinates.resolve_block(base::position!(10.5, 64.0, 0.0)),
+            BlockPosition::new(10, 65, 5)
+        );
+
+        assert!(parse(ArgumentKind::BlockPos, "1.5 2 3").is_err());
+        assert!(parse(ArgumentKind::BlockPos, "^ ~ ^").is_err());
+        assert!(parse(ArgumentKind::BlockPos, "1 2").is_err());
+    }
+
+    #[test]
+    fn local_coordinates_move_forward() {
+        let coordinates = match parse(ArgumentKind::Vec3, "^ ^ ^2").unwrap() {
+            ArgumentValue::Coordinates(c) => c,
+            v => panic!("{:?}", v),
+        };
+        // Yaw 0 faces south (+z).
+        let pos = coordinates.resolve(base::position!(0.0, 64.0, 0.0));
+        assert!(pos.x.abs() < 1e-6);
+        assert!((pos.y - 64.0).abs() < 1e-6);
+        assert!((pos.z - 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn parse_gamemodes() {
+        assert_eq!(
+            parse(ArgumentKind::Gamemode, "creative"),
+            Ok(ArgumentValue::Gamemode(Gamemode::Creative))
+        );
+        assert!(parse(ArgumentKind::Gamemode, "hardcore").is_err());
+    }
+
+    #[test]
+    fn parse_block_states() {
+        match parse(ArgumentKind::BlockState, "minecraft:oak_log[axis=x]").unwrap() {
+            ArgumentValue::BlockState(block) => {
+                assert_eq!(block.kind(), BlockKind::OakLog);
+                assert_eq!(block.axis_xyz(), Some(base::AxisXyz::X));
+            }
+            v => panic!("{:?}", v),
+        }
+        assert!(parse(ArgumentKind::BlockState, "stone").is_ok());
+        assert!(parse(ArgumentKind::BlockState, "not_a_block").is_err());
+        assert!(parse(ArgumentKind::BlockState, "stone[axis=x]").is_err());
+    }
+}
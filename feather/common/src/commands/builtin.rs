@@ -0,0 +1,422 @@
+//! Vanilla commands implemented by the server itself.
+
+use std::convert::TryFrom;
+
+use base::{anvil::level::LevelData, GameRules, Gamemode, Text, ValidBlockPosition};
+use ecs::{Entity, SysResult};
+use libcraft_text::{TextComponentBuilder, TextValue};
+use quill_common::components::{
+    CanBuild, CanCreativeFly, CreativeFlying, Health, Instabreak, Invulnerable, Name,
+    PreviousGamemode,
+};
+
+use crate::{
+    chat::{ChatKind, ChatMessage},
+    damage::{self, DamageSource},
+    events::{GameRuleChangeEvent, GamemodeEvent, WeatherChangeEvent},
+    weather::Weather,
+    Game,
+};
+
+use super::{
+    argument, arguments::gamemode_name, literal, permission_level, ArgumentKind, CommandContext,
+    CommandDispatcher,
+};
+
+/// Ticks of weather set by `/weather` without a duration.
+const DEFAULT_WEATHER_DURATION: i32 = 6000;
+
+pub fn register(dispatcher: &mut CommandDispatcher) {
+    dispatcher.register(
+        literal("help").executes(help).then(
+            argument("command", ArgumentKind::String(super::StringKind::Greedy)).executes(help),
+        ),
+    );
+
+    dispatcher.register(
+        literal("say")
+            .requires(2)
+            .then(argument("message", ArgumentKind::Message).executes(say)),
+    );
+    dispatcher.register(literal("me").then(argument("action", ArgumentKind::Message).executes(me)));
+
+    let msg = dispatcher.register(
+        literal("msg").then(
+            argument(
+                "targets",
+                ArgumentKind::Entity {
+                    single: false,
+                    players_only: true,
+                },
+            )
+            .then(argument("message", ArgumentKind::Message).executes(msg)),
+        ),
+    );
+    dispatcher.register_alias("tell", msg);
+    dispatcher.register_alias("w", msg);
+
+    dispatcher.register(
+        literal("gamemode").requires(2).then(
+            argument("gamemode", ArgumentKind::Gamemode)
+                .executes(gamemode)
+                .then(
+                    argument(
+                        "target",
+                        ArgumentKind::Entity {
+                            single: false,
+                            players_only: true,
+                        },
+                    )
+                    .executes(gamemode),
+                ),
+        ),
+    );
+
+    dispatcher.register(
+        literal("setblock").requires(2).then(
+            argument("pos", ArgumentKind::BlockPos)
+                .then(argument("block", ArgumentKind::BlockState).executes(setblock)),
+        ),
+    );
+
+    dispatcher.register(
+        literal("kill").requires(2).executes(kill).then(
+            argument(
+                "targets",
+                ArgumentKind::Entity {
+                    single: false,
+                    players_only: false,
+                },
+            )
+            .executes(kill),
+        ),
+    );
+
+    let mut weather = literal("weather").requires(2);
+    for &(name, kind) in &[
+        ("clear", Weather::Clear),
+        ("rain", Weather::Rain),
+        ("thunder", Weather::Thunder),
+    ] {
+        weather = weather.then(
+            literal(name)
+                .executes(move |game, ctx| set_weather(game, ctx, kind))
+                .then(
+                    argument(
+                        "duration",
+                        ArgumentKind::Integer {
+                            min: Some(0),
+                            max: Some(1_000_000),
+                        },
+                    )
+                    .executes(move |game, ctx| set_weather(game, ctx, kind)),
+                ),
+        );
+    }
+    dispatcher.register(weather);
+
+    let mut gamerule = literal("gamerule").requires(2);
+    let defaults = GameRules::default();
+    for &rule in GameRules::NAMES {
+        let kind = match defaults.get(rule).as_deref() {
+            Some("true") | Some("false") => ArgumentKind::Bool,
+            _ => ArgumentKind::Integer {
+                min: Some(0),
+                max: None,
+            },
+        };
+        gamerule = gamerule.then(
+            literal(rule)
+                .executes(move |game, ctx| query_game_rule(game, ctx, rule))
+                .then(
+                    argument("value", kind)
+                        .executes(move |game, ctx| set_game_rule(game, ctx, rule)),
+                ),
+        );
+    }
+    dispatcher.register(gamerule);
+}
+
+fn help(game: &mut Game, ctx: &CommandContext) -> SysResult {
+    let filter = if ctx.has_argument("command") {
+        Some(ctx.string("command")?.to_owned())
+    } else {
+        None
+    };
+
+    let permission_level = permission_level(game, ctx.sender);
+    let usages: Vec<String> = {
+        let dispatcher = game.resources.get::<CommandDispatcher>()?;
+        let root = &dispatcher.nodes()[dispatcher.root()];
+        root.children()
+            .iter()
+            .filter(|&&command| dispatcher.can_use(command, permission_level))
+            .flat_map(|&command| dispatcher.usage(command))
+            .filter(|usage| {
+                filter
+                    .as_ref()
+                    .map_or(true, |filter| usage.starts_with(filter.as_str()))
+            })
+            .collect()
+    };
+
+    if usages.is_empty() {
+        return ctx.reply(
+            game,
+            Text::from(TextValue::translate("command.unknown.command")).red(),
+        );
+    }
+    for usage in usages {
+        ctx.reply(game, format!("/{}", usage))?;
+    }
+    Ok(())
+}
+
+fn say(game: &mut Game, ctx: &CommandContext) -> SysResult {
+    let message = Text::translate_with(
+        "chat.type.announcement",
+        vec![ctx.sender_name(game), ctx.string("message")?.to_owned()],
+    );
+    game.broadcast_chat(ChatKind::PlayerChat, message);
+    Ok(())
+}
+
+fn me(game: &mut Game, ctx: &CommandContext) -> SysResult {
+    let message = Text::translate_with(
+        "chat.type.emote",
+        vec![ctx.sender_name(game), ctx.string("action")?.to_owned()],
+    );
+    game.broadcast_chat(ChatKind::PlayerChat, message);
+    Ok(())
+}
+
+fn msg(game: &mut Game, ctx: &CommandContext) -> SysResult {
+    let targets = ctx.entities(game, "targets")?;
+    if targets.is_empty() {
+        return ctx.reply(
+            game,
+            Text::from(TextValue::translate("argument.entity.notfound.player")).red(),
+        );
+    }
+
+    let message = ctx.string("message")?.to_owned();
+    let sender_name = ctx.sender_name(game);
+    for target in targets {
+        let target_name = entity_name(game, target);
+        let incoming = Text::translate_with(
+            "commands.message.display.incoming",
+            vec![sender_name.clone(), message.clone()],
+        )
+        .gray()
+        .italic();
+        let outgoing = Text::translate_with(
+            "commands.message.display.outgoing",
+            vec![target_name, message.clone()],
+        )
+        .gray()
+        .italic();
+
+        game.send_message(target, ChatMessage::new(ChatKind::System, incoming))?;
+        ctx.reply(game, outgoing)?;
+    }
+    Ok(())
+}
+
+fn gamemode(game: &mut Game, ctx: &CommandContext) -> SysResult {
+    let gamemode = ctx.gamemode("gamemode")?;
+    let targets = if ctx.has_argument("target") {
+        ctx.entities(game, "target")?
+    } else if game.ecs.get::<Gamemode>(ctx.sender).is_ok() {
+        vec![ctx.sender]
+    } else {
+        return ctx.reply(
+            game,
+            Text::from(TextValue::translate("permissions.requires.player")).red(),
+        );
+    };
+
+    let mode_name = Text::from(TextValue::translate(format!(
+        "gameMode.{}",
+        gamemode_name(gamemode)
+    )));
+    for target in targets {
+        if !set_gamemode(game, target, gamemode)? {
+            continue;
+        }
+
+        if target == ctx.sender {
+            ctx.reply(
+                game,
+                Text::translate_with("commands.gamemode.success.self", vec![mode_name.clone()]),
+            )?;
+        } else {
+            let _ = game.send_message(
+                target,
+                ChatMessage::new(
+                    ChatKind::System,
+                    Text::translate_with("gameMode.changed", vec![mode_name.clone()]),
+                ),
+            );
+            ctx.reply(
+                game,
+                Text::translate_with(
+                    "commands.gamemode.success.other",
+                    vec![Text::from(entity_name(game, target)), mode_name.clone()],
+                ),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Changes an entity's gamemode and the abilities derived from it.
+///
+/// Returns `false` if the entity already had that gamemode.
+fn set_gamemode(game: &mut Game, entity: Entity, gamemode: Gamemode) -> anyhow::Result<bool> {
+    let previous = *game.ecs.get::<Gamemode>(entity)?;
+    if previous == gamemode {
+        return Ok(false);
+    }
+
+    let can_fly = matches!(gamemode, Gamemode::Creative | Gamemode::Spectator);
+    game.ecs.insert(entity, gamemode)?;
+    game.ecs.insert(entity, PreviousGamemode(Some(previous)))?;
+    game.ecs.insert(entity, CanCreativeFly(can_fly))?;
+    game.ecs.insert(
+        entity,
+        CreativeFlying(matches!(gamemode, Gamemode::Spectator)),
+    )?;
+    game.ecs
+        .insert(entity, CanBuild(!matches!(gamemode, Gamemode::Adventure)))?;
+    game.ecs
+        .insert(entity, Instabreak(matches!(gamemode, Gamemode::Creative)))?;
+    game.ecs.insert(entity, Invulnerable(can_fly))?;
+    game.ecs
+        .insert_entity_event(entity, GamemodeEvent(gamemode))?;
+    Ok(true)
+}
+
+fn setblock(game: &mut Game, ctx: &CommandContext) -> SysResult {
+    let pos = ctx.position(game, "pos")?.block();
+    let block = ctx.block_state("block")?;
+
+    let success = match ValidBlockPosition::try_from(pos) {
+        Ok(valid) => game.block(valid) != Some(block) && game.set_block(valid, block),
+        Err(_) => false,
+    };
+
+    if success {
+        ctx.reply(
+            game,
+            Text::translate_with(
+                "commands.setblock.success",
+                vec![pos.x.to_string(), pos.y.to_string(), pos.z.to_string()],
+            ),
+        )
+    } else {
+        ctx.reply(
+            game,
+            Text::from(TextValue::translate("commands.setblock.failed")).red(),
+        )
+    }
+}
+
+fn kill(game: &mut Game, ctx: &CommandContext) -> SysResult {
+    let targets = if ctx.has_argument("targets") {
+        ctx.entities(game, "targets")?
+    } else if game.ecs.get::<Health>(ctx.sender).is_ok() {
+        vec![ctx.sender]
+    } else {
+        return ctx.reply(
+            game,
+            Text::from(TextValue::translate("permissions.requires.entity")).red(),
+        );
+    };
+    if targets.is_empty() {
+        return ctx.reply(
+            game,
+            Text::from(TextValue::translate("argument.entity.notfound.entity")).red(),
+        );
+    }
+
+    for &target in &targets {
+        if game.ecs.get::<Health>(target).is_ok() {
+            damage::damage(game, target, DamageSource::Void, f32::MAX)?;
+        } else {
+            game.remove_entity(target)?;
+        }
+    }
+
+    let message = match targets.as_slice() {
+        [target] => Text::translate_with(
+            "commands.kill.success.single",
+            vec![damage::display_name(game, *target)],
+        ),
+        _ => Text::translate_with(
+            "commands.kill.success.multiple",
+            vec![targets.len().to_string()],
+        ),
+    };
+    ctx.reply(game, message)
+}
+
+fn set_weather(game: &mut Game, ctx: &CommandContext, weather: Weather) -> SysResult {
+    // The duration is given in seconds
+    let duration = if ctx.has_argument("duration") {
+        ctx.integer("duration")? * 20
+    } else {
+        DEFAULT_WEATHER_DURATION
+    };
+    let from = {
+        let mut level = game.resources.get_mut::<LevelData>()?;
+        let from = Weather::of(&level);
+        weather.apply(&mut level, duration);
+        from
+    };
+    if from != weather {
+        game.ecs
+            .insert_event(WeatherChangeEvent { from, to: weather });
+    }
+
+    let key = match weather {
+        Weather::Clear => "commands.weather.set.clear",
+        Weather::Rain => "commands.weather.set.rain",
+        Weather::Thunder => "commands.weather.set.thunder",
+    };
+    ctx.reply(game, Text::from(TextValue::translate(key)))
+}
+
+fn query_game_rule(game: &mut Game, ctx: &CommandContext, rule: &str) -> SysResult {
+    let value = game
+        .resources
+        .get::<GameRules>()?
+        .get(rule)
+        .unwrap_or_default();
+    ctx.reply(
+        game,
+        Text::translate_with("commands.gamerule.query", vec![rule.to_owned(), value]),
+    )
+}
+
+fn set_game_rule(game: &mut Game, ctx: &CommandContext, rule: &str) -> SysResult {
+    let value = if let Ok(value) = ctx.bool("value") {
+        value.to_string()
+    } else {
+        ctx.integer("value")?.to_string()
+    };
+    game.resources.get_mut::<GameRules>()?.set(rule, &value)?;
+    game.ecs.insert_event(GameRuleChangeEvent {
+        rule: rule.to_owned(),
+    });
+    ctx.reply(
+        game,
+        Text::translate_with("commands.gamerule.set", vec![rule.to_owned(), value]),
+    )
+}
+
+fn entity_name(game: &Game, entity: Entity) -> String {
+    game.ecs
+        .get::<Name>(entity)
+        .map(|name| name.to_string())
+        .unwrap_or_else(|_| "Server".to_owned())
+}
@@ -0,0 +1,241 @@
+use std::fmt::{self, Display};
+
+/// An error encountered while parsing a command.
+///
+/// `cursor` is the byte offset into the command
+/// where parsing failed, used to point the player
+/// at the offending input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxError {
+    pub message: String,
+    pub cursor: usize,
+}
+
+impl SyntaxError {
+    pub fn new(message: impl Into<String>, cursor: usize) -> Self {
+        Self {
+            message: message.into(),
+            cursor,
+        }
+    }
+}
+
+impl Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.cursor)
+    }
+}
+
+impl std::error::Error for SyntaxError {}
+
+/// A cursor over command input, modeled after
+/// Brigadier's `StringReader`.
+#[derive(Debug, Clone)]
+pub struct StringReader<'a> {
+    input: &'a str,
+    cursor: usize,
+}
+
+impl<'a> StringReader<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self { input, cursor: 0 }
+    }
+
+    pub fn input(&self) -> &'a str {
+        self.input
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn set_cursor(&mut self, cursor: usize) {
+        self.cursor = cursor;
+    }
+
+    /// Returns the input which has not been consumed yet.
+    pub fn remaining(&self) -> &'a str {
+        &self.input[self.cursor..]
+    }
+
+    pub fn can_read(&self) -> bool {
+        self.cursor < self.input.len()
+    }
+
+    pub fn peek(&self) -> Option<char> {
+        self.remaining().chars().next()
+    }
+
+    pub fn skip(&mut self) {
+        if let Some(c) = self.peek() {
+            self.cursor += c.len_utf8();
+        }
+    }
+
+    pub fn error(&self, message: impl Into<String>) -> SyntaxError {
+        SyntaxError::new(message, self.cursor)
+    }
+
+    pub fn skip_whitespace(&mut self) {
+        while self.peek() == Some(' ') {
+            self.skip();
+        }
+    }
+
+    /// Consumes `expected` or fails.
+    pub fn expect(&mut self, expected: char) -> Result<(), SyntaxError> {
+        if self.peek() == Some(expected) {
+            self.skip();
+            Ok(())
+        } else {
+            Err(self.error(format!("Expected '{}'", expected)))
+        }
+    }
+
+    /// Consumes characters while `predicate` returns true.
+    pub fn read_while(&mut self, predicate: impl Fn(char) -> bool) -> &'a str {
+        let start = self.cursor;
+        while let Some(c) = self.peek() {
+            if !predicate(c) {
+                break;
+            }
+            self.skip();
+        }
+        &self.input[start..self.cursor]
+    }
+
+    /// Reads until the next space or the end of input.
+    pub fn read_word(&mut self) -> &'a str {
+        self.read_while(|c| c != ' ')
+    }
+
+    /// Reads a string made of characters which are
+    /// allowed outside of quotes.
+    pub fn read_unquoted_string(&mut self) -> &'a str {
+        self.read_while(is_allowed_in_unquoted_string)
+    }
+
+    /// Reads a string delimited by `"` or `'`, handling `\` escapes.
+    pub fn read_quoted_string(&mut self) -> Result<String, SyntaxError> {
+        let quote = match self.peek() {
+            Some(c) if is_quote(c) => c,
+            _ => return Err(self.error("Expected quote to start a string")),
+        };
+        self.skip();
+
+        let mut result = String::new();
+        let mut escaped = false;
+        while let Some(c) = self.peek() {
+            self.skip();
+            if escaped {
+                if c == quote || c == '\\' {
+                    result.push(c);
+                    escaped = false;
+                } else {
+                    return Err(
+                        self.error(format!("Invalid escape sequence '{}' in quoted string", c))
+                    );
+                }
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == quote {
+                return Ok(result);
+            } else {
+                result.push(c);
+            }
+        }
+
+        Err(self.error("Unclosed quoted string"))
+    }
+
+    /// Reads either a quoted or an unquoted string.
+    pub fn read_string(&mut self) -> Result<String, SyntaxError> {
+        match self.peek() {
+            Some(c) if is_quote(c) => self.read_quoted_string(),
+            _ => Ok(self.read_unquoted_string().to_owned()),
+        }
+    }
+
+    pub fn read_int(&mut self) -> Result<i32, SyntaxError> {
+        let start = self.cursor;
+        let number = self.read_while(is_allowed_number);
+        if number.is_empty() {
+            return Err(self.error("Expected integer"));
+        }
+        number.parse().map_err(|_| {
+            self.cursor = start;
+            self.error(format!("Invalid integer '{}'", number))
+        })
+    }
+
+    pub fn read_double(&mut self) -> Result<f64, SyntaxError> {
+        let start = self.cursor;
+        let number = self.read_while(is_allowed_number);
+        if number.is_empty() {
+            return Err(self.error("Expected double"));
+        }
+        number.parse().map_err(|_| {
+            self.cursor = start;
+            self.error(format!("Invalid double '{}'", number))
+        })
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, SyntaxError> {
+        let start = self.cursor;
+        match self.read_unquoted_string() {
+            "true" => Ok(true),
+            "false" => Ok(false),
+            "" => Err(self.error("Expected bool")),
+            value => {
+                self.cursor = start;
+                Err(self.error(format!(
+                    "Invalid bool, expected true or false but found '{}'",
+                    value
+                )))
+            }
+        }
+    }
+}
+
+pub fn is_allowed_in_unquoted_string(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '+')
+}
+
+pub fn is_allowed_number(c: char) -> bool {
+    c.is_ascii_digit() || c == '.' || c == '-'
+}
+
+fn is_quote(c: char) -> bool {
+    c == '"' || c == '\''
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_words_and_numbers() {
+        let mut reader = StringReader::new("hello 42 -1.5 true");
+        assert_eq!(reader.read_word(), "hello");
+        reader.skip_whitespace();
+        assert_eq!(reader.read_int(), Ok(42));
+        reader.skip_whitespace();
+        assert_eq!(reader.read_double(), Ok(-1.5));
+        reader.skip_whitespace();
+        assert_eq!(reader.read_bool(), Ok(true));
+        assert!(!reader.can_read());
+    }
+
+    #[test]
+    fn read_quoted_string_with_escapes() {
+        let mut reader = StringReader::new(r#""say \"hi\"" rest"#);
+        assert_eq!(reader.read_string().unwrap(), r#"say "hi""#);
+        assert_eq!(reader.remaining(), " rest");
+    }
+
+    #[test]
+    fn unclosed_quote_is_an_error() {
+        let mut reader = StringReader::new("'oops");
+        assert!(reader.read_string().is_err());
+    }
+}
@@ -0,0 +1,292 @@
+//! Entity selectors: `@p`, `@r`, `@a`, `@e`, `@s`
+//! and player names.
+//!
+//! See <https://minecraft.fandom.com/wiki/Target_selectors>.
+
+use base::{EntityKind, Position};
+use ecs::Entity;
+use quill_common::{components::Name, entities::Player};
+use rand::seq::SliceRandom;
+
+use crate::Game;
+
+use super::reader::{StringReader, SyntaxError};
+
+/// The base of a selector, i.e. the part before the `[arguments]`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SelectorTarget {
+    /// `@p`
+    NearestPlayer,
+    /// `@r`
+    RandomPlayer,
+    /// `@a`
+    AllPlayers,
+    /// `@e`
+    AllEntities,
+    /// `@s`
+    Sender,
+    /// A player's username.
+    Player(String),
+}
+
+/// A parsed entity selector. Use [`EntitySelector::select`]
+/// to find the entities it refers to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntitySelector {
+    pub target: SelectorTarget,
+    /// `limit=<n>`
+    pub limit: Option<usize>,
+    /// `distance=<range>`; bounds are inclusive.
+    pub distance: Option<(Option<f64>, Option<f64>)>,
+    /// `name=<name>`, possibly negated with `!`.
+    pub name: Option<(String, bool)>,
+}
+
+impl EntitySelector {
+    fn new(target: SelectorTarget) -> Self {
+        Self {
+            target,
+            limit: None,
+            distance: None,
+            name: None,
+        }
+    }
+
+    /// Parses a selector.
+    pub fn parse(reader: &mut StringReader) -> Result<Self, SyntaxError> {
+        if reader.peek() != Some('@') {
+            let start = reader.cursor();
+            let name = reader.read_while(|c| c != ' ');
+            if name.is_empty() || name.len() > 16 {
+                reader.set_cursor(start);
+                return Err(reader.error("Invalid name or UUID"));
+            }
+            return Ok(Self::new(SelectorTarget::Player(name.to_owned())));
+        }
+
+        reader.skip();
+        let target = match reader.peek() {
+            Some('p') => SelectorTarget::NearestPlayer,
+            Some('r') => SelectorTarget::RandomPlayer,
+            Some('a') => SelectorTarget::AllPlayers,
+            Some('e') => SelectorTarget::AllEntities,
+            Some('s') => SelectorTarget::Sender,
+            _ => return Err(reader.error("Unknown selector type")),
+        };
+        reader.skip();
+
+        let mut selector = Self::new(target);
+        if reader.peek() == Some('[') {
+            reader.skip();
+            selector.parse_arguments(reader)?;
+        }
+
+        Ok(selector)
+    }
+
+    fn parse_arguments(&mut self, reader: &mut StringReader) -> Result<(), SyntaxError> {
+        reader.skip_whitespace();
+        while reader.peek() != Some(']') {
+            let start = reader.cursor();
+            let key = reader.read_unquoted_string();
+            reader.skip_whitespace();
+            reader.expect('=')?;
+            reader.skip_whitespace();
+
+            match key {
+                "limit" => {
+                    let limit = reader.read_int()?;
+                    if limit < 1 {
+                        return Err(reader.error("Limit must be at least 1"));
+                    }
+                    self.limit = Some(limit as usize);
+                }
+                "distance" => {
+                    let range = parse_range(reader)?;
+                    if range.0.map_or(false, |min| min < 0.0) {
+                        return Err(reader.error("Distance cannot be negative"));
+                    }
+                    self.distance = Some(range);
+                }
+                "name" => {
+                    let negated = reader.peek() == Some('!');
+                    if negated {
+                        reader.skip();
+                    }
+                    self.name = Some((reader.read_string()?, negated));
+                }
+                _ => {
+                    reader.set_cursor(start);
+                    return Err(reader.error(format!("Unknown option '{}'", key)));
+                }
+            }
+
+            reader.skip_whitespace();
+            if reader.peek() == Some(',') {
+                reader.skip();
+                reader.skip_whitespace();
+            } else if reader.peek() != Some(']') {
+                return Err(reader.error("Expected end of options"));
+            }
+        }
+        reader.expect(']')
+    }
+
+    /// Returns whether this selector can match more than one entity.
+    pub fn may_select_multiple(&self) -> bool {
+        match self.target {
+            SelectorTarget::AllPlayers | SelectorTarget::AllEntities => {
+                self.limit.map_or(true, |limit| limit > 1)
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns whether this selector can match non-player entities.
+    pub fn may_select_non_players(&self) -> bool {
+        matches!(
+            self.target,
+            SelectorTarget::AllEntities | SelectorTarget::Sender
+        )
+    }
+
+    /// Finds the entities matched by this selector, as seen by `sender`.
+    pub fn select(&self, game: &Game, sender: Entity) -> Vec<Entity> {
+        let origin = game
+            .ecs
+            .get::<Position>(sender)
+            .map(|pos| *pos)
+            .unwrap_or_default();
+
+        let mut candidates: Vec<(Entity, Position)> = match &self.target {
+            SelectorTarget::Sender => game
+                .ecs
+                .get::<Position>(sender)
+                .map(|pos| vec![(sender, *pos)])
+                .unwrap_or_default(),
+            SelectorTarget::Player(name) => game
+                .ecs
+                .query::<(&Player, &Name, &Position)>()
+                .iter()
+                .filter(|(_, (_, player_name, _))| player_name.as_str().eq_ignore_ascii_case(name))
+                .map(|(entity, (_, _, &pos))| (entity, pos))
+                .collect(),
+            SelectorTarget::AllEntities => game
+                .ecs
+                .query::<(&EntityKind, &Position)>()
+                .iter()
+                .map(|(entity, (_, &pos))| (entity, pos))
+                .collect(),
+            _ => game
+                .ecs
+                .query::<(&Player, &Position)>()
+                .iter()
+                .map(|(entity, (_, &pos))| (entity, pos))
+                .collect(),
+        };
+
+        if let Some((min, max)) = self.distance {
+            candidates.retain(|(_, pos)| {
+                let distance = pos.distance_to(origin);
+                min.map_or(true, |min| distance >= min) && max.map_or(true, |max| distance <= max)
+            });
+        }
+
+        if let Some((name, negated)) = &self.name {
+            candidates.retain(|&(entity, _)| {
+                let matches = game
+                    .ecs
+                    .get::<Name>(entity)
+                    .map_or(false, |entity_name| entity_name.as_str() == name);
+                matches != *negated
+            });
+        }
+
+        let limit = match self.target {
+            SelectorTarget::NearestPlayer => {
+                candidates.sort_by(|(_, a), (_, b)| {
+                    a.distance_squared_to(origin)
+                        .partial_cmp(&b.distance_squared_to(origin))
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                });
+                self.limit.unwrap_or(1)
+            }
+            SelectorTarget::RandomPlayer => {
+                candidates.shuffle(&mut rand::thread_rng());
+                self.limit.unwrap_or(1)
+            }
+            _ => self.limit.unwrap_or(usize::MAX),
+        };
+
+        candidates
+            .into_iter()
+            .take(limit)
+            .map(|(entity, _)| entity)
+            .collect()
+    }
+}
+
+/// Parses a range like `5`, `..5`, `5..` or `1..5`.
+fn parse_range(reader: &mut StringReader) -> Result<(Option<f64>, Option<f64>), SyntaxError> {
+    let start = reader.cursor();
+    let range = reader.read_while(|c| c.is_ascii_digit() || c == '.' || c == '-');
+    let parse_bound = |bound: &str| -> Result<Option<f64>, SyntaxError> {
+        if bound.is_empty() {
+            Ok(None)
+        } else {
+            bound
+                .parse()
+                .map(Some)
+                .map_err(|_| SyntaxError::new(format!("Invalid range '{}'", range), start))
+        }
+    };
+
+    let (min, max) = match range.find("..") {
+        Some(index) => (
+            parse_bound(&range[..index])?,
+            parse_bound(&range[index + 2..])?,
+        ),
+        None => {
+            let value = parse_bound(range)?;
+            (value, value)
+        }
+    };
+
+    if min.is_none() && max.is_none() {
+        return Err(SyntaxError::new("Expected value or range of values", start));
+    }
+    Ok((min, max))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> Result<EntitySelector, SyntaxError> {
+        EntitySelector::parse(&mut StringReader::new(input))
+    }
+
+    #[test]
+    fn parse_selector_types() {
+        assert_eq!(parse("@p").unwrap().target, SelectorTarget::NearestPlayer);
+        assert_eq!(parse("@e").unwrap().target, SelectorTarget::AllEntities);
+        assert_eq!(
+            parse("Notch").unwrap().target,
+            SelectorTarget::Player("Notch".to_owned())
+        );
+        assert!(parse("@x").is_err());
+    }
+
+    #[test]
+    fn parse_selector_arguments() {
+        let selector = parse("@a[limit=2, distance=..10,name=!Steve]").unwrap();
+        assert_eq!(selector.limit, Some(2));
+        assert_eq!(selector.distance, Some((None, Some(10.0))));
+        assert_eq!(selector.name, Some(("Steve".to_owned(), true)));
+        assert!(selector.may_select_multiple());
+
+        assert!(!parse("@a[limit=1]").unwrap().may_select_multiple());
+        assert!(parse("@a[foo=1]").is_err());
+        assert!(parse("@a[limit=1").is_err());
+    }
+}
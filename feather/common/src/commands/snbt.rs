@@ -0,0 +1,208 @@
+//! Parser for stringified NBT (SNBT), the textual
+//! NBT format used in command arguments like
+//! `{CustomName:'"Bob"',Health:20.0f}`.
+
+use nbt::Value;
+
+use super::reader::{is_allowed_in_unquoted_string, StringReader, SyntaxError};
+
+/// Parses a compound tag.
+pub fn parse_compound(reader: &mut StringReader) -> Result<Value, SyntaxError> {
+    reader.skip_whitespace();
+    if reader.peek() != Some('{') {
+        return Err(reader.error("Expected '{'"));
+    }
+    parse_value(reader)
+}
+
+/// Parses any tag.
+pub fn parse_value(reader: &mut StringReader) -> Result<Value, SyntaxError> {
+    reader.skip_whitespace();
+    match reader.peek() {
+        Some('{') => parse_compound_body(reader),
+        Some('[') => parse_list_or_array(reader),
+        Some('"') | Some('\'') => Ok(Value::String(reader.read_quoted_string()?)),
+        Some(_) => {
+            let token = reader.read_unquoted_string();
+            if token.is_empty() {
+                return Err(reader.error("Expected value"));
+            }
+            Ok(parse_primitive(token).unwrap_or_else(|| Value::String(token.to_owned())))
+        }
+        None => Err(reader.error("Expected value")),
+    }
+}
+
+fn parse_compound_body(reader: &mut StringReader) -> Result<Value, SyntaxError> {
+    reader.expect('{')?;
+    let mut entries = Vec::new();
+
+    reader.skip_whitespace();
+    while reader.peek() != Some('}') {
+        reader.skip_whitespace();
+        let key = reader.read_string()?;
+        if key.is_empty() {
+            return Err(reader.error("Expected key"));
+        }
+        reader.skip_whitespace();
+        reader.expect(':')?;
+        let value = parse_value(reader)?;
+        entries.push((key, value));
+
+        reader.skip_whitespace();
+        if reader.peek() == Some(',') {
+            reader.skip();
+            reader.skip_whitespace();
+        } else if reader.peek() != Some('}') {
+            return Err(reader.error("Expected '}'"));
+        }
+    }
+    reader.expect('}')?;
+
+    Ok(Value::Compound(entries.into_iter().collect()))
+}
+
+fn parse_list_or_array(reader: &mut StringReader) -> Result<Value, SyntaxError> {
+    reader.expect('[')?;
+
+    let remaining = reader.remaining();
+    let array_kind = match (remaining.chars().next(), remaining.chars().nth(1)) {
+        (Some(kind @ 'B'), Some(';'))
+        | (Some(kind @ 'I'), Some(';'))
+        | (Some(kind @ 'L'), Some(';')) => Some(kind),
+        _ => None,
+    };
+
+    if let Some(kind) = array_kind {
+        reader.skip();
+        reader.skip();
+        let start = reader.cursor();
+        let values = parse_elements(reader)?;
+        return match kind {
+            'B' => values
+                .into_iter()
+                .map(|value| match value {
+                    Value::Byte(b) => Ok(b),
+                    _ => Err(SyntaxError::new("Expected byte in byte array", start)),
+                })
+                .collect::<Result<_, _>>()
+                .map(Value::ByteArray),
+            'I' => values
+                .into_iter()
+                .map(|value| match value {
+                    Value::Int(i) => Ok(i),
+                    _ => Err(SyntaxError::new("Expected int in int array", start)),
+                })
+                .collect::<Result<_, _>>()
+                .map(Value::IntArray),
+            _ => values
+                .into_iter()
+                .map(|value| match value {
+                    Value::Long(l) => Ok(l),
+                    _ => Err(SyntaxError::new("Expected long in long array", start)),
+                })
+                .collect::<Result<_, _>>()
+                .map(Value::LongArray),
+        };
+    }
+
+    Ok(Value::List(parse_elements(reader)?))
+}
+
+/// Parses comma-separated values up to and including the closing `]`.
+fn parse_elements(reader: &mut StringReader) -> Result<Vec<Value>, SyntaxError> {
+    let mut values = Vec::new();
+
+    reader.skip_whitespace();
+    while reader.peek() != Some(']') {
+        values.push(parse_value(reader)?);
+
+        reader.skip_whitespace();
+        if reader.peek() == Some(',') {
+            reader.skip();
+            reader.skip_whitespace();
+        } else if reader.peek() != Some(']') {
+            return Err(reader.error("Expected ']'"));
+        }
+    }
+    reader.expect(']')?;
+
+    Ok(values)
+}
+
+/// Interprets an unquoted token as a number or boolean,
+/// returning `None` if it should be treated as a string.
+fn parse_primitive(token: &str) -> Option<Value> {
+    match token {
+        "true" => return Some(Value::Byte(1)),
+        "false" => return Some(Value::Byte(0)),
+        _ => (),
+    }
+
+    if !token.chars().all(is_allowed_in_unquoted_string) {
+        return None;
+    }
+
+    let (number, suffix) = match token.chars().last()? {
+        c if c.is_ascii_alphabetic() => (&token[..token.len() - 1], Some(c.to_ascii_lowercase())),
+        _ => (token, None),
+    };
+
+    match suffix {
+        Some('b') => number.parse().ok().map(Value::Byte),
+        Some('s') => number.parse().ok().map(Value::Short),
+        Some('l') => number.parse().ok().map(Value::Long),
+        Some('f') => number.parse().ok().map(Value::Float),
+        Some('d') => number.parse().ok().map(Value::Double),
+        Some(_) => None,
+        None if number.contains('.') => number.parse().ok().map(Value::Double),
+        None => number.parse().ok().map(Value::Int),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> Result<Value, SyntaxError> {
+        parse_compound(&mut StringReader::new(input))
+    }
+
+    #[test]
+    fn parse_nested_compound() {
+        let value = parse("{Health:20.0f,Tags:[a,\"b c\"],Pos:[I;1,2,3],Sub:{Flag:true}}").unwrap();
+        let compound = match value {
+            Value::Compound(compound) => compound,
+            value => panic!("expected compound, got {:?}", value),
+        };
+        assert_eq!(compound["Health"], Value::Float(20.0));
+        assert_eq!(
+            compound["Tags"],
+            Value::List(vec![
+                Value::String("a".to_owned()),
+                Value::String("b c".to_owned())
+            ])
+        );
+        assert_eq!(compound["Pos"], Value::IntArray(vec![1, 2, 3]));
+        match &compound["Sub"] {
+            Value::Compound(sub) => assert_eq!(sub["Flag"], Value::Byte(1)),
+            value => panic!("expected compound, got {:?}", value),
+        }
+    }
+
+    #[test]
+    fn parse_number_suffixes() {
+        assert_eq!(parse_primitive("3b"), Some(Value::Byte(3)));
+        assert_eq!(parse_primitive("3s"), Some(Value::Short(3)));
+        assert_eq!(parse_primitive("3L"), Some(Value::Long(3)));
+        assert_eq!(parse_primitive("3.5d"), Some(Value::Double(3.5)));
+        assert_eq!(parse_primitive("3"), Some(Value::Int(3)));
+        assert_eq!(parse_primitive("abc"), None);
+    }
+
+    #[test]
+    fn reject_unclosed_compound() {
+        assert!(parse("{a:1").is_err());
+        assert!(parse("[1]").is_err());
+    }
+}
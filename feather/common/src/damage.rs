@@ -0,0 +1,649 @@
+//! Health, damage and death.
+//!
+//! All damage goes through [`damage`], which applies
+//! invulnerability ticks and armor before lowering an
+//! entity's [`Health`]. Entities whose health drops to
+//! zero die; players stay [`Dead`] until they respawn.
+
+use std::convert::TryFrom;
+
+use base::{
+    inventory::{SLOT_ARMOR_MAX, SLOT_ARMOR_MIN, SLOT_CRAFTING_INPUT_X0_Y0, SLOT_OFFHAND},
+    BlockKind, EntityKind, GameRules, Position, Text, ValidBlockPosition,
+};
+use ecs::{Entity, SysResult, SystemExecutor};
+use libcraft_items::{EnchantmentKind, InventorySlot, Item};
+use libcraft_text::TextValue;
+use quill_common::{
+    components::{CreativeFlying, Health, Invulnerable, Name, OnGround},
+    entities::Player,
+};
+
+use crate::{
+    chat::ChatKind,
+    digging::{self, enchantment_level},
+    dropped_items,
+    events::{
+        EntityDamageEvent, EntityDeathEvent, EntityRemoveEvent, HealthChangeEvent,
+        InventoryUpdateEvent,
+    },
+    Game, Window,
+};
+
+/// The health of players after joining for the first time or respawning.
+pub const PLAYER_MAX_HEALTH: f32 = 20.0;
+
+/// Number of ticks an entity can't take full damage after being hurt.
+const INVULNERABILITY_TICKS: u32 = 20;
+
+/// Entities falling further than this take fall damage.
+const SAFE_FALL_DISTANCE: f64 = 3.0;
+
+/// Entities below this height take void damage.
+const VOID_Y: f64 = -64.0;
+
+/// Damage per tick taken in the void.
+const VOID_DAMAGE: f32 = 4.0;
+
+/// Ticks between each half heart regenerated by players. There is
+/// no hunger yet, so players heal like vanilla players with a
+/// nearly full food bar.
+const REGENERATION_INTERVAL: u64 = 80;
+
+/// Ticks until a dead entity other than a player is removed,
+/// so that clients can play the death animation.
+const DEATH_ANIMATION_TICKS: u32 = 20;
+
+/// The cause of damage to an entity.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DamageSource {
+    Generic,
+    Fall,
+    /// Burning after standing in fire or lava.
+    Fire,
+    Lava,
+    Drowning,
+    Starvation,
+    /// Falling out of the world or the `/kill` command.
+    Void,
+    Attack {
+        attacker: Entity,
+    },
+}
+
+impl DamageSource {
+    /// Whether armor points don't reduce this damage.
+    pub fn bypasses_armor(self) -> bool {
+        !matches!(self, DamageSource::Lava | DamageSource::Attack { .. })
+    }
+
+    /// Whether this damage hurts even invulnerable
+    /// entities, like players in creative mode.
+    pub fn bypasses_invulnerability(self) -> bool {
+        self == DamageSource::Void
+    }
+
+    /// Returns the death message of `victim` killed by this damage.
+    pub fn death_message(self, game: &Game, victim: Entity) -> Text {
+        let victim = display_name(game, victim);
+        let key = match self {
+            DamageSource::Generic => "death.attack.generic",
+            DamageSource::Fall => "death.fell.accident.generic",
+            DamageSource::Fire => "death.attack.onFire",
+            DamageSource::Lava => "death.attack.lava",
+            DamageSource::Drowning => "death.attack.drown",
+            DamageSource::Starvation => "death.attack.starve",
+            DamageSource::Void => "death.attack.outOfWorld",
+            DamageSource::Attack { attacker } => {
+                let key = if game.ecs.get::<Player>(attacker).is_ok() {
+                    "death.attack.player"
+                } else {
+                    "death.attack.mob"
+                };
+                return Text::translate_with(key, vec![victim, display_name(game, attacker)]);
+            }
+        };
+        Text::translate_with(key, vec![victim])
+    }
+}
+
+/// Marks a dead entity. Holds the number of ticks since it died.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Dead(pub u32);
+
+/// Tracks the invulnerability ticks of an entity after it was hurt.
+///
+/// While more than half of the ticks remain, the entity only
+/// takes damage exceeding the damage that caused them.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct HurtCooldown {
+    pub ticks: u32,
+    pub last_damage: f32,
+}
+
+impl HurtCooldown {
+    /// Returns the damage an entity takes from a hit of
+    /// `amount`, updating the cooldown. `None` if the
+    /// hit is absorbed entirely.
+    fn hit(&mut self, amount: f32) -> Option<f32> {
+        if self.ticks > INVULNERABILITY_TICKS / 2 {
+            if amount <= self.last_damage {
+                return None;
+            }
+            let taken = amount - self.last_damage;
+            self.last_damage = amount;
+            Some(taken)
+        } else {
+            self.ticks = INVULNERABILITY_TICKS;
+            self.last_damage = amount;
+            Some(amount)
+        }
+    }
+}
+
+/// The distance an entity has fallen since it last
+/// stood on the ground.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct FallDistance {
+    pub distance: f64,
+    last_y: Option<f64>,
+}
+
+impl FallDistance {
+    /// Forgets the fall so far, e.g. after a teleport.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+pub fn register(systems: &mut SystemExecutor<Game>) {
+    systems
+        .add_system(tick_hurt_cooldowns)
+        .add_system(apply_fall_damage)
+        .add_system(apply_void_damage)
+        .add_system(regenerate_health)
+        .add_system(remove_dead_entities);
+}
+
+/// Damages an entity with a [`Health`] component.
+///
+/// Returns whether the entity took damage.
+pub fn damage(
+    game: &mut Game,
+    entity: Entity,
+    source: DamageSource,
+    amount: f32,
+) -> anyhow::Result<bool> {
+    if game.ecs.get::<Dead>(entity).is_ok()
+        || game.ecs.get::<EntityRemoveEvent>(entity).is_ok()
+        || game.ecs.get::<Health>(entity).is_err()
+        || !can_be_hurt_by(game, entity, source)?
+    {
+        return Ok(false);
+    }
+
+    let mut cooldown = game
+        .ecs
+        .get::<HurtCooldown>(entity)
+        .map(|cooldown| *cooldown)
+        .unwrap_or_default();
+    let amount = match cooldown.hit(amount) {
+        Some(amount) => amount,
+        None => return Ok(false),
+    };
+    game.ecs.insert(entity, cooldown)?;
+
+    let amount = reduce_damage(game, entity, source, amount)?;
+    let health = {
+        let mut health = game.ecs.get_mut::<Health>(entity)?;
+        health.0 = (health.0 - amount).max(0.0);
+        health.0
+    };
+    game.ecs
+        .insert_entity_event(entity, EntityDamageEvent { source, amount })?;
+    game.ecs.insert_entity_event(entity, HealthChangeEvent)?;
+
+    if health <= 0.0 {
+        kill(game, entity, source)?;
+    }
+    Ok(true)
+}
+
+/// Whether an entity takes damage from `source` at all.
+fn can_be_hurt_by(game: &Game, entity: Entity, source: DamageSource) -> anyhow::Result<bool> {
+    if source.bypasses_invulnerability() {
+        return Ok(true);
+    }
+    if game
+        .ecs
+        .get::<Invulnerable>(entity)
+        .map_or(false, |invulnerable| invulnerable.0)
+    {
+        return Ok(false);
+    }
+
+    let rules = game.resources.get::<GameRules>()?;
+    Ok(match source {
+        DamageSource::Fall => rules.fall_damage,
+        DamageSource::Fire | DamageSource::Lava => rules.fire_damage,
+        DamageSource::Drowning => rules.drowning_damage,
+        _ => true,
+    })
+}
+
+/// Applies the armor and protection enchantments
+/// of an entity to damage it takes.
+fn reduce_damage(
+    game: &Game,
+    entity: Entity,
+    source: DamageSource,
+    amount: f32,
+) -> anyhow::Result<f32> {
+    let window = match game.ecs.get::<Window>(entity) {
+        Ok(window) => window,
+        Err(_) => return Ok(amount),
+    };
+
+    let mut armor = 0.0;
+    let mut toughness = 0.0;
+    let mut protection = 0;
+    for slot in SLOT_ARMOR_MIN..=SLOT_ARMOR_MAX {
+        let item = window.item(slot)?;
+        if let InventorySlot::Filled(stack) = &*item {
+            let (points, item_toughness) = armor_attributes(stack.item());
+            armor += points;
+            toughness += item_toughness;
+        }
+        protection += protection_factor(&item, source);
+    }
+
+    let mut amount = amount;
+    if !source.bypasses_armor() {
+        amount = damage_after_armor(amount, armor, toughness);
+    }
+    Ok(damage_after_protection(amount, protection))
+}
+
+/// Returns the armor points and armor toughness of an item.
+fn armor_attributes(item: Item) -> (f32, f32) {
+    match item {
+        Item::LeatherHelmet | Item::LeatherBoots => (1.0, 0.0),
+        Item::LeatherChestplate => (3.0, 0.0),
+        Item::LeatherLeggings => (2.0, 0.0),
+        Item::ChainmailHelmet => (2.0, 0.0),
+        Item::ChainmailChestplate => (5.0, 0.0),
+        Item::ChainmailLeggings => (4.0, 0.0),
+        Item::ChainmailBoots => (1.0, 0.0),
+        Item::IronHelmet | Item::IronBoots => (2.0, 0.0),
+        Item::IronChestplate => (6.0, 0.0),
+        Item::IronLeggings => (5.0, 0.0),
+        Item::GoldenHelmet => (2.0, 0.0),
+        Item::GoldenChestplate => (5.0, 0.0),
+        Item::GoldenLeggings => (3.0, 0.0),
+        Item::GoldenBoots => (1.0, 0.0),
+        Item::DiamondHelmet | Item::DiamondBoots => (3.0, 2.0),
+        Item::DiamondChestplate => (8.0, 2.0),
+        Item::DiamondLeggings => (6.0, 2.0),
+        Item::NetheriteHelmet | Item::NetheriteBoots => (3.0, 3.0),
+        Item::NetheriteChestplate => (8.0, 3.0),
+        Item::NetheriteLeggings => (6.0, 3.0),
+        Item::TurtleHelmet => (2.0, 0.0),
+        _ => (0.0, 0.0),
+    }
+}
+
+/// The enchantment protection factor of an armor piece against `source`.
+fn protection_factor(item: &InventorySlot, source: DamageSource) -> u32 {
+    if source.bypasses_invulnerability() || source == DamageSource::Starvation {
+        return 0;
+    }
+    let mut factor = enchantment_level(item, EnchantmentKind::Protection);
+    match source {
+        DamageSource::Fire | DamageSource::Lava => {
+            factor += 2 * enchantment_level(item, EnchantmentKind::FireProtection)
+        }
+        DamageSource::Fall => {
+            factor += 3 * enchantment_level(item, EnchantmentKind::FeatherFalling)
+        }
+        _ => {}
+    }
+    factor
+}
+
+/// Vanilla's armor formula: each armor point blocks 4% of the
+/// damage, but strong hits pierce armor unless it is tough.
+fn damage_after_armor(amount: f32, armor: f32, toughness: f32) -> f32 {
+    let piercing = amount / (2.0 + toughness / 4.0);
+    let effective_armor = (armor - piercing).max(armor * 0.2).min(20.0);
+    amount * (1.0 - effective_armor / 25.0)
+}
+
+/// Each point of protection blocks 4% of the damage, up to 80%.
+fn damage_after_protection(amount: f32, protection: u32) -> f32 {
+    amount * (1.0 - protection.min(20) as f32 / 25.0)
+}
+
+/// Kills an entity, regardless of its health.
+pub fn kill(game: &mut Game, entity: Entity, source: DamageSource) -> SysResult {
+    if game.ecs.get::<Dead>(entity).is_ok() {
+        return Ok(());
+    }
+    game.ecs.get_mut::<Health>(entity)?.0 = 0.0;
+    game.ecs.insert(entity, Dead::default())?;
+    game.ecs.insert_entity_event(entity, HealthChangeEvent)?;
+    let message = source.death_message(game, entity);
+
+    if game.ecs.get::<Player>(entity).is_ok() {
+        digging::stop_digging(game, entity)?;
+        let rules = game.resources.get::<GameRules>()?.clone();
+        if rules.show_death_messages {
+            game.broadcast_chat(ChatKind::System, message.clone());
+        }
+        if !rules.keep_inventory {
+            drop_inventory(game, entity)?;
+        }
+    }
+
+    game.ecs
+        .insert_entity_event(entity, EntityDeathEvent { source, message })?;
+    Ok(())
+}
+
+/// Drops the whole inventory of a player, including
+/// their armor and crafting grid, around them.
+fn drop_inventory(game: &mut Game, player: Entity) -> SysResult {
+    let slots: Vec<usize> = (SLOT_CRAFTING_INPUT_X0_Y0..=SLOT_OFFHAND).collect();
+    let items = {
+        let window = game.ecs.get::<Window>(player)?;
+        let mut items = Vec::new();
+        for &slot in &slots {
+            if let InventorySlot::Filled(item) = window.item(slot)?.take_all() {
+                items.push(item);
+            }
+        }
+        items
+    };
+    game.ecs
+        .insert_entity_event(player, InventoryUpdateEvent(slots))?;
+
+    let position = *game.ecs.get::<Position>(player)?;
+    dropped_items::scatter_items(game, position, items);
+    Ok(())
+}
+
+/// The name of an entity as shown in death messages.
+pub fn display_name(game: &Game, entity: Entity) -> Text {
+    if let Ok(name) = game.ecs.get::<Name>(entity) {
+        return Text::from(name.to_string());
+    }
+    let kind = game
+        .ecs
+        .get::<EntityKind>(entity)
+        .map(|kind| *kind)
+        .unwrap_or(EntityKind::Player);
+    Text::from(TextValue::translate(format!(
+        "entity.minecraft.{}",
+        kind.name()
+    )))
+}
+
+/// The health mobs spawn with. `None` for entities which
+/// can't be hurt, like items, and for players, whose health
+/// is loaded from their saved data or [`PLAYER_MAX_HEALTH`].
+pub fn max_health(kind: EntityKind) -> Option<f32> {
+    let health = match kind {
+        EntityKind::Bat | EntityKind::Parrot => 6.0,
+        EntityKind::Bee
+        | EntityKind::Cat
+        | EntityKind::Cow
+        | EntityKind::Dolphin
+        | EntityKind::Fox
+        | EntityKind::Ghast
+        | EntityKind::Mooshroom
+        | EntityKind::Ocelot
+        | EntityKind::Pig
+        | EntityKind::Squid => 10.0,
+        EntityKind::Chicken | EntityKind::SnowGolem => 4.0,
+        EntityKind::Cod
+        | EntityKind::Salmon
+        | EntityKind::Pufferfish
+        | EntityKind::TropicalFish
+        | EntityKind::Rabbit => 3.0,
+        EntityKind::Sheep | EntityKind::Wolf | EntityKind::Silverfish | EntityKind::Endermite => {
+            8.0
+        }
+        EntityKind::CaveSpider => 12.0,
+        EntityKind::Vex => 14.0,
+        EntityKind::Donkey
+        | EntityKind::Horse
+        | EntityKind::Llama
+        | EntityKind::Mule
+        | EntityKind::SkeletonHorse
+        | EntityKind::TraderLlama
+        | EntityKind::ZombieHorse => 15.0,
+        EntityKind::Piglin | EntityKind::Spider => 16.0,
+        EntityKind::Blaze
+        | EntityKind::Creeper
+        | EntityKind::Drowned
+        | EntityKind::Husk
+        | EntityKind::Panda
+        | EntityKind::Phantom
+        | EntityKind::Skeleton
+        | EntityKind::Stray
+        | EntityKind::Strider
+        | EntityKind::Villager
+        | EntityKind::WanderingTrader
+        | EntityKind::WitherSkeleton
+        | EntityKind::Zombie
+        | EntityKind::ZombieVillager
+        | EntityKind::ZombifiedPiglin => 20.0,
+        EntityKind::Evoker | EntityKind::Pillager | EntityKind::Vindicator => 24.0,
+        EntityKind::Witch => 26.0,
+        EntityKind::Guardian | EntityKind::PolarBear | EntityKind::Shulker | EntityKind::Turtle => {
+            30.0
+        }
+        EntityKind::Illusioner => 32.0,
+        EntityKind::Enderman | EntityKind::Hoglin | EntityKind::Zoglin => 40.0,
+        EntityKind::PiglinBrute => 50.0,
+        EntityKind::ElderGuardian => 80.0,
+        EntityKind::Giant | EntityKind::IronGolem | EntityKind::Ravager => 100.0,
+        EntityKind::EnderDragon => 200.0,
+        EntityKind::Wither => 300.0,
+        // The smallest size
+        EntityKind::Slime | EntityKind::MagmaCube => 1.0,
+        _ => return None,
+    };
+    Some(health)
+}
+
+fn tick_hurt_cooldowns(game: &mut Game) -> SysResult {
+    for (_, cooldown) in game.ecs.query::<&mut HurtCooldown>().iter() {
+        cooldown.ticks = cooldown.ticks.saturating_sub(1);
+    }
+    Ok(())
+}
+
+/// Accumulates the distance players fall and
+/// damages them when they land.
+fn apply_fall_damage(game: &mut Game) -> SysResult {
+    let mut landed = Vec::new();
+    for (entity, (fall, &position, on_ground, flying)) in game
+        .ecs
+        .query::<(&mut FallDistance, &Position, &OnGround, &CreativeFlying)>()
+        .iter()
+    {
+        if flying.0 || breaks_fall(game, position) {
+            fall.reset();
+            continue;
+        }
+
+        if let Some(last_y) = fall.last_y {
+            if position.y < last_y {
+                fall.distance += last_y - position.y;
+            }
+        }
+        fall.last_y = Some(position.y);
+
+        if on_ground.0 {
+            if fall.distance > SAFE_FALL_DISTANCE {
+                landed.push((entity, (fall.distance - SAFE_FALL_DISTANCE).ceil() as f32));
+            }
+            fall.distance = 0.0;
+        }
+    }
+
+    for (entity, amount) in landed {
+        damage(game, entity, DamageSource::Fall, amount)?;
+    }
+    Ok(())
+}
+
+/// Whether the block at `position` stops a fall,
+/// like water or a ladder.
+fn breaks_fall(game: &Game, position: Position) -> bool {
+    let block = match ValidBlockPosition::try_from(position.block())
+        .ok()
+        .and_then(|pos| game.block(pos))
+    {
+        Some(block) => block,
+        None => return false,
+    };
+    block.waterlogged() == Some(true)
+        || matches!(
+            block.kind(),
+            BlockKind::Water
+                | BlockKind::Ladder
+                | BlockKind::Vine
+                | BlockKind::Scaffolding
+                | BlockKind::Cobweb
+        )
+}
+
+fn apply_void_damage(game: &mut Game) -> SysResult {
+    let in_void: Vec<Entity> = game
+        .ecs
+        .query::<(&Health, &Position)>()
+        .iter()
+        .filter(|(_, (_, position))| position.y < VOID_Y)
+        .map(|(entity, _)| entity)
+        .collect();
+
+    for entity in in_void {
+        damage(game, entity, DamageSource::Void, VOID_DAMAGE)?;
+    }
+    Ok(())
+}
+
+/// Heals players over time unless the
+/// `naturalRegeneration` game rule is off.
+fn regenerate_health(game: &mut Game) -> SysResult {
+    if game.tick_count % REGENERATION_INTERVAL != 0
+        || !game.resources.get::<GameRules>()?.natural_regeneration
+    {
+        return Ok(());
+    }
+
+    let mut healed = Vec::new();
+    for (player, (_, health)) in game.ecs.query::<(&Player, &mut Health)>().iter() {
+        // Dead players have no health left
+        if health.0 > 0.0 && health.0 < PLAYER_MAX_HEALTH {
+            health.0 = (health.0 + 1.0).min(PLAYER_MAX_HEALTH);
+            healed.push(player);
+        }
+    }
+    for player in healed {
+        game.ecs.insert_entity_event(player, HealthChangeEvent)?;
+    }
+    Ok(())
+}
+
+/// Removes dead entities once their death animation is over.
+/// Dead players remain until they respawn.
+fn remove_dead_entities(game: &mut Game) -> SysResult {
+    let mut removed = Vec::new();
+    for (entity, dead) in game.ecs.query::<&mut Dead>().iter() {
+        dead.0 += 1;
+        if dead.0 == DEATH_ANIMATION_TICKS && game.ecs.get::<Player>(entity).is_err() {
+            removed.push(entity);
+        }
+    }
+
+    for entity in removed {
+        game.remove_entity(entity)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use libcraft_items::ItemStackBuilder;
+
+    use super::*;
+
+    #[test]
+    fn armor_reduces_damage() {
+        // Full iron armor
+        assert!((damage_after_armor(5.0, 15.0, 0.0) - 2.5).abs() < 1e-4);
+        // Strong hits pierce armor, but a fifth of it always counts
+        assert!((damage_after_armor(40.0, 15.0, 0.0) - 35.2).abs() < 1e-4);
+        // Toughness counters piercing
+        assert!(damage_after_armor(20.0, 20.0, 8.0) < damage_after_armor(20.0, 20.0, 0.0));
+        assert_eq!(damage_after_armor(5.0, 0.0, 0.0), 5.0);
+    }
+
+    #[test]
+    fn protection_is_capped() {
+        assert!((damage_after_protection(10.0, 4) - 8.4).abs() < 1e-4);
+        assert!((damage_after_protection(10.0, 40) - 2.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn invulnerability_ticks() {
+        let mut cooldown = HurtCooldown::default();
+        assert_eq!(cooldown.hit(4.0), Some(4.0));
+        // Weaker hits are absorbed, stronger ones deal the difference
+        cooldown.ticks = 15;
+        assert_eq!(cooldown.hit(3.0), None);
+        assert_eq!(cooldown.hit(6.0), Some(2.0));
+        // Hits land fully after half of the ticks
+        cooldown.ticks = 10;
+        assert_eq!(cooldown.hit(1.0), Some(1.0));
+        assert_eq!(cooldown.ticks, INVULNERABILITY_TICKS);
+    }
+
+    #[test]
+    fn natural_regeneration() {
+        let mut game = Game::new();
+        game.insert_resource(GameRules::default());
+        let player = game.ecs.spawn((Player, Health(10.0)));
+
+        game.tick_count = REGENERATION_INTERVAL;
+        regenerate_health(&mut game).unwrap();
+        assert_eq!(game.ecs.get::<Health>(player).unwrap().0, 11.0);
+
+        // Only every few ticks
+        game.tick_count += 1;
+        regenerate_health(&mut game).unwrap();
+        assert_eq!(game.ecs.get::<Health>(player).unwrap().0, 11.0);
+
+        game.resources
+            .get_mut::<GameRules>()
+            .unwrap()
+            .natural_regeneration = false;
+        game.tick_count = 2 * REGENERATION_INTERVAL;
+        regenerate_health(&mut game).unwrap();
+        assert_eq!(game.ecs.get::<Health>(player).unwrap().0, 11.0);
+    }
+
+    #[test]
+    fn feather_falling_only_protects_from_falls() {
+        let boots = InventorySlot::Filled(
+            ItemStackBuilder::with_item(Item::DiamondBoots)
+                .enchantment(EnchantmentKind::FeatherFalling, 4)
+                .into(),
+        );
+        assert_eq!(protection_factor(&boots, DamageSource::Fall), 12);
+        assert_eq!(protection_factor(&boots, DamageSource::Generic), 0);
+        assert_eq!(protection_factor(&boots, DamageSource::Void), 0);
+    }
+}
@@ -0,0 +1,463 @@
+//! Survival block breaking.
+//!
+//! The client tells the server when it starts and finishes
+//! digging a block. In between, the server tracks the dig in
+//! a [`Digging`] component and accumulates progress each tick,
+//! based on the block's hardness and the player's tool, so that
+//! blocks broken faster than possible are rejected.
+
+use std::convert::TryFrom;
+
+use base::{
+    inventory::{SLOT_ARMOR_HEAD, SLOT_HOTBAR_OFFSET},
+    BlockId, BlockKind, BlockPosition, Position, ValidBlockPosition,
+};
+use blocks::{HalfUpperLower, Part, SlabKind};
+use ecs::{Entity, SysResult, SystemExecutor};
+use libcraft_items::{EnchantmentKind, InventorySlot, Item, ItemStack};
+use quill_common::components::{CanBuild, Instabreak, OnGround};
+use rand::Rng;
+
+use crate::{
+    dropped_items,
+    entities::player::HotbarSlot,
+    events::{BlockBreakProgressEvent, BlockChangeEvent},
+    Game, Window,
+};
+
+/// Maximum squared distance between a player
+/// and the block they dig.
+const MAX_REACH_SQUARED: f64 = 8.0 * 8.0;
+
+/// Progress at which a finished dig is accepted. Lower than 1
+/// because the client and server tick at slightly different
+/// times; vanilla uses the same tolerance.
+const FINISH_THRESHOLD: f32 = 0.7;
+
+/// Height of a player's eyes above their feet.
+const EYE_HEIGHT: f64 = 1.62;
+
+/// Tracks the block a player is digging. Removed once the
+/// block breaks or the player stops digging.
+#[derive(Debug, Clone)]
+pub struct Digging {
+    pub position: ValidBlockPosition,
+    /// The block when digging started. Digging stops
+    /// if the block changes in the meantime.
+    pub block: BlockId,
+    /// Fraction of the block broken so far.
+    pub progress: f32,
+    /// The destroy stage last shown to other players.
+    stage: Option<u8>,
+}
+
+pub fn register(systems: &mut SystemExecutor<Game>) {
+    systems.add_system(update_digging);
+}
+
+/// Advances the progress of each dig.
+fn update_digging(game: &mut Game) -> SysResult {
+    let digs: Vec<(Entity, Digging)> = game
+        .ecs
+        .query::<&Digging>()
+        .iter()
+        .map(|(player, digging)| (player, digging.clone()))
+        .collect();
+
+    for (player, digging) in digs {
+        if game.block(digging.position) != Some(digging.block) {
+            stop_digging(game, player)?;
+            continue;
+        }
+
+        let progress = digging.progress + dig_speed(game, player, digging.block.kind())?;
+        let stage = Some(destroy_stage(progress));
+        game.ecs.get_mut::<Digging>(player)?.progress = progress;
+        if stage != digging.stage {
+            game.ecs.get_mut::<Digging>(player)?.stage = stage;
+            game.ecs.insert_entity_event(
+                player,
+                BlockBreakProgressEvent {
+                    position: digging.position,
+                    stage,
+                },
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Called when a player starts digging the block at `position`.
+///
+/// Breaks the block right away if the player has `Instabreak`
+/// or the block breaks within a tick.
+pub fn start_digging(game: &mut Game, player: Entity, position: ValidBlockPosition) -> SysResult {
+    stop_digging(game, player)?;
+
+    let block = match game.block(position) {
+        Some(block) => block,
+        None => return Ok(()),
+    };
+    let (can_build, instabreak, player_position) = {
+        let player = game.ecs.entity(player)?;
+        (
+            player.get::<CanBuild>()?.0,
+            player.get::<Instabreak>()?.0,
+            *player.get::<Position>()?,
+        )
+    };
+    if !can_build || player_position.distance_squared_to(position.position()) > MAX_REACH_SQUARED {
+        game.ecs.insert_event(BlockChangeEvent::single(position));
+        return Ok(());
+    }
+
+    if instabreak {
+        destroy_block(game, position, block);
+        return Ok(());
+    }
+
+    let speed = dig_speed(game, player, block.kind())?;
+    if speed >= 1.0 {
+        let tool = held_item(game, player)?;
+        destroy_block(game, position, block);
+        drop_loot(game, position, block, &tool);
+    } else {
+        game.ecs.insert(
+            player,
+            Digging {
+                position,
+                block,
+                progress: speed,
+                stage: None,
+            },
+        )?;
+    }
+    Ok(())
+}
+
+/// Called when a player says they finished digging the block
+/// at `position`. Breaks the block if enough time has passed,
+/// or restores it on the client otherwise.
+pub fn finish_digging(game: &mut Game, player: Entity, position: ValidBlockPosition) -> SysResult {
+    let digging = game.ecs.get::<Digging>(player).ok().map(|d| d.clone());
+    stop_digging(game, player)?;
+
+    let digging = match digging {
+        Some(digging)
+            if digging.position == position
+                && digging.progress >= FINISH_THRESHOLD
+                && game.block(position) == Some(digging.block) =>
+        {
+            digging
+        }
+        _ => {
+            log::debug!("Rejected finished dig at {:?}", position);
+            game.ecs.insert_event(BlockChangeEvent::single(position));
+            return Ok(());
+        }
+    };
+
+    let tool = held_item(game, player)?;
+    destroy_block(game, position, digging.block);
+    drop_loot(game, position, digging.block, &tool);
+    Ok(())
+}
+
+/// Called when a player stops digging without breaking the block.
+pub fn stop_digging(game: &mut Game, player: Entity) -> SysResult {
+    if let Ok(digging) = game.ecs.remove::<Digging>(player) {
+        game.ecs.insert_entity_event(
+            player,
+            BlockBreakProgressEvent {
+                position: digging.position,
+                stage: None,
+            },
+        )?;
+    }
+    Ok(())
+}
+
+/// Returns the item in the main hand of a player.
+pub(crate) fn held_item(game: &Game, player: Entity) -> anyhow::Result<InventorySlot> {
+    let slot = SLOT_HOTBAR_OFFSET + game.ecs.get::<HotbarSlot>(player)?.get();
+    let item = game.ecs.get::<Window>(player)?.item(slot)?.clone();
+    Ok(item)
+}
+
+/// Returns the progress a player makes on a block per tick.
+fn dig_speed(game: &Game, player: Entity, block: BlockKind) -> anyhow::Result<f32> {
+    let tool = held_item(game, player)?;
+    let player = game.ecs.entity(player)?;
+    let on_ground = player.get::<OnGround>()?.0;
+
+    let mut eyes = *player.get::<Position>()?;
+    eyes.y += EYE_HEIGHT;
+    let underwater = ValidBlockPosition::try_from(eyes.block())
+        .ok()
+        .and_then(|pos| game.block(pos))
+        .map_or(false, |block| block.kind() == BlockKind::Water);
+    let aqua_affinity = {
+        let window = player.get::<Window>()?;
+        let helmet = window.item(SLOT_ARMOR_HEAD)?;
+        enchantment_level(&helmet, EnchantmentKind::AquaAffinity) > 0
+    };
+
+    Ok(dig_progress(
+        block,
+        &tool,
+        on_ground,
+        underwater && !aqua_affinity,
+    ))
+}
+
+/// Computes the fraction of `block` broken per tick, following
+/// vanilla's formula. Status effects aren't implemented yet.
+fn dig_progress(block: BlockKind, tool: &InventorySlot, on_ground: bool, underwater: bool) -> f32 {
+    if !block.diggable() {
+        return 0.0;
+    }
+    let hardness = block.hardness();
+    if hardness <= 0.0 {
+        return 1.0;
+    }
+
+    let mut speed = tool
+        .item_kind()
+        .and_then(|item| {
+            block
+                .dig_multipliers()
+                .iter()
+                .find(|(tool, _)| *tool == item)
+        })
+        .map_or(1.0, |&(_, multiplier)| multiplier);
+    let efficiency = enchantment_level(tool, EnchantmentKind::Efficiency);
+    if speed > 1.0 && efficiency > 0 {
+        speed += (efficiency * efficiency + 1) as f32;
+    }
+    if underwater {
+        speed /= 5.0;
+    }
+    if !on_ground {
+        speed /= 5.0;
+    }
+
+    let divisor = if can_harvest(block, tool) {
+        30.0
+    } else {
+        100.0
+    };
+    speed / hardness / divisor
+}
+
+fn destroy_stage(progress: f32) -> u8 {
+    ((progress * 10.0) as u8).min(9)
+}
+
+/// Whether breaking `block` with `tool` yields drops.
+fn can_harvest(block: BlockKind, tool: &InventorySlot) -> bool {
+    match block.harvest_tools() {
+        Some(tools) => tool.item_kind().map_or(false, |item| tools.contains(&item)),
+        None => true,
+    }
+}
+
+pub(crate) fn enchantment_level(item: &InventorySlot, kind: EnchantmentKind) -> u32 {
+    match item {
+        InventorySlot::Filled(stack) => stack
+            .enchantments()
+            .iter()
+            .filter(|enchantment| enchantment.kind() == kind)
+            .map(|enchantment| enchantment.level())
+            .max()
+            .unwrap_or_default(),
+        InventorySlot::Empty => 0,
+    }
+}
+
+/// Removes a block, including the other half of
+/// doors, tall plants and beds. Waterlogged
+/// blocks leave their water behind.
+fn destroy_block(game: &mut Game, position: ValidBlockPosition, block: BlockId) {
+    let other_half = match (block.half_upper_lower(), block.part()) {
+        (Some(HalfUpperLower::Lower), _) => Some(BlockPosition::from(position).up()),
+        (Some(HalfUpperLower::Upper), _) => Some(BlockPosition::from(position).down()),
+        (_, Some(part)) => block.facing_cardinal().map(|facing| {
+            let facing = match part {
+                Part::Foot => facing,
+                Part::Head => facing.opposite(),
+            };
+            let offset = facing.to_facing_cubic().offset();
+            let position = BlockPosition::from(position);
+            BlockPosition::new(
+                position.x + offset.x,
+                position.y + offset.y,
+                position.z + offset.z,
+            )
+        }),
+        _ => None,
+    };
+
+    game.set_block(position, remains(block));
+    if let Some(other_half) = other_half.and_then(|pos| ValidBlockPosition::try_from(pos).ok()) {
+        if let Some(other) = game.block(other_half) {
+            if other.kind() == block.kind() {
+                game.set_block(other_half, remains(other));
+            }
+        }
+    }
+}
+
+/// The block left behind after breaking `block`.
+fn remains(block: BlockId) -> BlockId {
+    if block.waterlogged() == Some(true) {
+        BlockId::water()
+    } else {
+        BlockId::air()
+    }
+}
+
+/// Spawns the items dropped by breaking `block` with `tool`,
+/// unless the `doTileDrops` game rule is disabled.
+fn drop_loot(game: &mut Game, position: ValidBlockPosition, block: BlockId, tool: &InventorySlot) {
+    let do_tile_drops = game
+        .resources
+        .get::<base::GameRules>()
+        .map_or(true, |rules| rules.do_tile_drops);
+    if !do_tile_drops || !can_harvest(block.kind(), tool) {
+        return;
+    }
+
+    let items = block_drops(block, tool);
+    dropped_items::drop_block_items(game, position, items);
+}
+
+/// Returns the items dropped by a block broken with a tool
+/// able to harvest it. Loot tables aren't implemented, so this
+/// covers the common blocks which don't simply drop themselves.
+fn block_drops(block: BlockId, tool: &InventorySlot) -> Vec<ItemStack> {
+    let kind = block.kind();
+    let silk_touch = enchantment_level(tool, EnchantmentKind::SilkTouch) > 0;
+    let name = kind.name();
+
+    let mut rng = rand::thread_rng();
+    let (item, count) = match kind {
+        _ if silk_touch => (Item::from_name(name), 1),
+        BlockKind::Stone => (Some(Item::Cobblestone), 1),
+        BlockKind::GrassBlock
+        | BlockKind::Mycelium
+        | BlockKind::Podzol
+        | BlockKind::GrassPath
+        | BlockKind::Farmland => (Some(Item::Dirt), 1),
+        BlockKind::CoalOre => (Some(Item::Coal), 1),
+        BlockKind::DiamondOre => (Some(Item::Diamond), 1),
+        BlockKind::EmeraldOre => (Some(Item::Emerald), 1),
+        BlockKind::LapisOre => (Some(Item::LapisLazuli), rng.gen_range(4..=9)),
+        BlockKind::RedstoneOre => (Some(Item::Redstone), rng.gen_range(4..=5)),
+        BlockKind::NetherQuartzOre => (Some(Item::Quartz), 1),
+        BlockKind::NetherGoldOre => (Some(Item::GoldNugget), rng.gen_range(2..=6)),
+        BlockKind::Gravel if rng.gen_bool(0.1) => (Some(Item::Flint), 1),
+        BlockKind::Clay => (Some(Item::ClayBall), 4),
+        BlockKind::Glowstone => (Some(Item::GlowstoneDust), rng.gen_range(2..=4)),
+        BlockKind::SnowBlock => (Some(Item::Snowball), 4),
+        BlockKind::Snow => (Some(Item::Snowball), 1),
+        BlockKind::Bookshelf => (Some(Item::Book), 3),
+        BlockKind::Melon => (Some(Item::MelonSlice), rng.gen_range(3..=7)),
+        BlockKind::Ice
+        | BlockKind::PackedIce
+        | BlockKind::BlueIce
+        | BlockKind::Grass
+        | BlockKind::Fern
+        | BlockKind::TallGrass
+        | BlockKind::LargeFern => (None, 0),
+        BlockKind::DeadBush => (Some(Item::Stick), rng.gen_range(0..=2)),
+        _ if name.ends_with("glass")
+            || name.ends_with("glass_pane")
+            || name.ends_with("leaves") =>
+        {
+            (None, 0)
+        }
+        _ if name.starts_with("potted_") => (Some(Item::FlowerPot), 1),
+        _ if name.starts_with("infested_") => (None, 0),
+        // Wall torches, signs, heads and coral fans
+        // drop the item of their standing variant.
+        _ if name.contains("wall_") => (Item::from_name(&name.replace("wall_", "")), 1),
+        _ => {
+            let count = if block.slab_kind() == Some(SlabKind::Double) {
+                2
+            } else {
+                1
+            };
+            (Item::from_name(name), count)
+        }
+    };
+
+    match item {
+        Some(item) if count > 0 => vec![ItemStack::new(item, count).unwrap()],
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tools_speed_up_digging() {
+        let hand = InventorySlot::Empty;
+        let pickaxe = InventorySlot::new(Item::IronPickaxe, 1);
+
+        // Stone takes 7.5 seconds by hand and 0.4 seconds with an iron pickaxe.
+        let by_hand = dig_progress(BlockKind::Stone, &hand, true, false);
+        assert!((by_hand - 1.0 / 150.0).abs() < 1e-6);
+        let with_pickaxe = dig_progress(BlockKind::Stone, &pickaxe, true, false);
+        assert!((with_pickaxe - 6.0 / 1.5 / 30.0).abs() < 1e-6);
+
+        assert!(dig_progress(BlockKind::Stone, &pickaxe, false, false) < with_pickaxe);
+        assert!(dig_progress(BlockKind::Stone, &pickaxe, true, true) < with_pickaxe);
+    }
+
+    #[test]
+    fn unbreakable_and_instant_blocks() {
+        let hand = InventorySlot::Empty;
+        assert_eq!(dig_progress(BlockKind::Bedrock, &hand, true, false), 0.0);
+        assert!(dig_progress(BlockKind::Grass, &hand, true, false) >= 1.0);
+    }
+
+    #[test]
+    fn harvest_tools() {
+        let hand = InventorySlot::Empty;
+        let wooden = InventorySlot::new(Item::WoodenPickaxe, 1);
+        let diamond = InventorySlot::new(Item::DiamondPickaxe, 1);
+        assert!(!can_harvest(BlockKind::Stone, &hand));
+        assert!(can_harvest(BlockKind::Stone, &wooden));
+        assert!(!can_harvest(BlockKind::Obsidian, &wooden));
+        assert!(can_harvest(BlockKind::Obsidian, &diamond));
+        assert!(can_harvest(BlockKind::Dirt, &hand));
+    }
+
+    #[test]
+    fn drops() {
+        let hand = InventorySlot::Empty;
+        let drops = |block: BlockId| -> Vec<Item> {
+            block_drops(block, &hand)
+                .iter()
+                .map(|stack| stack.item())
+                .collect()
+        };
+        assert_eq!(drops(BlockId::stone()), vec![Item::Cobblestone]);
+        assert_eq!(drops(BlockId::oak_planks()), vec![Item::OakPlanks]);
+        assert_eq!(drops(BlockId::wall_torch()), vec![Item::Torch]);
+        assert_eq!(drops(BlockId::glass()), vec![]);
+        assert_eq!(
+            block_drops(BlockId::oak_slab().with_slab_kind(SlabKind::Double), &hand)[0].count(),
+            2
+        );
+    }
+
+    #[test]
+    fn destroy_stages() {
+        assert_eq!(destroy_stage(0.0), 0);
+        assert_eq!(destroy_stage(0.55), 5);
+        assert_eq!(destroy_stage(1.5), 9);
+    }
+}
@@ -0,0 +1,416 @@
+//! Item entities dropped by players and broken blocks.
+//!
+//! Items fall with [`Physics::ITEM`](crate::physics::Physics::ITEM), merge with identical
+//! items nearby, despawn after five minutes and are picked
+//! up by players once their pickup delay expires.
+
+use std::f64::consts::PI;
+
+use base::{
+    inventory::SLOT_HOTBAR_OFFSET, position, Area, EntityKind, Gamemode, Inventory, ItemStack,
+    Position, ValidBlockPosition, Vec3d,
+};
+use ecs::{Entity, SysResult, SystemExecutor};
+use libcraft_items::InventorySlot;
+use quill_common::{entities::Player, entity_init::EntityInit};
+use rand::Rng;
+
+use crate::{
+    damage::Dead,
+    entities::player::HotbarSlot,
+    events::{EntityRemoveEvent, InventoryUpdateEvent, ItemCollectEvent, ItemStackChangeEvent},
+    physics::{overlaps, Velocity},
+    Game, Window,
+};
+
+/// Age in ticks at which items despawn.
+const DESPAWN_AGE: u32 = 5 * 60 * 20;
+
+/// Pickup delay of items thrown by players, so that
+/// they don't immediately pick them up again.
+const THROWN_PICKUP_DELAY: u32 = 40;
+
+/// Pickup delay of items dropped by blocks.
+const BLOCK_DROP_PICKUP_DELAY: u32 = 10;
+
+/// Items below this height are removed.
+const MIN_Y: f64 = -64.0;
+
+/// Height at which players throw items, relative to their feet.
+const THROW_HEIGHT: f64 = 1.32;
+
+/// Number of ticks an item entity has existed.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ItemAge(pub u32);
+
+/// Number of ticks until an item entity can be picked up.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PickupDelay(pub u32);
+
+pub fn register(systems: &mut SystemExecutor<Game>) {
+    systems
+        .add_system(age_items)
+        .add_system(merge_items)
+        .add_system(pick_up_items);
+}
+
+/// Spawns an item entity.
+pub fn spawn_item(
+    game: &mut Game,
+    position: Position,
+    item: ItemStack,
+    velocity: Vec3d,
+    pickup_delay: u32,
+) -> Entity {
+    let mut builder = game.create_entity_builder(position, EntityInit::Item);
+    builder
+        .add(item)
+        .add(Velocity(velocity))
+        .add(PickupDelay(pickup_delay));
+    game.spawn_entity(builder)
+}
+
+/// Spawns the items dropped by the block at `block`,
+/// popping them up with a small random velocity.
+pub fn drop_block_items(game: &mut Game, block: ValidBlockPosition, items: Vec<ItemStack>) {
+    for item in items {
+        let mut rng = rand::thread_rng();
+        let position = position!(
+            block.x() as f64 + rng.gen_range(0.25..0.75),
+            block.y() as f64 + rng.gen_range(0.25..0.75),
+            block.z() as f64 + rng.gen_range(0.25..0.75),
+        );
+        let velocity = Vec3d::new(rng.gen_range(-0.1..0.1), 0.2, rng.gen_range(-0.1..0.1));
+        spawn_item(game, position, item, velocity, BLOCK_DROP_PICKUP_DELAY);
+    }
+}
+
+/// Scatters items in random directions around `position`,
+/// like the inventory of a player who died.
+pub fn scatter_items(game: &mut Game, mut position: Position, items: Vec<ItemStack>) {
+    position.y += THROW_HEIGHT;
+    for item in items {
+        let velocity = scatter_velocity(&mut rand::thread_rng());
+        spawn_item(game, position, item, velocity, THROWN_PICKUP_DELAY);
+    }
+}
+
+/// Throws one item, or the whole stack, from the
+/// player's selected hotbar slot in the direction
+/// they are looking.
+pub fn drop_held_item(game: &mut Game, player: Entity, whole_stack: bool) -> SysResult {
+    let slot = SLOT_HOTBAR_OFFSET + game.ecs.get::<HotbarSlot>(player)?.get();
+    let dropped = {
+        let window = game.ecs.get::<Window>(player)?;
+        let mut item = window.item(slot)?;
+        if whole_stack {
+            item.take_all()
+        } else {
+            item.try_take(1)
+        }
+    };
+    game.ecs
+        .insert_entity_event(player, InventoryUpdateEvent(vec![slot]))?;
+
+    if let InventorySlot::Filled(item) = dropped {
+        let mut position = *game.ecs.get::<Position>(player)?;
+        let velocity = throw_velocity(position.yaw, position.pitch, &mut rand::thread_rng());
+        position.y += THROW_HEIGHT;
+        spawn_item(game, position, item, velocity, THROWN_PICKUP_DELAY);
+    }
+    Ok(())
+}
+
+/// Returns the velocity of an item thrown in the given direction,
+/// with a little randomness like in vanilla.
+fn throw_velocity(yaw: f32, pitch: f32, rng: &mut impl Rng) -> Vec3d {
+    let yaw = f64::from(yaw).to_radians();
+    let pitch = f64::from(pitch).to_radians();
+    let spread_angle = rng.gen_range(0.0..2.0 * PI);
+    let spread = rng.gen_range(0.0..0.02);
+    Vec3d::new(
+        -yaw.sin() * pitch.cos() * 0.3 + spread_angle.cos() * spread,
+        -pitch.sin() * 0.3 + 0.1 + (rng.gen::<f64>() - rng.gen::<f64>()) * 0.1,
+        yaw.cos() * pitch.cos() * 0.3 + spread_angle.sin() * spread,
+    )
+}
+
+/// Returns the velocity of an item flung in a random direction.
+fn scatter_velocity(rng: &mut impl Rng) -> Vec3d {
+    let speed = rng.gen_range(0.0..0.5);
+    let angle = rng.gen_range(0.0..2.0 * PI);
+    Vec3d::new(-angle.sin() * speed, 0.2, angle.cos() * speed)
+}
+
+/// Counts down pickup delays and removes old items
+/// and items which fell out of the world.
+fn age_items(game: &mut Game) -> SysResult {
+    let mut despawned = Vec::new();
+    for (item, (age, pickup_delay, position)) in game
+        .ecs
+        .query::<(&mut ItemAge, &mut PickupDelay, &Position)>()
+        .iter()
+    {
+        age.0 += 1;
+        pickup_delay.0 = pickup_delay.0.saturating_sub(1);
+        if age.0 >= DESPAWN_AGE || position.y < MIN_Y {
+            despawned.push(item);
+        }
+    }
+
+    for item in despawned {
+        if !is_removed(game, item) {
+            game.remove_entity(item)?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether an entity is being removed. Removed entities stay
+/// alive until the next tick, but must not be merged or
+/// picked up again.
+fn is_removed(game: &Game, entity: Entity) -> bool {
+    game.ecs.get::<EntityRemoveEvent>(entity).is_ok()
+}
+
+/// Whether two item stacks can merge into one.
+fn can_merge(a: &ItemStack, b: &ItemStack) -> bool {
+    a.stackable_types(b)
+        && a.has_same_damage(b)
+        && a.enchantments() == b.enchantments()
+        && a.count() + b.count() <= a.stack_size()
+}
+
+/// Merges items lying next to identical items
+/// into the larger of the two stacks.
+fn merge_items(game: &mut Game) -> SysResult {
+    let items: Vec<(Entity, Position)> = game
+        .ecs
+        .query::<(&ItemStack, &Position)>()
+        .iter()
+        .map(|(item, (_, &position))| (item, position))
+        .collect();
+
+    for (item, position) in items {
+        if is_removed(game, item) {
+            continue;
+        }
+
+        for other in game.chunk_entities.entities_near(position) {
+            if other == item || is_removed(game, other) {
+                continue;
+            }
+            let other_position = match game.ecs.get::<Position>(other) {
+                Ok(position) => *position,
+                Err(_) => continue,
+            };
+            if !overlaps(
+                (position, EntityKind::Item),
+                (other_position, EntityKind::Item),
+                Vec3d::new(0.5, 0.0, 0.5),
+            ) {
+                continue;
+            }
+
+            let (stack, other_stack) = match (
+                game.ecs.get::<ItemStack>(item),
+                game.ecs.get::<ItemStack>(other),
+            ) {
+                (Ok(stack), Ok(other_stack)) => (stack.clone(), other_stack.clone()),
+                _ => continue,
+            };
+            if !can_merge(&stack, &other_stack) {
+                continue;
+            }
+
+            let (into, from) = if stack.count() >= other_stack.count() {
+                (item, other)
+            } else {
+                (other, item)
+            };
+            merge_into(game, into, from)?;
+            if from == item {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Moves the stack of `from` into `into` and removes `from`.
+fn merge_into(game: &mut Game, into: Entity, from: Entity) -> SysResult {
+    let from_stack = game.ecs.get::<ItemStack>(from)?.clone();
+    let from_age = game.ecs.get::<ItemAge>(from)?.0;
+    let from_delay = game.ecs.get::<PickupDelay>(from)?.0;
+    {
+        let into = game.ecs.entity(into)?;
+        into.get_mut::<ItemStack>()?.add(from_stack.count())?;
+        let mut age = into.get_mut::<ItemAge>()?;
+        age.0 = age.0.min(from_age);
+        let mut delay = into.get_mut::<PickupDelay>()?;
+        delay.0 = delay.0.max(from_delay);
+    }
+    game.ecs.insert_entity_event(into, ItemStackChangeEvent)?;
+    game.remove_entity(from)?;
+    Ok(())
+}
+
+/// Moves items into the inventories of living players touching them.
+fn pick_up_items(game: &mut Game) -> SysResult {
+    let players: Vec<(Entity, Position)> = game
+        .ecs
+        .query::<(&Player, &Position, &Gamemode)>()
+        .iter()
+        .filter(|&(player, (_, _, &gamemode))| {
+            gamemode != Gamemode::Spectator && game.ecs.get::<Dead>(player).is_err()
+        })
+        .map(|(player, (_, &position, _))| (player, position))
+        .collect();
+
+    for (player, position) in players {
+        for item in game.chunk_entities.entities_near(position) {
+            if is_removed(game, item) {
+                continue;
+            }
+            let item_position = match game.ecs.get::<Position>(item) {
+                Ok(position) => *position,
+                Err(_) => continue,
+            };
+            match game.ecs.get::<PickupDelay>(item) {
+                Ok(delay) if delay.0 == 0 => {}
+                _ => continue,
+            }
+            if !overlaps(
+                (position, EntityKind::Player),
+                (item_position, EntityKind::Item),
+                Vec3d::new(1.0, 0.5, 1.0),
+            ) {
+                continue;
+            }
+
+            pick_up(game, player, item)?;
+        }
+    }
+    Ok(())
+}
+
+/// Moves as much of an item entity's stack as fits
+/// into a player's inventory.
+fn pick_up(game: &mut Game, player: Entity, item: Entity) -> SysResult {
+    let mut stack = InventorySlot::Filled(game.ecs.get::<ItemStack>(item)?.clone());
+    let count = stack.count();
+    let changed_slots = {
+        let player = game.ecs.entity(player)?;
+        let inventory = player.get::<Inventory>()?;
+        let window = player.get::<Window>()?;
+        insert_into_inventory(&inventory, &mut stack)
+            .into_iter()
+            .filter_map(|(area, slot)| window.inner().slot_to_index(&inventory, area, slot))
+            .collect::<Vec<_>>()
+    };
+    if changed_slots.is_empty() {
+        return Ok(());
+    }
+
+    game.ecs
+        .insert_entity_event(player, InventoryUpdateEvent(changed_slots))?;
+    game.ecs.insert_entity_event(
+        item,
+        ItemCollectEvent {
+            collector: player,
+            count: count - stack.count(),
+        },
+    )?;
+    match stack {
+        InventorySlot::Filled(remaining) => {
+            *game.ecs.get_mut::<ItemStack>(item)? = remaining;
+            game.ecs.insert_entity_event(item, ItemStackChangeEvent)?;
+        }
+        InventorySlot::Empty => game.remove_entity(item)?,
+    }
+    Ok(())
+}
+
+/// Inserts `item` into the hotbar and main inventory, filling
+/// existing stacks first. Returns the slots that changed.
+fn insert_into_inventory(inventory: &Inventory, item: &mut InventorySlot) -> Vec<(Area, usize)> {
+    let mut changed = Vec::new();
+    for &fill_empty in &[false, true] {
+        for &area in &[Area::Hotbar, Area::Storage] {
+            let mut i = 0;
+            while let Some(mut slot) = inventory.item(area, i) {
+                if slot.is_empty() == fill_empty && slot.merge(item) > 0 {
+                    changed.push((area, i));
+                }
+                if item.is_empty() {
+                    return changed;
+                }
+                i += 1;
+            }
+        }
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use base::Item;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn insert_fills_existing_stacks_first() {
+        let inventory = Inventory::player();
+        *inventory.item(Area::Hotbar, 3).unwrap() = InventorySlot::new(Item::Stone, 60);
+
+        let mut item = InventorySlot::new(Item::Stone, 10);
+        let changed = insert_into_inventory(&inventory, &mut item);
+
+        assert!(item.is_empty());
+        assert_eq!(changed, vec![(Area::Hotbar, 3), (Area::Hotbar, 0)]);
+        assert_eq!(inventory.item(Area::Hotbar, 3).unwrap().count(), 64);
+        assert_eq!(inventory.item(Area::Hotbar, 0).unwrap().count(), 6);
+    }
+
+    #[test]
+    fn insert_into_full_inventory() {
+        let inventory = Inventory::player();
+        for &area in &[Area::Hotbar, Area::Storage] {
+            let mut i = 0;
+            while let Some(mut slot) = inventory.item(area, i) {
+                *slot = InventorySlot::new(Item::Dirt, 64);
+                i += 1;
+            }
+        }
+
+        let mut item = InventorySlot::new(Item::Stone, 10);
+        assert!(insert_into_inventory(&inventory, &mut item).is_empty());
+        assert_eq!(item.count(), 10);
+    }
+
+    #[test]
+    fn merging() {
+        let stone = |count| ItemStack::new(Item::Stone, count).unwrap();
+        assert!(can_merge(&stone(10), &stone(20)));
+        assert!(!can_merge(&stone(40), &stone(40)));
+        assert!(!can_merge(
+            &stone(1),
+            &ItemStack::new(Item::Dirt, 1).unwrap()
+        ));
+        let sword = ItemStack::new(Item::IronSword, 1).unwrap();
+        assert!(!can_merge(&sword, &sword));
+    }
+
+    #[test]
+    fn items_are_thrown_forward() {
+        let mut rng = StdRng::seed_from_u64(0);
+        // Yaw 0 faces south (+Z)
+        let velocity = throw_velocity(0.0, 0.0, &mut rng);
+        assert!(velocity.z > 0.25);
+        assert!(velocity.x.abs() < 0.05);
+
+        let velocity = throw_velocity(90.0, 0.0, &mut rng);
+        assert!(velocity.x < -0.25);
+    }
+}
@@ -4,10 +4,19 @@
 //! It should export a `build_default(&mut EntityBuilder)` function to
 //! add default components for that entity.
 
+use base::EntityKind;
 use ecs::EntityBuilder;
-use quill_common::{components::OnGround, entity_init::EntityInit};
+use quill_common::{
+    components::{Health, OnGround},
+    entity_init::EntityInit,
+};
 use uuid::Uuid;
 
+use crate::{
+    damage::{self, HurtCooldown},
+    physics::{Physics, Velocity},
+};
+
 /// Adds default components shared between all entities.
 fn build_default(builder: &mut EntityBuilder) {
     builder.add(Uuid::new_v4()).add(OnGround(true));
@@ -233,4 +242,48 @@ pub fn add_entity_components(builder: &mut EntityBuilder, init: &EntityInit) {
         EntityInit::Player => player::build_default(builder),
         EntityInit::FishingBobber => fishing_bobber::build_default(builder),
     }
+
+    let kind = *builder
+        .get::<EntityKind>()
+        .expect("all entities have an EntityKind");
+    if let Some(health) = damage::max_health(kind) {
+        add_mob_components(builder, kind, health);
+    }
+}
+
+/// Lets mobs be hurt and knocked back.
+fn add_mob_components(builder: &mut EntityBuilder, kind: EntityKind, health: f32) {
+    if !builder.has::<Health>() {
+        builder.add(Health(health));
+    }
+    builder
+        .add(HurtCooldown::default())
+        .add(Velocity::default());
+
+    // Without AI, flying and swimming mobs would fall.
+    let floats = matches!(
+        kind,
+        EntityKind::Bat
+            | EntityKind::Bee
+            | EntityKind::Blaze
+            | EntityKind::Ghast
+            | EntityKind::Parrot
+            | EntityKind::Phantom
+            | EntityKind::Vex
+            | EntityKind::Wither
+            | EntityKind::EnderDragon
+            | EntityKind::Shulker
+            | EntityKind::Cod
+            | EntityKind::Salmon
+            | EntityKind::Pufferfish
+            | EntityKind::TropicalFish
+            | EntityKind::Squid
+            | EntityKind::Dolphin
+            | EntityKind::Guardian
+            | EntityKind::ElderGuardian
+            | EntityKind::Turtle
+    );
+    if !floats {
+        builder.add(Physics::LIVING_ENTITY);
+    }
 }
@@ -2,7 +2,18 @@ use base::EntityKind;
 use ecs::EntityBuilder;
 use quill_common::entities::Item;
 
+use crate::{
+    dropped_items::{ItemAge, PickupDelay},
+    physics::{Physics, Velocity},
+};
+
 pub fn build_default(builder: &mut EntityBuilder) {
     super::build_default(builder);
-    builder.add(Item).add(EntityKind::Item);
+    builder
+        .add(Item)
+        .add(EntityKind::Item)
+        .add(Velocity::default())
+        .add(Physics::ITEM)
+        .add(ItemAge::default())
+        .add(PickupDelay::default());
 }
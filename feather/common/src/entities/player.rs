@@ -1,10 +1,27 @@
 use anyhow::bail;
-use base::EntityKind;
-use ecs::{EntityBuilder, SysResult};
+use base::{
+    anvil::{
+        entity::{AnimalData, BaseEntityData},
+        player::{InventorySlot, PlayerAbilities, PlayerData},
+    },
+    EntityKind, Gamemode, Inventory, Position,
+};
+use ecs::{Entity, EntityBuilder, SysResult};
 use quill_common::{
-    components::{CreativeFlying, Sneaking, Sprinting},
+    components::{
+        CanBuild, CanCreativeFly, CreativeFlying, CreativeFlyingSpeed, Health, Instabreak,
+        Invulnerable, PreviousGamemode, Sneaking, Sprinting, WalkSpeed,
+    },
     entities::Player,
 };
+use uuid::Uuid;
+
+use crate::{
+    combat::AttackCooldown,
+    damage::{FallDistance, HurtCooldown},
+    respawn::RespawnPoint,
+    Game,
+};
 
 pub fn build_default(builder: &mut EntityBuilder) {
     super::build_default(builder);
@@ -13,6 +30,9 @@ pub fn build_default(builder: &mut EntityBuilder) {
         .add(CreativeFlying(false))
         .add(Sneaking(false))
         .add(Sprinting(false))
+        .add(FallDistance::default())
+        .add(HurtCooldown::default())
+        .add(AttackCooldown::default())
         .add(EntityKind::Player);
 }
 
@@ -38,3 +58,71 @@ impl HotbarSlot {
         Ok(())
     }
 }
+
+/// Saves a player's data to the world's `playerdata` directory.
+pub fn save_player(game: &Game, player: Entity) -> SysResult {
+    let uuid = *game.ecs.get::<Uuid>(player)?;
+    let data = create_player_data(game, player)?;
+    game.world.save_player_data(uuid, &data)
+}
+
+/// Creates the persisted data of a player from its components.
+pub fn create_player_data(game: &Game, player: Entity) -> anyhow::Result<PlayerData> {
+    let player = game.ecs.entity(player)?;
+    let position = *player.get::<Position>()?;
+    let gamemode = *player.get::<Gamemode>()?;
+    let previous_gamemode = *player.get::<PreviousGamemode>()?;
+    let hotbar_slot = *player.get::<HotbarSlot>()?;
+    let inventory = player.get::<Inventory>()?;
+    let respawn_point = player.get::<RespawnPoint>().ok().map(|point| point.0);
+
+    Ok(PlayerData {
+        animal: AnimalData {
+            base: BaseEntityData {
+                position: [position.x, position.y, position.z].into(),
+                rotation: [position.yaw, position.pitch].into(),
+                velocity: [0.0, 0.0, 0.0].into(),
+            },
+            health: **player.get::<Health>()?,
+        },
+        gamemode: gamemode as i32,
+        previous_gamemode: previous_gamemode.id() as i32,
+        inventory: inventory
+            .to_vec()
+            .iter()
+            .enumerate()
+            // Here we filter out all empty slots.
+            .filter_map(|(slot, item)| {
+                match item {
+                    libcraft_items::InventorySlot::Filled(item) => {
+                        let res = InventorySlot::from_network_index(slot, item);
+                        match res {
+                            Some(i) => Some(i),
+                            None => {
+                                log::error!("Failed to convert the slot into anvil format.");
+                                None
+                            }
+                        }
+                    }
+                    libcraft_items::InventorySlot::Empty => {
+                        // Empty items are filtered out.
+                        None
+                    }
+                }
+            })
+            .collect(),
+        held_item: hotbar_slot.get() as i32,
+        spawn_x: respawn_point.map(|point| point.x()),
+        spawn_y: respawn_point.map(|point| point.y()),
+        spawn_z: respawn_point.map(|point| point.z()),
+        abilities: PlayerAbilities {
+            walk_speed: *player.get::<WalkSpeed>()?,
+            fly_speed: *player.get::<CreativeFlyingSpeed>()?,
+            may_fly: *player.get::<CanCreativeFly>()?,
+            is_flying: *player.get::<CreativeFlying>()?,
+            may_build: *player.get::<CanBuild>()?,
+            instabreak: *player.get::<Instabreak>()?,
+            invulnerable: *player.get::<Invulnerable>()?,
+        },
+    })
+}
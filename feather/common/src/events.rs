@@ -1,6 +1,7 @@
-use base::{ChunkHandle, ChunkPosition};
+use base::{ChunkHandle, ChunkPosition, Gamemode, Text, ValidBlockPosition, Vec3d};
+use ecs::Entity;
 
-use crate::view::View;
+use crate::{damage::DamageSource, view::View, weather::Weather};
 
 mod block_change;
 mod plugin_message;
@@ -73,3 +74,97 @@ pub struct EntityRemoveEvent;
 /// Triggered when an entity is added into the world.
 #[derive(Debug)]
 pub struct EntityCreateEvent;
+
+/// Triggered when the weather changes, e.g.
+/// when it starts raining.
+#[derive(Debug)]
+pub struct WeatherChangeEvent {
+    pub from: Weather,
+    pub to: Weather,
+}
+
+/// Triggered when an entity's `PermissionLevel`
+/// changes, e.g. when a player is made an operator.
+#[derive(Debug)]
+pub struct PermissionLevelChangeEvent;
+
+/// Triggered when a game rule is changed at runtime,
+/// e.g. by the `/gamerule` command.
+#[derive(Debug)]
+pub struct GameRuleChangeEvent {
+    /// The vanilla name of the rule, e.g. `keepInventory`.
+    pub rule: String,
+}
+
+/// Triggered on a player when slots of their inventory change
+/// without the client knowing, e.g. when a placed block is
+/// consumed. Holds the network indices of the changed slots.
+#[derive(Debug)]
+pub struct InventoryUpdateEvent(pub Vec<usize>);
+
+/// Triggered on an item entity when a player picks it up.
+/// The item is removed unless only part of it fit
+/// into the player's inventory.
+#[derive(Debug)]
+pub struct ItemCollectEvent {
+    pub collector: Entity,
+    pub count: u32,
+}
+
+/// Triggered on an item entity when its `ItemStack` changes,
+/// e.g. when it merges with another item.
+#[derive(Debug)]
+pub struct ItemStackChangeEvent;
+
+/// Triggered on a player when the crack animation of the
+/// block they are digging changes.
+#[derive(Debug)]
+pub struct BlockBreakProgressEvent {
+    pub position: ValidBlockPosition,
+    /// The destroy stage from 0 to 9, or `None`
+    /// to remove the animation.
+    pub stage: Option<u8>,
+}
+
+/// Triggered on an entity when it takes damage.
+#[derive(Debug)]
+pub struct EntityDamageEvent {
+    pub source: DamageSource,
+    /// The damage taken after armor.
+    pub amount: f32,
+}
+
+/// Triggered on an entity when it dies.
+#[derive(Debug)]
+pub struct EntityDeathEvent {
+    pub source: DamageSource,
+    pub message: Text,
+}
+
+/// Triggered on an entity when a melee attack against it
+/// is a critical hit or deals extra damage from enchantments.
+#[derive(Debug)]
+pub struct MeleeHitEvent {
+    /// Whether the attacker was falling.
+    pub critical: bool,
+    /// Whether enchantments like Sharpness added damage.
+    pub enchanted: bool,
+}
+
+/// Triggered on an entity when it is knocked back.
+/// Holds its new velocity.
+#[derive(Debug)]
+pub struct KnockbackEvent(pub Vec3d);
+
+/// Triggered on an entity when its `Health` changes.
+#[derive(Debug)]
+pub struct HealthChangeEvent;
+
+/// Triggered on a player when they respawn after dying.
+#[derive(Debug)]
+pub struct PlayerRespawnEvent;
+
+/// Triggered when an entity's gamemode is changed,
+/// e.g. by the `/gamemode` command.
+#[derive(Debug)]
+pub struct GamemodeEvent(pub Gamemode);
@@ -5,6 +5,8 @@
 
 #![allow(clippy::unnecessary_wraps)] // systems are required to return Results
 
+use base::GameRules;
+
 mod game;
 use ecs::SystemExecutor;
 pub use game::Game;
@@ -25,6 +27,18 @@ mod region_worker;
 pub mod world;
 pub use world::World;
 
+pub mod autosave;
+
+pub mod metrics;
+pub use metrics::Metrics;
+
+pub mod timings;
+pub use timings::Timings;
+
+pub mod time;
+
+pub mod weather;
+
 pub mod chat;
 pub use chat::ChatBox;
 
@@ -32,12 +46,45 @@ pub mod entities;
 
 pub mod interactable;
 
+pub mod block_placement;
+
+pub mod digging;
+
+pub mod physics;
+
+pub mod dropped_items;
+
+pub mod damage;
+
+pub mod combat;
+
+pub mod respawn;
+
+pub mod commands;
+
+pub mod permissions;
+
 /// Registers gameplay systems with the given `Game` and `SystemExecutor`.
 pub fn register(game: &mut Game, systems: &mut SystemExecutor<Game>) {
     view::register(game, systems);
     chunk::loading::register(game, systems);
     chunk::entities::register(systems);
+    autosave::register(game, systems);
+    metrics::register(game, systems);
+    time::register(systems);
+    weather::register(game, systems);
     interactable::register(game);
+    block_placement::register(systems);
+    digging::register(systems);
+    physics::register(systems);
+    dropped_items::register(systems);
+    damage::register(systems);
+    combat::register(systems);
+    respawn::register(game, systems);
+    commands::register(game);
+    timings::register(game);
+    permissions::register(game);
+    game.insert_resource(GameRules::default());
 
     game.add_entity_spawn_callback(entities::add_entity_components);
 }
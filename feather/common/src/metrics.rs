@@ -0,0 +1,297 @@
+//! Metrics about the server, exported in the Prometheus
+//! text format.
+//!
+//! All metrics are atomics, so recording them never
+//! blocks the game thread.
+
+use std::{
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use ecs::{SysResult, SystemExecutor};
+use once_cell::sync::OnceCell;
+
+use crate::Game;
+
+/// Upper bounds of the tick duration histogram buckets, in seconds.
+const TICK_DURATION_BUCKETS: [f64; 8] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0];
+
+/// Handle to the server's metrics. Stored as a resource.
+///
+/// Can be cloned to create a new handle.
+#[derive(Clone, Default)]
+pub struct Metrics {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    /// Number of ticks in each bucket, not cumulative.
+    /// The last bucket is `+Inf`.
+    tick_buckets: [AtomicU64; TICK_DURATION_BUCKETS.len() + 1],
+    tick_micros: AtomicU64,
+    ticks: AtomicU64,
+
+    /// Names and total run time in microseconds of each system.
+    systems: OnceCell<Vec<(String, AtomicU64)>>,
+    plugin_system_micros: AtomicU64,
+
+    loaded_chunks: AtomicU64,
+    cached_chunks: AtomicU64,
+    chunk_queue_depth: AtomicU64,
+    online_players: AtomicU64,
+
+    received_bytes: AtomicU64,
+    sent_bytes: AtomicU64,
+}
+
+impl Metrics {
+    pub fn record_tick(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        let bucket = TICK_DURATION_BUCKETS
+            .iter()
+            .position(|&bound| seconds <= bound)
+            .unwrap_or(TICK_DURATION_BUCKETS.len());
+        self.inner.tick_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.inner
+            .tick_micros
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+        self.inner.ticks.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Sets the names of all systems, in order. Only the first
+    /// call has an effect, so call this once all systems are registered.
+    pub fn set_system_names<'a>(&self, names: impl IntoIterator<Item = &'a str>) {
+        let systems = names
+            .into_iter()
+            .map(|name| (name.to_owned(), AtomicU64::new(0)))
+            .collect();
+        let _ = self.inner.systems.set(systems);
+    }
+
+    /// Records how long each system took, in the
+    /// order passed to [`set_system_names`](Self::set_system_names).
+    pub fn record_system_times(&self, times: impl IntoIterator<Item = Duration>) {
+        if let Some(systems) = self.inner.systems.get() {
+            for ((_, total), time) in systems.iter().zip(times) {
+                total.fetch_add(time.as_micros() as u64, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn record_plugin_system_time(&self, time: Duration) {
+        self.inner
+            .plugin_system_micros
+            .fetch_add(time.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub fn set_loaded_chunks(&self, count: usize) {
+        self.inner
+            .loaded_chunks
+            .store(count as u64, Ordering::Relaxed);
+    }
+
+    pub fn set_cached_chunks(&self, count: usize) {
+        self.inner
+            .cached_chunks
+            .store(count as u64, Ordering::Relaxed);
+    }
+
+    pub fn set_chunk_queue_depth(&self, depth: usize) {
+        self.inner
+            .chunk_queue_depth
+            .store(depth as u64, Ordering::Relaxed);
+    }
+
+    pub fn set_online_players(&self, count: u32) {
+        self.inner
+            .online_players
+            .store(u64::from(count), Ordering::Relaxed);
+    }
+
+    pub fn add_received_bytes(&self, bytes: usize) {
+        self.inner
+            .received_bytes
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn add_sent_bytes(&self, bytes: usize) {
+        self.inner
+            .sent_bytes
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Renders all metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let inner = &*self.inner;
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "feather_tick_duration_seconds",
+            "histogram",
+            "Time taken by each tick.",
+        );
+        let mut cumulative = 0;
+        for (i, count) in inner.tick_buckets.iter().enumerate() {
+            cumulative += count.load(Ordering::Relaxed);
+            let bound = match TICK_DURATION_BUCKETS.get(i) {
+                Some(bound) => bound.to_string(),
+                None => "+Inf".to_owned(),
+            };
+            let _ = writeln!(
+                out,
+                "feather_tick_duration_seconds_bucket{{le=\"{}\"}} {}",
+                bound, cumulative
+            );
+        }
+        let _ = writeln!(
+            out,
+            "feather_tick_duration_seconds_sum {}",
+            micros_to_seconds(&inner.tick_micros)
+        );
+        let _ = writeln!(
+            out,
+            "feather_tick_duration_seconds_count {}",
+            inner.ticks.load(Ordering::Relaxed)
+        );
+
+        header(
+            &mut out,
+            "feather_system_seconds_total",
+            "counter",
+            "Total time spent running each system.",
+        );
+        for (name, total) in inner.systems.get().into_iter().flatten() {
+            let _ = writeln!(
+                out,
+                "feather_system_seconds_total{{system=\"{}\"}} {}",
+                escape_label(name),
+                micros_to_seconds(total)
+            );
+        }
+
+        header(
+            &mut out,
+            "feather_plugin_system_seconds_total",
+            "counter",
+            "Total time spent running plugin systems.",
+        );
+        let _ = writeln!(
+            out,
+            "feather_plugin_system_seconds_total {}",
+            micros_to_seconds(&inner.plugin_system_micros)
+        );
+
+        let gauges = [
+            (
+                "feather_loaded_chunks",
+                "Number of loaded chunks.",
+                &inner.loaded_chunks,
+            ),
+            (
+                "feather_cached_chunks",
+                "Number of unloaded chunks kept in the chunk cache.",
+                &inner.cached_chunks,
+            ),
+            (
+                "feather_chunk_worker_queue_depth",
+                "Number of chunk loads and saves waiting for the chunk worker.",
+                &inner.chunk_queue_depth,
+            ),
+            (
+                "feather_online_players",
+                "Number of online players.",
+                &inner.online_players,
+            ),
+        ];
+        for (name, help, value) in gauges.iter() {
+            header(&mut out, name, "gauge", help);
+            let _ = writeln!(out, "{} {}", name, value.load(Ordering::Relaxed));
+        }
+
+        let counters = [
+            (
+                "feather_network_received_bytes_total",
+                "Bytes received from players.",
+                &inner.received_bytes,
+            ),
+            (
+                "feather_network_sent_bytes_total",
+                "Bytes sent to players.",
+                &inner.sent_bytes,
+            ),
+        ];
+        for (name, help, value) in counters.iter() {
+            header(&mut out, name, "counter", help);
+            let _ = writeln!(out, "{} {}", name, value.load(Ordering::Relaxed));
+        }
+
+        out
+    }
+}
+
+pub fn register(game: &mut Game, systems: &mut SystemExecutor<Game>) {
+    if game.resources.get::<Metrics>().is_err() {
+        game.insert_resource(Metrics::default());
+    }
+    systems.add_system(update_world_metrics);
+}
+
+fn update_world_metrics(game: &mut Game) -> SysResult {
+    let metrics = game.resources.get::<Metrics>()?;
+    metrics.set_loaded_chunks(game.world.chunk_map().len());
+    metrics.set_cached_chunks(game.world.cache.len());
+    metrics.set_chunk_queue_depth(game.world.chunk_worker_queue_depth());
+    Ok(())
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn micros_to_seconds(micros: &AtomicU64) -> f64 {
+    micros.load(Ordering::Relaxed) as f64 / 1_000_000.0
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render() {
+        let metrics = Metrics::default();
+        metrics.record_tick(Duration::from_millis(3));
+        metrics.record_tick(Duration::from_millis(40));
+        metrics.record_tick(Duration::from_secs(2));
+        metrics.set_system_names(vec!["tick_clients", "say \"hi\""]);
+        metrics.record_system_times(vec![Duration::from_millis(1), Duration::from_millis(2)]);
+        metrics.set_online_players(3);
+        metrics.add_sent_bytes(1024);
+
+        let rendered = metrics.render();
+        assert!(rendered.contains("feather_tick_duration_seconds_bucket{le=\"0.005\"} 1\n"));
+        assert!(rendered.contains("feather_tick_duration_seconds_bucket{le=\"0.05\"} 2\n"));
+        assert!(rendered.contains("feather_tick_duration_seconds_bucket{le=\"+Inf\"} 3\n"));
+        assert!(rendered.contains("feather_tick_duration_seconds_count 3\n"));
+        assert!(rendered.contains("feather_system_seconds_total{system=\"tick_clients\"} 0.001\n"));
+        assert!(
+            rendered.contains("feather_system_seconds_total{system=\"say \\\"hi\\\"\"} 0.002\n")
+        );
+        assert!(rendered.contains("feather_online_players 3\n"));
+        assert!(rendered.contains("feather_network_sent_bytes_total 1024\n"));
+    }
+}
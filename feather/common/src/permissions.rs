@@ -0,0 +1,599 @@
+//! Permission nodes, granted to players directly
+//! or through groups.
+//!
+//! Nodes are dot-separated strings like `feather.command.gamemode`.
+//! A node ending in `*` matches every node below it, and `*`
+//! on its own matches everything. When several entries match,
+//! the most specific one wins, so `foo.bar = false` overrides
+//! `foo.* = true`.
+//!
+//! A player's own entries take precedence over their groups'
+//! entries, which in turn take precedence over the groups
+//! they inherit from. Every player is implicitly a member of
+//! the [`DEFAULT_GROUP`].
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use base::Text;
+use ecs::{Entity, SysResult};
+use libcraft_text::{TextComponentBuilder, TextValue};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    commands::{
+        argument, literal, permission_level, ArgumentKind, CommandContext, CommandDispatcher,
+        StringKind, MAX_PERMISSION_LEVEL,
+    },
+    Game,
+};
+
+/// The group every player belongs to.
+pub const DEFAULT_GROUP: &str = "default";
+
+/// A named set of permission entries.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Group {
+    /// Groups whose entries this group inherits.
+    pub inherits: Vec<String>,
+    pub permissions: BTreeMap<String, bool>,
+}
+
+/// Groups and entries of a single player.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlayerPermissions {
+    pub groups: Vec<String>,
+    pub permissions: BTreeMap<String, bool>,
+}
+
+/// The contents of the permissions file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PermissionsFile {
+    pub groups: BTreeMap<String, Group>,
+    pub players: BTreeMap<Uuid, PlayerPermissions>,
+}
+
+/// Resource holding all groups and player entries.
+#[derive(Debug, Default)]
+pub struct Permissions {
+    file: PermissionsFile,
+    /// Where the permissions are persisted. `None`
+    /// if they only live in memory.
+    path: Option<PathBuf>,
+}
+
+impl Permissions {
+    /// Loads permissions from a TOML or JSON file, depending on
+    /// its extension. Creates the file if it does not exist.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let mut permissions = Self {
+            file: PermissionsFile::default(),
+            path: Some(path.as_ref().to_path_buf()),
+        };
+        if path.as_ref().exists() {
+            permissions.reload()?;
+        } else {
+            permissions
+                .file
+                .groups
+                .insert(DEFAULT_GROUP.to_owned(), Group::default());
+            permissions.save()?;
+        }
+        Ok(permissions)
+    }
+
+    /// Rereads the permissions file.
+    pub fn reload(&mut self) -> anyhow::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        self.file = if is_json(path) {
+            serde_json::from_str(&contents)?
+        } else {
+            toml::from_str(&contents)?
+        };
+        Ok(())
+    }
+
+    /// Writes the permissions back to their file.
+    pub fn save(&self) -> anyhow::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let contents = if is_json(path) {
+            serde_json::to_string_pretty(&self.file)?
+        } else {
+            toml::to_string_pretty(&self.file)?
+        };
+        fs::write(path, contents).with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    pub fn groups(&self) -> &BTreeMap<String, Group> {
+        &self.file.groups
+    }
+
+    pub fn player(&self, uuid: Uuid) -> Option<&PlayerPermissions> {
+        self.file.players.get(&uuid)
+    }
+
+    /// Determines whether a player has a permission node.
+    /// Pass `None` for senders which aren't players; they only
+    /// receive the default group's permissions.
+    pub fn check(&self, player: Option<Uuid>, node: &str) -> bool {
+        self.resolve(player, node).unwrap_or(false)
+    }
+
+    /// Finds the entry deciding whether a player has a node,
+    /// or `None` if nothing matches it.
+    pub fn resolve(&self, player: Option<Uuid>, node: &str) -> Option<bool> {
+        let player = player.and_then(|uuid| self.file.players.get(&uuid));
+        if let Some(value) = player.and_then(|player| lookup(&player.permissions, node)) {
+            return Some(value);
+        }
+
+        let mut visited = HashSet::new();
+        player
+            .into_iter()
+            .flat_map(|player| player.groups.iter())
+            .map(String::as_str)
+            .chain(std::iter::once(DEFAULT_GROUP))
+            .find_map(|group| self.resolve_in_group(group, node, &mut visited))
+    }
+
+    fn resolve_in_group<'a>(
+        &'a self,
+        name: &'a str,
+        node: &str,
+        visited: &mut HashSet<&'a str>,
+    ) -> Option<bool> {
+        // Guard against inheritance cycles
+        if !visited.insert(name) {
+            return None;
+        }
+        let group = self.file.groups.get(name)?;
+        lookup(&group.permissions, node).or_else(|| {
+            group
+                .inherits
+                .iter()
+                .find_map(|parent| self.resolve_in_group(parent, node, visited))
+        })
+    }
+
+    /// Sets or, if `value` is `None`, removes a player's own entry.
+    pub fn set_player_permission(&mut self, player: Uuid, node: &str, value: Option<bool>) {
+        let permissions = &mut self.file.players.entry(player).or_default().permissions;
+        set_entry(permissions, node, value);
+        self.remove_if_empty(player);
+    }
+
+    /// Adds a player to a group. Returns `false` if they
+    /// already were a member.
+    pub fn add_player_group(&mut self, player: Uuid, group: &str) -> bool {
+        let groups = &mut self.file.players.entry(player).or_default().groups;
+        if groups.iter().any(|g| g == group) {
+            return false;
+        }
+        groups.push(group.to_owned());
+        true
+    }
+
+    /// Removes a player from a group. Returns `false` if
+    /// they weren't a member.
+    pub fn remove_player_group(&mut self, player: Uuid, group: &str) -> bool {
+        let removed = match self.file.players.get_mut(&player) {
+            Some(permissions) => {
+                let len = permissions.groups.len();
+                permissions.groups.retain(|g| g != group);
+                permissions.groups.len() != len
+            }
+            None => false,
+        };
+        self.remove_if_empty(player);
+        removed
+    }
+
+    /// Sets or, if `value` is `None`, removes a group's entry.
+    /// Creates the group if needed.
+    pub fn set_group_permission(&mut self, group: &str, node: &str, value: Option<bool>) {
+        let permissions = &mut self.group_mut(group).permissions;
+        set_entry(permissions, node, value);
+    }
+
+    /// Makes a group inherit from another. Returns `false`
+    /// if it already did.
+    pub fn add_group_parent(&mut self, group: &str, parent: &str) -> bool {
+        let inherits = &mut self.group_mut(group).inherits;
+        if inherits.iter().any(|g| g == parent) {
+            return false;
+        }
+        inherits.push(parent.to_owned());
+        true
+    }
+
+    /// Stops a group from inheriting from another. Returns
+    /// `false` if it didn't.
+    pub fn remove_group_parent(&mut self, group: &str, parent: &str) -> bool {
+        match self.file.groups.get_mut(group) {
+            Some(group) => {
+                let len = group.inherits.len();
+                group.inherits.retain(|g| g != parent);
+                group.inherits.len() != len
+            }
+            None => false,
+        }
+    }
+
+    fn group_mut(&mut self, group: &str) -> &mut Group {
+        self.file.groups.entry(group.to_owned()).or_default()
+    }
+
+    fn remove_if_empty(&mut self, player: Uuid) {
+        if self.file.players.get(&player) == Some(&PlayerPermissions::default()) {
+            self.file.players.remove(&player);
+        }
+    }
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension()
+        .map_or(false, |extension| extension == "json")
+}
+
+fn set_entry(permissions: &mut BTreeMap<String, bool>, node: &str, value: Option<bool>) {
+    match value {
+        Some(value) => {
+            permissions.insert(node.to_owned(), value);
+        }
+        None => {
+            permissions.remove(node);
+        }
+    }
+}
+
+/// Finds the most specific entry matching `node`.
+fn lookup(permissions: &BTreeMap<String, bool>, node: &str) -> Option<bool> {
+    if let Some(&value) = permissions.get(node) {
+        return Some(value);
+    }
+    let mut prefix = node;
+    while let Some(dot) = prefix.rfind('.') {
+        prefix = &prefix[..dot];
+        if let Some(&value) = permissions.get(&format!("{}.*", prefix)) {
+            return Some(value);
+        }
+    }
+    permissions.get("*").copied()
+}
+
+/// Determines whether an entity has a permission node.
+///
+/// Senders with the maximum [`PermissionLevel`](crate::commands::PermissionLevel),
+/// like the console, have every node. Entities that don't
+/// exist have no nodes, not even those of the default group.
+pub fn has_permission(game: &Game, entity: Entity, node: &str) -> bool {
+    if game.ecs.entity(entity).is_err() {
+        return false;
+    }
+    if permission_level(game, entity) >= MAX_PERMISSION_LEVEL {
+        return true;
+    }
+    let uuid = game.ecs.get::<Uuid>(entity).ok().map(|uuid| *uuid);
+    game.resources
+        .get::<Permissions>()
+        .map_or(false, |permissions| permissions.check(uuid, node))
+}
+
+pub fn register(game: &mut Game) {
+    game.insert_resource(Permissions::default());
+
+    let player = || {
+        argument(
+            "player",
+            ArgumentKind::Entity {
+                single: true,
+                players_only: true,
+            },
+        )
+    };
+    let group = || argument("group", ArgumentKind::String(StringKind::Word));
+    let node = || argument("node", ArgumentKind::String(StringKind::Greedy));
+
+    let mut dispatcher = game
+        .resources
+        .get_mut::<CommandDispatcher>()
+        .expect("commands not registered");
+    dispatcher.register(
+        literal("permission")
+            .requires(MAX_PERMISSION_LEVEL)
+            .then(literal("reload").executes(reload))
+            .then(literal("check").then(player().then(node().executes(check))))
+            .then(
+                literal("player").then(
+                    player()
+                        .then(
+                            literal("allow").then(node().executes(|game, ctx| {
+                                set_player_permission(game, ctx, Some(true))
+                            })),
+                        )
+                        .then(literal("deny").then(
+                            node().executes(|game, ctx| {
+                                set_player_permission(game, ctx, Some(false))
+                            }),
+                        ))
+                        .then(literal("unset").then(
+                            node().executes(|game, ctx| set_player_permission(game, ctx, None)),
+                        ))
+                        .then(
+                            literal("group")
+                                .then(literal("add").then(
+                                    group().executes(|game, ctx| set_player_group(game, ctx, true)),
+                                ))
+                                .then(
+                                    literal("remove").then(
+                                        group().executes(|game, ctx| {
+                                            set_player_group(game, ctx, false)
+                                        }),
+                                    ),
+                                ),
+                        ),
+                ),
+            )
+            .then(
+                literal("group").then(
+                    group()
+                        .then(
+                            literal("allow").then(
+                                node().executes(|game, ctx| {
+                                    set_group_permission(game, ctx, Some(true))
+                                }),
+                            ),
+                        )
+                        .then(
+                            literal("deny").then(node().executes(|game, ctx| {
+                                set_group_permission(game, ctx, Some(false))
+                            })),
+                        )
+                        .then(literal("unset").then(
+                            node().executes(|game, ctx| set_group_permission(game, ctx, None)),
+                        ))
+                        .then(
+                            literal("parent")
+                                .then(
+                                    literal("add").then(
+                                        argument("parent", ArgumentKind::String(StringKind::Word))
+                                            .executes(|game, ctx| {
+                                                set_group_parent(game, ctx, true)
+                                            }),
+                                    ),
+                                )
+                                .then(
+                                    literal("remove").then(
+                                        argument("parent", ArgumentKind::String(StringKind::Word))
+                                            .executes(|game, ctx| {
+                                                set_group_parent(game, ctx, false)
+                                            }),
+                                    ),
+                                ),
+                        ),
+                ),
+            ),
+    );
+}
+
+fn reload(game: &mut Game, ctx: &CommandContext) -> SysResult {
+    game.resources.get_mut::<Permissions>()?.reload()?;
+    ctx.reply(game, "Reloaded permissions")
+}
+
+fn check(game: &mut Game, ctx: &CommandContext) -> SysResult {
+    let player = match target_player(game, ctx)? {
+        Some(player) => player,
+        None => return Ok(()),
+    };
+    let node = ctx.string("node")?;
+    let value = has_permission(game, player, node);
+    let message = format!("{} {} {}", player_name(game, player), verb(value), node);
+    ctx.reply(game, message)
+}
+
+fn set_player_permission(game: &mut Game, ctx: &CommandContext, value: Option<bool>) -> SysResult {
+    let player = match target_player(game, ctx)? {
+        Some(player) => player,
+        None => return Ok(()),
+    };
+    let uuid = *game.ecs.get::<Uuid>(player)?;
+    let node = ctx.string("node")?;
+    {
+        let mut permissions = game.resources.get_mut::<Permissions>()?;
+        permissions.set_player_permission(uuid, node, value);
+        permissions.save()?;
+    }
+    let message = match value {
+        Some(value) => format!("{} now {} {}", player_name(game, player), verb(value), node),
+        None => format!("Unset {} for {}", node, player_name(game, player)),
+    };
+    ctx.reply(game, message)
+}
+
+fn set_player_group(game: &mut Game, ctx: &CommandContext, add: bool) -> SysResult {
+    let player = match target_player(game, ctx)? {
+        Some(player) => player,
+        None => return Ok(()),
+    };
+    let uuid = *game.ecs.get::<Uuid>(player)?;
+    let group = ctx.string("group")?;
+    let changed = {
+        let mut permissions = game.resources.get_mut::<Permissions>()?;
+        let changed = if add {
+            permissions.add_player_group(uuid, group)
+        } else {
+            permissions.remove_player_group(uuid, group)
+        };
+        permissions.save()?;
+        changed
+    };
+
+    let name = player_name(game, player);
+    let message = match (add, changed) {
+        (true, true) => format!("Added {} to group {}", name, group),
+        (false, true) => format!("Removed {} from group {}", name, group),
+        (true, false) => return failure(game, ctx, format!("{} is already in {}", name, group)),
+        (false, false) => return failure(game, ctx, format!("{} is not in {}", name, group)),
+    };
+    ctx.reply(game, message)
+}
+
+fn set_group_permission(game: &mut Game, ctx: &CommandContext, value: Option<bool>) -> SysResult {
+    let group = ctx.string("group")?;
+    let node = ctx.string("node")?;
+    {
+        let mut permissions = game.resources.get_mut::<Permissions>()?;
+        permissions.set_group_permission(group, node, value);
+        permissions.save()?;
+    }
+    let message = match value {
+        Some(value) => format!("Group {} now {} {}", group, verb(value), node),
+        None => format!("Unset {} for group {}", node, group),
+    };
+    ctx.reply(game, message)
+}
+
+fn set_group_parent(game: &mut Game, ctx: &CommandContext, add: bool) -> SysResult {
+    let group = ctx.string("group")?;
+    let parent = ctx.string("parent")?;
+    let changed = {
+        let mut permissions = game.resources.get_mut::<Permissions>()?;
+        let changed = if add {
+            permissions.add_group_parent(group, parent)
+        } else {
+            permissions.remove_group_parent(group, parent)
+        };
+        permissions.save()?;
+        changed
+    };
+
+    match (add, changed) {
+        (true, true) => ctx.reply(game, format!("Group {} now inherits {}", group, parent)),
+        (false, true) => ctx.reply(
+            game,
+            format!("Group {} no longer inherits {}", group, parent),
+        ),
+        (true, false) => failure(game, ctx, format!("{} already inherits {}", group, parent)),
+        (false, false) => failure(game, ctx, format!("{} does not inherit {}", group, parent)),
+    }
+}
+
+fn target_player(game: &mut Game, ctx: &CommandContext) -> anyhow::Result<Option<Entity>> {
+    let player = ctx.entities(game, "player")?.into_iter().next();
+    if player.is_none() {
+        ctx.reply(
+            game,
+            Text::from(TextValue::translate("argument.entity.notfound.player")).red(),
+        )?;
+    }
+    Ok(player)
+}
+
+fn player_name(game: &Game, player: Entity) -> String {
+    game.ecs
+        .get::<quill_common::components::Name>(player)
+        .map(|name| name.to_string())
+        .unwrap_or_default()
+}
+
+fn verb(value: bool) -> &'static str {
+    if value {
+        "has"
+    } else {
+        "lacks"
+    }
+}
+
+fn failure(game: &mut Game, ctx: &CommandContext, message: String) -> SysResult {
+    ctx.reply(game, Text::from(message).red())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn permissions() -> Permissions {
+        let file: PermissionsFile = toml::from_str(
+            r#"
+            [groups.default.permissions]
+            "feather.command.help" = true
+
+            [groups.moderator]
+            inherits = ["default"]
+            [groups.moderator.permissions]
+            "feather.command.*" = true
+            "feather.command.stop" = false
+
+            [groups.admin]
+            inherits = ["moderator", "admin"]
+            permissions = { "*" = true }
+
+            [players.00000000-0000-0000-0000-000000000001]
+            groups = ["moderator"]
+            permissions = { "feather.command.stop" = true }
+            "#,
+        )
+        .unwrap();
+        Permissions { file, path: None }
+    }
+
+    #[test]
+    fn wildcards_and_specificity() {
+        let mut permissions = permissions();
+        let moderator = Uuid::from_u128(2);
+        permissions.add_player_group(moderator, "moderator");
+
+        let moderator = Some(moderator);
+        assert!(permissions.check(moderator, "feather.command.gamemode"));
+        assert!(!permissions.check(moderator, "feather.command.stop"));
+        assert!(!permissions.check(moderator, "feather.other"));
+        assert!(permissions.check(None, "feather.command.help"));
+        assert!(!permissions.check(None, "feather.command.gamemode"));
+    }
+
+    #[test]
+    fn player_overrides_and_inheritance() {
+        let mut permissions = permissions();
+        let player = Some(Uuid::from_u128(1));
+        assert!(permissions.check(player, "feather.command.stop"));
+
+        let admin = Uuid::from_u128(3);
+        permissions.add_player_group(admin, "admin");
+        // Cyclic inheritance must not loop forever
+        assert!(permissions.check(Some(admin), "anything"));
+
+        permissions.set_player_permission(admin, "anything", Some(false));
+        assert!(!permissions.check(Some(admin), "anything"));
+        permissions.set_player_permission(admin, "anything", None);
+        assert!(permissions.remove_player_group(admin, "admin"));
+        assert!(permissions.player(admin).is_none());
+    }
+
+    #[test]
+    fn missing_entities_have_no_permissions() {
+        let mut game = Game::new();
+        game.insert_resource(permissions());
+        let entity = game.ecs.spawn((Uuid::from_u128(4),));
+        assert!(has_permission(&game, entity, "feather.command.help"));
+
+        game.ecs.despawn(entity).unwrap();
+        assert!(!has_permission(&game, entity, "feather.command.help"));
+    }
+}
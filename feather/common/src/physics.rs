@@ -0,0 +1,184 @@
+//! Simple physics for entities simulated by the server,
+//! like dropped items. Players move themselves.
+
+use std::convert::TryFrom;
+
+use base::{BlockPosition, EntityKind, Position, ValidBlockPosition, Vec3d};
+use ecs::{SysResult, SystemExecutor};
+use quill_common::components::OnGround;
+
+use crate::Game;
+
+/// Horizontal speed kept per tick by entities on the ground,
+/// on top of the drag. Vanilla's slipperiness of most blocks.
+const GROUND_FRICTION: f64 = 0.6;
+
+/// The velocity of an entity, in blocks per tick.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Velocity(pub Vec3d);
+
+/// Marks an entity as simulated by [`simulate_physics`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Physics {
+    /// Acceleration downwards, in blocks per tick squared.
+    pub gravity: f64,
+    /// Fraction of its velocity the entity keeps each tick.
+    pub drag: f64,
+}
+
+impl Physics {
+    pub const ITEM: Physics = Physics {
+        gravity: 0.04,
+        drag: 0.98,
+    };
+
+    pub const LIVING_ENTITY: Physics = Physics {
+        gravity: 0.08,
+        drag: 0.98,
+    };
+}
+
+pub fn register(systems: &mut SystemExecutor<Game>) {
+    systems.add_system(simulate_physics);
+}
+
+/// Moves entities by their velocity, stopping them at
+/// solid blocks, then applies gravity and drag.
+fn simulate_physics(game: &mut Game) -> SysResult {
+    for (_, (position, velocity, on_ground, physics, kind)) in game
+        .ecs
+        .query::<(
+            &mut Position,
+            &mut Velocity,
+            &mut OnGround,
+            &Physics,
+            &EntityKind,
+        )>()
+        .iter()
+    {
+        let size = kind.bounding_box().max;
+        let velocity = &mut velocity.0;
+        velocity.y -= physics.gravity;
+
+        let (new_position, collided) = move_entity(game, *position, *velocity, size);
+        if new_position != *position {
+            *position = new_position;
+        }
+        on_ground.0 = collided.y && velocity.y < 0.0;
+
+        if collided.x {
+            velocity.x = 0.0;
+        }
+        if collided.y {
+            velocity.y = 0.0;
+        }
+        if collided.z {
+            velocity.z = 0.0;
+        }
+        *velocity *= physics.drag;
+        if on_ground.0 {
+            velocity.x *= GROUND_FRICTION;
+            velocity.z *= GROUND_FRICTION;
+        }
+    }
+    Ok(())
+}
+
+/// Which axes a movement was blocked on.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct Collisions {
+    x: bool,
+    y: bool,
+    z: bool,
+}
+
+/// Moves an entity of the given size one axis at a time,
+/// vertically first, stopping at solid blocks.
+fn move_entity(
+    game: &Game,
+    mut position: Position,
+    velocity: Vec3d,
+    size: Vec3d,
+) -> (Position, Collisions) {
+    let mut collided = Collisions::default();
+
+    let mut moved = position;
+    moved.y += velocity.y;
+    if !collides(game, moved, size) {
+        position = moved;
+    } else {
+        collided.y = true;
+        // Land on top of the block instead of hovering
+        // up to a tick's movement above it.
+        if velocity.y < 0.0 {
+            moved.y = moved.y.floor() + 1.0;
+            if moved.y <= position.y && !collides(game, moved, size) {
+                position = moved;
+            }
+        }
+    }
+
+    let mut moved = position;
+    moved.x += velocity.x;
+    if collides(game, moved, size) {
+        collided.x = true;
+    } else {
+        position = moved;
+    }
+
+    let mut moved = position;
+    moved.z += velocity.z;
+    if collides(game, moved, size) {
+        collided.z = true;
+    } else {
+        position = moved;
+    }
+
+    (position, collided)
+}
+
+/// Whether two entities' bounding boxes overlap once the first
+/// is expanded by `grow` in each direction.
+pub fn overlaps(a: (Position, EntityKind), b: (Position, EntityKind), grow: Vec3d) -> bool {
+    let (a_size, b_size) = (a.1.bounding_box().max, b.1.bounding_box().max);
+    let (a, b) = (a.0, b.0);
+    (a.x - b.x).abs() < (a_size.x + b_size.x) / 2.0 + grow.x
+        && (a.z - b.z).abs() < (a_size.z + b_size.z) / 2.0 + grow.z
+        && a.y - grow.y < b.y + b_size.y
+        && b.y < a.y + a_size.y + grow.y
+}
+
+/// Whether an entity of the given size at `position` overlaps
+/// a solid block. Unloaded blocks count as solid so that
+/// entities don't fall out of the loaded world.
+fn collides(game: &Game, position: Position, size: Vec3d) -> bool {
+    let half_width = size.x / 2.0;
+    let min = BlockPosition::new(
+        (position.x - half_width).floor() as i32,
+        position.y.floor() as i32,
+        (position.z - half_width).floor() as i32,
+    );
+    // Subtract a small epsilon so that an entity touching
+    // a block face doesn't count as being inside the block.
+    let max = BlockPosition::new(
+        (position.x + half_width - 1e-7).floor() as i32,
+        (position.y + size.y - 1e-7).floor() as i32,
+        (position.z + half_width - 1e-7).floor() as i32,
+    );
+
+    for x in min.x..=max.x {
+        for y in min.y..=max.y {
+            for z in min.z..=max.z {
+                let solid = match ValidBlockPosition::try_from(BlockPosition::new(x, y, z)) {
+                    Ok(pos) => game.block(pos).map_or(true, |block| block.is_solid()),
+                    // Below or above the world
+                    Err(_) => false,
+                };
+                if solid {
+                    return true;
+                }
+            }
+        }
+    }
+    false
+}
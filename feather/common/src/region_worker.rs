@@ -1,6 +1,7 @@
 use std::{
     collections::hash_map::Entry,
     path::PathBuf,
+    thread::JoinHandle,
     time::{Duration, Instant},
 };
 
@@ -60,11 +61,11 @@ impl RegionWorker {
         )
     }
 
-    pub fn start(self) {
+    pub fn start(self) -> JoinHandle<()> {
         std::thread::Builder::new()
             .name("chunk_worker".to_owned())
             .spawn(move || self.run())
-            .expect("failed to create chunk worker thread");
+            .expect("failed to create chunk worker thread")
     }
 
     fn run(mut self) {
@@ -74,6 +75,10 @@ impl RegionWorker {
                 Ok(req) => match req {
                     WorkerRequest::Load(load) => self.load_chunk(load),
                     WorkerRequest::Save(save) => self.save_chunk(save).unwrap(),
+                    WorkerRequest::Shutdown => {
+                        log::info!("Chunk worker shutting down");
+                        return;
+                    }
                 },
                 Err(flume::RecvTimeoutError::Timeout) => (),
                 Err(flume::RecvTimeoutError::Disconnected) => {
@@ -128,7 +133,11 @@ impl RegionWorker {
 
         file.last_used = Instant::now();
 
-        ChunkLoadResult::Loaded(LoadedChunk { pos, chunk })
+        ChunkLoadResult::Loaded(LoadedChunk {
+            pos,
+            chunk,
+            generated: false,
+        })
     }
 
     fn region_file_handle(&mut self, region: RegionPosition) -> Option<&mut OpenRegionFile> {
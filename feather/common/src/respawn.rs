@@ -0,0 +1,193 @@
+//! Respawning dead players at their bed or the world spawn.
+
+use std::convert::TryFrom;
+
+use base::{
+    anvil::level::LevelData, BlockId, BlockKind, BlockPosition, Position, Text, ValidBlockPosition,
+};
+use ecs::{Entity, SysResult, SystemExecutor};
+use libcraft_text::TextValue;
+use quill_common::{components::Health, events::BlockInteractEvent};
+
+use crate::{
+    chat::{ChatKind, ChatMessage},
+    damage::{Dead, FallDistance, HurtCooldown, PLAYER_MAX_HEALTH},
+    events::{HealthChangeEvent, PlayerRespawnEvent},
+    interactable::InteractableRegistry,
+    Game,
+};
+
+const BEDS: [BlockKind; 16] = [
+    BlockKind::WhiteBed,
+    BlockKind::OrangeBed,
+    BlockKind::MagentaBed,
+    BlockKind::LightBlueBed,
+    BlockKind::YellowBed,
+    BlockKind::LimeBed,
+    BlockKind::PinkBed,
+    BlockKind::GrayBed,
+    BlockKind::LightGrayBed,
+    BlockKind::CyanBed,
+    BlockKind::PurpleBed,
+    BlockKind::BlueBed,
+    BlockKind::BrownBed,
+    BlockKind::GreenBed,
+    BlockKind::RedBed,
+    BlockKind::BlackBed,
+];
+
+/// The bed at which a player respawns.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RespawnPoint(pub ValidBlockPosition);
+
+pub fn register(game: &mut Game, systems: &mut SystemExecutor<Game>) {
+    {
+        let mut registry = game
+            .resources
+            .get_mut::<InteractableRegistry>()
+            .expect("the interactable registry is inserted before beds are registered");
+        for &bed in &BEDS {
+            registry.register(bed);
+        }
+    }
+    systems.add_system(set_respawn_points);
+}
+
+/// Sets the respawn point of players who use a bed.
+fn set_respawn_points(game: &mut Game) -> SysResult {
+    let interactions: Vec<(Entity, BlockPosition)> = game
+        .ecs
+        .query::<&BlockInteractEvent>()
+        .iter()
+        .map(|(player, event)| (player, event.location))
+        .collect();
+
+    for (player, location) in interactions {
+        let bed = match ValidBlockPosition::try_from(location) {
+            Ok(bed) if game.block(bed).map_or(false, is_bed) => bed,
+            _ => continue,
+        };
+        if game
+            .ecs
+            .get::<RespawnPoint>(player)
+            .map_or(false, |point| point.0 == bed)
+        {
+            continue;
+        }
+
+        game.ecs.insert(player, RespawnPoint(bed))?;
+        game.send_message(
+            player,
+            ChatMessage::new(
+                ChatKind::System,
+                Text::from(TextValue::translate("block.minecraft.set_spawn")),
+            ),
+        )?;
+    }
+    Ok(())
+}
+
+fn is_bed(block: BlockId) -> bool {
+    BEDS.contains(&block.kind())
+}
+
+/// Respawns a dead player with full health at their bed,
+/// or at the world spawn if the bed is missing or obstructed.
+pub fn respawn(game: &mut Game, player: Entity) -> SysResult {
+    if game.ecs.remove::<Dead>(player).is_err() {
+        return Ok(());
+    }
+
+    let bed = game.ecs.get::<RespawnPoint>(player).map(|point| point.0);
+    let position = match bed {
+        Ok(bed) => match bed_spawn_position(game, bed) {
+            Some(position) => Some(position),
+            None => {
+                game.ecs.remove::<RespawnPoint>(player)?;
+                game.send_message(
+                    player,
+                    ChatMessage::new(
+                        ChatKind::System,
+                        Text::from(TextValue::translate("block.minecraft.spawn.not_valid")),
+                    ),
+                )?;
+                None
+            }
+        },
+        Err(_) => None,
+    };
+    let position = match position {
+        Some(position) => position,
+        None => world_spawn(&*game.resources.get::<LevelData>()?),
+    };
+
+    game.ecs.insert(player, position)?;
+    game.ecs.insert(player, Health(PLAYER_MAX_HEALTH))?;
+    game.ecs.insert(player, HurtCooldown::default())?;
+    game.ecs.insert(player, FallDistance::default())?;
+    game.ecs.insert_entity_event(player, HealthChangeEvent)?;
+    game.ecs.insert_entity_event(player, PlayerRespawnEvent)?;
+    Ok(())
+}
+
+/// Gets the position at which new players spawn.
+pub fn world_spawn(level: &LevelData) -> Position {
+    Position {
+        x: level.spawn_x as f64 + 0.5,
+        y: level.spawn_y as f64,
+        z: level.spawn_z as f64 + 0.5,
+        yaw: 0.0,
+        pitch: 0.0,
+    }
+}
+
+/// Finds a free spot next to a bed to respawn at.
+///
+/// Returns `None` if the bed was destroyed or is obstructed.
+/// Beds in unloaded chunks are assumed to still exist.
+fn bed_spawn_position(game: &Game, bed: ValidBlockPosition) -> Option<Position> {
+    match game.block(bed) {
+        Some(block) if !is_bed(block) => return None,
+        Some(_) => {}
+        None => {
+            return Some(Position {
+                x: bed.x() as f64 + 0.5,
+                y: bed.y() as f64 + 0.5625,
+                z: bed.z() as f64 + 0.5,
+                yaw: 0.0,
+                pitch: 0.0,
+            })
+        }
+    }
+
+    for &dy in &[0, 1] {
+        for dx in -1..=1 {
+            for dz in -1..=1 {
+                let feet = BlockPosition::new(bed.x() + dx, bed.y() + dy, bed.z() + dz);
+                if can_stand_at(game, feet) {
+                    return Some(Position {
+                        x: feet.x as f64 + 0.5,
+                        y: feet.y as f64,
+                        z: feet.z as f64 + 0.5,
+                        yaw: 0.0,
+                        pitch: 0.0,
+                    });
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Whether a player fits at `feet` with solid ground below.
+fn can_stand_at(game: &Game, feet: BlockPosition) -> bool {
+    let is_solid = |position: BlockPosition| {
+        ValidBlockPosition::try_from(position)
+            .ok()
+            .and_then(|position| game.block(position))
+            .map(|block| block.is_solid())
+    };
+    is_solid(feet.down()) == Some(true)
+        && is_solid(feet) == Some(false)
+        && is_solid(feet.up()) == Some(false)
+}
@@ -2,10 +2,13 @@ use std::time::Instant;
 
 use base::TICK_DURATION;
 
+use crate::Metrics;
+
 /// Utility to invoke a function in a tick loop, once
 /// every 50ms.
 pub struct TickLoop {
     function: Box<dyn FnMut() -> bool>,
+    metrics: Option<Metrics>,
 }
 
 impl TickLoop {
@@ -15,9 +18,16 @@ impl TickLoop {
     pub fn new(function: impl FnMut() -> bool + 'static) -> Self {
         Self {
             function: Box::new(function),
+            metrics: None,
         }
     }
 
+    /// Records the duration of each tick in the given `Metrics`.
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
     /// Runs the tick loop until the callback returns `true`.
     pub fn run(mut self) {
         loop {
@@ -28,6 +38,9 @@ impl TickLoop {
             }
 
             let elapsed = start.elapsed();
+            if let Some(metrics) = &self.metrics {
+                metrics.record_tick(elapsed);
+            }
             if elapsed > TICK_DURATION {
                 log::warn!("Tick took too long ({:?})", elapsed);
             } else {
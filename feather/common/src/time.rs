@@ -0,0 +1,28 @@
+//! Advances the world age and the time of day.
+
+use base::{anvil::level::LevelData, GameRules};
+use ecs::{SysResult, SystemExecutor};
+
+use crate::Game;
+
+/// Number of ticks in a Minecraft day.
+pub const TICKS_PER_DAY: i64 = 24_000;
+
+pub fn register(systems: &mut SystemExecutor<Game>) {
+    systems.add_system(advance_time);
+}
+
+/// Advances the time stored in the `LevelData` resource.
+///
+/// The time of day only advances if the `doDaylightCycle`
+/// game rule is enabled.
+fn advance_time(game: &mut Game) -> SysResult {
+    let daylight_cycle = game.resources.get::<GameRules>()?.do_daylight_cycle;
+    if let Ok(mut level) = game.resources.get_mut::<LevelData>() {
+        level.time += 1;
+        if daylight_cycle {
+            level.day_time += 1;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,334 @@
+//! An opt-in profiler recording how long each system
+//! takes, including systems registered by plugins.
+//!
+//! Enabled with `/timings on`. `/timings report` prints
+//! the systems taking the most time, and `/timings dump`
+//! writes a JSON or folded-stack report to disk. Folded
+//! stacks can be rendered with `flamegraph.pl` or `inferno`.
+
+use std::{fs, path::PathBuf, time::Duration};
+
+use base::Text;
+use ecs::SysResult;
+use libcraft_text::TextComponentBuilder;
+use serde::Serialize;
+
+use crate::{
+    commands::{argument, literal, ArgumentKind, CommandContext, CommandDispatcher},
+    Game,
+};
+
+/// Number of ticks kept for each system, i.e. 30 seconds.
+const WINDOW: usize = 600;
+
+/// Number of systems listed by `/timings report` by default.
+const DEFAULT_REPORT_COUNT: i32 = 10;
+
+/// Permission level required to use `/timings`.
+const TIMINGS_LEVEL: u8 = 4;
+
+/// Per-system timings of recent ticks. Stored as a resource.
+#[derive(Default)]
+pub struct Timings {
+    enabled: bool,
+    systems: Vec<SystemSamples>,
+}
+
+/// The last [`WINDOW`] run times of a system, in microseconds.
+struct SystemSamples {
+    name: String,
+    samples: Vec<u32>,
+    /// Index of the next sample to overwrite
+    /// once `samples` is full.
+    next: usize,
+}
+
+impl SystemSamples {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            samples: Vec::with_capacity(WINDOW),
+            next: 0,
+        }
+    }
+
+    fn push(&mut self, time: Duration) {
+        let micros = time.as_micros().min(u32::MAX as u128) as u32;
+        if self.samples.len() < WINDOW {
+            self.samples.push(micros);
+        } else {
+            self.samples[self.next] = micros;
+            self.next = (self.next + 1) % WINDOW;
+        }
+    }
+}
+
+/// Summary of a system's recent run times, in microseconds.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SystemStats {
+    pub name: String,
+    pub samples: usize,
+    pub total: u64,
+    pub average: f64,
+    pub p50: u32,
+    pub p95: u32,
+    pub p99: u32,
+    pub max: u32,
+}
+
+impl SystemStats {
+    fn from_samples(name: &str, samples: &[u32]) -> Self {
+        let mut sorted = samples.to_vec();
+        sorted.sort_unstable();
+        let total: u64 = sorted.iter().map(|&sample| u64::from(sample)).sum();
+        let average = if sorted.is_empty() {
+            0.0
+        } else {
+            total as f64 / sorted.len() as f64
+        };
+        Self {
+            name: name.to_owned(),
+            samples: sorted.len(),
+            total,
+            average,
+            p50: percentile(&sorted, 50),
+            p95: percentile(&sorted, 95),
+            p99: percentile(&sorted, 99),
+            max: sorted.last().copied().unwrap_or_default(),
+        }
+    }
+}
+
+/// Nearest-rank percentile of sorted samples.
+fn percentile(sorted: &[u32], percent: usize) -> u32 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (percent * sorted.len() + 99) / 100;
+    sorted[rank.max(1) - 1]
+}
+
+impl Timings {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Discards all recorded samples.
+    pub fn reset(&mut self) {
+        self.systems.clear();
+    }
+
+    /// Records the run time of each system in the last tick,
+    /// in the order the executor runs them. Does nothing
+    /// while disabled.
+    pub fn record<'a>(&mut self, timings: impl IntoIterator<Item = (&'a str, Duration)>) {
+        if !self.enabled {
+            return;
+        }
+
+        for (i, (name, time)) in timings.into_iter().enumerate() {
+            // Systems may be added after recording starts,
+            // e.g. when a plugin is enabled.
+            match self.systems.get(i) {
+                Some(system) if system.name == name => {}
+                Some(_) => self.systems[i] = SystemSamples::new(name),
+                None => self.systems.push(SystemSamples::new(name)),
+            }
+            self.systems[i].push(time);
+        }
+    }
+
+    /// Returns statistics for each system, most expensive first.
+    pub fn stats(&self) -> Vec<SystemStats> {
+        let mut stats: Vec<SystemStats> = self
+            .systems
+            .iter()
+            .map(|system| SystemStats::from_samples(&system.name, &system.samples))
+            .collect();
+        stats.sort_by(|a, b| b.average.partial_cmp(&a.average).unwrap());
+        stats
+    }
+
+    /// Renders the statistics as JSON.
+    pub fn to_json(&self) -> anyhow::Result<String> {
+        Ok(serde_json::to_string_pretty(&self.stats())?)
+    }
+
+    /// Renders the total time of each system in the folded-stack
+    /// format used by flamegraph tools, one `tick;system micros`
+    /// line per system.
+    pub fn to_folded(&self) -> String {
+        self.stats()
+            .iter()
+            .map(|stats| format!("tick;{} {}\n", stats.name.replace(';', ":"), stats.total))
+            .collect()
+    }
+}
+
+pub fn register(game: &mut Game) {
+    game.insert_resource(Timings::default());
+    if let Ok(mut dispatcher) = game.resources.get_mut::<CommandDispatcher>() {
+        register_commands(&mut dispatcher);
+    }
+}
+
+fn register_commands(dispatcher: &mut CommandDispatcher) {
+    dispatcher.register(
+        literal("timings")
+            .requires(TIMINGS_LEVEL)
+            .then(literal("on").executes(|game, ctx| set_enabled(game, ctx, true)))
+            .then(literal("off").executes(|game, ctx| set_enabled(game, ctx, false)))
+            .then(literal("reset").executes(reset))
+            .then(
+                literal("report").executes(report).then(
+                    argument(
+                        "count",
+                        ArgumentKind::Integer {
+                            min: Some(1),
+                            max: None,
+                        },
+                    )
+                    .executes(report),
+                ),
+            )
+            .then(
+                literal("dump")
+                    .executes(|game, ctx| dump(game, ctx, false))
+                    .then(literal("json").executes(|game, ctx| dump(game, ctx, false)))
+                    .then(literal("folded").executes(|game, ctx| dump(game, ctx, true))),
+            ),
+    );
+}
+
+fn set_enabled(game: &mut Game, ctx: &CommandContext, enabled: bool) -> SysResult {
+    game.resources.get_mut::<Timings>()?.set_enabled(enabled);
+    let message = if enabled {
+        "Enabled timings"
+    } else {
+        "Disabled timings"
+    };
+    ctx.reply(game, message)
+}
+
+fn reset(game: &mut Game, ctx: &CommandContext) -> SysResult {
+    game.resources.get_mut::<Timings>()?.reset();
+    ctx.reply(game, "Reset timings")
+}
+
+fn report(game: &mut Game, ctx: &CommandContext) -> SysResult {
+    let count = ctx.integer("count").unwrap_or(DEFAULT_REPORT_COUNT) as usize;
+    let (enabled, stats) = {
+        let timings = game.resources.get::<Timings>()?;
+        (timings.is_enabled(), timings.stats())
+    };
+
+    if stats.is_empty() {
+        let hint = if enabled {
+            "No timings recorded yet"
+        } else {
+            "No timings recorded; enable them with /timings on"
+        };
+        return ctx.reply(game, Text::from(hint).red());
+    }
+
+    ctx.reply(
+        game,
+        format!(
+            "Slowest systems over the last {} ticks (avg / p95 / p99 / max):",
+            stats[0].samples
+        ),
+    )?;
+    for stats in stats.iter().take(count) {
+        ctx.reply(
+            game,
+            format!(
+                "{}: {} / {} / {} / {}",
+                stats.name,
+                format_micros(stats.average),
+                format_micros(f64::from(stats.p95)),
+                format_micros(f64::from(stats.p99)),
+                format_micros(f64::from(stats.max)),
+            ),
+        )?;
+    }
+    Ok(())
+}
+
+fn dump(game: &mut Game, ctx: &CommandContext, folded: bool) -> SysResult {
+    let (contents, extension) = {
+        let timings = game.resources.get::<Timings>()?;
+        if folded {
+            (timings.to_folded(), "folded")
+        } else {
+            (timings.to_json()?, "json")
+        }
+    };
+
+    let path = PathBuf::from(format!(
+        "timings-{}.{}",
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+        extension
+    ));
+    match fs::write(&path, contents) {
+        Ok(()) => ctx.reply(game, format!("Wrote timings to {}", path.display())),
+        Err(e) => {
+            log::error!("Failed to write timings to {}: {}", path.display(), e);
+            ctx.reply(game, Text::from("Failed to write timings").red())
+        }
+    }
+}
+
+fn format_micros(micros: f64) -> String {
+    if micros >= 1000.0 {
+        format!("{:.2}ms", micros / 1000.0)
+    } else {
+        format!("{:.0}µs", micros)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentiles() {
+        let samples: Vec<u32> = (1..=100).collect();
+        let stats = SystemStats::from_samples("system", &samples);
+        assert_eq!(stats.p50, 50);
+        assert_eq!(stats.p95, 95);
+        assert_eq!(stats.p99, 99);
+        assert_eq!(stats.max, 100);
+        assert!((stats.average - 50.5).abs() < f64::EPSILON);
+
+        assert_eq!(percentile(&[], 99), 0);
+        assert_eq!(percentile(&[7], 50), 7);
+    }
+
+    #[test]
+    fn record_rolls_over() {
+        let mut timings = Timings::default();
+        timings.record(vec![("a", Duration::from_micros(5))]);
+        assert!(timings.stats().is_empty());
+
+        timings.set_enabled(true);
+        for i in 0..WINDOW + 10 {
+            timings.record(vec![
+                ("a", Duration::from_micros(i as u64)),
+                ("b", Duration::from_micros(1000)),
+            ]);
+        }
+        let stats = timings.stats();
+        assert_eq!(stats[0].name, "b");
+        assert_eq!(stats[1].samples, WINDOW);
+        assert_eq!(stats[1].max, (WINDOW + 9) as u32);
+
+        assert_eq!(timings.to_folded().lines().next(), Some("tick;b 600000"));
+    }
+}
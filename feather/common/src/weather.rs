@@ -0,0 +1,238 @@
+//! Rain and thunder cycles, including lightning strikes.
+//!
+//! The weather state lives in the `LevelData` resource,
+//! so it is persisted to level.dat along with the time.
+
+use base::{anvil::level::LevelData, GameRules, Position};
+use ecs::{SysResult, SystemExecutor};
+use quill_common::{entities::LightningBolt, entity_init::EntityInit};
+use rand::Rng;
+
+use crate::{events::WeatherChangeEvent, Game};
+
+/// Inverse chance of lightning striking
+/// a loaded chunk each tick during a thunderstorm.
+const LIGHTNING_CHANCE: u32 = 100_000;
+
+/// Number of ticks a lightning bolt exists before it is removed.
+const LIGHTNING_LIFETIME: u32 = 10;
+
+pub fn register(game: &mut Game, systems: &mut SystemExecutor<Game>) {
+    if game.resources.get::<WeatherLevels>().is_err() {
+        game.insert_resource(WeatherLevels::default());
+    }
+    systems
+        .add_system(update_weather)
+        .add_system(strike_lightning)
+        .add_system(remove_lightning_bolts);
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Weather {
+    Clear,
+    Rain,
+    Thunder,
+}
+
+impl Weather {
+    /// Returns the current weather stored in `level`.
+    pub fn of(level: &LevelData) -> Self {
+        if level.thundering && level.raining {
+            Weather::Thunder
+        } else if level.raining {
+            Weather::Rain
+        } else {
+            Weather::Clear
+        }
+    }
+
+    /// Sets the weather in `level` for `duration` ticks,
+    /// as done by the vanilla `/weather` command.
+    pub fn apply(self, level: &mut LevelData, duration: i32) {
+        let (clear, raining, thundering) = match self {
+            Weather::Clear => (duration, false, false),
+            Weather::Rain => (0, true, false),
+            Weather::Thunder => (0, true, true),
+        };
+        level.clear_weather_time = clear;
+        level.rain_time = if raining { duration } else { 0 };
+        level.thunder_time = if thundering { duration } else { 0 };
+        level.raining = raining;
+        level.thundering = thundering;
+    }
+}
+
+/// The intensity of rain and thunder, which
+/// fade in and out over time. Clients use these
+/// to render the sky and precipitation.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct WeatherLevels {
+    pub rain: f32,
+    pub thunder: f32,
+}
+
+impl WeatherLevels {
+    /// Returns the levels after the weather in `level`
+    /// has been going on for a while, e.g. on startup.
+    pub fn settled(level: &LevelData) -> Self {
+        Self {
+            rain: if level.raining { 1.0 } else { 0.0 },
+            thunder: if level.thundering { 1.0 } else { 0.0 },
+        }
+    }
+
+    fn update(&mut self, level: &LevelData) {
+        let step = |value: f32, increase: bool| {
+            let delta = if increase { 0.01 } else { -0.01 };
+            (value + delta).max(0.0).min(1.0)
+        };
+        self.rain = step(self.rain, level.raining);
+        self.thunder = step(self.thunder, level.thundering);
+    }
+}
+
+fn update_weather(game: &mut Game) -> SysResult {
+    let weather_cycle = game.resources.get::<GameRules>()?.do_weather_cycle;
+    let mut level = match game.resources.get_mut::<LevelData>() {
+        Ok(level) => level,
+        Err(_) => return Ok(()),
+    };
+
+    let from = Weather::of(&level);
+    if weather_cycle {
+        tick_weather(&mut level, &mut rand::thread_rng());
+    }
+    let to = Weather::of(&level);
+
+    game.resources.get_mut::<WeatherLevels>()?.update(&level);
+    drop(level);
+
+    if from != to {
+        log::debug!("Weather changed from {:?} to {:?}", from, to);
+        game.ecs.insert_event(WeatherChangeEvent { from, to });
+    }
+    Ok(())
+}
+
+/// Counts down the weather timers, starting or stopping
+/// rain and thunder when they expire. Mirrors vanilla.
+fn tick_weather(level: &mut LevelData, rng: &mut impl Rng) {
+    if level.clear_weather_time > 0 {
+        level.clear_weather_time -= 1;
+        level.thunder_time = if level.thundering { 0 } else { 1 };
+        level.rain_time = if level.raining { 0 } else { 1 };
+        level.thundering = false;
+        level.raining = false;
+        return;
+    }
+
+    if level.thunder_time > 0 {
+        level.thunder_time -= 1;
+        if level.thunder_time == 0 {
+            level.thundering = !level.thundering;
+        }
+    } else if level.thundering {
+        level.thunder_time = rng.gen_range(3_600..15_600);
+    } else {
+        level.thunder_time = rng.gen_range(12_000..180_000);
+    }
+
+    if level.rain_time > 0 {
+        level.rain_time -= 1;
+        if level.rain_time == 0 {
+            level.raining = !level.raining;
+        }
+    } else if level.raining {
+        level.rain_time = rng.gen_range(12_000..24_000);
+    } else {
+        level.rain_time = rng.gen_range(12_000..180_000);
+    }
+}
+
+/// Tracks how long a lightning bolt has existed.
+struct LightningAge(u32);
+
+/// Randomly strikes loaded chunks with lightning during thunderstorms.
+fn strike_lightning(game: &mut Game) -> SysResult {
+    match game.resources.get::<LevelData>() {
+        Ok(level) if Weather::of(&level) == Weather::Thunder => (),
+        _ => return Ok(()),
+    }
+
+    let mut rng = rand::thread_rng();
+    let mut strikes = Vec::new();
+    for chunk in game.world.chunk_map().iter_chunks() {
+        if rng.gen_range(0..LIGHTNING_CHANCE) != 0 {
+            continue;
+        }
+        let chunk = chunk.read();
+        let (x, z) = (rng.gen_range(0..16), rng.gen_range(0..16));
+        let y = chunk.heightmaps().motion_blocking.height(x, z).unwrap_or(0);
+        let pos = chunk.position();
+        strikes.push(Position {
+            x: (pos.x * 16 + x as i32) as f64 + 0.5,
+            y: y as f64,
+            z: (pos.z * 16 + z as i32) as f64 + 0.5,
+            yaw: 0.0,
+            pitch: 0.0,
+        });
+    }
+
+    for position in strikes {
+        let mut builder = game.create_entity_builder(position, EntityInit::LightningBolt);
+        builder.add(LightningAge(0));
+        game.spawn_entity(builder);
+    }
+    Ok(())
+}
+
+fn remove_lightning_bolts(game: &mut Game) -> SysResult {
+    let mut expired = Vec::new();
+    for (entity, (_, age)) in game
+        .ecs
+        .query::<(&LightningBolt, &mut LightningAge)>()
+        .iter()
+    {
+        age.0 += 1;
+        if age.0 == LIGHTNING_LIFETIME {
+            expired.push(entity);
+        }
+    }
+    for entity in expired {
+        game.remove_entity(entity)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clear_weather_time_prevents_rain() {
+        let mut level = LevelData::default();
+        Weather::Clear.apply(&mut level, 2);
+        let mut rng = rand::thread_rng();
+        tick_weather(&mut level, &mut rng);
+        tick_weather(&mut level, &mut rng);
+        assert_eq!(level.clear_weather_time, 0);
+        assert_eq!(Weather::of(&level), Weather::Clear);
+        assert_eq!(level.rain_time, 1);
+
+        tick_weather(&mut level, &mut rng);
+        assert!(level.raining);
+    }
+
+    #[test]
+    fn rain_stops_when_timer_expires() {
+        let mut level = LevelData::default();
+        Weather::Thunder.apply(&mut level, 1);
+        let mut rng = rand::thread_rng();
+        tick_weather(&mut level, &mut rng);
+        assert_eq!(Weather::of(&level), Weather::Clear);
+
+        tick_weather(&mut level, &mut rng);
+        assert_eq!(Weather::of(&level), Weather::Clear);
+        assert!(level.rain_time >= 12_000);
+    }
+}
@@ -4,7 +4,7 @@ use ahash::{AHashMap, AHashSet};
 use parking_lot::{RwLockReadGuard, RwLockWriteGuard};
 use uuid::Uuid;
 
-use base::anvil::player::PlayerData;
+use base::anvil::{level::LevelData, player::PlayerData};
 use base::{
     BlockPosition, Chunk, ChunkHandle, ChunkLock, ChunkPosition, ValidBlockPosition, CHUNK_HEIGHT,
 };
@@ -91,6 +91,10 @@ impl World {
             let chunk = loaded.chunk;
 
             self.chunk_map.insert_chunk(chunk);
+            if loaded.generated {
+                // Generated chunks don't exist on disk yet
+                self.chunk_map.0[&loaded.pos].mark_dirty();
+            }
             ecs.insert_event(ChunkLoadEvent {
                 chunk: Arc::clone(&self.chunk_map.0[&loaded.pos]),
                 position: loaded.pos,
@@ -104,12 +108,9 @@ impl World {
     pub fn unload_chunk(&mut self, pos: ChunkPosition) -> anyhow::Result<()> {
         if let Some((pos, handle)) = self.chunk_map.0.remove_entry(&pos) {
             handle.set_unloaded()?;
-            self.chunk_worker.queue_chunk_save(SaveRequest {
-                pos,
-                chunk: handle.clone(),
-                entities: vec![],
-                block_entities: vec![],
-            });
+            if handle.take_dirty() {
+                self.queue_chunk_save(pos, Arc::clone(&handle));
+            }
             self.cache.insert(pos, handle);
         }
         self.chunk_map.remove_chunk(pos);
@@ -159,6 +160,67 @@ impl World {
         &mut self.chunk_map
     }
 
+    /// Returns the positions of loaded chunks which
+    /// have changed since they were last saved.
+    pub fn dirty_chunks(&self) -> Vec<ChunkPosition> {
+        self.chunk_map
+            .0
+            .iter()
+            .filter(|(_, handle)| handle.is_dirty())
+            .map(|(&pos, _)| pos)
+            .collect()
+    }
+
+    /// Queues the given chunk to be saved if it is loaded and dirty.
+    ///
+    /// Returns whether a save was queued.
+    pub fn save_chunk(&mut self, pos: ChunkPosition) -> bool {
+        match self.chunk_map.chunk_handle_at(pos) {
+            Some(handle) if handle.take_dirty() => {
+                self.queue_chunk_save(pos, handle);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn queue_chunk_save(&mut self, pos: ChunkPosition, chunk: ChunkHandle) {
+        self.chunk_worker.queue_chunk_save(SaveRequest {
+            pos,
+            chunk,
+            entities: vec![],
+            block_entities: vec![],
+        });
+    }
+
+    /// Returns the number of chunk loads and saves
+    /// waiting for the chunk worker.
+    pub fn chunk_worker_queue_depth(&self) -> usize {
+        self.chunk_worker.queue_depth()
+    }
+
+    /// Saves all dirty chunks and blocks until they
+    /// have been written to disk.
+    ///
+    /// Chunks can no longer be loaded or saved afterward,
+    /// so this should only be called when shutting down.
+    pub fn save_all_and_close(&mut self) -> anyhow::Result<()> {
+        let dirty_chunks = self.dirty_chunks();
+        log::info!("Saving {} chunks", dirty_chunks.len());
+        for pos in dirty_chunks {
+            self.save_chunk(pos);
+        }
+        self.chunk_worker.shutdown()
+    }
+
+    pub fn load_level_data(&self) -> anyhow::Result<Option<LevelData>> {
+        base::anvil::level::load_level_data(&self.world_dir)
+    }
+
+    pub fn save_level_data(&self, data: &LevelData) -> anyhow::Result<()> {
+        base::anvil::level::save_level_data(&self.world_dir, data)
+    }
+
     pub fn load_player_data(&self, uuid: Uuid) -> anyhow::Result<PlayerData> {
         Ok(base::anvil::player::load_player_data(
             &self.world_dir,
@@ -243,6 +305,15 @@ impl ChunkMap {
     pub fn remove_chunk(&mut self, pos: ChunkPosition) -> bool {
         self.0.remove(&pos).is_some()
     }
+
+    /// Returns the number of loaded chunks.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
 }
 
 fn check_coords(pos: ValidBlockPosition) -> Option<()> {
@@ -281,4 +352,22 @@ mod tests {
             .block_at(BlockPosition::new(0, 0, 0).try_into().unwrap())
             .is_some());
     }
+
+    #[test]
+    fn setting_blocks_marks_chunks_dirty() {
+        let mut world = World::new();
+        world
+            .chunk_map_mut()
+            .insert_chunk(Chunk::new(ChunkPosition::new(0, 0)));
+        world
+            .chunk_map_mut()
+            .insert_chunk(Chunk::new(ChunkPosition::new(1, 0)));
+        assert!(world.dirty_chunks().is_empty());
+
+        world.set_block_at(
+            BlockPosition::new(16, 1, 0).try_into().unwrap(),
+            BlockId::stone(),
+        );
+        assert_eq!(world.dirty_chunks(), vec![ChunkPosition::new(1, 0)]);
+    }
 }
@@ -1,6 +1,14 @@
 //! System execution, using a simple "systems as functions" model.
 
-use std::{any::type_name, marker::PhantomData, sync::Arc};
+use std::{
+    any::type_name,
+    marker::PhantomData,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
 use crate::{Ecs, Resources};
 
@@ -25,6 +33,8 @@ type SystemFn<Input> = Box<dyn FnMut(&mut Input) -> SysResult>;
 struct System<Input> {
     function: SystemFn<Input>,
     name: String,
+    /// How long the last run took.
+    last_run_time: Duration,
 }
 
 impl<Input> System<Input> {
@@ -32,6 +42,7 @@ impl<Input> System<Input> {
         Self {
             function: Box::new(f),
             name: type_name::<F>().to_owned(),
+            last_run_time: Duration::default(),
         }
     }
 }
@@ -72,6 +83,10 @@ impl HasEcs for Ecs {
 pub struct SystemExecutor<Input> {
     systems: Vec<System<Input>>,
 
+    /// Index of the running system, or `usize::MAX`
+    /// if no system is running.
+    current_system: Arc<AtomicUsize>,
+
     is_first_run: bool,
 }
 
@@ -79,6 +94,7 @@ impl<Input> Default for SystemExecutor<Input> {
     fn default() -> Self {
         Self {
             systems: Vec::new(),
+            current_system: Arc::new(AtomicUsize::new(usize::MAX)),
             is_first_run: true,
         }
     }
@@ -131,6 +147,7 @@ impl<Input> SystemExecutor<Input> {
     {
         for (i, system) in self.systems.iter_mut().enumerate() {
             input.ecs_mut().set_current_system_index(i);
+            self.current_system.store(i, Ordering::Relaxed);
 
             // For the first cycle, we don't want to clear
             // events because some code may have triggered
@@ -141,7 +158,9 @@ impl<Input> SystemExecutor<Input> {
                 input.ecs_mut().remove_old_events();
             }
 
+            let start = Instant::now();
             let result = (system.function)(input);
+            system.last_run_time = start.elapsed();
             if let Err(e) = result {
                 log::error!(
                     "System {} returned an error; this is a bug: {:?}",
@@ -151,13 +170,29 @@ impl<Input> SystemExecutor<Input> {
             }
         }
 
+        self.current_system.store(usize::MAX, Ordering::Relaxed);
         self.is_first_run = false;
     }
 
+    /// Gets a handle to the index of the running system,
+    /// which can be read from other threads. The index
+    /// is `usize::MAX` while no system is running.
+    pub fn current_system(&self) -> Arc<AtomicUsize> {
+        Arc::clone(&self.current_system)
+    }
+
     /// Gets an iterator over system names.
     pub fn system_names(&self) -> impl Iterator<Item = &'_ str> + '_ {
         self.systems.iter().map(|system| system.name.as_str())
     }
+
+    /// Gets an iterator over system names and how
+    /// long each system took in the last run.
+    pub fn timings(&self) -> impl Iterator<Item = (&'_ str, Duration)> + '_ {
+        self.systems
+            .iter()
+            .map(|system| (system.name.as_str(), system.last_run_time))
+    }
 }
 
 /// Builder for a group. Created with [`SystemExecutor::group`].
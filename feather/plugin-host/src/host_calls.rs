@@ -65,6 +65,7 @@ host_calls! {
     "entity_exists" => entity_exists,
     "entity_send_message" => entity_send_message,
     "entity_send_title" => entity_send_title,
+    "entity_has_permission" => entity_has_permission,
     "block_get" => block_get,
     "block_set" => block_set,
     "block_fill_chunk_section" => block_fill_chunk_section,
@@ -1,5 +1,8 @@
 use feather_base::Text;
-use feather_common::chat::{ChatKind, ChatMessage};
+use feather_common::{
+    chat::{ChatKind, ChatMessage},
+    permissions,
+};
 use feather_ecs::Entity;
 use feather_plugin_host_macros::host_function;
 
@@ -38,3 +41,16 @@ pub fn entity_send_title(
     cx.game_mut().send_title(entity, title);
     Ok(())
 }
+
+#[host_function]
+pub fn entity_has_permission(
+    cx: &PluginContext,
+    entity: u64,
+    node_ptr: PluginPtr<u8>,
+    node_len: u32,
+) -> anyhow::Result<u32> {
+    let node = cx.read_string(node_ptr, node_len)?;
+    let entity = Entity::from_bits(entity);
+    let has_permission = permissions::has_permission(&cx.game_mut(), entity, &node);
+    Ok(has_permission as u32)
+}
@@ -1,8 +1,8 @@
 #![allow(warnings)]
 
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, rc::Rc, time::Instant};
 
-use feather_common::Game;
+use feather_common::{Game, Metrics};
 use feather_ecs::{HasResources, SysResult};
 use feather_plugin_host_macros::host_function;
 
@@ -19,6 +19,9 @@ pub fn register_system(
     name_len: u32,
 ) -> anyhow::Result<()> {
     let name = cx.read_string(name_ptr, name_len)?;
+    // Prefix with the plugin name so plugin systems
+    // can be told apart in timings.
+    let name = format!("[{}] {}", cx.plugin_name(), name);
 
     let game = cx.game_mut();
     game.system_executor
@@ -34,7 +37,11 @@ fn plugin_system(id: PluginId, data_ptr: PluginPtrMut<u8>) -> impl FnMut(&mut Ga
         let plugin_manager = plugin_manager.borrow();
         let plugin = plugin_manager.plugin(id);
         if let Some(plugin) = plugin {
+            let start = Instant::now();
             plugin.run_system(game, data_ptr)?;
+            if let Ok(metrics) = game.resources.get::<Metrics>() {
+                metrics.record_plugin_system_time(start.elapsed());
+            }
         }
 
         Ok(())
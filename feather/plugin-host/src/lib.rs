@@ -125,6 +125,11 @@ impl PluginManager {
         self.plugins.get(id.0)
     }
 
+    /// Iterates over all loaded plugins.
+    pub fn plugins(&self) -> impl Iterator<Item = &Plugin> + '_ {
+        self.plugins.iter().map(|(_, plugin)| plugin)
+    }
+
     /// Mutably gets the plugin with the given ID,
     /// or `None` if it has been unloaded.
     pub fn plugin_mut(&mut self, id: PluginId) -> Option<&mut Plugin> {
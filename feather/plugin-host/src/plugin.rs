@@ -36,7 +36,7 @@ impl Plugin {
 
         let (inner, context) = match &file.metadata().target {
             PluginTarget::Wasm => {
-                let context = Arc::new(PluginContext::new_wasm(id));
+                let context = Arc::new(PluginContext::new_wasm(id, file.metadata().name.clone()));
                 let plugin =
                     wasm::WasmPlugin::load(manager, &context, file.module(), file.metadata())?;
                 (Inner::Wasm(plugin), context)
@@ -50,7 +50,7 @@ impl Plugin {
                     );
                 }
                 let plugin = native::NativePlugin::load(file.module())?;
-                let context = PluginContext::new_native(id);
+                let context = PluginContext::new_native(id, file.metadata().name.clone());
                 (Inner::Native(plugin), Arc::new(context))
             }
         };
@@ -81,6 +81,11 @@ impl Plugin {
         Ok(())
     }
 
+    /// Gets the metadata of this plugin, like its name and version.
+    pub fn metadata(&self) -> &PluginMetadata {
+        &self.metadata
+    }
+
     /// Runs a plugin system.
     ///
     /// `data` must be the data pointer passed
@@ -1,5 +1,6 @@
 use crate::{io::VarInt, ProtocolVersion, Readable, Writeable};
 use aes::Aes128;
+use anyhow::bail;
 use bytes::BytesMut;
 use cfb8::{
     cipher::{AsyncStreamCipher, NewCipher},
@@ -17,14 +18,24 @@ pub type CompressionThreshold = usize;
 /// An encryption key for use with AES-CFB8.
 pub type CryptKey = [u8; 16];
 
+/// Default maximum size of a decompressed packet. Same as vanilla.
+pub const DEFAULT_MAX_PACKET_SIZE: usize = 8 * 1024 * 1024;
+
+/// Maximum length of a packet as sent, which is
+/// the largest value of a 3-byte VarInt.
+const MAX_PACKET_LENGTH: i32 = 2_097_151;
+const MAX_PACKET_LENGTH_LENGTH: usize = 3;
+
 /// State to serialize and deserialize packets from a byte stream.
-#[derive(Default)]
 pub struct MinecraftCodec {
     /// If encryption is enabled, then this is the cryptor state.
     cryptor: Option<AesCfb8>,
     crypt_key: Option<CryptKey>,
     /// If compression is enabled, then this is the compression threshold.
     compression: Option<CompressionThreshold>,
+    /// Received packets larger than this after
+    /// decompression are rejected.
+    max_packet_size: usize,
 
     /// A buffer of received bytes.
     received_buf: BytesMut,
@@ -34,6 +45,20 @@ pub struct MinecraftCodec {
     compression_target: Vec<u8>,
 }
 
+impl Default for MinecraftCodec {
+    fn default() -> Self {
+        Self {
+            cryptor: None,
+            crypt_key: None,
+            compression: None,
+            max_packet_size: DEFAULT_MAX_PACKET_SIZE,
+            received_buf: BytesMut::new(),
+            staging_buf: Vec::new(),
+            compression_target: Vec::new(),
+        }
+    }
+}
+
 impl MinecraftCodec {
     pub fn new() -> Self {
         Self::default()
@@ -51,6 +76,12 @@ impl MinecraftCodec {
         self.compression = Some(threshold);
     }
 
+    /// Sets the maximum size of a received packet after decompression.
+    /// Larger packets cause [`next_packet`](Self::next_packet) to fail.
+    pub fn set_max_packet_size(&mut self, max_packet_size: usize) {
+        self.max_packet_size = max_packet_size;
+    }
+
     /// Gets another `MinecraftCodec` with the same compression and encryption
     /// parameters.
     pub fn clone_with_settings(&self) -> MinecraftCodec {
@@ -60,6 +91,7 @@ impl MinecraftCodec {
                 .map(|key| AesCfb8::new_from_slices(&key, &key).expect("key size is invalid")),
             crypt_key: self.crypt_key,
             compression: self.compression,
+            max_packet_size: self.max_packet_size,
             received_buf: BytesMut::new(),
             staging_buf: Vec::new(),
             compression_target: Vec::new(),
@@ -154,6 +186,9 @@ impl MinecraftCodec {
     {
         let mut cursor = Cursor::new(&self.received_buf[..]);
         let packet = if let Ok(length) = VarInt::read(&mut cursor, ProtocolVersion::V1_16_2) {
+            if length.0 < 0 || length.0 > MAX_PACKET_LENGTH {
+                bail!("packet length {} is out of bounds", length.0);
+            }
             let length_field_length = cursor.position() as usize;
 
             if self.received_buf.len() - length_field_length >= length.0 as usize {
@@ -165,9 +200,28 @@ impl MinecraftCodec {
                 if self.compression.is_some() {
                     let data_length = VarInt::read(&mut cursor, ProtocolVersion::V1_16_2)?;
                     if data_length.0 != 0 {
-                        let mut decoder =
+                        if data_length.0 < 0 || data_length.0 as usize > self.max_packet_size {
+                            bail!(
+                                "decompressed packet size {} exceeds the maximum of {}",
+                                data_length.0,
+                                self.max_packet_size
+                            );
+                        }
+                        // Never decompress more than the declared size,
+                        // so small packets can't expand without bound.
+                        self.compression_target.clear();
+                        let decoder =
                             ZlibDecoder::new(&cursor.get_ref()[cursor.position() as usize..]);
-                        decoder.read_to_end(&mut self.compression_target)?;
+                        decoder
+                            .take(data_length.0 as u64)
+                            .read_to_end(&mut self.compression_target)?;
+                        if self.compression_target.len() != data_length.0 as usize {
+                            bail!(
+                                "decompressed packet size {} does not match the declared size {}",
+                                self.compression_target.len(),
+                                data_length.0
+                            );
+                        }
                         cursor = Cursor::new(&self.compression_target);
                     }
                 }
@@ -182,6 +236,9 @@ impl MinecraftCodec {
             } else {
                 None
             }
+        } else if self.received_buf.len() >= MAX_PACKET_LENGTH_LENGTH {
+            // A valid length would have been complete by now.
+            bail!("malformed packet length");
         } else {
             None
         };
@@ -189,3 +246,110 @@ impl MinecraftCodec {
         Ok(packet)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn var_int(value: i32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        VarInt(value)
+            .write(&mut bytes, ProtocolVersion::V1_16_2)
+            .unwrap();
+        bytes
+    }
+
+    /// Frames `data_length` and `data` as a compressed packet.
+    fn compressed_packet(data_length: i32, data: &[u8]) -> Vec<u8> {
+        let mut body = var_int(data_length);
+        body.extend_from_slice(data);
+        let mut packet = var_int(body.len() as i32);
+        packet.extend(body);
+        packet
+    }
+
+    fn compress(data: &[u8]) -> Vec<u8> {
+        let mut compressed = Vec::new();
+        ZlibEncoder::new(data, Compression::default())
+            .read_to_end(&mut compressed)
+            .unwrap();
+        compressed
+    }
+
+    #[test]
+    fn roundtrip_compressed() {
+        let mut codec = MinecraftCodec::new();
+        codec.enable_compression(0);
+        codec.set_max_packet_size(64);
+
+        let message = "a".repeat(32);
+        let mut bytes = Vec::new();
+        codec.encode(&message, &mut bytes).unwrap();
+        codec.accept(&bytes);
+        assert_eq!(codec.next_packet::<String>().unwrap(), Some(message));
+        assert_eq!(codec.next_packet::<String>().unwrap(), None);
+    }
+
+    #[test]
+    fn rejects_packets_over_max_size() {
+        let mut codec = MinecraftCodec::new();
+        codec.enable_compression(0);
+        codec.set_max_packet_size(64);
+
+        let data = vec![0; 65];
+        codec.accept(&compressed_packet(65, &compress(&data)));
+        assert!(codec.next_packet::<String>().is_err());
+    }
+
+    #[test]
+    fn rejects_negative_data_length() {
+        let mut codec = MinecraftCodec::new();
+        codec.enable_compression(0);
+
+        codec.accept(&compressed_packet(-1, &compress(&[0])));
+        assert!(codec.next_packet::<String>().is_err());
+    }
+
+    #[test]
+    fn rejects_mismatched_data_length() {
+        let mut codec = MinecraftCodec::new();
+        codec.enable_compression(0);
+
+        let data = vec![0; 16];
+        codec.accept(&compressed_packet(32, &compress(&data)));
+        assert!(codec.next_packet::<String>().is_err());
+    }
+
+    #[test]
+    fn rejects_out_of_bounds_length() {
+        let mut codec = MinecraftCodec::new();
+        codec.accept(&var_int(MAX_PACKET_LENGTH + 1));
+        assert!(codec.next_packet::<String>().is_err());
+
+        let mut codec = MinecraftCodec::new();
+        codec.accept(&var_int(-1));
+        assert!(codec.next_packet::<String>().is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_length() {
+        let mut codec = MinecraftCodec::new();
+        codec.accept(&[0xFF; MAX_PACKET_LENGTH_LENGTH]);
+        assert!(codec.next_packet::<String>().is_err());
+    }
+
+    #[test]
+    fn waits_for_incomplete_packets() {
+        let mut codec = MinecraftCodec::new();
+        let mut bytes = Vec::new();
+        codec.encode(&"hello".to_owned(), &mut bytes).unwrap();
+
+        codec.accept(&bytes[..2]);
+        assert_eq!(codec.next_packet::<String>().unwrap(), None);
+        codec.accept(&bytes[2..]);
+        assert_eq!(
+            codec.next_packet::<String>().unwrap(),
+            Some("hello".to_owned())
+        );
+    }
+}
@@ -4,6 +4,9 @@ use base::{
     BlockState, EntityMetadata, Gamemode, ParticleKind, ProfileProperty, ValidBlockPosition,
 };
 pub use chunk_data::{ChunkData, ChunkDataKind};
+pub use declare_commands::{
+    ArgumentParser, CommandNode, CommandNodeKind, DeclareCommands, StringArgumentKind,
+};
 use quill_common::components::PreviousGamemode;
 pub use update_light::UpdateLight;
 
@@ -12,6 +15,7 @@ use crate::{io::VarLong, Readable, Writeable};
 use super::*;
 
 mod chunk_data;
+mod declare_commands;
 mod update_light;
 packets! {
     SpawnEntity {
@@ -236,24 +240,6 @@ packets! {
         tooltip Option<String>;
     }
 
-    DeclareCommands {
-        // (not implemented)
-        __todo__ LengthInferredVecU8;
-        /* nodes LengthPrefixedVec<CommandNode>;
-        root_index VarInt; */
-    }
-
-    CommandNode {
-        flags u8;
-        children VarIntPrefixedVec<VarInt>;
-        redirect_node Option<VarInt>;
-        name Option<String>;
-        parser Option<String>;
-        // TODO: handle properties, which vary depending on the value of `parser`.
-        // This can be handled with an enum.
-        __todo__ LengthInferredVecU8;
-    }
-
     WindowConfirmation {
         window_id u8;
         action_number i16;
@@ -854,7 +840,7 @@ packets! {
         world_name String;
         hashed_seed u64;
         gamemode Gamemode;
-        previous_gamemode Gamemode;
+        previous_gamemode PreviousGamemode;
         is_debug bool;
         is_flat bool;
         copy_metadata bool;
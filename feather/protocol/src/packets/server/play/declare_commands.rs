@@ -0,0 +1,326 @@
+use anyhow::bail;
+
+use crate::{io::VarInt, ProtocolVersion, Readable, Writeable};
+
+const NODE_TYPE_MASK: u8 = 0x03;
+const FLAG_EXECUTABLE: u8 = 0x04;
+const FLAG_REDIRECT: u8 = 0x08;
+const FLAG_SUGGESTIONS: u8 = 0x10;
+
+/// Sends the command tree to the client, which uses it
+/// to highlight, validate and complete commands locally.
+#[derive(Debug, Clone)]
+pub struct DeclareCommands {
+    pub nodes: Vec<CommandNode>,
+    pub root_index: i32,
+}
+
+#[derive(Debug, Clone)]
+pub struct CommandNode {
+    pub kind: CommandNodeKind,
+    pub executable: bool,
+    pub children: Vec<i32>,
+    pub redirect_node: Option<i32>,
+    /// Identifier of a suggestions provider, e.g. `minecraft:ask_server`.
+    pub suggestions_type: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum CommandNodeKind {
+    Root,
+    Literal {
+        name: String,
+    },
+    Argument {
+        name: String,
+        parser: ArgumentParser,
+    },
+}
+
+/// How a string argument consumes input.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StringArgumentKind {
+    SingleWord = 0,
+    QuotablePhrase = 1,
+    GreedyPhrase = 2,
+}
+
+/// An argument parser and its properties.
+#[derive(Debug, Clone)]
+pub enum ArgumentParser {
+    Bool,
+    Double {
+        min: Option<f64>,
+        max: Option<f64>,
+    },
+    Float {
+        min: Option<f32>,
+        max: Option<f32>,
+    },
+    Integer {
+        min: Option<i32>,
+        max: Option<i32>,
+    },
+    String(StringArgumentKind),
+    Entity {
+        single: bool,
+        only_players: bool,
+    },
+    ScoreHolder {
+        multiple: bool,
+    },
+    Range {
+        decimals: bool,
+    },
+    /// A parser without properties, e.g. `minecraft:block_pos`.
+    Other(String),
+}
+
+impl ArgumentParser {
+    fn identifier(&self) -> &str {
+        match self {
+            ArgumentParser::Bool => "brigadier:bool",
+            ArgumentParser::Double { .. } => "brigadier:double",
+            ArgumentParser::Float { .. } => "brigadier:float",
+            ArgumentParser::Integer { .. } => "brigadier:integer",
+            ArgumentParser::String(_) => "brigadier:string",
+            ArgumentParser::Entity { .. } => "minecraft:entity",
+            ArgumentParser::ScoreHolder { .. } => "minecraft:score_holder",
+            ArgumentParser::Range { .. } => "minecraft:range",
+            ArgumentParser::Other(identifier) => identifier,
+        }
+    }
+
+    fn write_properties(
+        &self,
+        buffer: &mut Vec<u8>,
+        version: ProtocolVersion,
+    ) -> anyhow::Result<()> {
+        match self {
+            ArgumentParser::Double { min, max } => write_bounds(*min, *max, buffer, version)?,
+            ArgumentParser::Float { min, max } => write_bounds(*min, *max, buffer, version)?,
+            ArgumentParser::Integer { min, max } => write_bounds(*min, *max, buffer, version)?,
+            ArgumentParser::String(kind) => VarInt(*kind as i32).write(buffer, version)?,
+            ArgumentParser::Entity {
+                single,
+                only_players,
+            } => {
+                let mut flags = 0u8;
+                if *single {
+                    flags |= 0x01;
+                }
+                if *only_players {
+                    flags |= 0x02;
+                }
+                flags.write(buffer, version)?;
+            }
+            ArgumentParser::ScoreHolder { multiple } => (*multiple as u8).write(buffer, version)?,
+            ArgumentParser::Range { decimals } => decimals.write(buffer, version)?,
+            ArgumentParser::Bool | ArgumentParser::Other(_) => (),
+        }
+        Ok(())
+    }
+
+    fn read(
+        identifier: String,
+        buffer: &mut std::io::Cursor<&[u8]>,
+        version: ProtocolVersion,
+    ) -> anyhow::Result<Self> {
+        Ok(match identifier.as_str() {
+            "brigadier:bool" => ArgumentParser::Bool,
+            "brigadier:double" => {
+                let (min, max) = read_bounds(buffer, version)?;
+                ArgumentParser::Double { min, max }
+            }
+            "brigadier:float" => {
+                let (min, max) = read_bounds(buffer, version)?;
+                ArgumentParser::Float { min, max }
+            }
+            "brigadier:integer" => {
+                let (min, max) = read_bounds(buffer, version)?;
+                ArgumentParser::Integer { min, max }
+            }
+            "brigadier:string" => ArgumentParser::String(match VarInt::read(buffer, version)?.0 {
+                0 => StringArgumentKind::SingleWord,
+                1 => StringArgumentKind::QuotablePhrase,
+                2 => StringArgumentKind::GreedyPhrase,
+                kind => bail!("invalid string argument kind {}", kind),
+            }),
+            "minecraft:entity" => {
+                let flags = u8::read(buffer, version)?;
+                ArgumentParser::Entity {
+                    single: flags & 0x01 != 0,
+                    only_players: flags & 0x02 != 0,
+                }
+            }
+            "minecraft:score_holder" => ArgumentParser::ScoreHolder {
+                multiple: u8::read(buffer, version)? & 0x01 != 0,
+            },
+            "minecraft:range" => ArgumentParser::Range {
+                decimals: bool::read(buffer, version)?,
+            },
+            _ => ArgumentParser::Other(identifier),
+        })
+    }
+}
+
+fn write_bounds<T: Writeable>(
+    min: Option<T>,
+    max: Option<T>,
+    buffer: &mut Vec<u8>,
+    version: ProtocolVersion,
+) -> anyhow::Result<()> {
+    let mut flags = 0u8;
+    if min.is_some() {
+        flags |= 0x01;
+    }
+    if max.is_some() {
+        flags |= 0x02;
+    }
+    flags.write(buffer, version)?;
+    if let Some(min) = min {
+        min.write(buffer, version)?;
+    }
+    if let Some(max) = max {
+        max.write(buffer, version)?;
+    }
+    Ok(())
+}
+
+fn read_bounds<T: Readable>(
+    buffer: &mut std::io::Cursor<&[u8]>,
+    version: ProtocolVersion,
+) -> anyhow::Result<(Option<T>, Option<T>)> {
+    let flags = u8::read(buffer, version)?;
+    let min = if flags & 0x01 != 0 {
+        Some(T::read(buffer, version)?)
+    } else {
+        None
+    };
+    let max = if flags & 0x02 != 0 {
+        Some(T::read(buffer, version)?)
+    } else {
+        None
+    };
+    Ok((min, max))
+}
+
+impl Writeable for CommandNode {
+    fn write(&self, buffer: &mut Vec<u8>, version: ProtocolVersion) -> anyhow::Result<()> {
+        let mut flags = match self.kind {
+            CommandNodeKind::Root => 0,
+            CommandNodeKind::Literal { .. } => 1,
+            CommandNodeKind::Argument { .. } => 2,
+        };
+        if self.executable {
+            flags |= FLAG_EXECUTABLE;
+        }
+        if self.redirect_node.is_some() {
+            flags |= FLAG_REDIRECT;
+        }
+        if self.suggestions_type.is_some() {
+            flags |= FLAG_SUGGESTIONS;
+        }
+        flags.write(buffer, version)?;
+
+        VarInt(self.children.len() as i32).write(buffer, version)?;
+        for &child in &self.children {
+            VarInt(child).write(buffer, version)?;
+        }
+
+        if let Some(redirect) = self.redirect_node {
+            VarInt(redirect).write(buffer, version)?;
+        }
+
+        match &self.kind {
+            CommandNodeKind::Root => (),
+            CommandNodeKind::Literal { name } => name.write(buffer, version)?,
+            CommandNodeKind::Argument { name, parser } => {
+                name.write(buffer, version)?;
+                parser.identifier().to_owned().write(buffer, version)?;
+                parser.write_properties(buffer, version)?;
+            }
+        }
+
+        if let Some(suggestions_type) = &self.suggestions_type {
+            suggestions_type.write(buffer, version)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Readable for CommandNode {
+    fn read(buffer: &mut std::io::Cursor<&[u8]>, version: ProtocolVersion) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        let flags = u8::read(buffer, version)?;
+
+        let num_children = VarInt::read(buffer, version)?.0;
+        let mut children = Vec::new();
+        for _ in 0..num_children {
+            children.push(VarInt::read(buffer, version)?.0);
+        }
+
+        let redirect_node = if flags & FLAG_REDIRECT != 0 {
+            Some(VarInt::read(buffer, version)?.0)
+        } else {
+            None
+        };
+
+        let kind = match flags & NODE_TYPE_MASK {
+            0 => CommandNodeKind::Root,
+            1 => CommandNodeKind::Literal {
+                name: String::read(buffer, version)?,
+            },
+            2 => {
+                let name = String::read(buffer, version)?;
+                let identifier = String::read(buffer, version)?;
+                let parser = ArgumentParser::read(identifier, buffer, version)?;
+                CommandNodeKind::Argument { name, parser }
+            }
+            kind => bail!("invalid command node type {}", kind),
+        };
+
+        let suggestions_type = if flags & FLAG_SUGGESTIONS != 0 {
+            Some(String::read(buffer, version)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            kind,
+            executable: flags & FLAG_EXECUTABLE != 0,
+            children,
+            redirect_node,
+            suggestions_type,
+        })
+    }
+}
+
+impl Writeable for DeclareCommands {
+    fn write(&self, buffer: &mut Vec<u8>, version: ProtocolVersion) -> anyhow::Result<()> {
+        VarInt(self.nodes.len() as i32).write(buffer, version)?;
+        for node in &self.nodes {
+            node.write(buffer, version)?;
+        }
+        VarInt(self.root_index).write(buffer, version)?;
+        Ok(())
+    }
+}
+
+impl Readable for DeclareCommands {
+    fn read(buffer: &mut std::io::Cursor<&[u8]>, version: ProtocolVersion) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        let num_nodes = VarInt::read(buffer, version)?.0;
+        let mut nodes = Vec::new();
+        for _ in 0..num_nodes {
+            nodes.push(CommandNode::read(buffer, version)?);
+        }
+        let root_index = VarInt::read(buffer, version)?.0;
+        Ok(Self { nodes, root_index })
+    }
+}
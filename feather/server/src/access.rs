@@ -0,0 +1,555 @@
+//! Ban lists, the whitelist and the operator list.
+//!
+//! The lists are stored in the vanilla JSON files
+//! (`banned-players.json`, `banned-ips.json`, `whitelist.json`
+//! and `ops.json`) so they can be copied from and to a vanilla
+//! server. Files edited by hand are reloaded automatically.
+
+use std::{
+    fs,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+
+use anyhow::Context;
+use base::Text;
+use chrono::{DateTime, FixedOffset, Local};
+use parking_lot::RwLock;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Date format used by vanilla list files.
+const DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S %z";
+
+/// The `expires` value of permanent bans.
+const FOREVER: &str = "forever";
+
+pub const DEFAULT_BAN_REASON: &str = "Banned by an operator.";
+
+/// A player's name and UUID.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Profile {
+    pub uuid: Uuid,
+    pub name: String,
+}
+
+/// Details shared by player and IP bans.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BanDetails {
+    pub created: String,
+    /// Who issued the ban.
+    pub source: String,
+    /// The expiry date, or `forever`.
+    pub expires: String,
+    pub reason: String,
+}
+
+impl BanDetails {
+    /// Creates the details of a permanent ban issued now.
+    pub fn new(source: &str, reason: Option<&str>) -> Self {
+        Self {
+            created: Local::now().format(DATE_FORMAT).to_string(),
+            source: source.to_owned(),
+            expires: FOREVER.to_owned(),
+            reason: reason.unwrap_or(DEFAULT_BAN_REASON).to_owned(),
+        }
+    }
+
+    /// Returns the expiry date, or `None` if the ban is permanent.
+    pub fn expiry(&self) -> Option<DateTime<FixedOffset>> {
+        if self.expires == FOREVER {
+            return None;
+        }
+        DateTime::parse_from_str(&self.expires, DATE_FORMAT).ok()
+    }
+
+    pub fn has_expired(&self) -> bool {
+        self.expiry().map_or(false, |expiry| {
+            expiry.timestamp() < Local::now().timestamp()
+        })
+    }
+
+    /// The message shown to banned players.
+    fn disconnect_reason(&self, key: &str) -> Text {
+        let mut reason = Text::translate_with(key, vec![self.reason.clone()]);
+        if let Some(expiry) = self.expiry() {
+            reason = reason
+                + Text::translate_with(
+                    "multiplayer.disconnect.banned.expiration",
+                    vec![expiry.format("%Y-%m-%d %H:%M:%S %Z").to_string()],
+                );
+        }
+        reason
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlayerBan {
+    #[serde(flatten)]
+    pub profile: Profile,
+    #[serde(flatten)]
+    pub details: BanDetails,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IpBan {
+    pub ip: IpAddr,
+    #[serde(flatten)]
+    pub details: BanDetails,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Operator {
+    #[serde(flatten)]
+    pub profile: Profile,
+    pub level: u8,
+    pub bypasses_player_limit: bool,
+}
+
+/// A list stored in a JSON file.
+struct ListFile<T> {
+    path: PathBuf,
+    entries: Vec<T>,
+    /// Modification time of the file when it was last read or written.
+    modified: Option<SystemTime>,
+}
+
+impl<T> ListFile<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    /// Loads the list, creating an empty file if it does not exist.
+    fn load(path: PathBuf) -> anyhow::Result<Self> {
+        let mut list = Self {
+            path,
+            entries: Vec::new(),
+            modified: None,
+        };
+        if list.path.exists() {
+            list.reload()?;
+        } else {
+            list.save()?;
+        }
+        Ok(list)
+    }
+
+    fn reload(&mut self) -> anyhow::Result<()> {
+        let contents = fs::read_to_string(&self.path)
+            .with_context(|| format!("failed to read {}", self.path.display()))?;
+        self.entries = if contents.trim().is_empty() {
+            Vec::new()
+        } else {
+            serde_json::from_str(&contents)
+                .with_context(|| format!("invalid {}", self.path.display()))?
+        };
+        self.modified = modified_time(&self.path);
+        Ok(())
+    }
+
+    /// Reloads the list if the file was changed
+    /// by someone else. Returns whether it was reloaded.
+    ///
+    /// If the changed file is invalid, the previous entries are
+    /// kept and the error is only returned once, not again until
+    /// the file changes.
+    fn reload_if_changed(&mut self) -> anyhow::Result<bool> {
+        let modified = modified_time(&self.path);
+        if modified.is_none() || modified == self.modified {
+            return Ok(false);
+        }
+        self.modified = modified;
+        self.reload()?;
+        Ok(true)
+    }
+
+    fn save(&mut self) -> anyhow::Result<()> {
+        fs::write(&self.path, serde_json::to_string_pretty(&self.entries)?)
+            .with_context(|| format!("failed to write {}", self.path.display()))?;
+        self.modified = modified_time(&self.path);
+        Ok(())
+    }
+
+    /// Runs `f` on the entries and saves them if it returns `true`.
+    fn modify(&mut self, f: impl FnOnce(&mut Vec<T>) -> bool) -> anyhow::Result<bool> {
+        let changed = f(&mut self.entries);
+        if changed {
+            self.save()?;
+        }
+        Ok(changed)
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+struct Lists {
+    banned_players: ListFile<PlayerBan>,
+    banned_ips: ListFile<IpBan>,
+    whitelist: ListFile<Profile>,
+    ops: ListFile<Operator>,
+    whitelist_enabled: bool,
+}
+
+/// The ban lists, whitelist and operator list.
+///
+/// Can be cloned to create a new handle. Stored
+/// as a resource and shared with connection workers
+/// so logins can be checked.
+#[derive(Clone)]
+pub struct AccessLists {
+    inner: Arc<RwLock<Lists>>,
+}
+
+impl AccessLists {
+    /// Loads the lists from `dir`, creating missing files.
+    pub fn load(dir: &Path, whitelist_enabled: bool) -> anyhow::Result<Self> {
+        let lists = Lists {
+            banned_players: ListFile::load(dir.join("banned-players.json"))?,
+            banned_ips: ListFile::load(dir.join("banned-ips.json"))?,
+            whitelist: ListFile::load(dir.join("whitelist.json"))?,
+            ops: ListFile::load(dir.join("ops.json"))?,
+            whitelist_enabled,
+        };
+        Ok(Self {
+            inner: Arc::new(RwLock::new(lists)),
+        })
+    }
+
+    /// Reloads files which were changed on disk. Each list is
+    /// reloaded on its own, so an invalid file doesn't hold
+    /// back the others; invalid files are logged and keep
+    /// their previous entries. Returns whether anything was reloaded.
+    pub fn reload_changed(&self) -> bool {
+        let mut lists = self.inner.write();
+        let results = vec![
+            lists.banned_players.reload_if_changed(),
+            lists.banned_ips.reload_if_changed(),
+            lists.whitelist.reload_if_changed(),
+            lists.ops.reload_if_changed(),
+        ];
+
+        let mut changed = false;
+        for result in results {
+            match result {
+                Ok(reloaded) => changed |= reloaded,
+                Err(e) => log::error!("Failed to reload an access list: {:?}", e),
+            }
+        }
+        changed
+    }
+
+    /// Returns the reason `profile` may not join from `ip`,
+    /// or `None` if they are allowed to.
+    pub fn check_login(&self, profile: &Profile, ip: IpAddr) -> Option<Text> {
+        let lists = self.inner.read();
+        if let Some(ban) = lists
+            .banned_players
+            .entries
+            .iter()
+            .find(|ban| ban.profile.uuid == profile.uuid && !ban.details.has_expired())
+        {
+            return Some(
+                ban.details
+                    .disconnect_reason("multiplayer.disconnect.banned.reason"),
+            );
+        }
+        if lists.whitelist_enabled
+            && !lists
+                .whitelist
+                .entries
+                .iter()
+                .any(|p| p.uuid == profile.uuid)
+            && !lists
+                .ops
+                .entries
+                .iter()
+                .any(|op| op.profile.uuid == profile.uuid)
+        {
+            return Some(Text::translate_with(
+                "multiplayer.disconnect.not_whitelisted",
+                Vec::<String>::new(),
+            ));
+        }
+        if let Some(ban) = lists
+            .banned_ips
+            .entries
+            .iter()
+            .find(|ban| ban.ip == ip && !ban.details.has_expired())
+        {
+            return Some(
+                ban.details
+                    .disconnect_reason("multiplayer.disconnect.banned_ip.reason"),
+            );
+        }
+        None
+    }
+
+    /// Bans a player. Returns `false` if they were already banned.
+    pub fn ban_player(&self, profile: Profile, details: BanDetails) -> anyhow::Result<bool> {
+        self.inner.write().banned_players.modify(|bans| {
+            if bans
+                .iter()
+                .any(|ban| ban.profile.uuid == profile.uuid && !ban.details.has_expired())
+            {
+                return false;
+            }
+            bans.retain(|ban| ban.profile.uuid != profile.uuid);
+            bans.push(PlayerBan { profile, details });
+            true
+        })
+    }
+
+    /// Removes a player's ban. Returns `false` if they were not banned.
+    pub fn pardon_player(&self, name: &str) -> anyhow::Result<bool> {
+        self.inner
+            .write()
+            .banned_players
+            .modify(|bans| remove_where(bans, |ban| ban.profile.name.eq_ignore_ascii_case(name)))
+    }
+
+    /// Bans an IP address. Returns `false` if it was already banned.
+    pub fn ban_ip(&self, ip: IpAddr, details: BanDetails) -> anyhow::Result<bool> {
+        self.inner.write().banned_ips.modify(|bans| {
+            if bans
+                .iter()
+                .any(|ban| ban.ip == ip && !ban.details.has_expired())
+            {
+                return false;
+            }
+            bans.retain(|ban| ban.ip != ip);
+            bans.push(IpBan { ip, details });
+            true
+        })
+    }
+
+    /// Removes an IP ban. Returns `false` if the address was not banned.
+    pub fn pardon_ip(&self, ip: IpAddr) -> anyhow::Result<bool> {
+        self.inner
+            .write()
+            .banned_ips
+            .modify(|bans| remove_where(bans, |ban| ban.ip == ip))
+    }
+
+    pub fn banned_players(&self) -> Vec<PlayerBan> {
+        self.inner.read().banned_players.entries.clone()
+    }
+
+    pub fn banned_ips(&self) -> Vec<IpBan> {
+        self.inner.read().banned_ips.entries.clone()
+    }
+
+    pub fn is_whitelist_enabled(&self) -> bool {
+        self.inner.read().whitelist_enabled
+    }
+
+    /// Enables or disables the whitelist until the server restarts.
+    pub fn set_whitelist_enabled(&self, enabled: bool) {
+        self.inner.write().whitelist_enabled = enabled;
+    }
+
+    /// Adds a player to the whitelist. Returns `false`
+    /// if they were already whitelisted.
+    pub fn whitelist_add(&self, profile: Profile) -> anyhow::Result<bool> {
+        self.inner.write().whitelist.modify(|whitelist| {
+            if whitelist.iter().any(|p| p.uuid == profile.uuid) {
+                return false;
+            }
+            whitelist.push(profile);
+            true
+        })
+    }
+
+    /// Removes a player from the whitelist. Returns
+    /// `false` if they were not whitelisted.
+    pub fn whitelist_remove(&self, name: &str) -> anyhow::Result<bool> {
+        self.inner
+            .write()
+            .whitelist
+            .modify(|whitelist| remove_where(whitelist, |p| p.name.eq_ignore_ascii_case(name)))
+    }
+
+    pub fn whitelisted_names(&self) -> Vec<String> {
+        self.inner
+            .read()
+            .whitelist
+            .entries
+            .iter()
+            .map(|p| p.name.clone())
+            .collect()
+    }
+
+    /// Rereads the whitelist from disk.
+    pub fn reload_whitelist(&self) -> anyhow::Result<()> {
+        self.inner.write().whitelist.reload()
+    }
+
+    /// Returns the operator level of a player, or 0 if they are not an operator.
+    pub fn op_level(&self, uuid: Uuid) -> u8 {
+        self.inner
+            .read()
+            .ops
+            .entries
+            .iter()
+            .find(|op| op.profile.uuid == uuid)
+            .map_or(0, |op| op.level)
+    }
+
+    /// Returns whether a player may join when the server is full.
+    pub fn bypasses_player_limit(&self, uuid: Uuid) -> bool {
+        self.inner
+            .read()
+            .ops
+            .entries
+            .iter()
+            .any(|op| op.profile.uuid == uuid && op.bypasses_player_limit)
+    }
+
+    /// Makes a player an operator. Returns `false`
+    /// if they already were one.
+    pub fn op(&self, profile: Profile, level: u8) -> anyhow::Result<bool> {
+        self.inner.write().ops.modify(|ops| {
+            if ops.iter().any(|op| op.profile.uuid == profile.uuid) {
+                return false;
+            }
+            ops.push(Operator {
+                profile,
+                level,
+                bypasses_player_limit: false,
+            });
+            true
+        })
+    }
+
+    /// Removes a player's operator status. Returns the UUID of
+    /// the player, or `None` if they were not an operator.
+    pub fn deop(&self, name: &str) -> anyhow::Result<Option<Uuid>> {
+        let mut removed = None;
+        self.inner.write().ops.modify(|ops| {
+            removed = ops
+                .iter()
+                .find(|op| op.profile.name.eq_ignore_ascii_case(name))
+                .map(|op| op.profile.uuid);
+            remove_where(ops, |op| Some(op.profile.uuid) == removed)
+        })?;
+        Ok(removed)
+    }
+}
+
+/// Removes entries matching `predicate`, returning whether any were removed.
+fn remove_where<T>(entries: &mut Vec<T>, predicate: impl Fn(&T) -> bool) -> bool {
+    let len = entries.len();
+    entries.retain(|entry| !predicate(entry));
+    entries.len() != len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("feather-access-{}-{}", name, rand::random::<u64>()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn profile(name: &str) -> Profile {
+        Profile {
+            uuid: Uuid::from_u128(rand::random()),
+            name: name.to_owned(),
+        }
+    }
+
+    #[test]
+    fn vanilla_ban_format() {
+        let json = r#"[{
+            "uuid": "069a79f4-44e9-4726-a5be-fca90e38aaf5",
+            "name": "Notch",
+            "created": "2021-03-01 12:00:00 +0000",
+            "source": "Server",
+            "expires": "2000-01-01 00:00:00 +0000",
+            "reason": "Banned by an operator."
+        }]"#;
+        let bans: Vec<PlayerBan> = serde_json::from_str(json).unwrap();
+        assert_eq!(bans[0].profile.name, "Notch");
+        assert!(bans[0].details.has_expired());
+        assert!(!BanDetails::new("Server", None).has_expired());
+    }
+
+    #[test]
+    fn bans_and_whitelist() {
+        let dir = temp_dir("bans");
+        let lists = AccessLists::load(&dir, false).unwrap();
+        let ip: IpAddr = "127.0.0.1".parse().unwrap();
+        let notch = profile("Notch");
+        assert!(lists.check_login(&notch, ip).is_none());
+
+        assert!(lists
+            .ban_player(notch.clone(), BanDetails::new("Server", None))
+            .unwrap());
+        assert!(lists.check_login(&notch, ip).is_some());
+        assert!(lists.pardon_player("notch").unwrap());
+        assert!(lists.check_login(&notch, ip).is_none());
+
+        lists.set_whitelist_enabled(true);
+        assert!(lists.check_login(&notch, ip).is_some());
+        lists.whitelist_add(notch.clone()).unwrap();
+        assert!(lists.check_login(&notch, ip).is_none());
+
+        lists.ban_ip(ip, BanDetails::new("Server", None)).unwrap();
+        assert!(lists.check_login(&notch, ip).is_some());
+
+        // Changes are persisted
+        let reloaded = AccessLists::load(&dir, true).unwrap();
+        assert_eq!(reloaded.whitelisted_names(), vec!["Notch"]);
+        assert_eq!(reloaded.banned_ips().len(), 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn ops() {
+        let dir = temp_dir("ops");
+        let lists = AccessLists::load(&dir, false).unwrap();
+        let notch = profile("Notch");
+        assert_eq!(lists.op_level(notch.uuid), 0);
+        assert!(lists.op(notch.clone(), 4).unwrap());
+        assert!(!lists.op(notch.clone(), 4).unwrap());
+        assert_eq!(lists.op_level(notch.uuid), 4);
+        assert_eq!(lists.deop("Notch").unwrap(), Some(notch.uuid));
+        assert_eq!(lists.op_level(notch.uuid), 0);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn invalid_lists_dont_block_reloading() {
+        let dir = temp_dir("reload");
+        let lists = AccessLists::load(&dir, true).unwrap();
+        let ip: IpAddr = "127.0.0.1".parse().unwrap();
+        let notch = profile("Notch");
+        lists.whitelist_add(notch.clone()).unwrap();
+
+        // Edited by someone else while the whitelist is half written
+        AccessLists::load(&dir, true)
+            .unwrap()
+            .ban_player(notch.clone(), BanDetails::new("Server", None))
+            .unwrap();
+        fs::write(dir.join("whitelist.json"), "[{").unwrap();
+        {
+            // File times may be too coarse to tell the writes apart
+            let mut inner = lists.inner.write();
+            inner.banned_players.modified = Some(SystemTime::UNIX_EPOCH);
+            inner.whitelist.modified = Some(SystemTime::UNIX_EPOCH);
+        }
+
+        assert!(lists.reload_changed());
+        assert!(lists.check_login(&notch, ip).is_some());
+        assert_eq!(lists.whitelisted_names(), vec!["Notch"]);
+        // The invalid file isn't reloaded until it changes again
+        assert!(!lists.reload_changed());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
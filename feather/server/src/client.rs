@@ -1,20 +1,29 @@
 use std::{
     cell::{Cell, RefCell},
     collections::VecDeque,
+    convert::TryFrom,
     io::Cursor,
-    sync::Arc,
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
 };
 
-use ahash::AHashSet;
+use ahash::{AHashMap, AHashSet};
 use flume::{Receiver, Sender};
 use uuid::Uuid;
 
 use base::{
-    BlockId, ChunkHandle, ChunkPosition, EntityKind, EntityMetadata, Gamemode, Position,
-    ProfileProperty, Text, ValidBlockPosition,
+    anvil::level::{LevelData, LevelGeneratorType},
+    BlockId, BlockPosition, ChunkHandle, ChunkPosition, EntityKind, EntityMetadata, GameRules,
+    Gamemode, Position, ProfileProperty, Text, ValidBlockPosition, Vec3d,
 };
 use common::{
     chat::{ChatKind, ChatMessage},
+    commands::{ArgumentKind, CommandDispatcher, NodeKind, StringKind, Suggestions},
+    weather::WeatherLevels,
     Window,
 };
 use libcraft_items::InventorySlot;
@@ -26,10 +35,14 @@ use protocol::{
     packets::{
         self,
         server::{
-            AddPlayer, Animation, BlockChange, ChatPosition, ChunkData, ChunkDataKind,
-            DestroyEntities, Disconnect, EntityAnimation, EntityHeadLook, JoinGame, KeepAlive,
-            PlayerInfo, PlayerPositionAndLook, PluginMessage, SendEntityMetadata, SpawnPlayer,
-            Title, UnloadChunk, UpdateViewPosition, WindowItems,
+            AddPlayer, Animation, ArgumentParser, BlockBreakAnimation, BlockChange,
+            ChangeGameState, ChatPosition, ChunkData, ChunkDataKind, CollectItem, CombatEvent,
+            CombatEventKind, CommandNode, CommandNodeKind, DeclareCommands, DestroyEntities,
+            Disconnect, EntityAnimation, EntityHeadLook, EntityStatus, EntityVelocity, JoinGame,
+            KeepAlive, PlayerInfo, PlayerPositionAndLook, PluginMessage, Respawn,
+            SendEntityMetadata, SpawnEntity, SpawnPlayer, SpawnPosition, StateReason,
+            StringArgumentKind, TabComplete, TabCompleteMatch, TimeUpdate, Title, UnloadChunk,
+            UpdateHealth, UpdateViewPosition, WindowItems,
         },
     },
     ClientPlayPacket, Nbt, ProtocolVersion, ServerPlayPacket, Writeable,
@@ -47,6 +60,18 @@ use slab::Slab;
 /// Max number of chunks to send to a client per tick.
 const MAX_CHUNKS_PER_TICK: usize = 10;
 
+/// How long a client may stay over the outbound buffer
+/// limit before it is disconnected.
+const SLOW_CLIENT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long a client may go without answering
+/// keepalives before it is disconnected.
+const KEEPALIVE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Clients with this many times the outbound buffer limit
+/// queued are disconnected immediately.
+const HARD_LIMIT_FACTOR: usize = 4;
+
 /// ID of a client. Can be reused.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct ClientId(usize);
@@ -90,10 +115,13 @@ impl Clients {
 pub struct Client {
     packets_to_send: Sender<ServerPlayPacket>,
     received_packets: Receiver<ClientPlayPacket>,
+    /// Number of encoded bytes not yet written to the connection.
+    outbound_bytes: Arc<AtomicUsize>,
     options: Arc<Options>,
     username: String,
     profile: Vec<ProfileProperty>,
     uuid: Uuid,
+    ip: IpAddr,
 
     teleport_id_counter: Cell<i32>,
 
@@ -105,6 +133,20 @@ pub struct Client {
 
     chunk_send_queue: RefCell<VecDeque<ChunkData>>,
 
+    /// Latest positions of entities whose movement wasn't sent
+    /// because the connection was congested. Sent as teleports
+    /// once it catches up.
+    pending_moves: RefCell<AHashMap<NetworkId, (Position, bool)>>,
+    /// When the outbound buffer went over the limit.
+    congested_since: Cell<Option<Instant>>,
+
+    /// ID and send time of the keepalive awaiting a response.
+    pending_keepalive: Cell<Option<(u64, Instant)>>,
+    /// When the client last answered a keepalive, or joined.
+    last_keepalive_response: Cell<Instant>,
+    /// Smoothed round-trip time of keepalives.
+    ping: Cell<Duration>,
+
     /// The previous own position sent by the client.
     /// Used to detect when we need to teleport the client.
     client_known_position: Cell<Option<Position>>,
@@ -117,16 +159,23 @@ impl Client {
         Self {
             packets_to_send: player.packets_to_send,
             received_packets: player.received_packets,
+            outbound_bytes: player.outbound_bytes,
             options,
             username: player.username,
             teleport_id_counter: Cell::new(0),
             network_id: None,
             profile: player.profile,
             uuid: player.uuid,
+            ip: player.ip,
             sent_entities: RefCell::new(AHashSet::new()),
             knows_position: Cell::new(false),
             known_chunks: RefCell::new(AHashSet::new()),
             chunk_send_queue: RefCell::new(VecDeque::new()),
+            pending_moves: RefCell::new(AHashMap::new()),
+            congested_since: Cell::new(None),
+            pending_keepalive: Cell::new(None),
+            last_keepalive_response: Cell::new(Instant::now()),
+            ping: Cell::new(Duration::default()),
             client_known_position: Cell::new(None),
             disconnected: Cell::new(false),
         }
@@ -152,6 +201,12 @@ impl Client {
         self.uuid
     }
 
+    /// The IP address of the player, as forwarded
+    /// by the proxy if there is one.
+    pub fn ip(&self) -> IpAddr {
+        self.ip
+    }
+
     pub fn username(&self) -> &str {
         &self.username
     }
@@ -172,7 +227,21 @@ impl Client {
         self.knows_position.get()
     }
 
+    /// Returns whether more data is queued for this client
+    /// than the outbound buffer limit allows. While congested,
+    /// chunk sending pauses and cosmetic packets are dropped.
+    pub fn is_congested(&self) -> bool {
+        self.outbound_bytes.load(Ordering::Relaxed) > self.options.outbound_buffer_limit
+    }
+
     pub fn tick(&self) {
+        if self.is_congested() {
+            self.tick_congested();
+            return;
+        }
+        self.congested_since.set(None);
+        self.send_pending_moves();
+
         let num_to_send = MAX_CHUNKS_PER_TICK.min(self.chunk_send_queue.borrow().len());
         for packet in self.chunk_send_queue.borrow_mut().drain(0..num_to_send) {
             log::trace!(
@@ -186,6 +255,44 @@ impl Client {
         }
     }
 
+    /// Disconnects the client if it has been congested for too long
+    /// or has fallen too far behind.
+    fn tick_congested(&self) {
+        if self.disconnected.get() {
+            return;
+        }
+        let now = Instant::now();
+        let since = match self.congested_since.get() {
+            Some(since) => since,
+            None => {
+                self.congested_since.set(Some(now));
+                now
+            }
+        };
+
+        let queued = self.outbound_bytes.load(Ordering::Relaxed);
+        let hard_limit = self.options.outbound_buffer_limit * HARD_LIMIT_FACTOR;
+        if queued > hard_limit || now.duration_since(since) >= SLOW_CLIENT_TIMEOUT {
+            log::info!(
+                "Disconnecting {}: connection too slow ({} KiB queued, congested for {:?})",
+                self.username,
+                queued / 1024,
+                now.duration_since(since)
+            );
+            self.disconnect("Your connection is too slow to keep up with the server.");
+        }
+    }
+
+    fn send_pending_moves(&self) {
+        for (network_id, (position, on_ground)) in self.pending_moves.borrow_mut().drain() {
+            self.send_entity_teleport(network_id, position, on_ground);
+            self.send_packet(EntityHeadLook {
+                entity_id: network_id.0,
+                head_yaw: position.yaw,
+            });
+        }
+    }
+
     /// Returns whether the entity with the given ID
     /// is currently loaded on the client.
     pub fn is_entity_loaded(&self, network_id: NetworkId) -> bool {
@@ -196,34 +303,151 @@ impl Client {
         self.network_id = Some(network_id);
     }
 
-    pub fn send_join_game(&self, gamemode: Gamemode, previous_gamemode: PreviousGamemode) {
+    pub fn send_join_game(
+        &self,
+        gamemode: Gamemode,
+        previous_gamemode: PreviousGamemode,
+        level: &LevelData,
+        rules: &GameRules,
+    ) {
         log::trace!("Sending Join Game to {}", self.username);
         // Use the dimension codec sent by the default vanilla server. (Data acquired via tools/proxy)
         let dimension_codec = nbt::Blob::from_reader(&mut Cursor::new(include_bytes!(
             "../../../assets/dimension_codec.nbt"
         )))
         .expect("dimension codec asset is malformed");
-        let dimension = nbt::Blob::from_reader(&mut Cursor::new(include_bytes!(
-            "../../../assets/dimension.nbt"
-        )))
-        .expect("dimension asset is malformed");
 
         self.send_packet(JoinGame {
             entity_id: self.network_id.expect("No network id! Use client.set_network_id(NetworkId) before calling this method.").0,
-            is_hardcore: false,
+            is_hardcore: level.hardcore,
             gamemode,
             previous_gamemode,
             world_names: vec!["world".to_owned()],
             dimension_codec: Nbt(dimension_codec),
-            dimension: Nbt(dimension),
+            dimension: Nbt(dimension()),
             world_name: "world".to_owned(),
             hashed_seed: 0,
             max_players: 0,
             view_distance: self.options.view_distance as i32,
-            reduced_debug_info: false,
-            enable_respawn_screen: true,
+            reduced_debug_info: rules.reduced_debug_info,
+            enable_respawn_screen: !rules.do_immediate_respawn,
+            is_debug: false,
+            is_flat: level.generator_type() == LevelGeneratorType::Flat,
+        });
+    }
+
+    /// Respawns the player after they died. The client
+    /// forgets all entities, so they have to be sent again.
+    pub fn send_respawn(
+        &self,
+        gamemode: Gamemode,
+        previous_gamemode: PreviousGamemode,
+        level: &LevelData,
+    ) {
+        log::trace!("Sending Respawn to {}", self.username);
+        self.send_packet(Respawn {
+            dimension: Nbt(dimension()),
+            world_name: "world".to_owned(),
+            hashed_seed: 0,
+            gamemode,
+            previous_gamemode,
             is_debug: false,
-            is_flat: false,
+            is_flat: level.generator_type() == LevelGeneratorType::Flat,
+            copy_metadata: false,
+        });
+        self.sent_entities.borrow_mut().clear();
+        self.pending_moves.borrow_mut().clear();
+    }
+
+    /// Sends the player's health. Food isn't implemented,
+    /// so the hunger bar is always full.
+    pub fn send_health(&self, health: f32) {
+        self.send_packet(UpdateHealth {
+            health,
+            food: 20.into(),
+            food_saturation: 5.0,
+        });
+    }
+
+    /// Shows the death screen with the given death message.
+    pub fn send_death_screen(&self, message: &Text, killer: Option<NetworkId>) {
+        let player_id = match self.network_id {
+            Some(network_id) => network_id.0,
+            None => return,
+        };
+        self.send_packet(CombatEvent {
+            event: CombatEventKind::EntityDead {
+                player_id: player_id.into(),
+                entity_id: killer.map_or(-1, |killer| killer.0),
+                message: message.to_string(),
+            },
+        });
+    }
+
+    pub fn send_spawn_position(&self, position: BlockPosition) {
+        if let Ok(position) = ValidBlockPosition::try_from(position) {
+            self.send_packet(SpawnPosition { position });
+        }
+    }
+
+    /// Sends the world age and time of day, in ticks.
+    ///
+    /// If `daylight_cycle` is false, the client
+    /// stops advancing the time of day on its own.
+    pub fn send_time(&self, world_age: i64, time_of_day: i64, daylight_cycle: bool) {
+        let time_of_day = if daylight_cycle {
+            time_of_day
+        } else {
+            -time_of_day.max(1)
+        };
+        self.send_packet(TimeUpdate {
+            world_age: world_age as u64,
+            time_of_day: time_of_day as u64,
+        });
+    }
+
+    /// Starts or stops precipitation on the client.
+    pub fn set_raining(&self, raining: bool) {
+        self.send_packet(ChangeGameState {
+            reason: if raining {
+                StateReason::BeginningRain
+            } else {
+                StateReason::EndRaining
+            },
+            value: 0.0,
+        });
+    }
+
+    /// Sends the intensity of rain and thunder.
+    pub fn send_weather_levels(&self, levels: WeatherLevels) {
+        self.send_packet(ChangeGameState {
+            reason: StateReason::RainLevelChange,
+            value: levels.rain,
+        });
+        self.send_packet(ChangeGameState {
+            reason: StateReason::ThunderLevelChange,
+            value: levels.thunder,
+        });
+    }
+
+    /// Toggles the reduced debug screen of the player.
+    pub fn send_reduced_debug_info(&self, reduced: bool) {
+        let entity_id = match self.network_id {
+            Some(network_id) => network_id.0,
+            None => return,
+        };
+        self.send_packet(EntityStatus {
+            entity_id,
+            status: if reduced { 22 } else { 23 },
+        });
+    }
+
+    /// Toggles whether the player sees the respawn
+    /// screen after dying.
+    pub fn send_immediate_respawn(&self, immediate: bool) {
+        self.send_packet(ChangeGameState {
+            reason: StateReason::EnableRespawnScreen,
+            value: if immediate { 1.0 } else { 0.0 },
         });
     }
 
@@ -298,6 +522,25 @@ impl Client {
         });
     }
 
+    /// Shows the crack animation of a block being dug by another
+    /// entity. A `stage` of `None` removes the animation.
+    pub fn send_block_break_animation(
+        &self,
+        network_id: NetworkId,
+        position: ValidBlockPosition,
+        stage: Option<u8>,
+    ) {
+        if self.network_id == Some(network_id) {
+            return;
+        }
+        self.send_packet(BlockBreakAnimation {
+            entity_id: network_id.0,
+            position,
+            // Vanilla sends -1 to remove the animation.
+            destroy_stage: stage.unwrap_or(u8::MAX),
+        });
+    }
+
     pub fn unload_chunk(&self, pos: ChunkPosition) {
         log::trace!("Unloading chunk at {:?} on {}", pos, self.username);
         self.send_packet(UnloadChunk {
@@ -313,6 +556,7 @@ impl Client {
         name: String,
         profile: &[ProfileProperty],
         gamemode: Gamemode,
+        ping: Duration,
     ) {
         log::trace!("Sending AddPlayer({}) to {}", name, self.username);
         let action = AddPlayer {
@@ -320,12 +564,26 @@ impl Client {
             name,
             properties: profile.to_vec(),
             gamemode,
-            ping: 0,
+            ping: ping.as_millis() as i32,
             display_name: None,
         };
         self.send_packet(PlayerInfo::AddPlayers(vec![action]));
     }
 
+    pub fn update_tablist_gamemode(&self, uuid: Uuid, gamemode: Gamemode) {
+        self.send_packet(PlayerInfo::UpdateGamemodes(vec![(uuid, gamemode)]));
+    }
+
+    /// Updates the latency shown in the tablist,
+    /// given as `(uuid, ping)` pairs.
+    pub fn update_tablist_pings(&self, pings: &[(Uuid, Duration)]) {
+        let pings = pings
+            .iter()
+            .map(|&(uuid, ping)| (uuid, ping.as_millis() as i32))
+            .collect();
+        self.send_packet(PlayerInfo::UpdatePings(pings));
+    }
+
     pub fn remove_tablist_player(&self, uuid: Uuid) {
         log::trace!("Sending RemovePlayer({}) to {}", uuid, self.username);
         self.send_packet(PlayerInfo::RemovePlayers(vec![uuid]));
@@ -334,6 +592,7 @@ impl Client {
     pub fn unload_entity(&self, id: NetworkId) {
         log::trace!("Unloading {:?} on {}", id, self.username);
         self.sent_entities.borrow_mut().remove(&id);
+        self.pending_moves.borrow_mut().remove(&id);
         self.send_packet(DestroyEntities {
             entity_ids: vec![id.0.into()],
         });
@@ -383,6 +642,47 @@ impl Client {
         });
     }
 
+    /// Spawns a non-living entity, like a lightning bolt.
+    pub fn send_object_entity(
+        &self,
+        network_id: NetworkId,
+        uuid: Uuid,
+        pos: Position,
+        kind: EntityKind,
+        data: i32,
+        velocity: Vec3d,
+    ) {
+        log::trace!(
+            "Spawning a {:?} on {} (entity type ID: {})",
+            kind,
+            self.username,
+            kind.id()
+        );
+        self.send_packet(SpawnEntity {
+            entity_id: network_id.0,
+            uuid,
+            kind: kind.id() as i32,
+            x: pos.x,
+            y: pos.y,
+            z: pos.z,
+            pitch: pos.pitch,
+            yaw: pos.yaw,
+            data,
+            velocity_x: protocol_velocity(velocity.x),
+            velocity_y: protocol_velocity(velocity.y),
+            velocity_z: protocol_velocity(velocity.z),
+        });
+    }
+
+    /// Shows an entity picking up an item entity.
+    pub fn send_collect_item(&self, item: NetworkId, collector: NetworkId, count: u32) {
+        self.send_packet(CollectItem {
+            collected_entity_id: item.0,
+            collector_entity_id: collector.0,
+            item_count: count as i32,
+        });
+    }
+
     pub fn update_entity_position(
         &self,
         network_id: NetworkId,
@@ -401,21 +701,21 @@ impl Client {
             return;
         }
 
+        // Relative movement can't be dropped, so coalesce it
+        // into one teleport once the connection catches up.
+        if self.is_congested() || self.pending_moves.borrow().contains_key(&network_id) {
+            self.pending_moves
+                .borrow_mut()
+                .insert(network_id, (position, on_ground.0));
+            return;
+        }
+
         let no_change_yaw = (position.yaw - prev_position.0.yaw).abs() < 0.001;
         let no_change_pitch = (position.pitch - prev_position.0.pitch).abs() < 0.001;
 
         // If the entity jumps or falls we should send a teleport packet instead to keep relative movement in sync.
         if on_ground != prev_on_ground.0 {
-            self.send_packet(EntityTeleport {
-                entity_id: network_id.0,
-                x: position.x,
-                y: position.y,
-                z: position.z,
-                yaw: position.yaw,
-                pitch: position.pitch,
-                on_ground: *on_ground,
-            });
-
+            self.send_entity_teleport(network_id, position, on_ground.0);
             return;
         }
 
@@ -446,21 +746,88 @@ impl Client {
         }
     }
 
+    fn send_entity_teleport(&self, network_id: NetworkId, position: Position, on_ground: bool) {
+        self.send_packet(EntityTeleport {
+            entity_id: network_id.0,
+            x: position.x,
+            y: position.y,
+            z: position.z,
+            yaw: position.yaw,
+            pitch: position.pitch,
+            on_ground,
+        });
+    }
+
+    /// Sends a keepalive, unless the client
+    /// has yet to answer the previous one.
     pub fn send_keepalive(&self) {
+        if self.pending_keepalive.get().is_some() {
+            return;
+        }
         log::trace!("Sending keepalive to {}", self.username);
-        self.send_packet(KeepAlive { id: 0 });
+        let id = rand::random();
+        self.pending_keepalive.set(Some((id, Instant::now())));
+        self.send_packet(KeepAlive { id });
+    }
+
+    /// Handles a keepalive response from the client,
+    /// returning the updated ping if its ID matches
+    /// the pending keepalive.
+    pub fn handle_keepalive(&self, id: u64) -> Option<Duration> {
+        match self.pending_keepalive.get() {
+            Some((expected, sent)) if expected == id => {
+                let now = Instant::now();
+                self.pending_keepalive.set(None);
+                self.last_keepalive_response.set(now);
+
+                // Smooth the ping like vanilla does.
+                let round_trip = now.duration_since(sent);
+                let ping = (self.ping.get() * 3 + round_trip) / 4;
+                self.ping.set(ping);
+                Some(ping)
+            }
+            _ => {
+                log::debug!("{} sent an unexpected keepalive ID {}", self.username, id);
+                None
+            }
+        }
+    }
+
+    /// Returns whether the client has stopped answering keepalives.
+    pub fn is_keepalive_timed_out(&self, now: Instant) -> bool {
+        now.duration_since(self.last_keepalive_response.get()) >= KEEPALIVE_TIMEOUT
     }
 
     pub fn send_entity_animation(&self, network_id: NetworkId, animation: Animation) {
         if self.network_id == Some(network_id) {
             return;
         }
-        self.send_packet(EntityAnimation {
+        self.send_droppable_packet(EntityAnimation {
             entity_id: network_id.0,
             animation,
         })
     }
 
+    /// Sets the velocity of an entity, e.g. when it is knocked
+    /// back. Also sent to the client of the entity itself.
+    pub fn send_entity_velocity(&self, network_id: NetworkId, velocity: Vec3d) {
+        self.send_packet(EntityVelocity {
+            entity_id: network_id.0,
+            velocity_x: protocol_velocity(velocity.x),
+            velocity_y: protocol_velocity(velocity.y),
+            velocity_z: protocol_velocity(velocity.z),
+        });
+    }
+
+    /// Sends an entity status, like the hurt or death
+    /// animation of a living entity.
+    pub fn send_entity_status(&self, network_id: NetworkId, status: i8) {
+        self.send_packet(EntityStatus {
+            entity_id: network_id.0,
+            status,
+        });
+    }
+
     pub fn send_chat_message(&self, message: ChatMessage) {
         let packet = chat_packet(message);
         self.send_packet(packet);
@@ -539,7 +906,7 @@ impl Client {
     }
 
     pub fn send_particle(&self, particle: &base::Particle, position: &Position) {
-        self.send_packet(Particle {
+        self.send_droppable_packet(Particle {
             particle_kind: particle.kind,
             long_distance: true,
             x: position.x,
@@ -598,6 +965,52 @@ impl Client {
         });
     }
 
+    pub fn change_gamemode(&self, gamemode: Gamemode) {
+        self.send_packet(ChangeGameState {
+            reason: StateReason::ChangeGameMode,
+            value: gamemode as u8 as f32,
+        });
+    }
+
+    /// Sends the commands usable at `permission_level` so
+    /// the client can highlight and complete them.
+    pub fn send_declare_commands(&self, dispatcher: &CommandDispatcher, permission_level: u8) {
+        self.send_packet(declare_commands_packet(dispatcher, permission_level));
+    }
+
+    /// Tells the client its operator level, which
+    /// unlocks some debug keys and the gamemode switcher.
+    pub fn send_permission_level(&self, permission_level: u8) {
+        let entity_id = match self.network_id {
+            Some(network_id) => network_id.0,
+            None => return,
+        };
+        self.send_packet(EntityStatus {
+            entity_id,
+            status: 24 + permission_level.min(4) as i8,
+        });
+    }
+
+    /// Answers a tab completion request for `text`,
+    /// which the offsets in `suggestions` refer to.
+    pub fn send_tab_complete(&self, transaction_id: i32, text: &str, suggestions: Suggestions) {
+        let (start, length) = suggestions.utf16_range(text);
+        self.send_packet(TabComplete {
+            id: transaction_id,
+            start: start as i32,
+            length: length as i32,
+            matches: suggestions
+                .matches
+                .into_iter()
+                .map(|value| TabCompleteMatch {
+                    value,
+                    has_tooltip: false,
+                    tooltip: None,
+                })
+                .collect(),
+        });
+    }
+
     pub fn set_hotbar_slot(&self, slot: u8) {
         self.send_packet(HeldItemChange { slot });
     }
@@ -610,14 +1023,36 @@ impl Client {
         let _ = self.packets_to_send.try_send(packet.into());
     }
 
-    pub fn disconnect(&self, reason: &str) {
+    /// Sends a packet the client can do without,
+    /// unless the connection is congested.
+    fn send_droppable_packet(&self, packet: impl Into<ServerPlayPacket>) {
+        if !self.is_congested() {
+            self.send_packet(packet);
+        }
+    }
+
+    pub fn disconnect(&self, reason: impl Into<Text>) {
         self.disconnected.set(true);
         self.send_packet(Disconnect {
-            reason: Text::from(reason.to_owned()).to_string(),
+            reason: reason.into().to_string(),
         });
     }
 }
 
+/// Converts a velocity in blocks per tick to the protocol's
+/// unit of 1/8000 blocks per tick, clamped like in vanilla.
+fn protocol_velocity(velocity: f64) -> i16 {
+    (velocity.max(-3.9).min(3.9) * 8000.0) as i16
+}
+
+/// The dimension type of the world, as sent by the default vanilla server.
+fn dimension() -> nbt::Blob {
+    nbt::Blob::from_reader(&mut Cursor::new(include_bytes!(
+        "../../../assets/dimension.nbt"
+    )))
+    .expect("dimension asset is malformed")
+}
+
 fn chat_packet(message: ChatMessage) -> packets::server::ChatMessage {
     packets::server::ChatMessage {
         message: message.text().to_string(),
@@ -629,3 +1064,107 @@ fn chat_packet(message: ChatMessage) -> packets::server::ChatMessage {
         sender: Uuid::default(),
     }
 }
+
+fn declare_commands_packet(
+    dispatcher: &CommandDispatcher,
+    permission_level: u8,
+) -> DeclareCommands {
+    // Only send nodes the player may use, renumbering them
+    // so the packet has no gaps.
+    let mut indices = vec![None; dispatcher.nodes().len()];
+    let mut usable = Vec::new();
+    let mut stack = vec![dispatcher.root()];
+    while let Some(id) = stack.pop() {
+        if indices[id].is_some() || !dispatcher.can_use(id, permission_level) {
+            continue;
+        }
+        indices[id] = Some(usable.len() as i32);
+        usable.push(id);
+        let node = &dispatcher.nodes()[id];
+        stack.extend(node.children().iter().rev());
+        stack.extend(node.redirect());
+    }
+
+    let nodes = usable
+        .iter()
+        .map(|&id| {
+            let node = &dispatcher.nodes()[id];
+            let mut suggestions_type = None;
+            let kind = match node.kind() {
+                NodeKind::Root => CommandNodeKind::Root,
+                NodeKind::Literal(name) => CommandNodeKind::Literal { name: name.clone() },
+                NodeKind::Argument { name, kind } => {
+                    let parser = match kind {
+                        ArgumentKind::Bool => ArgumentParser::Bool,
+                        ArgumentKind::Integer { min, max } => ArgumentParser::Integer {
+                            min: *min,
+                            max: *max,
+                        },
+                        ArgumentKind::Double { min, max } => ArgumentParser::Double {
+                            min: *min,
+                            max: *max,
+                        },
+                        ArgumentKind::String(StringKind::Word) => {
+                            ArgumentParser::String(StringArgumentKind::SingleWord)
+                        }
+                        ArgumentKind::String(StringKind::Quotable) => {
+                            ArgumentParser::String(StringArgumentKind::QuotablePhrase)
+                        }
+                        ArgumentKind::String(StringKind::Greedy) => {
+                            ArgumentParser::String(StringArgumentKind::GreedyPhrase)
+                        }
+                        ArgumentKind::Entity {
+                            single,
+                            players_only,
+                        } => ArgumentParser::Entity {
+                            single: *single,
+                            only_players: *players_only,
+                        },
+                        ArgumentKind::GameProfile => {
+                            ArgumentParser::Other("minecraft:game_profile".to_owned())
+                        }
+                        ArgumentKind::BlockPos => {
+                            ArgumentParser::Other("minecraft:block_pos".to_owned())
+                        }
+                        ArgumentKind::Vec3 => ArgumentParser::Other("minecraft:vec3".to_owned()),
+                        // The client has no gamemode parser, so let it
+                        // ask us for completions.
+                        ArgumentKind::Gamemode => {
+                            suggestions_type = Some("minecraft:ask_server".to_owned());
+                            ArgumentParser::String(StringArgumentKind::SingleWord)
+                        }
+                        ArgumentKind::BlockState => {
+                            ArgumentParser::Other("minecraft:block_state".to_owned())
+                        }
+                        ArgumentKind::Nbt => {
+                            ArgumentParser::Other("minecraft:nbt_compound_tag".to_owned())
+                        }
+                        ArgumentKind::Message => {
+                            ArgumentParser::Other("minecraft:message".to_owned())
+                        }
+                    };
+                    CommandNodeKind::Argument {
+                        name: name.clone(),
+                        parser,
+                    }
+                }
+            };
+            CommandNode {
+                kind,
+                executable: node.is_executable(),
+                children: node
+                    .children()
+                    .iter()
+                    .filter_map(|&child| indices[child])
+                    .collect(),
+                redirect_node: node.redirect().and_then(|target| indices[target]),
+                suggestions_type,
+            }
+        })
+        .collect();
+
+    DeclareCommands {
+        nodes,
+        root_index: 0,
+    }
+}
@@ -1,12 +1,17 @@
 //! Loads an `Options` from a TOML config.
 
-use std::{fs, net::IpAddr, path::Path, str::FromStr};
+use std::{fs, net::IpAddr, path::Path, str::FromStr, time::Duration};
 
 use anyhow::Context;
 use base::Gamemode;
+use common::autosave::AutosaveOptions;
 use serde::{Deserialize, Deserializer};
 
-use crate::{favicon::Favicon, Options};
+use crate::{
+    favicon::Favicon, metrics::MetricsOptions, packet_limiter::PacketLimitOptions,
+    query::QueryOptions, rcon::RconOptions, throttle::ThrottleOptions, watchdog::WatchdogOptions,
+    Options,
+};
 
 const DEFAULT_CONFIG: &str = include_str!("../config.toml");
 
@@ -40,6 +45,16 @@ pub struct ConfigContainer {
 #[derive(Debug, Deserialize)]
 pub struct Config {
     pub network: Network,
+    #[serde(default)]
+    pub packet_limits: PacketLimits,
+    #[serde(default)]
+    pub rcon: Rcon,
+    #[serde(default)]
+    pub query: Query,
+    #[serde(default)]
+    pub metrics: Metrics,
+    #[serde(default)]
+    pub watchdog: Watchdog,
     pub server: ServerConfig,
     pub log: Log,
     pub world: World,
@@ -63,8 +78,14 @@ impl Config {
             } else {
                 Some(self.network.compression_threshold as usize)
             },
+            proxy_protocol: self.network.proxy_protocol,
+            throttle: self.network.throttle_options(),
+            outbound_buffer_limit: self.network.outbound_buffer_limit as usize * 1024,
+            packet_limits: self.packet_limits.to_options(),
+            max_packet_size: self.packet_limits.max_packet_size as usize * 1024,
             view_distance: self.server.view_distance,
             max_players: self.server.max_players,
+            whitelist: self.server.whitelist,
             default_gamemode: self.server.default_gamemode,
             proxy_mode: match self.proxy.proxy_mode {
                 ProxyMode::None => None,
@@ -72,8 +93,34 @@ impl Config {
                 ProxyMode::Velocity => Some(crate::options::ProxyMode::Velocity),
             },
             velocity_secret: self.proxy.velocity_secret.clone(),
+            rcon: self.rcon.to_options(),
+            query: self.query.to_options(),
+            metrics: self.metrics.to_options(),
         }
     }
+
+    pub fn autosave_options(&self) -> AutosaveOptions {
+        AutosaveOptions {
+            interval: match self.world.autosave_interval {
+                0 => None,
+                secs => Some(Duration::from_secs(secs)),
+            },
+            chunks_per_tick: self.world.autosave_chunks_per_tick.max(1),
+        }
+    }
+
+    pub fn watchdog_options(&self) -> Option<WatchdogOptions> {
+        if self.watchdog.warn_after == 0 {
+            return None;
+        }
+        Some(WatchdogOptions {
+            warn_after: Duration::from_secs(self.watchdog.warn_after),
+            shutdown_after: match self.watchdog.shutdown_after {
+                0 => None,
+                secs => Some(Duration::from_secs(secs)),
+            },
+        })
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -81,6 +128,194 @@ pub struct Network {
     pub address: IpAddr,
     pub port: u16,
     pub compression_threshold: i32,
+    #[serde(default)]
+    pub proxy_protocol: bool,
+    #[serde(default = "default_login_throttle")]
+    pub login_throttle: u64,
+    #[serde(default = "default_max_connections_per_ip")]
+    pub max_connections_per_ip: u32,
+    #[serde(default = "default_max_handshakes_per_second")]
+    pub max_handshakes_per_second: u32,
+    #[serde(default = "default_outbound_buffer_limit")]
+    pub outbound_buffer_limit: u32,
+}
+
+impl Network {
+    fn throttle_options(&self) -> ThrottleOptions {
+        ThrottleOptions {
+            login_interval: match self.login_throttle {
+                0 => None,
+                millis => Some(Duration::from_millis(millis)),
+            },
+            max_connections_per_ip: match self.max_connections_per_ip {
+                0 => None,
+                max => Some(max),
+            },
+            max_handshakes_per_second: match self.max_handshakes_per_second {
+                0 => None,
+                max => Some(max),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct PacketLimits {
+    pub total: u32,
+    pub chat_message: u32,
+    pub tab_complete: u32,
+    pub click_window: u32,
+    pub creative_inventory_action: u32,
+    pub player_digging: u32,
+    pub player_block_placement: u32,
+    pub kick_threshold: u32,
+    pub max_packet_size: u32,
+}
+
+impl Default for PacketLimits {
+    fn default() -> Self {
+        Self {
+            total: 500,
+            chat_message: 5,
+            tab_complete: 10,
+            click_window: 40,
+            creative_inventory_action: 40,
+            player_digging: 40,
+            player_block_placement: 40,
+            kick_threshold: 100,
+            max_packet_size: 8192,
+        }
+    }
+}
+
+impl PacketLimits {
+    fn to_options(&self) -> PacketLimitOptions {
+        let limit = |limit| match limit {
+            0 => None,
+            limit => Some(limit),
+        };
+        PacketLimitOptions {
+            total: limit(self.total),
+            chat_message: limit(self.chat_message),
+            tab_complete: limit(self.tab_complete),
+            click_window: limit(self.click_window),
+            creative_inventory_action: limit(self.creative_inventory_action),
+            player_digging: limit(self.player_digging),
+            player_block_placement: limit(self.player_block_placement),
+            kick_threshold: limit(self.kick_threshold),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Rcon {
+    pub enabled: bool,
+    pub port: u16,
+    pub password: String,
+}
+
+impl Default for Rcon {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 25575,
+            password: String::new(),
+        }
+    }
+}
+
+impl Rcon {
+    fn to_options(&self) -> Option<RconOptions> {
+        if !self.enabled {
+            return None;
+        }
+        if self.password.is_empty() {
+            log::warn!("RCON is enabled, but has no password. Not starting it.");
+            return None;
+        }
+        Some(RconOptions {
+            port: self.port,
+            password: self.password.clone(),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Query {
+    pub enabled: bool,
+    pub port: u16,
+    pub requests_per_second: u32,
+}
+
+impl Default for Query {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 25565,
+            requests_per_second: 5,
+        }
+    }
+}
+
+impl Query {
+    fn to_options(&self) -> Option<QueryOptions> {
+        if !self.enabled {
+            return None;
+        }
+        Some(QueryOptions {
+            port: self.port,
+            requests_per_second: self.requests_per_second,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Metrics {
+    pub enabled: bool,
+    pub address: IpAddr,
+    pub port: u16,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: IpAddr::from([127, 0, 0, 1]),
+            port: 9225,
+        }
+    }
+}
+
+impl Metrics {
+    fn to_options(&self) -> Option<MetricsOptions> {
+        if !self.enabled {
+            return None;
+        }
+        Some(MetricsOptions {
+            address: self.address.to_string(),
+            port: self.port,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Watchdog {
+    pub warn_after: u64,
+    pub shutdown_after: u64,
+}
+
+impl Default for Watchdog {
+    fn default() -> Self {
+        Self {
+            warn_after: 10,
+            shutdown_after: 0,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -90,6 +325,8 @@ pub struct ServerConfig {
     pub max_players: u32,
     pub default_gamemode: Gamemode,
     pub view_distance: u32,
+    #[serde(default)]
+    pub whitelist: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -103,6 +340,56 @@ pub struct World {
     pub name: String,
     pub generator: String,
     pub seed: String,
+    #[serde(default = "default_autosave_interval")]
+    pub autosave_interval: u64,
+    #[serde(default = "default_autosave_chunks_per_tick")]
+    pub autosave_chunks_per_tick: usize,
+}
+
+impl World {
+    /// Gets the seed for a new world. Like in vanilla, integers
+    /// are used as-is, other strings are hashed and an empty
+    /// seed is random.
+    pub fn seed(&self) -> i64 {
+        let seed = self.seed.trim();
+        if seed.is_empty() {
+            rand::random()
+        } else if let Ok(seed) = seed.parse() {
+            seed
+        } else {
+            java_string_hash(seed) as i64
+        }
+    }
+}
+
+/// Java's `String.hashCode`, which vanilla uses to hash seeds.
+fn java_string_hash(s: &str) -> i32 {
+    s.encode_utf16()
+        .fold(0i32, |hash, c| hash.wrapping_mul(31).wrapping_add(c as i32))
+}
+
+fn default_login_throttle() -> u64 {
+    4000
+}
+
+fn default_max_connections_per_ip() -> u32 {
+    8
+}
+
+fn default_max_handshakes_per_second() -> u32 {
+    100
+}
+
+fn default_outbound_buffer_limit() -> u32 {
+    4096
+}
+
+fn default_autosave_interval() -> u64 {
+    300
+}
+
+fn default_autosave_chunks_per_tick() -> usize {
+    32
 }
 
 #[derive(Debug, Deserialize)]
@@ -139,4 +426,13 @@ mod tests {
     fn default_config_is_valid() {
         let _config: Config = toml::from_str(DEFAULT_CONFIG).unwrap();
     }
+
+    #[test]
+    fn seeds() {
+        let mut config: Config = toml::from_str(DEFAULT_CONFIG).unwrap();
+        config.world.seed = "-1234".to_owned();
+        assert_eq!(config.world.seed(), -1234);
+        config.world.seed = "feather".to_owned();
+        assert_eq!(config.world.seed(), -979_220_317);
+    }
 }
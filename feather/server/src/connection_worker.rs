@@ -1,6 +1,17 @@
-use std::{fmt::Debug, io, net::SocketAddr, sync::Arc, time::Duration};
+use std::{
+    fmt::Debug,
+    io,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
+use anyhow::bail;
 use base::Text;
+use common::Metrics;
 use flume::{Receiver, Sender};
 use futures_lite::FutureExt;
 use io::ErrorKind;
@@ -18,9 +29,12 @@ use tokio::{
 };
 
 use crate::{
+    access::AccessLists,
     initial_handler::{InitialHandling, NewPlayer},
     options::Options,
+    packet_limiter::{PacketLimiter, Verdict},
     player_count::PlayerCount,
+    throttle::{ConnectionGuard, Throttle},
 };
 
 /// Tokio task which handles a connection and processes
@@ -34,35 +48,64 @@ use crate::{
 pub struct Worker {
     reader: Reader,
     writer: Writer,
+    addr: SocketAddr,
     options: Arc<Options>,
     player_count: PlayerCount,
+    access_lists: AccessLists,
+    throttle: Throttle,
+    connection: ConnectionGuard,
     packets_to_send_tx: Sender<ServerPlayPacket>,
     received_packets_rx: Receiver<ClientPlayPacket>,
+    outbound_bytes: Arc<AtomicUsize>,
     new_players: Sender<NewPlayer>,
 }
 
 impl Worker {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         stream: TcpStream,
-        _addr: SocketAddr,
+        addr: SocketAddr,
         options: Arc<Options>,
         player_count: PlayerCount,
+        access_lists: AccessLists,
+        throttle: Throttle,
+        connection: ConnectionGuard,
+        metrics: Metrics,
         new_players: Sender<NewPlayer>,
     ) -> Self {
         let (reader, writer) = stream.into_split();
 
         let (received_packets_tx, received_packets_rx) = flume::bounded(32);
         let (packets_to_send_tx, packets_to_send_rx) = flume::unbounded();
-        let reader = Reader::new(reader, received_packets_tx);
-        let writer = Writer::new(writer, packets_to_send_rx);
+        let limiter = PacketLimiter::new(&options.packet_limits, Instant::now());
+        let mut reader = Reader::new(
+            reader,
+            received_packets_tx,
+            packets_to_send_tx.clone(),
+            limiter,
+            metrics.clone(),
+        );
+        reader.codec.set_max_packet_size(options.max_packet_size);
+        let outbound_bytes = Arc::new(AtomicUsize::new(0));
+        let writer = Writer::new(
+            writer,
+            packets_to_send_rx,
+            Arc::clone(&outbound_bytes),
+            metrics,
+        );
 
         Self {
             reader,
             writer,
+            addr,
             options,
             player_count,
+            access_lists,
+            throttle,
+            connection,
             packets_to_send_tx,
             received_packets_rx,
+            outbound_bytes,
             new_players,
         }
     }
@@ -86,12 +129,17 @@ impl Worker {
             InitialHandling::Disconnect => (),
             InitialHandling::Join(new_player) => {
                 if self.player_count.try_add_player().is_err() {
-                    self.write(ServerPlayPacket::Disconnect(Disconnect {
-                        reason: Text::from("The server is full!").to_string(),
-                    }))
-                    .await
-                    .ok();
-                    return;
+                    // Some operators may join a full server.
+                    if self.access_lists.bypasses_player_limit(new_player.uuid) {
+                        self.player_count.add_player();
+                    } else {
+                        self.write(ServerPlayPacket::Disconnect(Disconnect {
+                            reason: Text::from("The server is full!").to_string(),
+                        }))
+                        .await
+                        .ok();
+                        return;
+                    }
                 }
 
                 let username = new_player.username.clone();
@@ -109,6 +157,19 @@ impl Worker {
         self.player_count.get()
     }
 
+    pub fn access_lists(&self) -> &AccessLists {
+        &self.access_lists
+    }
+
+    pub fn throttle(&self) -> &Throttle {
+        &self.throttle
+    }
+
+    /// The address of the other end of the connection.
+    pub fn remote_address(&self) -> SocketAddr {
+        self.addr
+    }
+
     #[allow(unused)]
     pub fn enable_compression(&mut self, threshold: usize) {
         self.reader.codec.enable_compression(threshold);
@@ -137,9 +198,11 @@ impl Worker {
             reader,
             writer,
             player_count,
+            connection,
             ..
         } = self;
-        let reader = tokio::task::spawn(async move { reader.run().await });
+        let reader_username = username.clone();
+        let reader = tokio::task::spawn(async move { reader.run(&reader_username).await });
         let writer = tokio::task::spawn(async move { writer.run().await });
 
         tokio::task::spawn(async move {
@@ -149,6 +212,7 @@ impl Worker {
                 log::debug!("{} lost connection: {}", username, message);
             }
             player_count.remove_player();
+            drop(connection);
         });
     }
 
@@ -159,6 +223,12 @@ impl Worker {
     pub fn received_packets(&self) -> Receiver<ClientPlayPacket> {
         self.received_packets_rx.clone()
     }
+
+    /// Number of encoded bytes waiting to be written
+    /// to the connection. Updated by the writer task.
+    pub fn outbound_bytes(&self) -> Arc<AtomicUsize> {
+        Arc::clone(&self.outbound_bytes)
+    }
 }
 
 struct Reader {
@@ -166,21 +236,51 @@ struct Reader {
     codec: MinecraftCodec,
     buffer: [u8; 512],
     received_packets: Sender<ClientPlayPacket>,
+    /// Used to send the reason when kicking the client.
+    packets_to_send: Sender<ServerPlayPacket>,
+    limiter: PacketLimiter,
+    metrics: Metrics,
 }
 
 impl Reader {
-    pub fn new(stream: OwnedReadHalf, received_packets: Sender<ClientPlayPacket>) -> Self {
+    pub fn new(
+        stream: OwnedReadHalf,
+        received_packets: Sender<ClientPlayPacket>,
+        packets_to_send: Sender<ServerPlayPacket>,
+        limiter: PacketLimiter,
+        metrics: Metrics,
+    ) -> Self {
         Self {
             stream,
             codec: MinecraftCodec::new(),
             buffer: [0; 512],
             received_packets,
+            packets_to_send,
+            limiter,
+            metrics,
         }
     }
 
-    pub async fn run(mut self) -> anyhow::Result<()> {
+    pub async fn run(mut self, username: &str) -> anyhow::Result<()> {
         loop {
             let packet = self.read::<ClientPlayPacket>().await?;
+            match self.limiter.check(&packet, Instant::now()) {
+                Verdict::Accept => (),
+                Verdict::Drop => {
+                    log::trace!("Dropped packet from {} over the rate limit", username);
+                    continue;
+                }
+                Verdict::Kick => {
+                    log::info!("Kicking {} for sending too many packets", username);
+                    let _ =
+                        self.packets_to_send
+                            .try_send(ServerPlayPacket::Disconnect(Disconnect {
+                                reason: Text::from("Kicked for sending too many packets")
+                                    .to_string(),
+                            }));
+                    bail!("sent too many packets");
+                }
+            }
             let result = self.received_packets.send_async(packet).await;
             if result.is_err() {
                 // server dropped connection
@@ -201,6 +301,7 @@ impl Reader {
             if read_bytes == 0 {
                 return Err(io::Error::new(ErrorKind::UnexpectedEof, "read 0 bytes").into());
             }
+            self.metrics.add_received_bytes(read_bytes);
 
             let bytes = &self.buffer[..read_bytes];
             self.codec.accept(bytes);
@@ -208,36 +309,131 @@ impl Reader {
     }
 }
 
+/// How long to keep writing queued packets, like the
+/// disconnect packet, after the server dropped the client.
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
 struct Writer {
     stream: OwnedWriteHalf,
     codec: MinecraftCodec,
     packets_to_send: Receiver<ServerPlayPacket>,
+    /// Encoded packets waiting to be written.
     buffer: Vec<u8>,
+    /// Number of bytes at the start of `buffer` already written.
+    written: usize,
+    /// Scratch buffer for encoding a single packet,
+    /// since the codec encrypts the whole output.
+    staging: Vec<u8>,
+    outbound_bytes: Arc<AtomicUsize>,
+    metrics: Metrics,
+}
+
+enum WriterEvent {
+    Written(io::Result<usize>),
+    Packet(Option<ServerPlayPacket>),
 }
 
 impl Writer {
-    pub fn new(stream: OwnedWriteHalf, packets_to_send: Receiver<ServerPlayPacket>) -> Self {
+    pub fn new(
+        stream: OwnedWriteHalf,
+        packets_to_send: Receiver<ServerPlayPacket>,
+        outbound_bytes: Arc<AtomicUsize>,
+        metrics: Metrics,
+    ) -> Self {
         Self {
             stream,
             codec: MinecraftCodec::new(),
             packets_to_send,
             buffer: Vec::new(),
+            written: 0,
+            staging: Vec::new(),
+            outbound_bytes,
+            metrics,
         }
     }
 
+    /// Writes packets until the server drops the client.
+    ///
+    /// Packets are encoded as soon as they are queued, so
+    /// [`Worker::outbound_bytes`] reflects how far the
+    /// connection is behind.
     pub async fn run(mut self) -> anyhow::Result<()> {
-        while let Ok(packet) = self.packets_to_send.recv_async().await {
-            self.write(packet).await?;
+        loop {
+            if self.queued() == 0 {
+                match self.packets_to_send.recv_async().await {
+                    Ok(packet) => self.queue(&packet)?,
+                    Err(_) => return Ok(()),
+                }
+            }
+            while let Ok(packet) = self.packets_to_send.try_recv() {
+                self.queue(&packet)?;
+            }
+            self.update_outbound_bytes();
+
+            let unwritten = &self.buffer[self.written..];
+            let event = tokio::select! {
+                written = self.stream.write(unwritten) => WriterEvent::Written(written),
+                packet = self.packets_to_send.recv_async() => WriterEvent::Packet(packet.ok()),
+            };
+            match event {
+                WriterEvent::Written(written) => self.advance(written?)?,
+                WriterEvent::Packet(Some(packet)) => self.queue(&packet)?,
+                WriterEvent::Packet(None) => {
+                    // The server dropped the client. Try to deliver
+                    // what is left, e.g. the disconnect reason.
+                    let remaining = &self.buffer[self.written..];
+                    if let Ok(Ok(())) =
+                        timeout(FLUSH_TIMEOUT, self.stream.write_all(remaining)).await
+                    {
+                        self.metrics.add_sent_bytes(remaining.len());
+                    }
+                    return Ok(());
+                }
+            }
+            self.update_outbound_bytes();
         }
-        Ok(())
     }
 
     pub async fn write(&mut self, packet: impl Writeable + Debug) -> anyhow::Result<()> {
-        self.codec.encode(&packet, &mut self.buffer)?;
-        self.stream.write_all(&self.buffer).await?;
-        self.buffer.clear();
+        self.codec.encode(&packet, &mut self.staging)?;
+        self.stream.write_all(&self.staging).await?;
+        self.metrics.add_sent_bytes(self.staging.len());
+        self.staging.clear();
         Ok(())
     }
+
+    fn queue(&mut self, packet: &ServerPlayPacket) -> anyhow::Result<()> {
+        self.codec.encode(packet, &mut self.staging)?;
+        self.buffer.extend_from_slice(&self.staging);
+        self.staging.clear();
+        Ok(())
+    }
+
+    fn advance(&mut self, written: usize) -> anyhow::Result<()> {
+        if written == 0 {
+            return Err(io::Error::new(ErrorKind::WriteZero, "wrote 0 bytes").into());
+        }
+        self.written += written;
+        self.metrics.add_sent_bytes(written);
+        if self.written == self.buffer.len() {
+            self.buffer.clear();
+            self.written = 0;
+        } else if self.written > self.buffer.len() / 2 {
+            // Keep the buffer from growing while a
+            // slow connection never fully catches up.
+            self.buffer.drain(..self.written);
+            self.written = 0;
+        }
+        Ok(())
+    }
+
+    fn queued(&self) -> usize {
+        self.buffer.len() - self.written
+    }
+
+    fn update_outbound_bytes(&self) {
+        self.outbound_bytes.store(self.queued(), Ordering::Relaxed);
+    }
 }
 
 fn disconnected_message(e: anyhow::Error) -> String {
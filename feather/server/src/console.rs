@@ -0,0 +1,68 @@
+//! Reads commands typed into the server console.
+
+use std::thread;
+
+use flume::{Receiver, Sender};
+use rustyline::{error::ReadlineError, Editor};
+
+/// Lines read from standard input, waiting to be
+/// executed as commands on the game thread.
+///
+/// Insert this as a resource to enable the console;
+/// lines are drained once per tick.
+pub struct ConsoleInput {
+    lines: Receiver<String>,
+}
+
+impl ConsoleInput {
+    /// Spawns a thread reading lines from standard input.
+    ///
+    /// The reader supports line editing and keeps a history
+    /// of previously entered commands. Since the line editor
+    /// intercepts Ctrl-C, `on_interrupt` is invoked instead
+    /// of the usual SIGINT handling.
+    pub fn spawn(on_interrupt: impl FnOnce() + Send + 'static) -> Self {
+        let (sender, lines) = flume::unbounded();
+        thread::Builder::new()
+            .name("console".to_owned())
+            .spawn(move || read_lines(sender, on_interrupt))
+            .expect("failed to spawn console thread");
+        Self { lines }
+    }
+
+    /// Returns the lines entered since the last call.
+    pub fn drain(&self) -> impl Iterator<Item = String> + '_ {
+        self.lines.try_iter()
+    }
+}
+
+fn read_lines(sender: Sender<String>, on_interrupt: impl FnOnce()) {
+    let mut editor = Editor::<()>::new();
+    loop {
+        match editor.readline("") {
+            Ok(line) => {
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+                editor.add_history_entry(line);
+                if sender.send(line.to_owned()).is_err() {
+                    // Game shut down
+                    return;
+                }
+            }
+            Err(ReadlineError::Interrupted) => {
+                on_interrupt();
+                return;
+            }
+            Err(ReadlineError::Eof) => {
+                log::debug!("Console input closed");
+                return;
+            }
+            Err(e) => {
+                log::error!("Failed to read console input: {}", e);
+                return;
+            }
+        }
+    }
+}
@@ -1,5 +1,7 @@
-use base::{EntityKind, Position};
+use base::{metadata::META_INDEX_ITEM, EntityKind, EntityMetadata, ItemStack, Position};
+use common::physics::Velocity;
 use ecs::{EntityBuilder, EntityRef, SysResult};
+use libcraft_items::InventorySlot;
 use quill_common::{components::OnGround, entity_init::EntityInit};
 use uuid::Uuid;
 
@@ -44,10 +46,12 @@ pub fn add_entity_components(builder: &mut EntityBuilder, init: &EntityInit) {
 }
 
 fn add_spawn_packet(builder: &mut EntityBuilder, init: &EntityInit) {
-    // TODO: object entities spawned with Spawn Entity
-    // (minecarts, items, ...)
+    // TODO: other object entities spawned with
+    // Spawn Entity (minecarts, arrows, ...)
     let spawn_packet = match init {
         EntityInit::Player => spawn_player,
+        EntityInit::LightningBolt => spawn_object,
+        EntityInit::Item => spawn_item,
         _ => spawn_living_entity,
     };
     builder.add(SpawnPacketSender(spawn_packet));
@@ -71,3 +75,36 @@ fn spawn_living_entity(entity: &EntityRef, client: &Client) -> SysResult {
     client.send_living_entity(network_id, uuid, pos, kind);
     Ok(())
 }
+
+fn spawn_object(entity: &EntityRef, client: &Client) -> SysResult {
+    send_object(entity, client, 0)
+}
+
+fn spawn_item(entity: &EntityRef, client: &Client) -> SysResult {
+    // Like vanilla, which sends items with a data value of 1.
+    send_object(entity, client, 1)?;
+    client.send_entity_metadata(*entity.get::<NetworkId>()?, item_metadata(entity)?);
+    Ok(())
+}
+
+/// Spawns an object entity with the given data for the client.
+fn send_object(entity: &EntityRef, client: &Client, data: i32) -> SysResult {
+    let network_id = *entity.get::<NetworkId>()?;
+    let uuid = *entity.get::<Uuid>()?;
+    let pos = *entity.get::<Position>()?;
+    let kind = *entity.get::<EntityKind>()?;
+    let velocity = entity
+        .get::<Velocity>()
+        .map(|velocity| velocity.0)
+        .unwrap_or_default();
+
+    client.send_object_entity(network_id, uuid, pos, kind, data, velocity);
+    Ok(())
+}
+
+/// Returns the metadata of an item entity, which
+/// tells clients which item to display.
+pub fn item_metadata(entity: &EntityRef) -> anyhow::Result<EntityMetadata> {
+    let item = entity.get::<ItemStack>()?.clone();
+    Ok(EntityMetadata::entity_base().with(META_INDEX_ITEM, InventorySlot::Filled(item)))
+}
@@ -1,6 +1,6 @@
 //! Initial handling of a connection.
 
-use crate::{connection_worker::Worker, favicon::Favicon};
+use crate::{access::Profile, connection_worker::Worker, favicon::Favicon};
 use anyhow::bail;
 use base::{ProfileProperty, Text};
 use flume::{Receiver, Sender};
@@ -22,12 +22,18 @@ use rand::rngs::OsRng;
 use rsa::{PaddingScheme, PublicKeyParts, RSAPrivateKey};
 use serde::{Deserialize, Serialize};
 use sha1::Sha1;
-use std::convert::TryInto;
+use std::{
+    convert::TryInto,
+    net::IpAddr,
+    sync::{atomic::AtomicUsize, Arc},
+    time::Instant,
+};
 use uuid::Uuid;
 
 use self::proxy::ProxyData;
 
-const SERVER_NAME: &str = "Feather 1.16.5";
+pub(crate) const SERVER_NAME: &str = "Feather 1.16.5";
+pub(crate) const MINECRAFT_VERSION: &str = "1.16.5";
 const PROTOCOL_VERSION: i32 = 754;
 
 mod proxy;
@@ -38,9 +44,12 @@ pub struct NewPlayer {
     pub uuid: Uuid,
     pub username: String,
     pub profile: Vec<ProfileProperty>,
+    pub ip: IpAddr,
 
     pub received_packets: Receiver<ClientPlayPacket>,
     pub packets_to_send: Sender<ServerPlayPacket>,
+    /// Number of encoded bytes not yet written to the connection.
+    pub outbound_bytes: Arc<AtomicUsize>,
 }
 
 /// Result of initial handling.
@@ -60,6 +69,11 @@ pub async fn handle(worker: &mut Worker) -> anyhow::Result<InitialHandling> {
 
     let ClientHandshakePacket::Handshake(handshake) = handshake;
 
+    let addr = worker.remote_address().ip();
+    if !worker.throttle().allow_handshake(addr, Instant::now()) {
+        return Ok(InitialHandling::Disconnect);
+    }
+
     match handshake.next_state {
         HandshakeState::Status => handle_status(worker).await,
         HandshakeState::Login => {
@@ -163,8 +177,27 @@ async fn handle_login(
         proxy_data = Some(proxy::do_velocity_ip_forwarding(worker).await?);
     }
 
+    // Behind a proxy, the connection comes from the proxy
+    // and the player's address is forwarded.
+    let ip = proxy_data
+        .as_ref()
+        .and_then(|data| data.client.parse().ok())
+        .unwrap_or_else(|| worker.remote_address().ip());
+
+    // Throttle before doing any crypto or authentication work.
+    if !worker.throttle().allow_login(ip, Instant::now()) {
+        worker
+            .write(ServerLoginPacket::DisconnectLogin(DisconnectLogin {
+                reason: Text::from("Connection throttled! Please wait before reconnecting.")
+                    .to_string(),
+            }))
+            .await
+            .ok();
+        return Ok(InitialHandling::Disconnect);
+    }
+
     if worker.options().online_mode {
-        enable_encryption(worker, login_start.name).await
+        enable_encryption(worker, login_start.name, ip).await
     } else {
         let profile = match proxy_data {
             Some(proxy_data) => AuthResponse {
@@ -174,7 +207,7 @@ async fn handle_login(
             },
             None => offline_mode_profile(login_start.name),
         };
-        finish_login(worker, profile).await
+        finish_login(worker, profile, ip).await
     }
 }
 
@@ -187,7 +220,7 @@ fn offline_mode_profile(username: String) -> AuthResponse {
     }
 }
 
-fn offline_mode_uuid(username: &str) -> Uuid {
+pub(crate) fn offline_mode_uuid(username: &str) -> Uuid {
     // See: https://gist.github.com/games647/2b6a00a8fc21fd3b88375f03c9e2e603
     let mut hasher = md5::Md5::default();
     hasher.update(format!("OfflinePlayer:{}", username).as_bytes());
@@ -214,6 +247,7 @@ static RSA_KEY_ENCODED: Lazy<Vec<u8>> = Lazy::new(|| {
 async fn enable_encryption(
     worker: &mut Worker,
     username: String,
+    ip: IpAddr,
 ) -> anyhow::Result<InitialHandling> {
     log::debug!("Authenticating {}", username);
     let shared_secret = do_encryption_handshake(worker).await?;
@@ -221,7 +255,7 @@ async fn enable_encryption(
 
     let response = authenticate(shared_secret, username).await?;
 
-    finish_login(worker, response).await
+    finish_login(worker, response, ip).await
 }
 
 async fn do_encryption_handshake(worker: &mut Worker) -> anyhow::Result<CryptKey> {
@@ -293,7 +327,27 @@ fn hexdigest(bytes: &[u8]) -> String {
 async fn finish_login(
     worker: &mut Worker,
     response: AuthResponse,
+    ip: IpAddr,
 ) -> anyhow::Result<InitialHandling> {
+    let profile = Profile {
+        uuid: response.id,
+        name: response.name.clone(),
+    };
+    if let Some(reason) = worker.access_lists().check_login(&profile, ip) {
+        log::info!(
+            "Disconnecting {} ({}): not allowed to join",
+            profile.name,
+            ip
+        );
+        worker
+            .write(ServerLoginPacket::DisconnectLogin(DisconnectLogin {
+                reason: reason.to_string(),
+            }))
+            .await
+            .ok();
+        return Ok(InitialHandling::Disconnect);
+    }
+
     enable_compression(worker).await?;
 
     let success = LoginSuccess {
@@ -308,8 +362,10 @@ async fn finish_login(
         username: response.name,
         uuid: response.id,
         profile: response.properties,
+        ip,
         received_packets: worker.received_packets(),
         packets_to_send: worker.packets_to_send(),
+        outbound_bytes: worker.outbound_bytes(),
     };
     log::debug!("Completed initial handling for {}", new_player.username);
     Ok(InitialHandling::Join(new_player))
@@ -0,0 +1,99 @@
+//! Answers the server list ping of clients from before 1.7,
+//! which many uptime checkers still use.
+//!
+//! See <https://wiki.vg/Server_List_Ping#1.6> for the protocol.
+
+use std::time::Duration;
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    time::timeout,
+};
+
+use crate::{initial_handler::MINECRAFT_VERSION, options::Options, player_count::PlayerCount};
+
+/// First byte of a legacy ping. Modern clients start with
+/// the length of their handshake as a VarInt, which only
+/// begins with this byte for handshakes of at least 254
+/// bytes. Vanilla makes the same assumption.
+pub const LEGACY_PING: u8 = 0xFE;
+
+/// Packet ID of the kick packet containing the response.
+const KICK: u8 = 0xFF;
+
+/// Protocol version reported to legacy clients. Like vanilla,
+/// we report one no legacy client supports.
+const LEGACY_PROTOCOL_VERSION: i32 = 127;
+
+/// Responds to a legacy ping. The first byte of the
+/// stream must be [`LEGACY_PING`].
+pub async fn respond(
+    mut stream: TcpStream,
+    options: &Options,
+    player_count: &PlayerCount,
+) -> anyhow::Result<()> {
+    let mut buf = [0; 256];
+    let mut len = timeout(Duration::from_secs(10), stream.read(&mut buf)).await??;
+    if len == 1 {
+        // Clients since 1.4 follow up with 0x01, but
+        // older ones send nothing else.
+        if let Ok(read) = timeout(Duration::from_millis(500), stream.read(&mut buf[1..])).await {
+            len += read?;
+        }
+    }
+
+    let response = if len > 1 && buf[1] == 0x01 {
+        format_response(options, player_count.get())
+    } else {
+        format_beta_response(options, player_count.get())
+    };
+    stream.write_all(&encode_kick(&response)).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// The response understood by 1.4 to 1.6 clients.
+fn format_response(options: &Options, online: u32) -> String {
+    format!(
+        "§1\0{}\0{}\0{}\0{}\0{}",
+        LEGACY_PROTOCOL_VERSION, MINECRAFT_VERSION, options.motd, online, options.max_players
+    )
+}
+
+/// The response understood by Beta 1.8 to 1.3 clients,
+/// which can't display `§` in the MOTD.
+fn format_beta_response(options: &Options, online: u32) -> String {
+    format!(
+        "{}§{}§{}",
+        options.motd.replace('§', ""),
+        online,
+        options.max_players
+    )
+}
+
+/// Encodes a kick packet: the packet ID, the string length
+/// in UTF-16 code units and the UTF-16BE string.
+fn encode_kick(message: &str) -> Vec<u8> {
+    let units: Vec<u16> = message.encode_utf16().collect();
+    let mut buf = Vec::with_capacity(3 + units.len() * 2);
+    buf.push(KICK);
+    buf.extend_from_slice(&(units.len() as u16).to_be_bytes());
+    for unit in units {
+        buf.extend_from_slice(&unit.to_be_bytes());
+    }
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kick_packet() {
+        assert_eq!(
+            encode_kick("§1\0A"),
+            vec![0xFF, 0, 4, 0x00, 0xA7, 0x00, 0x31, 0x00, 0x00, 0x00, 0x41]
+        );
+    }
+}
@@ -1,28 +1,43 @@
 #![allow(clippy::unnecessary_wraps)] // systems are required to return Results
 
-use std::{sync::Arc, time::Instant};
+use std::{path::Path, sync::Arc, time::Instant};
 
-use base::Position;
+use access::AccessLists;
+use base::{Position, Text};
 use chunk_subscriptions::ChunkSubscriptions;
-use common::Game;
+use common::{Game, Metrics};
 use ecs::SystemExecutor;
 use flume::Receiver;
 use initial_handler::NewPlayer;
-use listener::Listener;
+use listener::{Listener, QueryListener};
+use protocol::{packets::server::Disconnect, ServerPlayPacket};
+use query::QueryData;
+use rcon::RconCommand;
+use tokio::task::JoinHandle;
 
+pub mod access;
 mod chunk_subscriptions;
 pub mod client;
 pub mod config;
 mod connection_worker;
+pub mod console;
 mod entities;
 pub mod favicon;
 mod initial_handler;
+mod legacy_ping;
 mod listener;
+pub mod metrics;
 mod network_id_registry;
 mod options;
 mod packet_handlers;
+mod packet_limiter;
 mod player_count;
+mod proxy_protocol;
+pub mod query;
+pub mod rcon;
 mod systems;
+mod throttle;
+pub mod watchdog;
 
 pub use client::{Client, ClientId, Clients};
 pub use network_id_registry::NetworkId;
@@ -41,6 +56,8 @@ pub struct Server {
     options: Arc<Options>,
     clients: Clients,
     new_players: Receiver<NewPlayer>,
+    listener: JoinHandle<()>,
+    shutting_down: bool,
 
     waiting_chunks: WaitingChunks,
     chunk_subscriptions: ChunkSubscriptions,
@@ -48,6 +65,16 @@ pub struct Server {
     last_keepalive_time: Instant,
 
     player_count: PlayerCount,
+    access_lists: AccessLists,
+
+    rcon_commands: Option<Receiver<RconCommand>>,
+    rcon_listener: Option<JoinHandle<()>>,
+
+    query_data: QueryData,
+    query_listener: Option<JoinHandle<()>>,
+
+    metrics: Metrics,
+    metrics_listener: Option<JoinHandle<()>>,
 }
 
 impl Server {
@@ -57,9 +84,19 @@ impl Server {
     pub async fn bind(options: Options) -> anyhow::Result<Self> {
         let options = Arc::new(options);
         let player_count = PlayerCount::new(options.max_players);
+        let access_lists = AccessLists::load(Path::new("."), options.whitelist)?;
+
+        let metrics = Metrics::default();
 
         let (new_players_tx, new_players) = flume::bounded(4);
-        Listener::start(Arc::clone(&options), player_count.clone(), new_players_tx).await?;
+        let listener = Listener::start(
+            Arc::clone(&options),
+            player_count.clone(),
+            access_lists.clone(),
+            metrics.clone(),
+            new_players_tx,
+        )
+        .await?;
 
         log::info!(
             "Server is listening on {}:{}",
@@ -67,14 +104,50 @@ impl Server {
             options.port
         );
 
+        let (rcon_commands, rcon_listener) = match &options.rcon {
+            Some(rcon) => {
+                let (commands, listener) = rcon::start(&options.bind_address, rcon.clone()).await?;
+                (Some(commands), Some(listener))
+            }
+            None => (None, None),
+        };
+
+        let query_data = QueryData::default();
+        let query_listener = match &options.query {
+            Some(query) => Some(
+                QueryListener::start(
+                    Arc::clone(&options),
+                    query.clone(),
+                    player_count.clone(),
+                    query_data.clone(),
+                )
+                .await?,
+            ),
+            None => None,
+        };
+
+        let metrics_listener = match &options.metrics {
+            Some(metrics_options) => Some(metrics::start(metrics_options, metrics.clone()).await?),
+            None => None,
+        };
+
         Ok(Self {
             options,
             clients: Clients::new(),
             new_players,
+            listener,
+            shutting_down: false,
             waiting_chunks: WaitingChunks::default(),
             chunk_subscriptions: ChunkSubscriptions::default(),
             last_keepalive_time: Instant::now(),
             player_count,
+            access_lists,
+            rcon_commands,
+            rcon_listener,
+            query_data,
+            query_listener,
+            metrics,
+            metrics_listener,
         })
     }
 
@@ -85,10 +158,49 @@ impl Server {
         game.add_entity_spawn_callback(entities::add_entity_components);
     }
 
+    /// Gets the ban lists, whitelist and operator list.
+    pub fn access_lists(&self) -> &AccessLists {
+        &self.access_lists
+    }
+
+    /// Gets the data reported by the query listener.
+    pub fn query_data(&self) -> &QueryData {
+        &self.query_data
+    }
+
+    /// Gets the metrics served by the metrics endpoint.
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
     /// Gets the number of online players.
     pub fn player_count(&self) -> u32 {
         self.player_count.get()
     }
+
+    /// Stops accepting connections and disconnects
+    /// all players with the given reason.
+    ///
+    /// Disconnected players are saved and removed
+    /// from the `Game` on the next tick.
+    pub fn shutdown(&mut self, reason: &str) {
+        self.listener.abort();
+        for listener in self
+            .rcon_listener
+            .iter()
+            .chain(&self.query_listener)
+            .chain(&self.metrics_listener)
+        {
+            listener.abort();
+        }
+        self.shutting_down = true;
+        self.broadcast_with(|client| client.disconnect(reason.to_owned()));
+    }
+
+    /// Returns whether [`shutdown`](Server::shutdown) has been called.
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down
+    }
 }
 
 /// Low-level functions, mostly used internally.
@@ -98,6 +210,17 @@ impl Server {
     pub fn accept_new_players(&mut self) -> Vec<ClientId> {
         let mut clients = Vec::new();
         for player in self.new_players.clone().try_iter() {
+            if self.shutting_down {
+                // Connections which finished logging in
+                // after the listener was stopped.
+                let _ = player
+                    .packets_to_send
+                    .send(ServerPlayPacket::Disconnect(Disconnect {
+                        reason: Text::from("Server closed").to_string(),
+                    }));
+                continue;
+            }
+
             if let Some(old_client) = self.clients.iter().find(|x| x.uuid() == player.uuid) {
                 old_client.disconnect("Logged in from another location!");
             }
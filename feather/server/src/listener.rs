@@ -1,12 +1,28 @@
-use std::{net::SocketAddr, sync::Arc};
+use std::{
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use anyhow::Context;
+use common::Metrics;
 use flume::Sender;
-use tokio::net::{TcpListener, TcpStream};
+use tokio::{
+    net::{TcpListener, TcpStream, UdpSocket},
+    task::JoinHandle,
+    time::timeout,
+};
 
 use crate::{
-    connection_worker::Worker, initial_handler::NewPlayer, options::Options,
+    access::AccessLists,
+    connection_worker::Worker,
+    initial_handler::NewPlayer,
+    legacy_ping,
+    options::Options,
     player_count::PlayerCount,
+    proxy_protocol,
+    query::{QueryData, QueryOptions, QueryResponder},
+    throttle::Throttle,
 };
 
 /// Listens for and accepts incoming connections.
@@ -14,6 +30,9 @@ pub struct Listener {
     listener: TcpListener,
     options: Arc<Options>,
     player_count: PlayerCount,
+    access_lists: AccessLists,
+    throttle: Throttle,
+    metrics: Metrics,
     new_players: Sender<NewPlayer>,
 }
 
@@ -21,23 +40,29 @@ impl Listener {
     pub async fn start(
         options: Arc<Options>,
         player_count: PlayerCount,
+        access_lists: AccessLists,
+        metrics: Metrics,
         new_players: Sender<NewPlayer>,
-    ) -> anyhow::Result<()> {
+    ) -> anyhow::Result<JoinHandle<()>> {
         let listener = TcpListener::bind(format!("{}:{}", options.bind_address, options.port))
             .await
             .context("failed to bind to port - maybe a server is already running?")?;
 
+        let throttle = Throttle::new(options.throttle.clone());
         let listener = Listener {
             listener,
             options,
             player_count,
+            access_lists,
+            throttle,
+            metrics,
             new_players,
         };
-        tokio::task::spawn(async move {
+        let task = tokio::task::spawn(async move {
             listener.run().await;
         });
 
-        Ok(())
+        Ok(task)
     }
 
     async fn run(mut self) {
@@ -48,14 +73,117 @@ impl Listener {
         }
     }
 
-    async fn accept(&mut self, stream: TcpStream, addr: SocketAddr) {
-        let worker = Worker::new(
-            stream,
-            addr,
-            Arc::clone(&self.options),
-            self.player_count.clone(),
-            self.new_players.clone(),
+    async fn accept(&mut self, mut stream: TcpStream, mut addr: SocketAddr) {
+        let options = Arc::clone(&self.options);
+        let player_count = self.player_count.clone();
+        let access_lists = self.access_lists.clone();
+        let throttle = self.throttle.clone();
+        let metrics = self.metrics.clone();
+        let new_players = self.new_players.clone();
+        tokio::task::spawn(async move {
+            if options.proxy_protocol {
+                match proxy_protocol::read_header(&mut stream).await {
+                    Ok(Some(client)) => addr = client,
+                    Ok(None) => (),
+                    Err(e) => {
+                        log::debug!("Rejected connection from {}: {:?}", addr, e);
+                        return;
+                    }
+                }
+            }
+
+            let connection = match throttle.open_connection(addr.ip()) {
+                Some(connection) => connection,
+                None => return,
+            };
+
+            // Legacy pings can be told apart by their first byte.
+            // Peeking leaves it in place for the normal codec.
+            let mut first_byte = [0];
+            match timeout(Duration::from_secs(10), stream.peek(&mut first_byte)).await {
+                Ok(Ok(1)) => (),
+                _ => return,
+            }
+
+            if first_byte[0] == legacy_ping::LEGACY_PING {
+                if let Err(e) = legacy_ping::respond(stream, &options, &player_count).await {
+                    log::debug!("Failed to answer legacy ping from {}: {:?}", addr, e);
+                }
+                return;
+            }
+
+            let worker = Worker::new(
+                stream,
+                addr,
+                options,
+                player_count,
+                access_lists,
+                throttle,
+                connection,
+                metrics,
+                new_players,
+            );
+            worker.start();
+        });
+    }
+}
+
+/// Answers query requests over UDP.
+pub struct QueryListener {
+    socket: UdpSocket,
+    responder: QueryResponder,
+}
+
+impl QueryListener {
+    pub async fn start(
+        options: Arc<Options>,
+        query_options: QueryOptions,
+        player_count: PlayerCount,
+        data: QueryData,
+    ) -> anyhow::Result<JoinHandle<()>> {
+        let socket = UdpSocket::bind(format!("{}:{}", options.bind_address, query_options.port))
+            .await
+            .context("failed to bind query port")?;
+        log::info!(
+            "Query is listening on {}:{}",
+            options.bind_address,
+            query_options.port
         );
-        worker.start();
+
+        let listener = QueryListener {
+            socket,
+            responder: QueryResponder::new(options, query_options, player_count, data),
+        };
+        let task = tokio::task::spawn(async move {
+            listener.run().await;
+        });
+
+        Ok(task)
+    }
+
+    async fn run(mut self) {
+        let mut buf = [0; 1460];
+        let mut last_clean_up = Instant::now();
+        loop {
+            let (len, addr) = match self.socket.recv_from(&mut buf).await {
+                Ok(received) => received,
+                Err(e) => {
+                    log::debug!("Failed to receive query request: {}", e);
+                    continue;
+                }
+            };
+
+            let now = Instant::now();
+            if let Some(response) = self.responder.handle(&buf[..len], addr, now) {
+                if let Err(e) = self.socket.send_to(&response, addr).await {
+                    log::debug!("Failed to answer query from {}: {}", addr, e);
+                }
+            }
+
+            if now.duration_since(last_clean_up) > Duration::from_secs(30) {
+                self.responder.clean_up(now);
+                last_clean_up = now;
+            }
+        }
     }
 }
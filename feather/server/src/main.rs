@@ -1,17 +1,27 @@
-use std::{cell::RefCell, rc::Rc, sync::Arc};
+use std::{cell::RefCell, path::Path, rc::Rc, sync::Arc};
 
 use anyhow::Context;
-use base::anvil::level::SuperflatGeneratorOptions;
-use common::{Game, TickLoop, World};
-use ecs::SystemExecutor;
-use feather_server::{config::Config, Server};
+use base::anvil::level::{self, LevelData, LevelGeneratorType, SuperflatGeneratorOptions};
+use common::{
+    permissions::Permissions, weather::WeatherLevels, Game, Metrics, TickLoop, Timings, World,
+};
+use ecs::{SysResult, SystemExecutor};
+use feather_server::{
+    config::Config,
+    console::ConsoleInput,
+    watchdog::{Watchdog, WatchdogOptions},
+    Server,
+};
 use plugin_host::PluginManager;
+use shutdown::ShutdownFlag;
 use worldgen::{ComposableGenerator, SuperflatWorldGenerator, WorldGenerator};
 
 mod logging;
+mod shutdown;
 
 const PLUGINS_DIRECTORY: &str = "plugins";
 const CONFIG_PATH: &str = "config.toml";
+const PERMISSIONS_PATH: &str = "permissions.toml";
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -29,18 +39,36 @@ async fn main() -> anyhow::Result<()> {
     let options = config.to_options();
     let server = Server::bind(options).await?;
 
-    let game = init_game(server, &config)?;
+    let shutdown = ShutdownFlag::default();
+    shutdown::listen_for_signals(shutdown.clone());
 
-    run(game);
+    let game = init_game(server, &config, &shutdown)?;
+
+    run(game, shutdown, config.watchdog_options());
 
     Ok(())
 }
 
-fn init_game(server: Server, config: &Config) -> anyhow::Result<Game> {
+fn init_game(server: Server, config: &Config, shutdown: &ShutdownFlag) -> anyhow::Result<Game> {
     let mut game = Game::new();
+    game.insert_resource(config.autosave_options());
+    game.insert_resource(server.metrics().clone());
     init_systems(&mut game, server);
-    init_world_source(&mut game, config);
+    game.insert_resource(
+        Permissions::load(PERMISSIONS_PATH).context("failed to load permissions")?,
+    );
+    log::info!("Loaded permissions");
+    game.resources
+        .get::<Server>()?
+        .query_data()
+        .set_map(&config.world.name);
+    init_world_source(&mut game, config)?;
     init_plugin_manager(&mut game)?;
+    game.resources
+        .get::<Metrics>()?
+        .set_system_names(game.system_executor.borrow().system_names());
+    let shutdown = shutdown.clone();
+    game.insert_resource(ConsoleInput::spawn(move || shutdown.request()));
     Ok(game)
 }
 
@@ -58,27 +86,78 @@ fn init_systems(game: &mut Game, server: Server) {
     game.system_executor = Rc::new(RefCell::new(systems));
 }
 
-fn init_world_source(game: &mut Game, config: &Config) {
+fn init_world_source(game: &mut Game, config: &Config) -> anyhow::Result<()> {
     // Load chunks from the world save first,
-    // and fall back to generating a superflat
-    // world otherwise. This is a placeholder:
-    // we don't have proper world generation yet.
-
-    let seed = 42; // FIXME: load from the level file
+    // and fall back to generating them with
+    // the generator from level.dat otherwise.
+    let world_dir = Path::new(&config.world.name);
+    let level = match level::load_level_data(world_dir).context("failed to load level.dat")? {
+        Some(level) => {
+            log::info!("Loaded level.dat");
+            level
+        }
+        None => {
+            let level = create_level(config);
+            level::save_level_data(world_dir, &level)?;
+            log::info!("Created level.dat with seed {}", level.seed);
+            level
+        }
+    };
 
-    let generator: Arc<dyn WorldGenerator> = match &config.world.generator[..] {
-        "flat" => Arc::new(SuperflatWorldGenerator::new(
-            SuperflatGeneratorOptions::default(),
+    let generator: Arc<dyn WorldGenerator> = match level.generator_type() {
+        LevelGeneratorType::Flat => Arc::new(SuperflatWorldGenerator::new(
+            level.generator_options.clone().unwrap_or_default(),
         )),
-        _ => Arc::new(ComposableGenerator::default_with_seed(seed)),
+        _ => Arc::new(ComposableGenerator::default_with_seed(level.seed as u64)),
+    };
+    game.world = World::with_gen_and_path(generator, world_dir);
+    game.insert_resource(level.game_rules.clone());
+    game.insert_resource(WeatherLevels::settled(&level));
+    game.insert_resource(level);
+    Ok(())
+}
+
+/// Creates the level.dat for a new world.
+fn create_level(config: &Config) -> LevelData {
+    let (generator_name, generator_options, spawn_y) = match &config.world.generator[..] {
+        "flat" => {
+            let options = SuperflatGeneratorOptions::default();
+            let height = options.layers.iter().map(|layer| layer.height as i32).sum();
+            ("flat", Some(options), height)
+        }
+        _ => ("default", None, 64),
     };
-    game.world = World::with_gen_and_path(generator, config.world.name.clone());
+
+    LevelData {
+        allow_commands: true,
+        border_size: 60_000_000.0,
+        border_safe_zone: 5.0,
+        game_type: config.server.default_gamemode as i32,
+        initialized: true,
+        seed: config.world.seed(),
+        spawn_y,
+        generator_name: generator_name.to_owned(),
+        generator_options,
+        ..Default::default()
+    }
 }
 
 fn init_plugin_manager(game: &mut Game) -> anyhow::Result<()> {
     let mut plugin_manager = PluginManager::new();
     plugin_manager.load_dir(game, PLUGINS_DIRECTORY)?;
 
+    {
+        let server = game.resources.get::<Server>()?;
+        server
+            .query_data()
+            .set_plugins(plugin_manager.plugins().map(|plugin| {
+                (
+                    plugin.metadata().name.as_str(),
+                    plugin.metadata().version.as_str(),
+                )
+            }));
+    }
+
     let plugin_manager_rc = Rc::new(RefCell::new(plugin_manager));
     game.insert_resource(plugin_manager_rc);
     Ok(())
@@ -89,18 +168,76 @@ fn print_systems(systems: &SystemExecutor<Game>) {
     log::debug!("---SYSTEMS---\n{:#?}\n", systems);
 }
 
-fn run(game: Game) {
-    let tick_loop = create_tick_loop(game);
+fn run(game: Game, shutdown: ShutdownFlag, watchdog: Option<WatchdogOptions>) {
+    let tick_loop = create_tick_loop(game, shutdown, watchdog);
     log::debug!("Launching the game loop");
     tick_loop.run();
 }
 
-fn create_tick_loop(mut game: Game) -> TickLoop {
+fn create_tick_loop(
+    mut game: Game,
+    shutdown: ShutdownFlag,
+    watchdog: Option<WatchdogOptions>,
+) -> TickLoop {
+    let metrics = game
+        .resources
+        .get::<Metrics>()
+        .map(|metrics| metrics.clone())
+        .unwrap_or_default();
+    let system_metrics = metrics.clone();
+    let watchdog = watchdog.map(|options| spawn_watchdog(&game, options, &shutdown));
     TickLoop::new(move || {
         let systems = Rc::clone(&game.system_executor);
         systems.borrow_mut().run(&mut game);
+        system_metrics.record_system_times(systems.borrow().timings().map(|(_, time)| time));
+        if let Ok(mut timings) = game.resources.get_mut::<Timings>() {
+            timings.record(systems.borrow().timings());
+        }
         game.tick_count += 1;
+        if let Some(watchdog) = &watchdog {
+            watchdog.tick();
+        }
+
+        if shutdown.is_requested() {
+            if let Some(watchdog) = &watchdog {
+                watchdog.stop();
+            }
+            if let Err(e) = shut_down(&mut game) {
+                log::error!("Failed to shut down cleanly: {:?}", e);
+            }
+            return true;
+        }
 
         false
     })
+    .with_metrics(metrics)
+}
+
+fn spawn_watchdog(game: &Game, options: WatchdogOptions, shutdown: &ShutdownFlag) -> Watchdog {
+    let systems = game.system_executor.borrow();
+    let shutdown = shutdown.clone();
+    Watchdog::spawn(
+        options,
+        systems.current_system(),
+        systems.system_names().map(str::to_owned).collect(),
+        move || shutdown.request(),
+    )
+}
+
+/// Disconnects all players, then saves players and chunks.
+fn shut_down(game: &mut Game) -> SysResult {
+    log::info!("Stopping the server");
+    game.resources
+        .get_mut::<Server>()?
+        .shutdown("Server closed");
+
+    // Run one more tick so that the disconnected
+    // players are saved and removed.
+    let systems = Rc::clone(&game.system_executor);
+    systems.borrow_mut().run(game);
+
+    common::autosave::save_level(game)?;
+    game.world.save_all_and_close()?;
+    log::info!("Saved the world");
+    Ok(())
 }
@@ -0,0 +1,131 @@
+//! A small HTTP endpoint serving metrics
+//! in the Prometheus text format.
+
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use common::Metrics;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+    time::timeout,
+};
+
+/// Maximum length of a request, including headers.
+const MAX_REQUEST_LENGTH: usize = 8192;
+
+/// Options for the metrics endpoint.
+#[derive(Debug, Clone)]
+pub struct MetricsOptions {
+    /// Address to bind to. Usually a local address,
+    /// since the metrics aren't authenticated.
+    pub address: String,
+    pub port: u16,
+}
+
+/// Starts serving `metrics` on `/metrics`.
+/// Returns the task running the endpoint.
+pub async fn start(options: &MetricsOptions, metrics: Metrics) -> anyhow::Result<JoinHandle<()>> {
+    let listener = TcpListener::bind(format!("{}:{}", options.address, options.port))
+        .await
+        .context("failed to bind metrics port")?;
+    log::info!(
+        "Metrics are served on http://{}:{}/metrics",
+        options.address,
+        options.port
+    );
+
+    let task = tokio::task::spawn(async move {
+        loop {
+            if let Ok((stream, addr)) = listener.accept().await {
+                let metrics = metrics.clone();
+                tokio::task::spawn(async move {
+                    if let Err(e) = handle_connection(stream, &metrics).await {
+                        log::debug!("Metrics request from {} failed: {:?}", addr, e);
+                    }
+                });
+            }
+        }
+    });
+
+    Ok(task)
+}
+
+async fn handle_connection(mut stream: TcpStream, metrics: &Metrics) -> anyhow::Result<()> {
+    let request = timeout(Duration::from_secs(5), read_request(&mut stream))
+        .await
+        .context("timed out")??;
+
+    let response = match parse_request_line(&request) {
+        Some(("GET", "/metrics")) => http_response(
+            "200 OK",
+            "text/plain; version=0.0.4; charset=utf-8",
+            &metrics.render(),
+        ),
+        Some(("GET", _)) => http_response("404 Not Found", "text/plain", "Not Found\n"),
+        _ => http_response(
+            "405 Method Not Allowed",
+            "text/plain",
+            "Method Not Allowed\n",
+        ),
+    };
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// Reads the request line and headers. The body
+/// is ignored, since only `GET` is supported.
+async fn read_request(stream: &mut TcpStream) -> anyhow::Result<String> {
+    let mut request = Vec::new();
+    let mut buf = [0; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        if request.len() >= MAX_REQUEST_LENGTH {
+            bail!("request is too long");
+        }
+        let read = stream.read(&mut buf).await?;
+        if read == 0 {
+            bail!("connection closed before the end of the request");
+        }
+        request.extend_from_slice(&buf[..read]);
+    }
+    Ok(String::from_utf8_lossy(&request).into_owned())
+}
+
+/// Returns the method and path of a request, ignoring any query string.
+fn parse_request_line(request: &str) -> Option<(&str, &str)> {
+    let mut parts = request.lines().next()?.split(' ');
+    let method = parts.next()?;
+    let target = parts.next()?;
+    let path = target.split('?').next()?;
+    Some((method, path))
+}
+
+fn http_response(status: &str, content_type: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_line() {
+        assert_eq!(
+            parse_request_line("GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n"),
+            Some(("GET", "/metrics"))
+        );
+        assert_eq!(
+            parse_request_line("GET /metrics?name=x HTTP/1.1\r\n\r\n"),
+            Some(("GET", "/metrics"))
+        );
+        assert_eq!(parse_request_line(""), None);
+    }
+}
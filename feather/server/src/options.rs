@@ -1,6 +1,9 @@
 use base::Gamemode;
 
-use crate::favicon::Favicon;
+use crate::{
+    favicon::Favicon, metrics::MetricsOptions, packet_limiter::PacketLimitOptions,
+    query::QueryOptions, rcon::RconOptions, throttle::ThrottleOptions,
+};
 
 /// Options for building a [`Server`](crate::Server).
 #[derive(Debug, Clone)]
@@ -25,6 +28,9 @@ pub struct Options {
     /// Maximum number of players to allow on the server.
     pub max_players: u32,
 
+    /// Whether only whitelisted players and operators may join.
+    pub whitelist: bool,
+
     /// The default gamemode for new players.
     pub default_gamemode: Gamemode,
 
@@ -33,8 +39,64 @@ pub struct Options {
     // HMAC key used with Velocity IP forwarding.
     pub velocity_secret: String,
 
+    /// Whether connections must start with a PROXY protocol
+    /// header carrying the real client address.
+    pub proxy_protocol: bool,
+
+    /// Limits on connections and logins per address.
+    pub throttle: ThrottleOptions,
+
+    /// Number of bytes that may be queued for a client before
+    /// its connection counts as congested.
+    pub outbound_buffer_limit: usize,
+
+    /// Limits on how often clients may send packets.
+    pub packet_limits: PacketLimitOptions,
+
+    /// Maximum size of a received packet after decompression.
+    pub max_packet_size: usize,
+
     /// Packet size threshold at which to compress data
     pub compression_threshold: Option<usize>,
+
+    /// Options for the RCON listener, or `None` if RCON is disabled.
+    pub rcon: Option<RconOptions>,
+
+    /// Options for the query listener, or `None` if query is disabled.
+    pub query: Option<QueryOptions>,
+
+    /// Options for the metrics endpoint, or `None` if it is disabled.
+    pub metrics: Option<MetricsOptions>,
+}
+
+/// Options for tests, which only spell out what they rely on.
+/// The server itself takes its defaults from the config file.
+#[cfg(test)]
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            port: 25565,
+            bind_address: "0.0.0.0".to_owned(),
+            favicon: None,
+            motd: "A Feather server".to_owned(),
+            online_mode: false,
+            view_distance: 8,
+            max_players: 16,
+            whitelist: false,
+            default_gamemode: Gamemode::Survival,
+            proxy_mode: None,
+            velocity_secret: String::new(),
+            proxy_protocol: false,
+            throttle: Default::default(),
+            outbound_buffer_limit: 4 * 1024 * 1024,
+            packet_limits: Default::default(),
+            max_packet_size: protocol::codec::DEFAULT_MAX_PACKET_SIZE,
+            compression_threshold: None,
+            rcon: None,
+            query: None,
+            metrics: None,
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -1,5 +1,9 @@
 use base::{Position, Text};
-use common::{chat::ChatKind, Game};
+use common::{
+    chat::ChatKind,
+    commands::{self, CommandDispatcher, PermissionLevel},
+    respawn, Game,
+};
 use ecs::{Entity, EntityRef, SysResult};
 use interaction::{
     handle_held_item_change, handle_interact_entity, handle_player_block_placement,
@@ -12,9 +16,9 @@ use protocol::{
     },
     ClientPlayPacket,
 };
-use quill_common::components::Name;
+use quill_common::components::{Name, Ping};
 
-use crate::{NetworkId, Server};
+use crate::{ClientId, NetworkId, Server};
 
 mod entity_action;
 mod interaction;
@@ -45,7 +49,8 @@ pub fn handle_packet(
 
         ClientPlayPacket::Animation(packet) => handle_animation(server, player, packet),
 
-        ClientPlayPacket::ChatMessage(packet) => handle_chat_message(game, player, packet),
+        ClientPlayPacket::ChatMessage(packet) => handle_chat_message(game, player_id, packet),
+        ClientPlayPacket::TabComplete(packet) => handle_tab_complete(game, server, player, packet),
 
         ClientPlayPacket::PlayerDigging(packet) => {
             handle_player_digging(game, server, packet, player_id)
@@ -69,6 +74,8 @@ pub fn handle_packet(
 
         ClientPlayPacket::ClientSettings(packet) => handle_client_settings(server, player, packet),
 
+        ClientPlayPacket::KeepAlive(packet) => handle_keepalive(server, player, packet),
+
         ClientPlayPacket::PlayerAbilities(packet) => {
             movement::handle_player_abilities(game, player_id, packet)
         }
@@ -77,11 +84,11 @@ pub fn handle_packet(
             entity_action::handle_entity_action(game, player_id, packet)
         }
 
+        ClientPlayPacket::ClientStatus(packet) => handle_client_status(game, player_id, packet),
+
         ClientPlayPacket::TeleportConfirm(_)
         | ClientPlayPacket::QueryBlockNbt(_)
         | ClientPlayPacket::SetDifficulty(_)
-        | ClientPlayPacket::ClientStatus(_)
-        | ClientPlayPacket::TabComplete(_)
         | ClientPlayPacket::WindowConfirmation(_)
         | ClientPlayPacket::ClickWindowButton(_)
         | ClientPlayPacket::CloseWindow(_)
@@ -89,7 +96,6 @@ pub fn handle_packet(
         | ClientPlayPacket::EditBook(_)
         | ClientPlayPacket::QueryEntityNbt(_)
         | ClientPlayPacket::GenerateStructure(_)
-        | ClientPlayPacket::KeepAlive(_)
         | ClientPlayPacket::LockDifficulty(_)
         | ClientPlayPacket::VehicleMove(_)
         | ClientPlayPacket::SteerBoat(_)
@@ -132,13 +138,52 @@ fn handle_animation(
     Ok(())
 }
 
-fn handle_chat_message(game: &Game, player: EntityRef, packet: client::ChatMessage) -> SysResult {
-    let name = player.get::<Name>()?;
-    let message = Text::translate_with("chat.type.text", vec![name.to_string(), packet.message]);
+fn handle_client_status(
+    game: &mut Game,
+    player: Entity,
+    packet: client::ClientStatus,
+) -> SysResult {
+    match packet {
+        client::ClientStatus::PerformRespawn => respawn::respawn(game, player),
+        // Statistics aren't tracked
+        client::ClientStatus::RequestStats => Ok(()),
+    }
+}
+
+fn handle_chat_message(game: &mut Game, player: Entity, packet: client::ChatMessage) -> SysResult {
+    if let Some(command) = packet.message.strip_prefix('/') {
+        return commands::execute(game, player, command);
+    }
+
+    let name = game.ecs.get::<Name>(player)?.to_string();
+    let message = Text::translate_with("chat.type.text", vec![name, packet.message]);
     game.broadcast_chat(ChatKind::PlayerChat, message);
     Ok(())
 }
 
+fn handle_tab_complete(
+    game: &Game,
+    server: &mut Server,
+    player: EntityRef,
+    packet: client::TabComplete,
+) -> SysResult {
+    let command = packet.text.strip_prefix('/').unwrap_or(&packet.text);
+    let offset = packet.text.len() - command.len();
+    let permission_level = player.get::<PermissionLevel>().map_or(0, |level| level.0);
+    let mut suggestions =
+        game.resources
+            .get::<CommandDispatcher>()?
+            .suggest(game, command, permission_level);
+    // Make the offsets relative to the client's text
+    suggestions.start += offset;
+
+    let client_id = *player.get::<ClientId>()?;
+    if let Some(client) = server.clients.get(client_id) {
+        client.send_tab_complete(packet.transaction_id, &packet.text, suggestions);
+    }
+    Ok(())
+}
+
 fn handle_client_settings(
     server: &mut Server,
     player: EntityRef,
@@ -150,3 +195,17 @@ fn handle_client_settings(
     });
     Ok(())
 }
+
+fn handle_keepalive(
+    server: &mut Server,
+    player: EntityRef,
+    packet: client::KeepAlive,
+) -> SysResult {
+    let client_id = *player.get::<ClientId>()?;
+    if let Some(client) = server.clients.get(client_id) {
+        if let Some(ping) = client.handle_keepalive(packet.id) {
+            player.get_mut::<Ping>()?.0 = ping;
+        }
+    }
+    Ok(())
+}
@@ -2,6 +2,7 @@ use crate::{ClientId, NetworkId, Server};
 use base::inventory::{SLOT_HOTBAR_OFFSET, SLOT_OFFHAND};
 use common::entities::player::HotbarSlot;
 use common::interactable::InteractableRegistry;
+use common::{digging, dropped_items};
 use common::{Game, Window};
 use ecs::{Entity, EntityRef, SysResult};
 use libcraft_core::{BlockFace as LibcraftBlockFace, Hand};
@@ -14,7 +15,9 @@ use quill_common::{
     events::{BlockInteractEvent, BlockPlacementEvent, InteractEntityEvent},
     EntityId,
 };
-/// Handles the player block placement packet. Currently just removes the block client side for the player.
+/// Handles the player block placement packet by triggering a
+/// `BlockInteractEvent` or `BlockPlacementEvent`. The block
+/// itself is placed in `common::block_placement`.
 pub fn handle_player_block_placement(
     game: &mut Game,
     _server: &mut Server,
@@ -119,10 +122,13 @@ pub fn handle_player_digging(
 ) -> SysResult {
     log::trace!("Got player digging with status {:?}", packet.status);
     match packet.status {
-        PlayerDiggingStatus::StartDigging | PlayerDiggingStatus::CancelDigging => {
-            game.break_block(packet.position);
-            Ok(())
+        PlayerDiggingStatus::StartDigging => digging::start_digging(game, player, packet.position),
+        PlayerDiggingStatus::CancelDigging => digging::stop_digging(game, player),
+        PlayerDiggingStatus::FinishDigging => {
+            digging::finish_digging(game, player, packet.position)
         }
+        PlayerDiggingStatus::DropItem => dropped_items::drop_held_item(game, player, false),
+        PlayerDiggingStatus::DropItemStack => dropped_items::drop_held_item(game, player, true),
         PlayerDiggingStatus::SwapItemInHand => {
             let window = game.ecs.get::<Window>(player)?;
 
@@ -180,14 +186,14 @@ pub fn handle_interact_entity(
 
     let event = match packet.kind {
         InteractEntityKind::Attack => InteractEntityEvent {
-            target: EntityId(target.id() as u64),
+            target: EntityId(target.to_bits()),
             ty: InteractionType::Attack,
             target_pos: None,
             hand: None,
             sneaking: packet.sneaking,
         },
         InteractEntityKind::Interact => InteractEntityEvent {
-            target: EntityId(target.id() as u64),
+            target: EntityId(target.to_bits()),
             ty: InteractionType::Interact,
             target_pos: None,
             hand: None,
@@ -206,8 +212,8 @@ pub fn handle_interact_entity(
             };
 
             InteractEntityEvent {
-                target: EntityId(target.id() as u64),
-                ty: InteractionType::Attack,
+                target: EntityId(target.to_bits()),
+                ty: InteractionType::InteractAt,
                 target_pos: Some(Vec3f::new(
                     target_x as f32,
                     target_y as f32,
@@ -0,0 +1,302 @@
+//! Limits how often clients may send certain packets, so
+//! floods of packets can't stall the game loop.
+
+use std::time::{Duration, Instant};
+
+use protocol::ClientPlayPacket;
+
+/// Period over which dropped packets count towards
+/// [`PacketLimitOptions::kick_threshold`].
+const KICK_PERIOD: Duration = Duration::from_secs(10);
+
+/// Options for packet rate limiting, in packets per second.
+/// Clients may send bursts of twice the rate. `None` disables
+/// the respective limit.
+#[derive(Debug, Clone, Default)]
+pub struct PacketLimitOptions {
+    /// Limit on packets of any kind.
+    pub total: Option<u32>,
+    pub chat_message: Option<u32>,
+    pub tab_complete: Option<u32>,
+    pub click_window: Option<u32>,
+    pub creative_inventory_action: Option<u32>,
+    pub player_digging: Option<u32>,
+    pub player_block_placement: Option<u32>,
+    /// Number of packets over the limits within ten
+    /// seconds after which the client is kicked.
+    pub kick_threshold: Option<u32>,
+}
+
+/// What to do with a received packet.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Accept,
+    Drop,
+    Kick,
+}
+
+/// Enforces [`PacketLimitOptions`] for a single connection.
+pub struct PacketLimiter {
+    total: Option<TokenBucket>,
+    chat_message: Option<TokenBucket>,
+    tab_complete: Option<TokenBucket>,
+    click_window: Option<TokenBucket>,
+    creative_inventory_action: Option<TokenBucket>,
+    player_digging: Option<TokenBucket>,
+    player_block_placement: Option<TokenBucket>,
+    /// Takes a token for each dropped packet.
+    /// The client is kicked once it's empty.
+    violations: Option<TokenBucket>,
+}
+
+impl PacketLimiter {
+    pub fn new(options: &PacketLimitOptions, now: Instant) -> Self {
+        let bucket = |limit: Option<u32>| {
+            limit.map(|per_second| {
+                TokenBucket::new(f64::from(per_second) * 2.0, f64::from(per_second), now)
+            })
+        };
+        Self {
+            total: bucket(options.total),
+            chat_message: bucket(options.chat_message),
+            tab_complete: bucket(options.tab_complete),
+            click_window: bucket(options.click_window),
+            creative_inventory_action: bucket(options.creative_inventory_action),
+            player_digging: bucket(options.player_digging),
+            player_block_placement: bucket(options.player_block_placement),
+            violations: options.kick_threshold.map(|threshold| {
+                let threshold = f64::from(threshold);
+                TokenBucket::new(threshold, threshold / KICK_PERIOD.as_secs_f64(), now)
+            }),
+        }
+    }
+
+    /// Counts a received packet and decides what to do with it.
+    ///
+    /// Keep alives and teleport confirmations are never limited,
+    /// since dropping them would time out or desync the client.
+    /// Tokens are only taken once every applicable limit allows
+    /// the packet, so dropped packets don't use up any limit.
+    pub fn check(&mut self, packet: &ClientPlayPacket, now: Instant) -> Verdict {
+        if matches!(
+            packet,
+            ClientPlayPacket::KeepAlive(_) | ClientPlayPacket::TeleportConfirm(_)
+        ) {
+            return Verdict::Accept;
+        }
+
+        let within_total = has_token(self.total.as_mut(), now);
+        let within_kind = has_token(self.bucket_mut(packet), now);
+        if within_total && within_kind {
+            take(self.total.as_mut(), now);
+            take(self.bucket_mut(packet), now);
+            return Verdict::Accept;
+        }
+
+        if take(self.violations.as_mut(), now) {
+            Verdict::Drop
+        } else {
+            Verdict::Kick
+        }
+    }
+
+    fn bucket_mut(&mut self, packet: &ClientPlayPacket) -> Option<&mut TokenBucket> {
+        match packet {
+            ClientPlayPacket::ChatMessage(_) => self.chat_message.as_mut(),
+            ClientPlayPacket::TabComplete(_) => self.tab_complete.as_mut(),
+            ClientPlayPacket::ClickWindow(_) => self.click_window.as_mut(),
+            ClientPlayPacket::CreativeInventoryAction(_) => self.creative_inventory_action.as_mut(),
+            ClientPlayPacket::PlayerDigging(_) => self.player_digging.as_mut(),
+            ClientPlayPacket::PlayerBlockPlacement(_) => self.player_block_placement.as_mut(),
+            _ => None,
+        }
+    }
+}
+
+/// Returns whether the bucket has a token left without taking
+/// it. A missing bucket means no limit.
+fn has_token(bucket: Option<&mut TokenBucket>, now: Instant) -> bool {
+    match bucket {
+        Some(bucket) => {
+            bucket.refill(now);
+            bucket.tokens >= 1.0
+        }
+        None => true,
+    }
+}
+
+/// Takes a token from the bucket, returning `false` if there
+/// was none. A missing bucket means no limit.
+fn take(bucket: Option<&mut TokenBucket>, now: Instant) -> bool {
+    match bucket {
+        Some(bucket) => bucket.try_take(now),
+        None => true,
+    }
+}
+
+struct TokenBucket {
+    capacity: f64,
+    per_second: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(capacity: f64, per_second: f64, now: Instant) -> Self {
+        Self {
+            capacity,
+            per_second,
+            tokens: capacity,
+            last_refill: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * self.per_second).min(self.capacity);
+        self.last_refill = now;
+    }
+
+    fn try_take(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use protocol::{
+        packets::client::{ChatMessage, KeepAlive, TabComplete, TeleportConfirm},
+        VarInt,
+    };
+
+    use super::*;
+
+    fn chat() -> ClientPlayPacket {
+        ClientPlayPacket::ChatMessage(ChatMessage {
+            message: "spam".to_owned(),
+        })
+    }
+
+    #[test]
+    fn drops_then_kicks() {
+        let now = Instant::now();
+        let mut limiter = PacketLimiter::new(
+            &PacketLimitOptions {
+                chat_message: Some(2),
+                kick_threshold: Some(3),
+                ..Default::default()
+            },
+            now,
+        );
+
+        for _ in 0..4 {
+            assert_eq!(limiter.check(&chat(), now), Verdict::Accept);
+        }
+        // Other packets aren't limited.
+        assert_eq!(
+            limiter.check(&ClientPlayPacket::KeepAlive(KeepAlive { id: 0 }), now),
+            Verdict::Accept
+        );
+        for _ in 0..3 {
+            assert_eq!(limiter.check(&chat(), now), Verdict::Drop);
+        }
+        assert_eq!(limiter.check(&chat(), now), Verdict::Kick);
+    }
+
+    #[test]
+    fn refills() {
+        let now = Instant::now();
+        let mut limiter = PacketLimiter::new(
+            &PacketLimitOptions {
+                total: Some(1),
+                ..Default::default()
+            },
+            now,
+        );
+        assert_eq!(limiter.check(&chat(), now), Verdict::Accept);
+        assert_eq!(limiter.check(&chat(), now), Verdict::Accept);
+        assert_eq!(limiter.check(&chat(), now), Verdict::Drop);
+        assert_eq!(
+            limiter.check(&chat(), now + Duration::from_secs(1)),
+            Verdict::Accept
+        );
+    }
+
+    #[test]
+    fn keepalives_and_teleport_confirms_are_exempt() {
+        let now = Instant::now();
+        let mut limiter = PacketLimiter::new(
+            &PacketLimitOptions {
+                total: Some(1),
+                kick_threshold: Some(1),
+                ..Default::default()
+            },
+            now,
+        );
+        for _ in 0..2 {
+            assert_eq!(limiter.check(&chat(), now), Verdict::Accept);
+        }
+        for id in 0..10 {
+            assert_eq!(
+                limiter.check(&ClientPlayPacket::KeepAlive(KeepAlive { id }), now),
+                Verdict::Accept
+            );
+            assert_eq!(
+                limiter.check(
+                    &ClientPlayPacket::TeleportConfirm(TeleportConfirm {
+                        teleport_id: VarInt(id as i32)
+                    }),
+                    now
+                ),
+                Verdict::Accept
+            );
+        }
+    }
+
+    #[test]
+    fn rejected_packets_take_no_tokens() {
+        let now = Instant::now();
+        let mut limiter = PacketLimiter::new(
+            &PacketLimitOptions {
+                total: Some(2),
+                chat_message: Some(1),
+                ..Default::default()
+            },
+            now,
+        );
+        let tab_complete = || {
+            ClientPlayPacket::TabComplete(TabComplete {
+                transaction_id: VarInt(0),
+                text: "/".to_owned(),
+            })
+        };
+
+        // Chat messages over their own limit must not
+        // use up the total limit...
+        for _ in 0..2 {
+            assert_eq!(limiter.check(&chat(), now), Verdict::Accept);
+        }
+        for _ in 0..5 {
+            assert_eq!(limiter.check(&chat(), now), Verdict::Drop);
+        }
+        for _ in 0..2 {
+            assert_eq!(limiter.check(&tab_complete(), now), Verdict::Accept);
+        }
+
+        // ...and packets over the total limit must not
+        // use up their own limit.
+        let later = now + Duration::from_secs(1);
+        for _ in 0..2 {
+            assert_eq!(limiter.check(&tab_complete(), later), Verdict::Accept);
+        }
+        assert_eq!(limiter.check(&chat(), later), Verdict::Drop);
+        let even_later = later + Duration::from_millis(500);
+        assert_eq!(limiter.check(&chat(), even_later), Verdict::Accept);
+    }
+}
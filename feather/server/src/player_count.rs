@@ -43,6 +43,11 @@ impl PlayerCount {
         }
     }
 
+    /// Adds a player even if the server is full.
+    pub fn add_player(&self) {
+        self.inner.count.fetch_add(1, Ordering::SeqCst);
+    }
+
     pub fn remove_player(&self) {
         self.inner.count.fetch_sub(1, Ordering::SeqCst);
     }
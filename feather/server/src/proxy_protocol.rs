@@ -0,0 +1,148 @@
+//! Parses the PROXY protocol headers sent by TCP load balancers
+//! like HAProxy, which carry the address of the real client.
+//!
+//! See <https://www.haproxy.org/download/2.4/doc/proxy-protocol.txt>.
+
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+
+use anyhow::{bail, Context};
+use tokio::{io::AsyncReadExt, net::TcpStream, time::timeout};
+
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+const V1_PREFIX: &[u8] = b"PROXY ";
+/// Maximum length of a v1 header, including the CRLF.
+const V1_MAX_LENGTH: usize = 107;
+
+const V2_COMMAND_LOCAL: u8 = 0x20;
+const V2_COMMAND_PROXY: u8 = 0x21;
+const V2_FAMILY_TCP4: u8 = 0x11;
+const V2_FAMILY_TCP6: u8 = 0x21;
+
+/// Reads the PROXY protocol header at the start of a connection,
+/// leaving the rest of the stream untouched.
+///
+/// Returns the address of the client, or `None` if the proxy
+/// didn't provide one, e.g. for its own health checks.
+pub async fn read_header(stream: &mut TcpStream) -> anyhow::Result<Option<SocketAddr>> {
+    timeout(Duration::from_secs(5), read_header_inner(stream))
+        .await
+        .context("timed out")?
+}
+
+async fn read_header_inner(stream: &mut TcpStream) -> anyhow::Result<Option<SocketAddr>> {
+    // Both versions' headers are at least 12 bytes long.
+    let mut start = [0; 12];
+    stream.read_exact(&mut start).await?;
+
+    if start == V2_SIGNATURE {
+        let mut header = [0; 4];
+        stream.read_exact(&mut header).await?;
+        let length = u16::from_be_bytes([header[2], header[3]]) as usize;
+        let mut addresses = vec![0; length];
+        stream.read_exact(&mut addresses).await?;
+        parse_v2(header[0], header[1], &addresses)
+    } else if start.starts_with(V1_PREFIX) {
+        // Read byte by byte so we don't consume
+        // any of the Minecraft handshake.
+        let mut line = start.to_vec();
+        while !line.ends_with(b"\r\n") {
+            if line.len() >= V1_MAX_LENGTH {
+                bail!("PROXY v1 header is too long");
+            }
+            line.push(stream.read_u8().await?);
+        }
+        parse_v1(&line)
+    } else {
+        bail!("missing PROXY protocol header")
+    }
+}
+
+/// Parses a v1 header like `PROXY TCP4 1.2.3.4 5.6.7.8 1234 25565\r\n`.
+fn parse_v1(line: &[u8]) -> anyhow::Result<Option<SocketAddr>> {
+    let line = std::str::from_utf8(line)?
+        .strip_suffix("\r\n")
+        .context("missing CRLF")?;
+    let parts: Vec<&str> = line.split(' ').collect();
+    match parts.as_slice() {
+        ["PROXY", "UNKNOWN", ..] => Ok(None),
+        ["PROXY", family @ ("TCP4" | "TCP6"), source, _destination, source_port, _destination_port] =>
+        {
+            let ip: IpAddr = source.parse()?;
+            if ip.is_ipv4() != (*family == "TCP4") {
+                bail!("address {} does not match family {}", ip, family);
+            }
+            Ok(Some(SocketAddr::new(ip, source_port.parse()?)))
+        }
+        _ => bail!("malformed PROXY v1 header"),
+    }
+}
+
+/// Parses the rest of a v2 header after the signature.
+fn parse_v2(
+    version_command: u8,
+    family: u8,
+    addresses: &[u8],
+) -> anyhow::Result<Option<SocketAddr>> {
+    match version_command {
+        V2_COMMAND_LOCAL => return Ok(None),
+        V2_COMMAND_PROXY => (),
+        _ => bail!(
+            "unsupported PROXY v2 version or command {:#x}",
+            version_command
+        ),
+    }
+
+    match family {
+        V2_FAMILY_TCP4 if addresses.len() >= 12 => {
+            let mut ip = [0; 4];
+            ip.copy_from_slice(&addresses[..4]);
+            let port = u16::from_be_bytes([addresses[8], addresses[9]]);
+            Ok(Some(SocketAddr::new(Ipv4Addr::from(ip).into(), port)))
+        }
+        V2_FAMILY_TCP6 if addresses.len() >= 36 => {
+            let mut ip = [0; 16];
+            ip.copy_from_slice(&addresses[..16]);
+            let port = u16::from_be_bytes([addresses[32], addresses[33]]);
+            Ok(Some(SocketAddr::new(Ipv6Addr::from(ip).into(), port)))
+        }
+        // Unix sockets and unknown protocols carry no usable address.
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn v1() {
+        assert_eq!(
+            parse_v1(b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 25565\r\n").unwrap(),
+            Some("192.168.0.1:56324".parse().unwrap())
+        );
+        assert_eq!(
+            parse_v1(b"PROXY TCP6 ::1 ::1 56324 25565\r\n").unwrap(),
+            Some("[::1]:56324".parse().unwrap())
+        );
+        assert_eq!(parse_v1(b"PROXY UNKNOWN\r\n").unwrap(), None);
+        assert!(parse_v1(b"PROXY TCP4 ::1 ::1 56324 25565\r\n").is_err());
+        assert!(parse_v1(b"PROXY TCP4 192.168.0.1\r\n").is_err());
+    }
+
+    #[test]
+    fn v2() {
+        let addresses = [127, 0, 0, 1, 10, 0, 0, 1, 0x1F, 0x90, 0x63, 0xDD];
+        assert_eq!(
+            parse_v2(V2_COMMAND_PROXY, V2_FAMILY_TCP4, &addresses).unwrap(),
+            Some("127.0.0.1:8080".parse().unwrap())
+        );
+        assert_eq!(
+            parse_v2(V2_COMMAND_LOCAL, V2_FAMILY_TCP4, &addresses).unwrap(),
+            None
+        );
+        assert!(parse_v2(0x11, V2_FAMILY_TCP4, &addresses).is_err());
+    }
+}
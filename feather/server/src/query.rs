@@ -0,0 +1,307 @@
+//! The GameSpy4 query protocol used by server lists
+//! and monitoring tools.
+//!
+//! See <https://wiki.vg/Query> for the protocol.
+
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use parking_lot::RwLock;
+
+use crate::{
+    initial_handler::{MINECRAFT_VERSION, SERVER_NAME},
+    options::Options,
+    player_count::PlayerCount,
+};
+
+const MAGIC: [u8; 2] = [0xFE, 0xFD];
+const TYPE_HANDSHAKE: u8 = 9;
+const TYPE_STAT: u8 = 0;
+
+/// How long a challenge token stays valid.
+const CHALLENGE_LIFETIME: Duration = Duration::from_secs(30);
+
+/// Options for the query listener.
+#[derive(Debug, Clone)]
+pub struct QueryOptions {
+    pub port: u16,
+    /// Maximum number of requests answered per second
+    /// for each source address.
+    pub requests_per_second: u32,
+}
+
+/// Information reported by query which only the game
+/// thread knows. Updated by a system.
+///
+/// Can be cloned to create a new handle.
+#[derive(Clone, Default)]
+pub struct QueryData {
+    inner: Arc<RwLock<QueryDataInner>>,
+}
+
+#[derive(Default)]
+struct QueryDataInner {
+    players: Vec<String>,
+    plugins: String,
+    map: String,
+}
+
+impl QueryData {
+    pub fn set_players(&self, players: Vec<String>) {
+        self.inner.write().players = players;
+    }
+
+    /// Sets the list of plugins as `(name, version)` pairs.
+    pub fn set_plugins<'a>(&self, plugins: impl IntoIterator<Item = (&'a str, &'a str)>) {
+        let plugins: Vec<String> = plugins
+            .into_iter()
+            .map(|(name, version)| format!("{} {}", name, version))
+            .collect();
+        let mut description = SERVER_NAME.to_owned();
+        if !plugins.is_empty() {
+            description.push_str(": ");
+            description.push_str(&plugins.join("; "));
+        }
+        self.inner.write().plugins = description;
+    }
+
+    pub fn set_map(&self, map: &str) {
+        self.inner.write().map = map.to_owned();
+    }
+}
+
+/// Answers query requests. Owned by the query listener task.
+pub struct QueryResponder {
+    options: Arc<Options>,
+    query_options: QueryOptions,
+    player_count: PlayerCount,
+    data: QueryData,
+
+    challenges: HashMap<SocketAddr, (i32, Instant)>,
+    /// Start of the current one-second window and
+    /// the number of requests in it, per address.
+    requests: HashMap<IpAddr, (Instant, u32)>,
+}
+
+impl QueryResponder {
+    pub fn new(
+        options: Arc<Options>,
+        query_options: QueryOptions,
+        player_count: PlayerCount,
+        data: QueryData,
+    ) -> Self {
+        Self {
+            options,
+            query_options,
+            player_count,
+            data,
+            challenges: HashMap::new(),
+            requests: HashMap::new(),
+        }
+    }
+
+    /// Handles a request, returning the response
+    /// to send back, if any.
+    pub fn handle(&mut self, request: &[u8], addr: SocketAddr, now: Instant) -> Option<Vec<u8>> {
+        if request.len() < 7 || request[..2] != MAGIC {
+            return None;
+        }
+        if !self.allow_request(addr.ip(), now) {
+            log::trace!("Rate limited query from {}", addr);
+            return None;
+        }
+
+        let kind = request[2];
+        let session_id = i32::from_be_bytes([request[3], request[4], request[5], request[6]]);
+        let payload = &request[7..];
+        match kind {
+            TYPE_HANDSHAKE => Some(self.handshake(session_id, addr, now)),
+            TYPE_STAT if payload.len() >= 4 => {
+                let token = i32::from_be_bytes([payload[0], payload[1], payload[2], payload[3]]);
+                if !self.is_valid_challenge(addr, token, now) {
+                    return None;
+                }
+                // Full stat requests pad the token with four bytes.
+                if payload.len() >= 8 {
+                    Some(self.full_stat(session_id))
+                } else {
+                    Some(self.basic_stat(session_id))
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Forgets expired challenges and rate limit windows.
+    pub fn clean_up(&mut self, now: Instant) {
+        self.challenges
+            .retain(|_, (_, created)| now.duration_since(*created) < CHALLENGE_LIFETIME);
+        self.requests
+            .retain(|_, (window, _)| now.duration_since(*window) < Duration::from_secs(1));
+    }
+
+    fn allow_request(&mut self, ip: IpAddr, now: Instant) -> bool {
+        let (window, count) = self.requests.entry(ip).or_insert((now, 0));
+        if now.duration_since(*window) >= Duration::from_secs(1) {
+            *window = now;
+            *count = 0;
+        }
+        *count += 1;
+        *count <= self.query_options.requests_per_second
+    }
+
+    fn is_valid_challenge(&self, addr: SocketAddr, token: i32, now: Instant) -> bool {
+        matches!(
+            self.challenges.get(&addr),
+            Some(&(expected, created))
+                if expected == token && now.duration_since(created) < CHALLENGE_LIFETIME
+        )
+    }
+
+    fn handshake(&mut self, session_id: i32, addr: SocketAddr, now: Instant) -> Vec<u8> {
+        let token = rand::random::<i32>() & 0x7FFF_FFFF;
+        self.challenges.insert(addr, (token, now));
+
+        let mut response = header(TYPE_HANDSHAKE, session_id);
+        write_string(&mut response, &token.to_string());
+        response
+    }
+
+    fn basic_stat(&self, session_id: i32) -> Vec<u8> {
+        let data = self.data.inner.read();
+        let mut response = header(TYPE_STAT, session_id);
+        write_string(&mut response, &self.options.motd);
+        write_string(&mut response, "SMP");
+        write_string(&mut response, &data.map);
+        write_string(&mut response, &self.player_count.get().to_string());
+        write_string(&mut response, &self.options.max_players.to_string());
+        response.extend_from_slice(&self.options.port.to_le_bytes());
+        write_string(&mut response, &self.options.bind_address);
+        response
+    }
+
+    fn full_stat(&self, session_id: i32) -> Vec<u8> {
+        let data = self.data.inner.read();
+        let mut response = header(TYPE_STAT, session_id);
+        response.extend_from_slice(b"splitnum\0\x80\0");
+
+        let values = [
+            ("hostname", self.options.motd.clone()),
+            ("gametype", "SMP".to_owned()),
+            ("game_id", "MINECRAFT".to_owned()),
+            ("version", MINECRAFT_VERSION.to_owned()),
+            ("plugins", data.plugins.clone()),
+            ("map", data.map.clone()),
+            ("numplayers", self.player_count.get().to_string()),
+            ("maxplayers", self.options.max_players.to_string()),
+            ("hostport", self.options.port.to_string()),
+            ("hostip", self.options.bind_address.clone()),
+        ];
+        for (key, value) in &values {
+            write_string(&mut response, key);
+            write_string(&mut response, value);
+        }
+        response.push(0);
+
+        response.extend_from_slice(b"\x01player_\0\0");
+        for player in &data.players {
+            write_string(&mut response, player);
+        }
+        response.push(0);
+        response
+    }
+}
+
+fn header(kind: u8, session_id: i32) -> Vec<u8> {
+    let mut buf = vec![kind];
+    buf.extend_from_slice(&session_id.to_be_bytes());
+    buf
+}
+
+/// Writes a null-terminated string.
+fn write_string(buf: &mut Vec<u8>, s: &str) {
+    buf.extend_from_slice(s.as_bytes());
+    buf.push(0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn responder(requests_per_second: u32) -> QueryResponder {
+        let options = Options {
+            motd: "A Feather server".to_owned(),
+            max_players: 16,
+            ..Default::default()
+        };
+        let query_options = QueryOptions {
+            port: 25565,
+            requests_per_second,
+        };
+        let data = QueryData::default();
+        data.set_players(vec!["caelunshun".to_owned()]);
+        data.set_map("world");
+        QueryResponder::new(Arc::new(options), query_options, PlayerCount::new(16), data)
+    }
+
+    fn request(kind: u8, payload: &[u8]) -> Vec<u8> {
+        let mut request = vec![0xFE, 0xFD, kind, 0, 0, 0, 1];
+        request.extend_from_slice(payload);
+        request
+    }
+
+    fn challenge(responder: &mut QueryResponder, addr: SocketAddr, now: Instant) -> [u8; 4] {
+        let response = responder
+            .handle(&request(TYPE_HANDSHAKE, &[]), addr, now)
+            .unwrap();
+        assert_eq!(&response[..5], &[TYPE_HANDSHAKE, 0, 0, 0, 1]);
+        let token = std::str::from_utf8(&response[5..response.len() - 1]).unwrap();
+        token.parse::<i32>().unwrap().to_be_bytes()
+    }
+
+    #[test]
+    fn basic_and_full_stat() {
+        let mut responder = responder(10);
+        let addr = "127.0.0.1:5000".parse().unwrap();
+        let now = Instant::now();
+        let token = challenge(&mut responder, addr, now);
+
+        let basic = responder
+            .handle(&request(TYPE_STAT, &token), addr, now)
+            .unwrap();
+        let fields: Vec<&[u8]> = basic[5..].split(|&b| b == 0).collect();
+        assert_eq!(fields[0], b"A Feather server");
+        assert_eq!(fields[2], b"world");
+        assert_eq!(fields[4], b"16");
+
+        let mut payload = token.to_vec();
+        payload.extend_from_slice(&[0; 4]);
+        let full = responder
+            .handle(&request(TYPE_STAT, &payload), addr, now)
+            .unwrap();
+        assert!(full.ends_with(b"player_\0\0caelunshun\0\0"));
+
+        // Other addresses can't reuse the token.
+        let other = "127.0.0.2:5000".parse().unwrap();
+        assert!(responder
+            .handle(&request(TYPE_STAT, &token), other, now)
+            .is_none());
+    }
+
+    #[test]
+    fn rate_limit() {
+        let mut responder = responder(2);
+        let addr = "127.0.0.1:5000".parse().unwrap();
+        let now = Instant::now();
+        challenge(&mut responder, addr, now);
+        challenge(&mut responder, addr, now);
+        assert!(responder
+            .handle(&request(TYPE_HANDSHAKE, &[]), addr, now)
+            .is_none());
+        challenge(&mut responder, addr, now + Duration::from_secs(1));
+    }
+}
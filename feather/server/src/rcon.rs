@@ -0,0 +1,215 @@
+//! A listener for the Source RCON protocol,
+//! which lets scripts run commands remotely.
+//!
+//! See <https://wiki.vg/RCON> for the protocol.
+
+use std::{net::SocketAddr, sync::Arc};
+
+use anyhow::{bail, Context};
+use flume::{Receiver, Sender};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::oneshot,
+    task::JoinHandle,
+};
+
+const SERVERDATA_RESPONSE_VALUE: i32 = 0;
+const SERVERDATA_EXECCOMMAND: i32 = 2;
+const SERVERDATA_AUTH_RESPONSE: i32 = 2;
+const SERVERDATA_AUTH: i32 = 3;
+
+/// Maximum length of a request packet.
+const MAX_REQUEST_LENGTH: i32 = 1460;
+/// Maximum length of the body of a response packet.
+/// Longer responses are split into several packets.
+const MAX_RESPONSE_BODY_LENGTH: usize = 4096;
+
+/// Options for the RCON listener.
+#[derive(Debug, Clone)]
+pub struct RconOptions {
+    pub port: u16,
+    pub password: String,
+}
+
+/// A command received over RCON, to be executed
+/// on the game thread.
+pub struct RconCommand {
+    pub command: String,
+    /// Receives the output of the command.
+    pub response: oneshot::Sender<String>,
+}
+
+/// Starts the RCON listener. Returns the receiver for commands
+/// and the task running the listener.
+pub async fn start(
+    bind_address: &str,
+    options: RconOptions,
+) -> anyhow::Result<(Receiver<RconCommand>, JoinHandle<()>)> {
+    let listener = TcpListener::bind(format!("{}:{}", bind_address, options.port))
+        .await
+        .context("failed to bind RCON port")?;
+    log::info!("RCON is listening on {}:{}", bind_address, options.port);
+
+    let (commands_tx, commands) = flume::unbounded();
+    let password = Arc::new(options.password);
+    let task = tokio::task::spawn(async move {
+        loop {
+            if let Ok((stream, addr)) = listener.accept().await {
+                let password = Arc::clone(&password);
+                let commands = commands_tx.clone();
+                tokio::task::spawn(async move {
+                    if let Err(e) = handle_connection(stream, addr, &password, commands).await {
+                        log::debug!("RCON connection from {} closed: {:?}", addr, e);
+                    }
+                });
+            }
+        }
+    });
+
+    Ok((commands, task))
+}
+
+struct Packet {
+    id: i32,
+    kind: i32,
+    body: String,
+}
+
+async fn handle_connection(
+    mut stream: TcpStream,
+    addr: SocketAddr,
+    password: &str,
+    commands: Sender<RconCommand>,
+) -> anyhow::Result<()> {
+    let mut authenticated = false;
+    loop {
+        let packet = read_packet(&mut stream).await?;
+        match packet.kind {
+            SERVERDATA_AUTH => {
+                authenticated = constant_time_eq(packet.body.as_bytes(), password.as_bytes());
+                let id = if authenticated {
+                    log::info!("RCON connection from {} authenticated", addr);
+                    packet.id
+                } else {
+                    log::warn!("RCON connection from {} used a wrong password", addr);
+                    -1
+                };
+                write_packet(&mut stream, id, SERVERDATA_AUTH_RESPONSE, "").await?;
+            }
+            SERVERDATA_EXECCOMMAND if authenticated => {
+                let (response_tx, response) = oneshot::channel();
+                commands
+                    .send_async(RconCommand {
+                        command: packet.body,
+                        response: response_tx,
+                    })
+                    .await?;
+                let output = response.await?;
+                write_response(&mut stream, packet.id, &output).await?;
+            }
+            SERVERDATA_EXECCOMMAND => {
+                write_packet(&mut stream, -1, SERVERDATA_AUTH_RESPONSE, "").await?;
+            }
+            kind => {
+                // Like vanilla, answer unknown requests
+                // instead of dropping the connection.
+                let message = format!("Unknown request {:x}", kind);
+                write_packet(&mut stream, packet.id, SERVERDATA_RESPONSE_VALUE, &message).await?;
+            }
+        }
+    }
+}
+
+async fn read_packet(stream: &mut TcpStream) -> anyhow::Result<Packet> {
+    let length = stream.read_i32_le().await?;
+    if !(10..=MAX_REQUEST_LENGTH).contains(&length) {
+        bail!("invalid packet length {}", length);
+    }
+    let id = stream.read_i32_le().await?;
+    let kind = stream.read_i32_le().await?;
+
+    // The body is followed by two null bytes.
+    let mut body = vec![0; length as usize - 8];
+    stream.read_exact(&mut body).await?;
+    body.truncate(body.len() - 2);
+    let body = String::from_utf8(body).context("body is not valid UTF-8")?;
+
+    Ok(Packet { id, kind, body })
+}
+
+async fn write_packet(
+    stream: &mut TcpStream,
+    id: i32,
+    kind: i32,
+    body: &str,
+) -> anyhow::Result<()> {
+    let mut buf = Vec::with_capacity(body.len() + 14);
+    buf.extend_from_slice(&(body.len() as i32 + 10).to_le_bytes());
+    buf.extend_from_slice(&id.to_le_bytes());
+    buf.extend_from_slice(&kind.to_le_bytes());
+    buf.extend_from_slice(body.as_bytes());
+    buf.extend_from_slice(&[0, 0]);
+    stream.write_all(&buf).await?;
+    Ok(())
+}
+
+async fn write_response(stream: &mut TcpStream, id: i32, output: &str) -> anyhow::Result<()> {
+    if output.is_empty() {
+        return write_packet(stream, id, SERVERDATA_RESPONSE_VALUE, "").await;
+    }
+    for chunk in split_response(output) {
+        write_packet(stream, id, SERVERDATA_RESPONSE_VALUE, chunk).await?;
+    }
+    Ok(())
+}
+
+/// Splits a response into bodies of at most [`MAX_RESPONSE_BODY_LENGTH`]
+/// bytes, without splitting characters.
+fn split_response(mut output: &str) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    while output.len() > MAX_RESPONSE_BODY_LENGTH {
+        let mut end = MAX_RESPONSE_BODY_LENGTH;
+        while !output.is_char_boundary(end) {
+            end -= 1;
+        }
+        let (chunk, rest) = output.split_at(end);
+        chunks.push(chunk);
+        output = rest;
+    }
+    chunks.push(output);
+    chunks
+}
+
+/// Compares two byte strings in a time that doesn't depend on where
+/// they differ, so the password can't be guessed from response times.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_long_responses() {
+        assert_eq!(split_response("hello"), vec!["hello"]);
+
+        let output = "é".repeat(MAX_RESPONSE_BODY_LENGTH);
+        let chunks = split_response(&output);
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks.iter().all(|c| c.len() <= MAX_RESPONSE_BODY_LENGTH));
+        assert_eq!(chunks.concat(), output);
+    }
+
+    #[test]
+    fn password_comparison() {
+        assert!(constant_time_eq(b"hunter2", b"hunter2"));
+        assert!(!constant_time_eq(b"hunter2", b"hunter3"));
+        assert!(!constant_time_eq(b"hunter2", b"hunter"));
+        assert!(constant_time_eq(b"", b""));
+    }
+}
@@ -0,0 +1,58 @@
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+/// Set when the server should shut down.
+#[derive(Clone, Default)]
+pub struct ShutdownFlag(Arc<AtomicBool>);
+
+impl ShutdownFlag {
+    pub fn request(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Requests a shutdown on SIGINT or SIGTERM.
+///
+/// A second signal exits immediately, in case saving hangs.
+///
+/// Must be called within the context of a Tokio runtime.
+pub fn listen_for_signals(flag: ShutdownFlag) {
+    tokio::task::spawn(async move {
+        if let Err(e) = wait_for_signal().await {
+            log::error!("Failed to listen for shutdown signals: {}", e);
+            return;
+        }
+        log::info!("Received shutdown signal");
+        flag.request();
+
+        if wait_for_signal().await.is_ok() {
+            log::warn!("Received second shutdown signal; exiting without saving");
+            std::process::exit(130);
+        }
+    });
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() -> io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = signal(SignalKind::terminate())?;
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result,
+        _ = terminate.recv() => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() -> io::Result<()> {
+    tokio::signal::ctrl_c().await
+}
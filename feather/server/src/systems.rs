@@ -1,41 +1,64 @@
 //! Systems linking a `Server` and a `Game`.
 
+mod access;
 mod block;
 mod chat;
+mod console;
 mod entity;
+mod game_rules;
+mod gamemode;
+mod health;
+mod inventory;
 mod particle;
 mod player_join;
 mod player_leave;
 mod plugin_message;
+mod query;
+mod rcon;
 mod tablist;
+mod time;
 pub mod view;
+mod weather;
 
 use std::time::{Duration, Instant};
 
+use base::Text;
 use common::Game;
 use ecs::{SysResult, SystemExecutor};
+use libcraft_text::TextValue;
 use quill_common::components::Name;
 
 use crate::{client::ClientId, Server};
 
 /// Registers systems for a `Server` with a `Game`.
 pub fn register(server: Server, game: &mut Game, systems: &mut SystemExecutor<Game>) {
+    access::register(game, &server, systems);
+    rcon::register(game, &server, systems);
     game.insert_resource(server);
 
     player_join::register(systems);
     systems
         .group::<Server>()
         .add_system(handle_packets)
-        .add_system(send_keepalives);
+        .add_system(send_keepalives)
+        .add_system(update_player_metrics);
     view::register(game, systems);
     crate::chunk_subscriptions::register(systems);
     player_leave::register(systems);
     tablist::register(systems);
+    gamemode::register(systems);
+    health::register(systems);
+    inventory::register(systems);
+    game_rules::register(systems);
+    time::register(systems);
+    weather::register(game, systems);
     block::register(systems);
     entity::register(game, systems);
-    chat::register(game, systems);
+    chat::register(systems);
+    console::register(game, systems);
     particle::register(systems);
     plugin_message::register(systems);
+    query::register(systems);
 
     systems.group::<Server>().add_system(tick_clients);
 }
@@ -66,12 +89,26 @@ fn handle_packets(game: &mut Game, server: &mut Server) -> SysResult {
     Ok(())
 }
 
-/// Sends out keepalive packets at an interval.
+/// Sends out keepalive packets at an interval
+/// and disconnects clients that stopped answering them.
 fn send_keepalives(_game: &mut Game, server: &mut Server) -> SysResult {
     let interval = Duration::from_secs(5);
-    if server.last_keepalive_time + interval < Instant::now() {
+    let now = Instant::now();
+    if server.last_keepalive_time + interval < now {
         server.broadcast_keepalive();
     }
+
+    for client in server.clients.iter() {
+        if !client.is_disconnected() && client.is_keepalive_timed_out(now) {
+            log::info!("{} timed out", client.username());
+            client.disconnect(Text::from(TextValue::translate("disconnect.timeout")));
+        }
+    }
+    Ok(())
+}
+
+fn update_player_metrics(_game: &mut Game, server: &mut Server) -> SysResult {
+    server.metrics.set_online_players(server.player_count());
     Ok(())
 }
 
@@ -0,0 +1,607 @@
+//! Enforces the access lists on online players and
+//! implements the commands which edit them.
+
+use std::{net::IpAddr, time::Duration};
+
+use base::Text;
+use common::{
+    commands::{
+        argument, literal, ArgumentKind, CommandContext, CommandDispatcher, PermissionLevel,
+        StringKind, MAX_PERMISSION_LEVEL,
+    },
+    events::PermissionLevelChangeEvent,
+    Game,
+};
+use ecs::{Entity, SysResult, SystemExecutor};
+use flume::{Receiver, Sender};
+use libcraft_text::{TextComponentBuilder, TextValue};
+use quill_common::{components::Name, entities::Player};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    access::{AccessLists, BanDetails, Profile},
+    initial_handler::offline_mode_uuid,
+    ClientId, Server,
+};
+
+/// Ticks between checks for changed list files.
+const RELOAD_INTERVAL: u64 = 40;
+
+/// Permission level required to edit the lists.
+const ADMIN_LEVEL: u8 = 3;
+
+/// Entity event which disconnects a player.
+struct KickEvent(Text);
+
+/// Finishes a command once the profile of its target is known.
+type ProfileCommand = fn(&mut Game, &CommandContext, Profile) -> SysResult;
+
+/// Resource receiving the results of Mojang profile
+/// lookups, which run outside the game thread.
+struct ProfileLookups {
+    sender: Sender<FinishedLookup>,
+    receiver: Receiver<FinishedLookup>,
+}
+
+struct FinishedLookup {
+    ctx: CommandContext,
+    name: String,
+    profile: anyhow::Result<Option<Profile>>,
+    then: ProfileCommand,
+}
+
+/// Marks the sender of a command which waits for a profile
+/// lookup. RCON keeps such senders until the command finishes.
+pub(crate) struct AwaitingProfile;
+
+pub fn register(game: &mut Game, server: &Server, systems: &mut SystemExecutor<Game>) {
+    game.insert_resource(server.access_lists().clone());
+    register_commands(
+        &mut *game
+            .resources
+            .get_mut::<CommandDispatcher>()
+            .expect("commands not registered"),
+        server.options.online_mode,
+    );
+
+    let (sender, receiver) = flume::unbounded();
+    game.insert_resource(ProfileLookups { sender, receiver });
+
+    systems
+        .add_system(reload_access_lists)
+        .add_system(finish_profile_lookups);
+    systems
+        .group::<Server>()
+        .add_system(kick_players)
+        .add_system(send_permission_levels);
+}
+
+/// Applies changes to the list files made while the server is running.
+fn reload_access_lists(game: &mut Game) -> SysResult {
+    if game.tick_count % RELOAD_INTERVAL != 0 {
+        return Ok(());
+    }
+    let lists = game.resources.get::<AccessLists>()?.clone();
+    if !lists.reload_changed() {
+        return Ok(());
+    }
+    log::info!("Reloaded changed access lists");
+
+    let mut kicks = Vec::new();
+    let mut level_changes = Vec::new();
+    for (player, (_, name, &uuid, &ip, level)) in game
+        .ecs
+        .query::<(&Player, &Name, &Uuid, &IpAddr, &PermissionLevel)>()
+        .iter()
+    {
+        let profile = Profile {
+            uuid,
+            name: name.to_string(),
+        };
+        if let Some(reason) = lists.check_login(&profile, ip) {
+            kicks.push((player, reason));
+        }
+        let new_level = lists.op_level(uuid);
+        if new_level != level.0 {
+            level_changes.push((player, new_level));
+        }
+    }
+
+    for (player, reason) in kicks {
+        game.ecs.insert_entity_event(player, KickEvent(reason))?;
+    }
+    for (player, level) in level_changes {
+        set_permission_level(game, player, level)?;
+    }
+    Ok(())
+}
+
+fn kick_players(game: &mut Game, server: &mut Server) -> SysResult {
+    for (_, (KickEvent(reason), &client_id, name)) in
+        game.ecs.query::<(&KickEvent, &ClientId, &Name)>().iter()
+    {
+        if let Some(client) = server.clients.get(client_id) {
+            log::info!("Kicking {}", name);
+            client.disconnect(reason.clone());
+        }
+    }
+    Ok(())
+}
+
+/// Tells players about changes to their permission level
+/// and sends them the commands they may now use.
+fn send_permission_levels(game: &mut Game, server: &mut Server) -> SysResult {
+    let dispatcher = game.resources.get::<CommandDispatcher>()?;
+    for (_, (_event, &client_id, level)) in game
+        .ecs
+        .query::<(&PermissionLevelChangeEvent, &ClientId, &PermissionLevel)>()
+        .iter()
+    {
+        if let Some(client) = server.clients.get(client_id) {
+            client.send_permission_level(level.0);
+            client.send_declare_commands(&dispatcher, level.0);
+        }
+    }
+    Ok(())
+}
+
+fn set_permission_level(game: &mut Game, player: Entity, level: u8) -> SysResult {
+    game.ecs.insert(player, PermissionLevel(level))?;
+    game.ecs
+        .insert_entity_event(player, PermissionLevelChangeEvent)?;
+    Ok(())
+}
+
+fn register_commands(dispatcher: &mut CommandDispatcher, online_mode: bool) {
+    let reason_argument = || argument("reason", ArgumentKind::Message);
+
+    dispatcher.register(
+        literal("ban").requires(ADMIN_LEVEL).then(
+            argument("target", ArgumentKind::GameProfile)
+                .executes(move |game, ctx| ban(game, ctx, online_mode))
+                .then(reason_argument().executes(move |game, ctx| ban(game, ctx, online_mode))),
+        ),
+    );
+    dispatcher.register(
+        literal("ban-ip").requires(ADMIN_LEVEL).then(
+            argument("target", ArgumentKind::String(StringKind::Word))
+                .executes(ban_ip)
+                .then(reason_argument().executes(ban_ip)),
+        ),
+    );
+    dispatcher.register(
+        literal("pardon")
+            .requires(ADMIN_LEVEL)
+            .then(argument("target", ArgumentKind::GameProfile).executes(pardon)),
+    );
+    dispatcher.register(
+        literal("pardon-ip")
+            .requires(ADMIN_LEVEL)
+            .then(argument("target", ArgumentKind::String(StringKind::Word)).executes(pardon_ip)),
+    );
+    dispatcher.register(
+        literal("banlist")
+            .requires(ADMIN_LEVEL)
+            .executes(|game, ctx| banlist(game, ctx, true, true))
+            .then(literal("players").executes(|game, ctx| banlist(game, ctx, true, false)))
+            .then(literal("ips").executes(|game, ctx| banlist(game, ctx, false, true))),
+    );
+
+    dispatcher.register(
+        literal("whitelist")
+            .requires(ADMIN_LEVEL)
+            .then(literal("on").executes(|game, ctx| set_whitelist_enabled(game, ctx, true)))
+            .then(literal("off").executes(|game, ctx| set_whitelist_enabled(game, ctx, false)))
+            .then(literal("list").executes(whitelist_list))
+            .then(literal("reload").executes(whitelist_reload))
+            .then(
+                literal("add").then(
+                    argument("target", ArgumentKind::GameProfile)
+                        .executes(move |game, ctx| whitelist_add(game, ctx, online_mode)),
+                ),
+            )
+            .then(
+                literal("remove")
+                    .then(argument("target", ArgumentKind::GameProfile).executes(whitelist_remove)),
+            ),
+    );
+
+    dispatcher.register(
+        literal("op").requires(ADMIN_LEVEL).then(
+            argument("target", ArgumentKind::GameProfile)
+                .executes(move |game, ctx| op(game, ctx, online_mode)),
+        ),
+    );
+    dispatcher.register(
+        literal("deop")
+            .requires(ADMIN_LEVEL)
+            .then(argument("target", ArgumentKind::GameProfile).executes(deop)),
+    );
+}
+
+fn translate(key: &str, args: Vec<String>) -> Text {
+    Text::translate_with(key.to_owned(), args)
+}
+
+fn failure(key: &str) -> Text {
+    Text::from(TextValue::translate(key.to_owned())).red()
+}
+
+fn access_lists(game: &Game) -> anyhow::Result<AccessLists> {
+    Ok(game.resources.get::<AccessLists>()?.clone())
+}
+
+/// Finds an online player by name.
+fn online_player(game: &Game, name: &str) -> Option<(Entity, Profile)> {
+    game.ecs
+        .query::<(&Player, &Name, &Uuid)>()
+        .iter()
+        .find(|(_, (_, player_name, _))| player_name.eq_ignore_ascii_case(name))
+        .map(|(player, (_, player_name, &uuid))| {
+            (
+                player,
+                Profile {
+                    uuid,
+                    name: player_name.to_string(),
+                },
+            )
+        })
+}
+
+/// Whether `name` can be the name of a Minecraft account.
+fn is_valid_username(name: &str) -> bool {
+    (1..=16).contains(&name.len())
+        && name
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || byte == b'_')
+}
+
+/// Finds the profile of the command's target, who need not be
+/// online, and finishes the command with it. Replies to the
+/// sender if the player doesn't exist.
+///
+/// In online mode, offline players are looked up with Mojang,
+/// like vanilla does. The lookup runs outside the game thread,
+/// so the command finishes on a later tick.
+fn with_target_profile(
+    game: &mut Game,
+    ctx: &CommandContext,
+    online_mode: bool,
+    then: ProfileCommand,
+) -> SysResult {
+    let name = ctx.string("target")?;
+    if let Some((_, profile)) = online_player(game, name) {
+        return then(game, ctx, profile);
+    }
+    if !is_valid_username(name) {
+        return ctx.reply(game, failure("argument.player.unknown"));
+    }
+    if !online_mode {
+        let profile = Profile {
+            uuid: offline_mode_uuid(name),
+            name: name.to_owned(),
+        };
+        return then(game, ctx, profile);
+    }
+
+    let finished = game.resources.get::<ProfileLookups>()?.sender.clone();
+    let ctx = ctx.clone();
+    let name = name.to_owned();
+    game.ecs.insert(ctx.sender, AwaitingProfile)?;
+    tokio::task::spawn_blocking(move || {
+        let profile = lookup_profile(&name);
+        let _ = finished.send(FinishedLookup {
+            ctx,
+            name,
+            profile,
+            then,
+        });
+    });
+    Ok(())
+}
+
+/// Looks up the profile of an account with Mojang. Blocks,
+/// so it must not run on the game thread.
+fn lookup_profile(name: &str) -> anyhow::Result<Option<Profile>> {
+    #[derive(Deserialize)]
+    struct MojangProfile {
+        id: Uuid,
+        name: String,
+    }
+
+    let url = format!("https://api.mojang.com/users/profiles/minecraft/{}", name);
+    let response = match ureq::get(&url).timeout(Duration::from_secs(5)).call() {
+        Ok(response) if response.status() == 200 => response,
+        // Mojang answers 204 or 404 for unknown names
+        Ok(_) | Err(ureq::Error::Status(..)) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let profile: MojangProfile = response.into_json()?;
+    Ok(Some(Profile {
+        uuid: profile.id,
+        name: profile.name,
+    }))
+}
+
+/// Finishes the commands whose profile lookup completed.
+fn finish_profile_lookups(game: &mut Game) -> SysResult {
+    let finished: Vec<FinishedLookup> = game
+        .resources
+        .get::<ProfileLookups>()?
+        .receiver
+        .try_iter()
+        .collect();
+
+    for lookup in finished {
+        let FinishedLookup {
+            ctx,
+            name,
+            profile,
+            then,
+        } = lookup;
+        let _ = game.ecs.remove::<AwaitingProfile>(ctx.sender);
+
+        let result = match profile {
+            Ok(Some(profile)) => then(game, &ctx, profile),
+            Ok(None) => ctx.reply(game, failure("argument.player.unknown")),
+            Err(e) => {
+                log::warn!("Failed to look up the profile of {}: {:?}", name, e);
+                ctx.reply(game, failure("argument.player.unknown"))
+            }
+        };
+        // The sender may have left in the meantime.
+        if let Err(e) = result {
+            log::debug!("Failed to finish /{}: {:?}", ctx.input, e);
+        }
+    }
+    Ok(())
+}
+
+fn reason(ctx: &CommandContext) -> anyhow::Result<Option<&str>> {
+    if ctx.has_argument("reason") {
+        Ok(Some(ctx.string("reason")?))
+    } else {
+        Ok(None)
+    }
+}
+
+fn ban(game: &mut Game, ctx: &CommandContext, online_mode: bool) -> SysResult {
+    with_target_profile(game, ctx, online_mode, ban_profile)
+}
+
+fn ban_profile(game: &mut Game, ctx: &CommandContext, profile: Profile) -> SysResult {
+    let details = BanDetails::new(&ctx.sender_name(game), reason(ctx)?);
+    let reason = details.reason.clone();
+    if !access_lists(game)?.ban_player(profile.clone(), details)? {
+        return ctx.reply(game, failure("commands.ban.failed"));
+    }
+
+    if let Some((player, _)) = online_player(game, &profile.name) {
+        let message = Text::from(TextValue::translate("multiplayer.disconnect.banned"));
+        game.ecs.insert_entity_event(player, KickEvent(message))?;
+    }
+    ctx.reply(
+        game,
+        translate("commands.ban.success", vec![profile.name, reason]),
+    )
+}
+
+fn ban_ip(game: &mut Game, ctx: &CommandContext) -> SysResult {
+    let target = ctx.string("target")?;
+    let ip = match target.parse::<IpAddr>() {
+        Ok(ip) => ip,
+        Err(_) => match online_player(game, target)
+            .and_then(|(player, _)| game.ecs.get::<IpAddr>(player).ok().map(|ip| *ip))
+        {
+            Some(ip) => ip,
+            None => return ctx.reply(game, failure("commands.banip.invalid")),
+        },
+    };
+    let details = BanDetails::new(&ctx.sender_name(game), reason(ctx)?);
+    let reason = details.reason.clone();
+    if !access_lists(game)?.ban_ip(ip, details)? {
+        return ctx.reply(game, failure("commands.banip.failed"));
+    }
+
+    let players: Vec<(Entity, String)> = game
+        .ecs
+        .query::<(&Player, &Name, &IpAddr)>()
+        .iter()
+        .filter(|(_, (_, _, &player_ip))| player_ip == ip)
+        .map(|(player, (_, name, _))| (player, name.to_string()))
+        .collect();
+    ctx.reply(
+        game,
+        translate("commands.banip.success", vec![ip.to_string(), reason]),
+    )?;
+    if !players.is_empty() {
+        let names: Vec<&str> = players.iter().map(|(_, name)| name.as_str()).collect();
+        ctx.reply(
+            game,
+            translate(
+                "commands.banip.info",
+                vec![players.len().to_string(), names.join(", ")],
+            ),
+        )?;
+    }
+    for (player, _) in players {
+        let message = Text::from(TextValue::translate("multiplayer.disconnect.ip_banned"));
+        game.ecs.insert_entity_event(player, KickEvent(message))?;
+    }
+    Ok(())
+}
+
+fn pardon(game: &mut Game, ctx: &CommandContext) -> SysResult {
+    let name = ctx.string("target")?.to_owned();
+    if access_lists(game)?.pardon_player(&name)? {
+        ctx.reply(game, translate("commands.pardon.success", vec![name]))
+    } else {
+        ctx.reply(game, failure("commands.pardon.failed"))
+    }
+}
+
+fn pardon_ip(game: &mut Game, ctx: &CommandContext) -> SysResult {
+    let ip = match ctx.string("target")?.parse::<IpAddr>() {
+        Ok(ip) => ip,
+        Err(_) => return ctx.reply(game, failure("commands.pardonip.invalid")),
+    };
+    if access_lists(game)?.pardon_ip(ip)? {
+        ctx.reply(
+            game,
+            translate("commands.pardonip.success", vec![ip.to_string()]),
+        )
+    } else {
+        ctx.reply(game, failure("commands.pardonip.failed"))
+    }
+}
+
+fn banlist(game: &mut Game, ctx: &CommandContext, players: bool, ips: bool) -> SysResult {
+    let lists = access_lists(game)?;
+    let mut entries = Vec::new();
+    if players {
+        for ban in lists.banned_players() {
+            entries.push((ban.profile.name, ban.details));
+        }
+    }
+    if ips {
+        for ban in lists.banned_ips() {
+            entries.push((ban.ip.to_string(), ban.details));
+        }
+    }
+
+    if entries.is_empty() {
+        return ctx.reply(
+            game,
+            Text::from(TextValue::translate("commands.banlist.none")),
+        );
+    }
+    ctx.reply(
+        game,
+        translate("commands.banlist.list", vec![entries.len().to_string()]),
+    )?;
+    for (target, details) in entries {
+        ctx.reply(
+            game,
+            translate(
+                "commands.banlist.entry",
+                vec![target, details.source, details.reason],
+            ),
+        )?;
+    }
+    Ok(())
+}
+
+fn set_whitelist_enabled(game: &mut Game, ctx: &CommandContext, enabled: bool) -> SysResult {
+    let lists = access_lists(game)?;
+    if lists.is_whitelist_enabled() == enabled {
+        let key = if enabled {
+            "commands.whitelist.alreadyOn"
+        } else {
+            "commands.whitelist.alreadyOff"
+        };
+        return ctx.reply(game, failure(key));
+    }
+
+    lists.set_whitelist_enabled(enabled);
+    let key = if enabled {
+        "commands.whitelist.enabled"
+    } else {
+        "commands.whitelist.disabled"
+    };
+    ctx.reply(game, Text::from(TextValue::translate(key)))
+}
+
+fn whitelist_list(game: &mut Game, ctx: &CommandContext) -> SysResult {
+    let names = access_lists(game)?.whitelisted_names();
+    if names.is_empty() {
+        return ctx.reply(
+            game,
+            Text::from(TextValue::translate("commands.whitelist.none")),
+        );
+    }
+    ctx.reply(
+        game,
+        translate(
+            "commands.whitelist.list",
+            vec![names.len().to_string(), names.join(", ")],
+        ),
+    )
+}
+
+fn whitelist_reload(game: &mut Game, ctx: &CommandContext) -> SysResult {
+    access_lists(game)?.reload_whitelist()?;
+    ctx.reply(
+        game,
+        Text::from(TextValue::translate("commands.whitelist.reloaded")),
+    )
+}
+
+fn whitelist_add(game: &mut Game, ctx: &CommandContext, online_mode: bool) -> SysResult {
+    with_target_profile(game, ctx, online_mode, whitelist_add_profile)
+}
+
+fn whitelist_add_profile(game: &mut Game, ctx: &CommandContext, profile: Profile) -> SysResult {
+    let name = profile.name.clone();
+    if access_lists(game)?.whitelist_add(profile)? {
+        ctx.reply(
+            game,
+            translate("commands.whitelist.add.success", vec![name]),
+        )
+    } else {
+        ctx.reply(game, failure("commands.whitelist.add.failed"))
+    }
+}
+
+fn whitelist_remove(game: &mut Game, ctx: &CommandContext) -> SysResult {
+    let name = ctx.string("target")?.to_owned();
+    if access_lists(game)?.whitelist_remove(&name)? {
+        ctx.reply(
+            game,
+            translate("commands.whitelist.remove.success", vec![name]),
+        )
+    } else {
+        ctx.reply(game, failure("commands.whitelist.remove.failed"))
+    }
+}
+
+fn op(game: &mut Game, ctx: &CommandContext, online_mode: bool) -> SysResult {
+    with_target_profile(game, ctx, online_mode, op_profile)
+}
+
+fn op_profile(game: &mut Game, ctx: &CommandContext, profile: Profile) -> SysResult {
+    let name = profile.name.clone();
+    if !access_lists(game)?.op(profile, MAX_PERMISSION_LEVEL)? {
+        return ctx.reply(game, failure("commands.op.failed"));
+    }
+    if let Some((player, _)) = online_player(game, &name) {
+        set_permission_level(game, player, MAX_PERMISSION_LEVEL)?;
+    }
+    ctx.reply(game, translate("commands.op.success", vec![name]))
+}
+
+fn deop(game: &mut Game, ctx: &CommandContext) -> SysResult {
+    let name = ctx.string("target")?.to_owned();
+    if access_lists(game)?.deop(&name)?.is_none() {
+        return ctx.reply(game, failure("commands.deop.failed"));
+    }
+    if let Some((player, _)) = online_player(game, &name) {
+        set_permission_level(game, player, 0)?;
+    }
+    ctx.reply(game, translate("commands.deop.success", vec![name]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn username_validation() {
+        assert!(is_valid_username("Notch"));
+        assert!(is_valid_username("a_b_1234567890XY"));
+        assert!(!is_valid_username(""));
+        assert!(!is_valid_username("seventeen_chars_x"));
+        assert!(!is_valid_username("../../users"));
+        assert!(!is_valid_username("Notch?at=0"));
+    }
+}
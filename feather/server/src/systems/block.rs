@@ -1,4 +1,5 @@
-//! Implements block change broadcasting.
+//! Implements block change and block break
+//! animation broadcasting.
 //!
 //! # Bulk updates
 //! The protocol provides three methods to change blocks
@@ -15,15 +16,19 @@
 
 use ahash::AHashMap;
 use base::{chunk::SECTION_VOLUME, position, ChunkPosition, CHUNK_WIDTH};
-use common::{events::BlockChangeEvent, Game};
+use common::{
+    events::{BlockBreakProgressEvent, BlockChangeEvent},
+    Game,
+};
 use ecs::{SysResult, SystemExecutor};
 
-use crate::Server;
+use crate::{NetworkId, Server};
 
 pub fn register(systems: &mut SystemExecutor<Game>) {
     systems
         .group::<Server>()
-        .add_system(broadcast_block_changes);
+        .add_system(broadcast_block_changes)
+        .add_system(broadcast_block_break_progress);
 }
 
 fn broadcast_block_changes(game: &mut Game, server: &mut Server) -> SysResult {
@@ -33,6 +38,19 @@ fn broadcast_block_changes(game: &mut Game, server: &mut Server) -> SysResult {
     Ok(())
 }
 
+fn broadcast_block_break_progress(game: &mut Game, server: &mut Server) -> SysResult {
+    for (_, (event, &network_id)) in game
+        .ecs
+        .query::<(&BlockBreakProgressEvent, &NetworkId)>()
+        .iter()
+    {
+        server.broadcast_nearby_with(event.position.position(), |client| {
+            client.send_block_break_animation(network_id, event.position, event.stage)
+        });
+    }
+    Ok(())
+}
+
 /// Threshold at which to switch from block change to chunk
 // overwrite packets.
 const CHUNK_OVERWRITE_THRESHOLD: usize = SECTION_VOLUME / 2;
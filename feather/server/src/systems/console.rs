@@ -0,0 +1,296 @@
+//! The console entity, which runs commands typed into
+//! the server console and logs the messages it receives.
+
+use base::{EntityKind, Text};
+use common::{
+    chat::ChatPreference,
+    commands::{self, PermissionLevel, MAX_PERMISSION_LEVEL},
+    ChatBox, Game,
+};
+use ecs::{EntityBuilder, SysResult, SystemExecutor};
+use serde_json::Value;
+
+use crate::console::ConsoleInput;
+
+/// Marker component for the console entity.
+pub struct Console;
+
+pub fn register(game: &mut Game, systems: &mut SystemExecutor<Game>) {
+    // Create the console entity so the console can receive messages
+    let mut console = EntityBuilder::new();
+    console
+        .add(Console)
+        .add(ChatBox::new(ChatPreference::All))
+        .add(PermissionLevel(MAX_PERMISSION_LEVEL));
+
+    // We can use the raw spawn method because
+    // the console isn't a "normal" entity.
+    game.ecs.spawn(console.build());
+
+    systems
+        .add_system(execute_console_commands)
+        .add_system(flush_console_chat_box);
+}
+
+/// Executes commands entered into the console.
+fn execute_console_commands(game: &mut Game) -> SysResult {
+    let lines: Vec<String> = match game.resources.get::<ConsoleInput>() {
+        Ok(input) => input.drain().collect(),
+        Err(_) => return Ok(()),
+    };
+
+    let console = match game.ecs.query::<&Console>().iter().next() {
+        Some((console, _)) => console,
+        None => return Ok(()),
+    };
+
+    for line in lines {
+        // Players need the slash, but the console doesn't.
+        let command = line.strip_prefix('/').unwrap_or(&line);
+        log::info!("Console issued server command: /{}", command);
+        commands::execute(game, console, command)?;
+    }
+
+    Ok(())
+}
+
+/// Prints chat messages to the console.
+fn flush_console_chat_box(game: &mut Game) -> SysResult {
+    for (_, (_console, mailbox)) in game.ecs.query::<(&Console, &mut ChatBox)>().iter() {
+        for message in mailbox.drain() {
+            log::info!("{}", plain_text(message.text())?);
+        }
+    }
+
+    Ok(())
+}
+
+/// Renders a text component as plain text,
+/// dropping colors and styles.
+pub(super) fn plain_text(text: &Text) -> anyhow::Result<String> {
+    let text = serde_json::to_value(text)?;
+    let mut plain = String::new();
+    write_plain_text(&text, &mut plain);
+    Ok(plain)
+}
+
+/// Renders a JSON text component as plain text,
+/// dropping colors and styles.
+fn write_plain_text(text: &Value, out: &mut String) {
+    match text {
+        Value::String(s) => out.push_str(s),
+        Value::Array(parts) => {
+            for part in parts {
+                write_plain_text(part, out);
+            }
+        }
+        Value::Object(component) => {
+            if let Some(Value::String(s)) = component.get("text") {
+                out.push_str(s);
+            }
+            if let Some(Value::String(key)) = component.get("translate") {
+                let args: Vec<String> = match component.get("with") {
+                    Some(Value::Array(args)) => args
+                        .iter()
+                        .map(|arg| {
+                            let mut s = String::new();
+                            write_plain_text(arg, &mut s);
+                            s
+                        })
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                write_translation(key, &args, out);
+            }
+            for key in &["selector", "keybind"] {
+                if let Some(Value::String(s)) = component.get(*key) {
+                    out.push_str(s);
+                }
+            }
+            if let Some(extra) = component.get("extra") {
+                write_plain_text(extra, out);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Formats a translation key with its arguments
+/// using the English text, if known.
+fn write_translation(key: &str, args: &[String], out: &mut String) {
+    let format = match english_translation(key) {
+        Some(format) => format,
+        None => {
+            out.push_str(key);
+            if !args.is_empty() {
+                out.push_str(&format!(" [{}]", args.join(", ")));
+            }
+            return;
+        }
+    };
+    write_format(format, args, out);
+}
+
+/// Substitutes `%s` and positional `%1$s` arguments into `format`.
+fn write_format(format: &str, args: &[String], out: &mut String) {
+    let mut next_arg = 0;
+    let mut rest = format;
+    while let Some(index) = rest.find('%') {
+        out.push_str(&rest[..index]);
+        rest = &rest[index + 1..];
+
+        if let Some(stripped) = rest.strip_prefix('%') {
+            out.push('%');
+            rest = stripped;
+        } else if let Some(stripped) = rest.strip_prefix('s') {
+            out.push_str(args.get(next_arg).map_or("", String::as_str));
+            next_arg += 1;
+            rest = stripped;
+        } else if let Some(dollar) = rest.find("$s") {
+            // Positional argument, e.g. `%2$s`
+            match rest[..dollar].parse::<usize>() {
+                Ok(position) if position > 0 => {
+                    out.push_str(args.get(position - 1).map_or("", String::as_str));
+                    rest = &rest[dollar + 2..];
+                }
+                _ => out.push('%'),
+            }
+        } else {
+            out.push('%');
+        }
+    }
+    out.push_str(rest);
+}
+
+fn english_translation(key: &str) -> Option<&'static str> {
+    Some(match key {
+        "chat.type.text" => "<%s> %s",
+        "chat.type.announcement" => "[%s] %s",
+        "chat.type.emote" => "* %s %s",
+        "multiplayer.player.joined" => "%s joined the game",
+        "multiplayer.player.left" => "%s left the game",
+        "commands.message.display.incoming" => "%s whispers to you: %s",
+        "commands.message.display.outgoing" => "You whisper to %s: %s",
+        "commands.gamemode.success.self" => "Set own game mode to %s",
+        "commands.gamemode.success.other" => "Set %s's game mode to %s",
+        "gameMode.changed" => "Your game mode has been updated to %s",
+        "gameMode.survival" => "Survival Mode",
+        "gameMode.creative" => "Creative Mode",
+        "gameMode.adventure" => "Adventure Mode",
+        "gameMode.spectator" => "Spectator Mode",
+        "commands.setblock.success" => "Changed the block at %s, %s, %s",
+        "commands.setblock.failed" => "Could not set the block",
+        "commands.gamerule.query" => "Gamerule %s is currently set to: %s",
+        "commands.gamerule.set" => "Gamerule %s is now set to: %s",
+        "commands.weather.set.clear" => "Set the weather to clear",
+        "commands.weather.set.rain" => "Set the weather to rain",
+        "commands.weather.set.thunder" => "Set the weather to rain & thunder",
+        "commands.kill.success.single" => "Killed %s",
+        "commands.kill.success.multiple" => "Killed %s entities",
+        "death.attack.generic" => "%1$s died",
+        "death.fell.accident.generic" => "%1$s fell from a high place",
+        "death.attack.onFire" => "%1$s burned to death",
+        "death.attack.lava" => "%1$s tried to swim in lava",
+        "death.attack.drown" => "%1$s drowned",
+        "death.attack.starve" => "%1$s starved to death",
+        "death.attack.outOfWorld" => "%1$s fell out of the world",
+        "death.attack.player" => "%1$s was slain by %2$s",
+        "death.attack.mob" => "%1$s was slain by %2$s",
+        "command.unknown.command" => "Unknown or incomplete command, see below for error",
+        "permissions.requires.player" => "A player is required to run this command here",
+        "permissions.requires.entity" => "An entity is required to run this command here",
+        "argument.entity.notfound.player" => "No player was found",
+        "argument.entity.notfound.entity" => "No entity was found",
+        "argument.player.unknown" => "That player does not exist",
+        "multiplayer.disconnect.banned" => "You are banned from this server",
+        "multiplayer.disconnect.ip_banned" => "You have been IP banned from this server",
+        "commands.ban.success" => "Banned %s: %s",
+        "commands.ban.failed" => "Nothing changed. The player is already banned",
+        "commands.banip.success" => "Banned IP %s: %s",
+        "commands.banip.info" => "This ban affects %s player(s): %s",
+        "commands.banip.failed" => "Nothing changed. That IP is already banned",
+        "commands.banip.invalid" => "Invalid IP address or unknown player",
+        "commands.pardon.success" => "Unbanned %s",
+        "commands.pardon.failed" => "Nothing changed. The player isn't banned",
+        "commands.pardonip.success" => "Unbanned IP %s",
+        "commands.pardonip.failed" => "Nothing changed. That IP isn't banned",
+        "commands.pardonip.invalid" => "Invalid IP address",
+        "commands.banlist.none" => "There are no bans",
+        "commands.banlist.list" => "There are %s ban(s):",
+        "commands.banlist.entry" => "%s was banned by %s: %s",
+        "commands.whitelist.add.success" => "Added %s to the whitelist",
+        "commands.whitelist.add.failed" => "Player is already whitelisted",
+        "commands.whitelist.remove.success" => "Removed %s from the whitelist",
+        "commands.whitelist.remove.failed" => "Player is not whitelisted",
+        "commands.whitelist.enabled" => "Whitelist is now turned on",
+        "commands.whitelist.alreadyOn" => "Whitelist is already turned on",
+        "commands.whitelist.disabled" => "Whitelist is now turned off",
+        "commands.whitelist.alreadyOff" => "Whitelist is already turned off",
+        "commands.whitelist.list" => "There are %s whitelisted players: %s",
+        "commands.whitelist.none" => "There are no whitelisted players",
+        "commands.whitelist.reloaded" => "Reloaded the whitelist",
+        "commands.op.success" => "Made %s a server operator",
+        "commands.op.failed" => "Nothing changed. The player already is an operator",
+        "commands.deop.success" => "Made %s no longer a server operator",
+        "commands.deop.failed" => "Nothing changed. The player is not an operator",
+        _ => match key
+            .strip_prefix("entity.minecraft.")
+            .and_then(EntityKind::from_name)
+        {
+            Some(kind) => kind.display_name(),
+            None => return None,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use base::Text;
+    use libcraft_text::TextValue;
+
+    use super::*;
+
+    fn plain(text: Text) -> String {
+        let mut out = String::new();
+        write_plain_text(&serde_json::to_value(&text).unwrap(), &mut out);
+        out
+    }
+
+    #[test]
+    fn plain_text_translations() {
+        let text = Text::translate_with("chat.type.text", vec!["caelunshun", "hello"]);
+        assert_eq!(plain(text), "<caelunshun> hello");
+
+        let text = Text::translate_with("some.unknown.key", vec!["a", "b"]);
+        assert_eq!(plain(text), "some.unknown.key [a, b]");
+    }
+
+    #[test]
+    fn death_messages() {
+        let text = Text::translate_with(
+            "death.attack.mob",
+            vec![
+                Text::from("caelunshun"),
+                Text::from(TextValue::translate("entity.minecraft.zombie")),
+            ],
+        );
+        assert_eq!(plain(text), "caelunshun was slain by Zombie");
+    }
+
+    #[test]
+    fn plain_text_concatenation() {
+        let text = Text::from("Hello, ") + Text::from("world");
+        assert_eq!(plain(text), "Hello, world");
+    }
+
+    #[test]
+    fn positional_arguments() {
+        let mut out = String::new();
+        write_format(
+            "%2$s, %1$s: 100%%",
+            &["a".to_owned(), "b".to_owned()],
+            &mut out,
+        );
+        assert_eq!(out, "b, a: 100%");
+    }
+}
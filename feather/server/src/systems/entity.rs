@@ -5,7 +5,10 @@ use base::{
     metadata::{EntityBitMask, Pose, META_INDEX_ENTITY_BITMASK, META_INDEX_POSE},
     EntityMetadata, Position,
 };
-use common::Game;
+use common::{
+    events::{ItemCollectEvent, ItemStackChangeEvent},
+    Game,
+};
 use ecs::{SysResult, SystemExecutor};
 use quill_common::{
     components::{OnGround, Sprinting},
@@ -13,17 +16,23 @@ use quill_common::{
 };
 
 use crate::{
-    entities::{PreviousOnGround, PreviousPosition},
+    entities::{self, PreviousOnGround, PreviousPosition},
     NetworkId, Server,
 };
 
 mod spawn_packet;
 
 pub fn register(game: &mut Game, systems: &mut SystemExecutor<Game>) {
+    // Collected items are removed in the same tick, so
+    // the animation has to be sent before they are unloaded.
+    systems
+        .group::<Server>()
+        .add_system(send_collect_item_animations);
     spawn_packet::register(game, systems);
     systems
         .group::<Server>()
         .add_system(send_entity_movement)
+        .add_system(send_item_stack_metadata)
         .add_system(send_entity_sneak_metadata)
         .add_system(send_entity_sprint_metadata);
 }
@@ -107,3 +116,33 @@ fn send_entity_sprint_metadata(game: &mut Game, server: &mut Server) -> SysResul
     }
     Ok(())
 }
+
+/// Sends the Collect Item animation when a player picks up an item.
+fn send_collect_item_animations(game: &mut Game, server: &mut Server) -> SysResult {
+    for (_, (event, &position, &network_id)) in game
+        .ecs
+        .query::<(&ItemCollectEvent, &Position, &NetworkId)>()
+        .iter()
+    {
+        let collector = *game.ecs.get::<NetworkId>(event.collector)?;
+        server.broadcast_nearby_with(position, |client| {
+            client.send_collect_item(network_id, collector, event.count);
+        });
+    }
+    Ok(())
+}
+
+/// Sends the new item of item entities whose stack changed.
+fn send_item_stack_metadata(game: &mut Game, server: &mut Server) -> SysResult {
+    for (entity, (_event, &position, &network_id)) in game
+        .ecs
+        .query::<(&ItemStackChangeEvent, &Position, &NetworkId)>()
+        .iter()
+    {
+        let metadata = entities::item_metadata(&game.ecs.entity(entity)?)?;
+        server.broadcast_nearby_with(position, |client| {
+            client.send_entity_metadata(network_id, metadata.clone());
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,42 @@
+//! Sends game rule changes to clients.
+
+use base::{anvil::level::LevelData, GameRules};
+use common::{events::GameRuleChangeEvent, Game};
+use ecs::{SysResult, SystemExecutor};
+
+use crate::Server;
+
+pub fn register(systems: &mut SystemExecutor<Game>) {
+    systems.group::<Server>().add_system(send_game_rule_changes);
+}
+
+fn send_game_rule_changes(game: &mut Game, server: &mut Server) -> SysResult {
+    let changed: Vec<String> = game
+        .ecs
+        .query::<&GameRuleChangeEvent>()
+        .iter()
+        .map(|(_, event)| event.rule.clone())
+        .collect();
+    if changed.is_empty() {
+        return Ok(());
+    }
+
+    let rules = game.resources.get::<GameRules>()?.clone();
+    for rule in changed {
+        match rule.as_str() {
+            "reducedDebugInfo" => server
+                .broadcast_with(|client| client.send_reduced_debug_info(rules.reduced_debug_info)),
+            "doImmediateRespawn" => server
+                .broadcast_with(|client| client.send_immediate_respawn(rules.do_immediate_respawn)),
+            "doDaylightCycle" => {
+                let level = game.resources.get::<LevelData>()?;
+                server.broadcast_with(|client| {
+                    client.send_time(level.time, level.day_time, rules.do_daylight_cycle)
+                });
+            }
+            _ => (),
+        }
+    }
+
+    Ok(())
+}
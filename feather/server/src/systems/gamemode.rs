@@ -0,0 +1,65 @@
+//! Sends gamemode changes to clients.
+
+use base::{anvil::player::PlayerAbilities, Gamemode};
+use common::{events::GamemodeEvent, Game};
+use ecs::{SysResult, SystemExecutor};
+use quill_common::components::{
+    CanBuild, CanCreativeFly, CreativeFlying, CreativeFlyingSpeed, Instabreak, Invulnerable,
+    WalkSpeed,
+};
+use uuid::Uuid;
+
+use crate::{ClientId, Server};
+
+pub fn register(systems: &mut SystemExecutor<Game>) {
+    systems.group::<Server>().add_system(send_gamemode_changes);
+}
+
+fn send_gamemode_changes(game: &mut Game, server: &mut Server) -> SysResult {
+    for (
+        _,
+        (
+            &GamemodeEvent(gamemode),
+            &client_id,
+            &uuid,
+            &walk_speed,
+            &fly_speed,
+            &may_fly,
+            &is_flying,
+            &may_build,
+            &instabreak,
+            &invulnerable,
+        ),
+    ) in game
+        .ecs
+        .query::<(
+            &GamemodeEvent,
+            &ClientId,
+            &Uuid,
+            &WalkSpeed,
+            &CreativeFlyingSpeed,
+            &CanCreativeFly,
+            &CreativeFlying,
+            &CanBuild,
+            &Instabreak,
+            &Invulnerable,
+        )>()
+        .iter()
+    {
+        if let Some(client) = server.clients.get(client_id) {
+            client.change_gamemode(gamemode);
+            client.send_abilities(&PlayerAbilities {
+                walk_speed,
+                fly_speed,
+                may_fly,
+                is_flying,
+                may_build,
+                instabreak,
+                invulnerable,
+            });
+        }
+
+        server.broadcast_with(|client| client.update_tablist_gamemode(uuid, gamemode));
+    }
+    Ok(())
+}
@@ -0,0 +1,202 @@
+//! Sends health, hurt and death effects and respawns to clients.
+
+use base::{
+    anvil::{level::LevelData, player::PlayerAbilities},
+    Gamemode, Position,
+};
+use common::{
+    commands::PermissionLevel,
+    damage::DamageSource,
+    entities::player::HotbarSlot,
+    events::{
+        EntityDamageEvent, EntityDeathEvent, HealthChangeEvent, KnockbackEvent, MeleeHitEvent,
+        PlayerRespawnEvent,
+    },
+    view::View,
+    Game, Window,
+};
+use ecs::{Entity, SysResult, SystemExecutor};
+use protocol::packets::server::Animation;
+use quill_common::components::{
+    CanBuild, CanCreativeFly, CreativeFlying, CreativeFlyingSpeed, Health, Instabreak,
+    Invulnerable, PreviousGamemode, WalkSpeed,
+};
+
+use crate::{
+    entities::{PreviousPosition, SpawnPacketSender},
+    ClientId, NetworkId, Server,
+};
+
+/// Entity status playing the hurt animation and sound.
+const STATUS_HURT: i8 = 2;
+
+/// Entity status playing the death animation.
+const STATUS_DEATH: i8 = 3;
+
+pub fn register(systems: &mut SystemExecutor<Game>) {
+    // Respawned clients reset their health, so it
+    // has to be sent after the respawn.
+    systems
+        .group::<Server>()
+        .add_system(send_respawns)
+        .add_system(send_health_updates)
+        .add_system(send_hurt_animations)
+        .add_system(send_critical_hits)
+        .add_system(send_knockback)
+        .add_system(send_deaths);
+}
+
+fn send_health_updates(game: &mut Game, server: &mut Server) -> SysResult {
+    for (_, (_event, health, &client_id)) in game
+        .ecs
+        .query::<(&HealthChangeEvent, &Health, &ClientId)>()
+        .iter()
+    {
+        if let Some(client) = server.clients.get(client_id) {
+            client.send_health(health.0);
+        }
+    }
+    Ok(())
+}
+
+fn send_hurt_animations(game: &mut Game, server: &mut Server) -> SysResult {
+    for (_, (_event, &position, &network_id)) in game
+        .ecs
+        .query::<(&EntityDamageEvent, &Position, &NetworkId)>()
+        .iter()
+    {
+        server.broadcast_nearby_with(position, |client| {
+            client.send_entity_status(network_id, STATUS_HURT)
+        });
+    }
+    Ok(())
+}
+
+fn send_critical_hits(game: &mut Game, server: &mut Server) -> SysResult {
+    for (_, (event, &position, &network_id)) in game
+        .ecs
+        .query::<(&MeleeHitEvent, &Position, &NetworkId)>()
+        .iter()
+    {
+        server.broadcast_nearby_with(position, |client| {
+            if event.critical {
+                client.send_entity_animation(network_id, Animation::CriticalEffect);
+            }
+            if event.enchanted {
+                client.send_entity_animation(network_id, Animation::MagicCriticalEffect);
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Sends the velocity of knocked back entities. Players
+/// only move when their own client receives it.
+fn send_knockback(game: &mut Game, server: &mut Server) -> SysResult {
+    for (_, (event, &position, &network_id)) in game
+        .ecs
+        .query::<(&KnockbackEvent, &Position, &NetworkId)>()
+        .iter()
+    {
+        server.broadcast_nearby_with(position, |client| {
+            client.send_entity_velocity(network_id, event.0)
+        });
+    }
+    Ok(())
+}
+
+/// Plays the death animation and shows
+/// dead players the death screen.
+fn send_deaths(game: &mut Game, server: &mut Server) -> SysResult {
+    for (player, (event, &position, &network_id)) in game
+        .ecs
+        .query::<(&EntityDeathEvent, &Position, &NetworkId)>()
+        .iter()
+    {
+        server.broadcast_nearby_with(position, |client| {
+            client.send_entity_status(network_id, STATUS_DEATH)
+        });
+
+        let client = match game.ecs.get::<ClientId>(player) {
+            Ok(client_id) => server.clients.get(*client_id),
+            Err(_) => None,
+        };
+        if let Some(client) = client {
+            let killer = match event.source {
+                DamageSource::Attack { attacker } => {
+                    game.ecs.get::<NetworkId>(attacker).ok().map(|id| *id)
+                }
+                _ => None,
+            };
+            client.send_death_screen(&event.message, killer);
+        }
+    }
+    Ok(())
+}
+
+/// Resets the client of a respawned player and sends
+/// it the entities around it again. Other clients
+/// get a fresh copy of the respawned player.
+fn send_respawns(game: &mut Game, server: &mut Server) -> SysResult {
+    let respawned: Vec<Entity> = game
+        .ecs
+        .query::<&PlayerRespawnEvent>()
+        .iter()
+        .map(|(player, _)| player)
+        .collect();
+    let level = game.resources.get::<LevelData>()?.clone();
+
+    for player in respawned {
+        let entity = game.ecs.entity(player)?;
+        let client = match server.clients.get(*entity.get::<ClientId>()?) {
+            Some(client) => client,
+            None => continue,
+        };
+        let position = *entity.get::<Position>()?;
+        let network_id = *entity.get::<NetworkId>()?;
+
+        client.send_respawn(
+            *entity.get::<Gamemode>()?,
+            *entity.get::<PreviousGamemode>()?,
+            &level,
+        );
+        client.send_abilities(&PlayerAbilities {
+            walk_speed: *entity.get::<WalkSpeed>()?,
+            fly_speed: *entity.get::<CreativeFlyingSpeed>()?,
+            may_fly: *entity.get::<CanCreativeFly>()?,
+            is_flying: *entity.get::<CreativeFlying>()?,
+            may_build: *entity.get::<CanBuild>()?,
+            instabreak: *entity.get::<Instabreak>()?,
+            invulnerable: *entity.get::<Invulnerable>()?,
+        });
+        client.send_permission_level(entity.get::<PermissionLevel>()?.0);
+        client.set_hotbar_slot(entity.get::<HotbarSlot>()?.get() as u8);
+        client.send_window_items(&*entity.get::<Window>()?);
+        client.update_own_position(position);
+
+        for chunk in entity.get::<View>()?.iter() {
+            for &other in game.chunk_entities.entities_in_chunk(chunk) {
+                if other == player {
+                    continue;
+                }
+                let other = game.ecs.entity(other)?;
+                if let Ok(spawn_packet) = other.get::<SpawnPacketSender>() {
+                    spawn_packet.send(&other, client)?;
+                }
+            }
+        }
+
+        let spawn_packet = entity.get::<SpawnPacketSender>()?;
+        for other_client in server.clients.iter() {
+            if other_client.network_id() != Some(network_id)
+                && other_client.is_entity_loaded(network_id)
+            {
+                other_client.unload_entity(network_id);
+                spawn_packet.send(&entity, other_client)?;
+            }
+        }
+        // Don't send the respawn as a relative movement.
+        entity.get_mut::<PreviousPosition>()?.0 = position;
+    }
+    Ok(())
+}
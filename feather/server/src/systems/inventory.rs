@@ -0,0 +1,25 @@
+//! Sends inventory changes made by the server to clients.
+
+use common::{events::InventoryUpdateEvent, Game, Window};
+use ecs::{SysResult, SystemExecutor};
+
+use crate::{ClientId, Server};
+
+pub fn register(systems: &mut SystemExecutor<Game>) {
+    systems.group::<Server>().add_system(send_inventory_updates);
+}
+
+fn send_inventory_updates(game: &mut Game, server: &mut Server) -> SysResult {
+    for (_, (InventoryUpdateEvent(slots), window, &client_id)) in game
+        .ecs
+        .query::<(&InventoryUpdateEvent, &Window, &ClientId)>()
+        .iter()
+    {
+        if let Some(client) = server.clients.get(client_id) {
+            for &slot in slots {
+                client.set_slot(slot as i16, &*window.item(slot)?);
+            }
+        }
+    }
+    Ok(())
+}
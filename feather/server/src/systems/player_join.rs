@@ -1,19 +1,27 @@
+use std::convert::TryFrom;
+
 use libcraft_items::InventorySlot;
 use log::debug;
 
-use base::anvil::player::PlayerAbilities;
-use base::{Gamemode, Inventory, ItemStack, Position, Text};
+use base::anvil::{level::LevelData, player::PlayerAbilities};
+use base::{
+    BlockPosition, GameRules, Gamemode, Inventory, ItemStack, Position, Text, ValidBlockPosition,
+};
 use common::{
     chat::{ChatKind, ChatPreference},
+    commands::{CommandDispatcher, PermissionLevel},
+    damage::{Dead, PLAYER_MAX_HEALTH},
     entities::player::HotbarSlot,
+    respawn::{self, RespawnPoint},
     view::View,
+    weather::WeatherLevels,
     window::BackingWindow,
     ChatBox, Game, Window,
 };
 use ecs::{SysResult, SystemExecutor};
 use quill_common::components::{
     CanBuild, CanCreativeFly, CreativeFlying, CreativeFlyingSpeed, Health, Instabreak,
-    Invulnerable, PreviousGamemode, WalkSpeed,
+    Invulnerable, Ping, PreviousGamemode, WalkSpeed,
 };
 use quill_common::{components::Name, entity_init::EntityInit};
 
@@ -34,6 +42,9 @@ fn poll_new_players(game: &mut Game, server: &mut Server) -> SysResult {
 
 fn accept_new_player(game: &mut Game, server: &mut Server, client_id: ClientId) -> SysResult {
     let client = server.clients.get_mut(client_id).unwrap();
+    let level = game.resources.get::<LevelData>()?.clone();
+    let rules = game.resources.get::<GameRules>()?.clone();
+    let spawn = respawn::world_spawn(&level);
     let player_data = game.world.load_player_data(client.uuid());
     let mut builder = game.create_entity_builder(
         player_data
@@ -45,7 +56,7 @@ fn accept_new_player(game: &mut Game, server: &mut Server, client_id: ClientId)
                 yaw: data.animal.base.rotation[0],
                 pitch: data.animal.base.rotation[1],
             })
-            .unwrap_or_default(),
+            .unwrap_or(spawn),
         EntityInit::Player,
     );
     client.set_network_id(*builder.get::<NetworkId>().unwrap());
@@ -62,8 +73,14 @@ fn accept_new_player(game: &mut Game, server: &mut Server, client_id: ClientId)
         .map(|data| PreviousGamemode::from_id(data.previous_gamemode as i8))
         .unwrap_or(PreviousGamemode(None));
 
-    client.send_join_game(gamemode, previous_gamemode);
+    client.send_join_game(gamemode, previous_gamemode, &level, &rules);
     client.send_brand();
+    client.send_spawn_position(spawn.block());
+    client.send_time(level.time, level.day_time, rules.do_daylight_cycle);
+    if level.raining {
+        client.set_raining(true);
+        client.send_weather_levels(*game.resources.get::<WeatherLevels>()?);
+    }
 
     // Abilities
     let abilities = player_abilities_or_default(
@@ -71,6 +88,12 @@ fn accept_new_player(game: &mut Game, server: &mut Server, client_id: ClientId)
         gamemode,
     );
     client.send_abilities(&abilities);
+    let permission_level = server.access_lists.op_level(client.uuid());
+    client.send_permission_level(permission_level);
+    client.send_declare_commands(
+        &*game.resources.get::<CommandDispatcher>()?,
+        permission_level,
+    );
 
     let hotbar_slot = player_data
         .as_ref()
@@ -102,6 +125,22 @@ fn accept_new_player(game: &mut Game, server: &mut Server, client_id: ClientId)
 
     client.send_window_items(&window);
 
+    let health = player_data
+        .as_ref()
+        .map(|data| data.animal.health)
+        .unwrap_or(PLAYER_MAX_HEALTH);
+    client.send_health(health);
+    if health <= 0.0 {
+        // The player left while dead, so show them the death screen again
+        builder.add(Dead::default());
+    }
+    if let Some(point) = player_data.as_ref().ok().and_then(|data| {
+        let position = BlockPosition::new(data.spawn_x?, data.spawn_y?, data.spawn_z?);
+        ValidBlockPosition::try_from(position).ok()
+    }) {
+        builder.add(RespawnPoint(point));
+    }
+
     builder
         .add(client_id)
         .add(View::new(
@@ -112,17 +151,15 @@ fn accept_new_player(game: &mut Game, server: &mut Server, client_id: ClientId)
         .add(previous_gamemode)
         .add(Name::new(client.username()))
         .add(client.uuid())
+        .add(client.ip())
         .add(client.profile().to_vec())
+        .add(PermissionLevel(permission_level))
+        .add(Ping::default())
         .add(ChatBox::new(ChatPreference::All))
         .add(inventory)
         .add(window)
         .add(hotbar_slot)
-        .add(Health(
-            player_data
-                .as_ref()
-                .map(|data| data.animal.health)
-                .unwrap_or(20.0),
-        ))
+        .add(Health(health))
         .add(abilities.walk_speed)
         .add(abilities.fly_speed)
         .add(abilities.is_flying)
@@ -0,0 +1,28 @@
+//! Keeps the data reported by the query listener up to date.
+
+use common::Game;
+use ecs::{SysResult, SystemExecutor};
+use quill_common::{components::Name, entities::Player};
+
+use crate::Server;
+
+/// Ticks between updates of the player list.
+const UPDATE_INTERVAL: u64 = 20;
+
+pub fn register(systems: &mut SystemExecutor<Game>) {
+    systems.group::<Server>().add_system(update_query_players);
+}
+
+fn update_query_players(game: &mut Game, server: &mut Server) -> SysResult {
+    if game.tick_count % UPDATE_INTERVAL != 0 {
+        return Ok(());
+    }
+    let players = game
+        .ecs
+        .query::<(&Player, &Name)>()
+        .iter()
+        .map(|(_, (_, name))| name.to_string())
+        .collect();
+    server.query_data().set_players(players);
+    Ok(())
+}
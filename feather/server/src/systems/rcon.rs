@@ -0,0 +1,97 @@
+//! Executes commands received over RCON.
+
+use common::{
+    chat::ChatPreference,
+    commands::{self, PermissionLevel, MAX_PERMISSION_LEVEL},
+    ChatBox, Game,
+};
+use ecs::{Entity, EntityBuilder, SysResult, SystemExecutor};
+use flume::Receiver;
+use quill_common::components::Name;
+use tokio::sync::oneshot;
+
+use crate::{rcon::RconCommand, Server};
+
+use super::access::AwaitingProfile;
+
+/// Resource holding the commands received by the RCON listener.
+struct RconCommands(Receiver<RconCommand>);
+
+/// Resource holding the senders of commands which
+/// finish on a later tick, like `/ban` of an offline player.
+#[derive(Default)]
+struct PendingRconCommands(Vec<(Entity, oneshot::Sender<String>)>);
+
+pub fn register(game: &mut Game, server: &Server, systems: &mut SystemExecutor<Game>) {
+    if let Some(commands) = &server.rcon_commands {
+        game.insert_resource(RconCommands(commands.clone()));
+        game.insert_resource(PendingRconCommands::default());
+        systems
+            .add_system(execute_rcon_commands)
+            .add_system(finish_pending_rcon_commands);
+    }
+}
+
+fn execute_rcon_commands(game: &mut Game) -> SysResult {
+    let received: Vec<RconCommand> = game.resources.get::<RconCommands>()?.0.try_iter().collect();
+
+    for RconCommand { command, response } in received {
+        log::info!("Rcon issued server command: /{}", command);
+
+        // Each command gets its own sender, so
+        // its output can be told apart.
+        let mut sender = EntityBuilder::new();
+        sender
+            .add(Name::new("Rcon"))
+            .add(ChatBox::new(ChatPreference::All))
+            .add(PermissionLevel(MAX_PERMISSION_LEVEL));
+        let sender = game.ecs.spawn(sender.build());
+
+        let command = command.strip_prefix('/').unwrap_or(&command);
+        if let Err(e) = commands::execute(game, sender, command) {
+            log::warn!("Failed to execute RCON command '{}': {:?}", command, e);
+        }
+
+        if game.ecs.get::<AwaitingProfile>(sender).is_ok() {
+            game.resources
+                .get_mut::<PendingRconCommands>()?
+                .0
+                .push((sender, response));
+        } else {
+            respond(game, sender, response)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn finish_pending_rcon_commands(game: &mut Game) -> SysResult {
+    let finished: Vec<(Entity, oneshot::Sender<String>)> = {
+        let mut pending = game.resources.get_mut::<PendingRconCommands>()?;
+        let (finished, waiting) = pending
+            .0
+            .drain(..)
+            .partition(|(sender, _)| game.ecs.get::<AwaitingProfile>(*sender).is_err());
+        pending.0 = waiting;
+        finished
+    };
+
+    for (sender, response) in finished {
+        respond(game, sender, response)?;
+    }
+    Ok(())
+}
+
+/// Sends the output of a command back over RCON
+/// and removes its sender.
+fn respond(game: &mut Game, sender: Entity, response: oneshot::Sender<String>) -> SysResult {
+    let mut output = Vec::new();
+    for message in game.ecs.get_mut::<ChatBox>(sender)?.drain() {
+        output.push(super::console::plain_text(message.text())?);
+    }
+    game.ecs.despawn(sender)?;
+
+    // The connection may have closed in the meantime.
+    let _ = response.send(output.join("\n"));
+    Ok(())
+}
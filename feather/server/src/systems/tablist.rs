@@ -6,16 +6,23 @@ use common::{
     Game,
 };
 use ecs::{SysResult, SystemExecutor};
-use quill_common::{components::Name, entities::Player};
+use quill_common::{
+    components::{Name, Ping},
+    entities::Player,
+};
 use uuid::Uuid;
 
 use crate::{ClientId, Server};
 
+/// Ticks between updates of the latency shown in the tablist.
+const PING_UPDATE_INTERVAL: u64 = 100;
+
 pub fn register(systems: &mut SystemExecutor<Game>) {
     systems
         .group::<Server>()
         .add_system(remove_tablist_players)
-        .add_system(add_tablist_players);
+        .add_system(add_tablist_players)
+        .add_system(update_tablist_pings);
 }
 
 fn remove_tablist_players(game: &mut Game, server: &mut Server) -> SysResult {
@@ -30,7 +37,7 @@ fn remove_tablist_players(game: &mut Game, server: &mut Server) -> SysResult {
 }
 
 fn add_tablist_players(game: &mut Game, server: &mut Server) -> SysResult {
-    for (player, (_, &client_id, &uuid, name, &gamemode, profile)) in game
+    for (player, (_, &client_id, &uuid, name, &gamemode, profile, &ping)) in game
         .ecs
         .query::<(
             &PlayerJoinEvent,
@@ -39,26 +46,44 @@ fn add_tablist_players(game: &mut Game, server: &mut Server) -> SysResult {
             &Name,
             &Gamemode,
             &Vec<ProfileProperty>,
+            &Ping,
         )>()
         .iter()
     {
         // Add this player to other players' tablists
         server.broadcast_with(|client| {
-            client.add_tablist_player(uuid, name.to_string(), profile, gamemode)
+            client.add_tablist_player(uuid, name.to_string(), profile, gamemode, ping.0)
         });
 
         // Add other players to this player's tablist
-        for (other_player, (&uuid, name, &gamemode, profile)) in game
+        for (other_player, (&uuid, name, &gamemode, profile, &ping)) in game
             .ecs
-            .query::<(&Uuid, &Name, &Gamemode, &Vec<ProfileProperty>)>()
+            .query::<(&Uuid, &Name, &Gamemode, &Vec<ProfileProperty>, &Ping)>()
             .iter()
         {
             if let Some(client) = server.clients.get(client_id) {
                 if other_player != player {
-                    client.add_tablist_player(uuid, name.to_string(), profile, gamemode);
+                    client.add_tablist_player(uuid, name.to_string(), profile, gamemode, ping.0);
                 }
             }
         }
     }
     Ok(())
 }
+
+fn update_tablist_pings(game: &mut Game, server: &mut Server) -> SysResult {
+    if game.tick_count % PING_UPDATE_INTERVAL != 0 {
+        return Ok(());
+    }
+
+    let pings: Vec<_> = game
+        .ecs
+        .query::<(&Uuid, &Ping)>()
+        .iter()
+        .map(|(_, (&uuid, &ping))| (uuid, ping.0))
+        .collect();
+    if !pings.is_empty() {
+        server.broadcast_with(|client| client.update_tablist_pings(&pings));
+    }
+    Ok(())
+}
@@ -0,0 +1,28 @@
+//! Sends the time of day to clients.
+
+use base::{anvil::level::LevelData, GameRules};
+use common::Game;
+use ecs::{SysResult, SystemExecutor};
+
+use crate::Server;
+
+/// Number of ticks between two time updates.
+/// Clients advance the time on their own in between.
+const TIME_UPDATE_INTERVAL: u64 = 20;
+
+pub fn register(systems: &mut SystemExecutor<Game>) {
+    systems.group::<Server>().add_system(send_time_updates);
+}
+
+fn send_time_updates(game: &mut Game, server: &mut Server) -> SysResult {
+    if game.tick_count % TIME_UPDATE_INTERVAL != 0 {
+        return Ok(());
+    }
+    let level = match game.resources.get::<LevelData>() {
+        Ok(level) => level,
+        Err(_) => return Ok(()),
+    };
+    let daylight_cycle = game.resources.get::<GameRules>()?.do_daylight_cycle;
+    server.broadcast_with(|client| client.send_time(level.time, level.day_time, daylight_cycle));
+    Ok(())
+}
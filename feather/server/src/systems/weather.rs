@@ -0,0 +1,46 @@
+//! Sends weather changes to clients.
+
+use common::{
+    events::WeatherChangeEvent,
+    weather::{Weather, WeatherLevels},
+    Game,
+};
+use ecs::{SysResult, SystemExecutor};
+
+use crate::Server;
+
+/// The weather levels last sent to clients.
+struct SentWeatherLevels(WeatherLevels);
+
+pub fn register(game: &mut Game, systems: &mut SystemExecutor<Game>) {
+    let levels = *game
+        .resources
+        .get::<WeatherLevels>()
+        .expect("weather not registered");
+    game.insert_resource(SentWeatherLevels(levels));
+    systems
+        .group::<Server>()
+        .add_system(send_weather_changes)
+        .add_system(send_weather_levels);
+}
+
+fn send_weather_changes(game: &mut Game, server: &mut Server) -> SysResult {
+    for (_, event) in game.ecs.query::<&WeatherChangeEvent>().iter() {
+        let was_raining = event.from != Weather::Clear;
+        let raining = event.to != Weather::Clear;
+        if was_raining != raining {
+            server.broadcast_with(|client| client.set_raining(raining));
+        }
+    }
+    Ok(())
+}
+
+fn send_weather_levels(game: &mut Game, server: &mut Server) -> SysResult {
+    let levels = *game.resources.get::<WeatherLevels>()?;
+    let mut sent = game.resources.get_mut::<SentWeatherLevels>()?;
+    if sent.0 != levels {
+        server.broadcast_with(|client| client.send_weather_levels(levels));
+        sent.0 = levels;
+    }
+    Ok(())
+}
@@ -0,0 +1,191 @@
+//! Limits how often and how many times addresses
+//! may connect, so a single address can't flood
+//! the server with connections or login attempts.
+
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use parking_lot::Mutex;
+
+/// Options for connection throttling. `None`
+/// disables the respective limit.
+#[derive(Debug, Clone, Default)]
+pub struct ThrottleOptions {
+    /// Minimum time between logins from the same address.
+    pub login_interval: Option<Duration>,
+    /// Maximum number of open connections per address.
+    pub max_connections_per_ip: Option<u32>,
+    /// Maximum number of handshakes per second
+    /// over all connections.
+    pub max_handshakes_per_second: Option<u32>,
+}
+
+/// Tracks connections to enforce [`ThrottleOptions`].
+///
+/// Can be cloned to create a new handle.
+#[derive(Clone)]
+pub struct Throttle {
+    options: ThrottleOptions,
+    inner: Arc<Mutex<Inner>>,
+}
+
+struct Inner {
+    connections: HashMap<IpAddr, u32>,
+    last_logins: HashMap<IpAddr, Instant>,
+    /// Start of the current one-second window and
+    /// the number of handshakes in it.
+    handshakes: (Instant, u32),
+}
+
+impl Throttle {
+    pub fn new(options: ThrottleOptions) -> Self {
+        Self {
+            options,
+            inner: Arc::new(Mutex::new(Inner {
+                connections: HashMap::new(),
+                last_logins: HashMap::new(),
+                handshakes: (Instant::now(), 0),
+            })),
+        }
+    }
+
+    /// Registers a new connection from an address. Returns `None`
+    /// if the address already has too many open connections.
+    ///
+    /// The connection counts as open until the returned
+    /// guard is dropped.
+    pub fn open_connection(&self, ip: IpAddr) -> Option<ConnectionGuard> {
+        let mut inner = self.inner.lock();
+        let connections = inner.connections.entry(ip).or_insert(0);
+        if let Some(max) = self.options.max_connections_per_ip {
+            if *connections >= max {
+                log::info!(
+                    "Throttled connection from {}: it already has {} open connections",
+                    ip,
+                    connections
+                );
+                return None;
+            }
+        }
+        *connections += 1;
+        Some(ConnectionGuard {
+            throttle: self.clone(),
+            ip,
+        })
+    }
+
+    /// Counts a handshake. Returns `false` if there have
+    /// been too many in the last second.
+    pub fn allow_handshake(&self, ip: IpAddr, now: Instant) -> bool {
+        let max = match self.options.max_handshakes_per_second {
+            Some(max) => max,
+            None => return true,
+        };
+        let mut inner = self.inner.lock();
+        let (window, count) = &mut inner.handshakes;
+        if now.duration_since(*window) >= Duration::from_secs(1) {
+            *window = now;
+            *count = 0;
+        }
+        *count += 1;
+        if *count > max {
+            log::info!(
+                "Throttled handshake from {}: more than {} handshakes in the last second",
+                ip,
+                max
+            );
+            return false;
+        }
+        true
+    }
+
+    /// Counts a login attempt. Returns `false` if the address
+    /// attempted to log in too recently.
+    pub fn allow_login(&self, ip: IpAddr, now: Instant) -> bool {
+        let interval = match self.options.login_interval {
+            Some(interval) => interval,
+            None => return true,
+        };
+        let mut inner = self.inner.lock();
+        inner
+            .last_logins
+            .retain(|_, last| now.duration_since(*last) < interval);
+        if inner.last_logins.contains_key(&ip) {
+            log::info!(
+                "Throttled login from {}: it attempted to log in less than {:?} ago",
+                ip,
+                interval
+            );
+            return false;
+        }
+        inner.last_logins.insert(ip, now);
+        true
+    }
+}
+
+/// Keeps a connection counted as open. See [`Throttle::open_connection`].
+pub struct ConnectionGuard {
+    throttle: Throttle,
+    ip: IpAddr,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        let mut inner = self.throttle.inner.lock();
+        if let Some(connections) = inner.connections.get_mut(&self.ip) {
+            *connections -= 1;
+            if *connections == 0 {
+                inner.connections.remove(&self.ip);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn throttle() -> Throttle {
+        Throttle::new(ThrottleOptions {
+            login_interval: Some(Duration::from_secs(4)),
+            max_connections_per_ip: Some(2),
+            max_handshakes_per_second: Some(3),
+        })
+    }
+
+    #[test]
+    fn connections_per_ip() {
+        let throttle = throttle();
+        let ip = "127.0.0.1".parse().unwrap();
+        let first = throttle.open_connection(ip).unwrap();
+        let _second = throttle.open_connection(ip).unwrap();
+        assert!(throttle.open_connection(ip).is_none());
+        assert!(throttle
+            .open_connection("127.0.0.2".parse().unwrap())
+            .is_some());
+
+        drop(first);
+        assert!(throttle.open_connection(ip).is_some());
+    }
+
+    #[test]
+    fn handshakes_and_logins() {
+        let throttle = throttle();
+        let ip = "127.0.0.1".parse().unwrap();
+        let now = Instant::now();
+        for _ in 0..3 {
+            assert!(throttle.allow_handshake(ip, now));
+        }
+        assert!(!throttle.allow_handshake(ip, now));
+        assert!(throttle.allow_handshake(ip, now + Duration::from_secs(1)));
+
+        assert!(throttle.allow_login(ip, now));
+        assert!(!throttle.allow_login(ip, now + Duration::from_secs(1)));
+        assert!(throttle.allow_login("127.0.0.2".parse().unwrap(), now));
+        assert!(throttle.allow_login(ip, now + Duration::from_secs(4)));
+    }
+}
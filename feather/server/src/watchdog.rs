@@ -0,0 +1,513 @@
+//! Detects ticks which take far too long, e.g. because
+//! a plugin or chunk operation hangs, and logs what
+//! the game thread is doing.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How often the watchdog checks on the game thread.
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Time between requesting a shutdown after the hard timeout
+/// and exiting the process if the tick still hasn't finished.
+const FORCE_EXIT_DELAY: Duration = Duration::from_secs(30);
+
+/// Options for the [`Watchdog`].
+#[derive(Debug, Clone)]
+pub struct WatchdogOptions {
+    /// Time without a finished tick after which the stall is
+    /// logged. Logged again each time this much time passes.
+    pub warn_after: Duration,
+    /// Time without a finished tick after which the server
+    /// shuts down, or `None` to wait indefinitely.
+    ///
+    /// The world can only be saved from the game thread, so it's
+    /// saved once the stalled tick finishes. If it still hasn't
+    /// finished 30 seconds later, the process exits without saving.
+    pub shutdown_after: Option<Duration>,
+}
+
+/// Handle to the watchdog thread. Call [`tick`](Self::tick)
+/// at the end of each tick.
+pub struct Watchdog {
+    state: Arc<State>,
+}
+
+struct State {
+    start: Instant,
+    /// Milliseconds since `start` at which the last tick finished.
+    last_tick: AtomicU64,
+    ticks: AtomicU64,
+    stopped: AtomicBool,
+}
+
+impl State {
+    fn since_last_tick(&self) -> Duration {
+        let last_tick = Duration::from_millis(self.last_tick.load(Ordering::Relaxed));
+        self.start.elapsed().saturating_sub(last_tick)
+    }
+}
+
+impl Watchdog {
+    /// Spawns the watchdog thread.
+    ///
+    /// Must be called on the game thread, which is the thread
+    /// the watchdog captures backtraces of. `current_system` and
+    /// `system_names` come from the game's `SystemExecutor`.
+    /// `on_hard_timeout` is invoked once the tick has been stalled
+    /// for `options.shutdown_after`; it should request a shutdown,
+    /// which saves the world if the tick eventually finishes.
+    pub fn spawn(
+        options: WatchdogOptions,
+        current_system: Arc<AtomicUsize>,
+        system_names: Vec<String>,
+        on_hard_timeout: impl FnOnce() + Send + 'static,
+    ) -> Self {
+        let state = Arc::new(State {
+            start: Instant::now(),
+            last_tick: AtomicU64::new(0),
+            ticks: AtomicU64::new(0),
+            stopped: AtomicBool::new(false),
+        });
+
+        let game_thread = game_thread::GameThread::current();
+        let watcher = Watcher {
+            options,
+            state: Arc::clone(&state),
+            current_system,
+            system_names,
+            game_thread,
+        };
+        thread::Builder::new()
+            .name("watchdog".to_owned())
+            .spawn(move || watcher.run(on_hard_timeout))
+            .expect("failed to spawn watchdog thread");
+
+        Self { state }
+    }
+
+    /// Notes that a tick has finished.
+    pub fn tick(&self) {
+        let millis = self.state.start.elapsed().as_millis() as u64;
+        self.state.last_tick.store(millis, Ordering::Relaxed);
+        self.state.ticks.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Stops the watchdog, e.g. before saving the
+    /// world on shutdown, which may take longer than a tick.
+    pub fn stop(&self) {
+        self.state.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+struct Watcher {
+    options: WatchdogOptions,
+    state: Arc<State>,
+    current_system: Arc<AtomicUsize>,
+    system_names: Vec<String>,
+    game_thread: game_thread::GameThread,
+}
+
+impl Watcher {
+    fn run(self, on_hard_timeout: impl FnOnce()) {
+        let mut on_hard_timeout = Some(on_hard_timeout);
+        let mut detector = StallDetector::new(self.options.clone());
+
+        loop {
+            thread::sleep(CHECK_INTERVAL);
+            if self.state.stopped.load(Ordering::Relaxed) {
+                return;
+            }
+
+            let ticks = self.state.ticks.load(Ordering::Relaxed);
+            let stalled_for = self.state.since_last_tick();
+            let check = detector.check(ticks, stalled_for);
+
+            if check.recovered {
+                log::info!("The server has recovered from a stalled tick");
+            }
+            if check.warn {
+                self.report(stalled_for);
+            }
+            if check.hard_timeout {
+                if let Some(on_hard_timeout) = on_hard_timeout.take() {
+                    log::error!(
+                        "A tick has been running for {}s; shutting down the server. \
+                         The world is saved once the tick finishes, but if it doesn't \
+                         finish within {}s, the server exits without saving",
+                        stalled_for.as_secs(),
+                        FORCE_EXIT_DELAY.as_secs()
+                    );
+                    on_hard_timeout();
+                }
+            }
+            if check.force_exit {
+                log::error!(
+                    "The tick never finished; exiting without saving. \
+                     Changes since the last autosave are lost"
+                );
+                std::process::exit(1);
+            }
+        }
+    }
+
+    fn report(&self, stalled_for: Duration) {
+        let system = match self.current_system.load(Ordering::Relaxed) {
+            usize::MAX => "none".to_owned(),
+            index => self
+                .system_names
+                .get(index)
+                .cloned()
+                .unwrap_or_else(|| format!("#{}", index)),
+        };
+        let backtrace = match self.game_thread.capture() {
+            Some(backtrace) => backtrace,
+            None => "unavailable".to_owned(),
+        };
+        log::warn!(
+            "The server has not finished a tick for {}s. Running system: {}. Game thread backtrace:\n{}",
+            stalled_for.as_secs(),
+            system,
+            backtrace
+        );
+    }
+}
+
+/// What the watchdog should do after checking on the game thread.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Check {
+    /// A tick finished after a stall was reported.
+    recovered: bool,
+    /// The stall should be reported.
+    warn: bool,
+    /// The server should shut down.
+    hard_timeout: bool,
+    /// The process should exit without saving.
+    force_exit: bool,
+}
+
+/// Decides when a stalled tick is reported and given up on.
+struct StallDetector {
+    options: WatchdogOptions,
+    last_ticks: u64,
+    next_warning: Duration,
+    stalled: bool,
+    timed_out: bool,
+}
+
+impl StallDetector {
+    fn new(options: WatchdogOptions) -> Self {
+        let next_warning = options.warn_after;
+        Self {
+            options,
+            last_ticks: 0,
+            next_warning,
+            stalled: false,
+            timed_out: false,
+        }
+    }
+
+    /// Checks on the game thread, given the number of ticks
+    /// finished so far and the time since the last one finished.
+    fn check(&mut self, ticks: u64, stalled_for: Duration) -> Check {
+        let mut check = Check::default();
+        if ticks != self.last_ticks {
+            check.recovered = self.stalled;
+            self.last_ticks = ticks;
+            self.next_warning = self.options.warn_after;
+            self.stalled = false;
+            return check;
+        }
+
+        if stalled_for >= self.next_warning {
+            self.stalled = true;
+            self.next_warning += self.options.warn_after;
+            check.warn = true;
+        }
+
+        if let Some(shutdown_after) = self.options.shutdown_after {
+            if stalled_for >= shutdown_after && !self.timed_out {
+                self.timed_out = true;
+                check.hard_timeout = true;
+            }
+            check.force_exit = stalled_for >= shutdown_after + FORCE_EXIT_DELAY;
+        }
+        check
+    }
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+mod game_thread {
+    //! Captures backtraces of the game thread from the watchdog thread.
+    //!
+    //! The watchdog sends a signal to the game thread. Its handler may
+    //! only do async-signal-safe work, so instead of unwinding it follows
+    //! the chain of frame pointers from the interrupted registers and
+    //! stores the return addresses in a static buffer. Each read is checked
+    //! to lie within the game thread's stack, so the walk ends early rather
+    //! than faulting when it reaches code built without frame pointers;
+    //! build with `-C force-frame-pointers=yes` for complete backtraces.
+    //! The watchdog then resolves the addresses to symbols.
+
+    use std::{
+        fmt::Write,
+        mem, ptr,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Once,
+        },
+        thread,
+        time::{Duration, Instant},
+    };
+
+    const SIGNAL: libc::c_int = libc::SIGUSR2;
+    const MAX_FRAMES: usize = 128;
+
+    /// Time to wait for the game thread to run the signal handler.
+    const CAPTURE_TIMEOUT: Duration = Duration::from_secs(1);
+
+    #[allow(clippy::declare_interior_mutable_const)]
+    const NO_FRAME: AtomicUsize = AtomicUsize::new(0);
+    static FRAMES: [AtomicUsize; MAX_FRAMES] = [NO_FRAME; MAX_FRAMES];
+    static FRAME_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    /// Generation of the latest capture the watchdog requested, and of
+    /// the latest one the handler completed. A handler that runs late,
+    /// after the watchdog gave up waiting for it, then can't be mistaken
+    /// for the next capture or overwrite frames while they're resolved.
+    static REQUESTED: AtomicUsize = AtomicUsize::new(0);
+    static CAPTURED: AtomicUsize = AtomicUsize::new(0);
+
+    /// Bounds of the game thread's stack. Both are zero
+    /// if unknown, in which case only the interrupted
+    /// instruction is recorded.
+    static STACK_LOW: AtomicUsize = AtomicUsize::new(0);
+    static STACK_HIGH: AtomicUsize = AtomicUsize::new(0);
+
+    static INSTALL_HANDLER: Once = Once::new();
+
+    pub struct GameThread {
+        thread: libc::pthread_t,
+    }
+
+    impl GameThread {
+        pub fn current() -> Self {
+            INSTALL_HANDLER.call_once(|| unsafe {
+                let mut action: libc::sigaction = mem::zeroed();
+                action.sa_sigaction = handle_signal as usize;
+                action.sa_flags = libc::SA_SIGINFO | libc::SA_RESTART;
+                libc::sigemptyset(&mut action.sa_mask);
+                libc::sigaction(SIGNAL, &action, ptr::null_mut());
+            });
+            let thread = unsafe { libc::pthread_self() };
+            record_stack_bounds(thread);
+            Self { thread }
+        }
+
+        /// Captures and symbolizes a backtrace of the game thread.
+        pub fn capture(&self) -> Option<String> {
+            let generation = REQUESTED.fetch_add(1, Ordering::SeqCst).wrapping_add(1);
+            if unsafe { libc::pthread_kill(self.thread, SIGNAL) } != 0 {
+                return None;
+            }
+
+            let start = Instant::now();
+            while CAPTURED.load(Ordering::SeqCst) != generation {
+                if start.elapsed() > CAPTURE_TIMEOUT {
+                    return None;
+                }
+                thread::sleep(Duration::from_millis(1));
+            }
+
+            let mut backtrace = String::new();
+            let count = FRAME_COUNT.load(Ordering::SeqCst);
+            for (i, frame) in FRAMES[..count].iter().enumerate() {
+                let ip = frame.load(Ordering::SeqCst);
+                let mut resolved = false;
+                backtrace::resolve(ip as *mut _, |symbol| {
+                    resolved = true;
+                    let name = symbol
+                        .name()
+                        .map(|name| name.to_string())
+                        .unwrap_or_else(|| "<unknown>".to_owned());
+                    let _ = write!(backtrace, "{:4}: {}", i, name);
+                    if let (Some(file), Some(line)) = (symbol.filename(), symbol.lineno()) {
+                        let _ = write!(backtrace, "\n          at {}:{}", file.display(), line);
+                    }
+                    backtrace.push('\n');
+                });
+                if !resolved {
+                    let _ = writeln!(backtrace, "{:4}: {:#x}", i, ip);
+                }
+            }
+            Some(backtrace)
+        }
+    }
+
+    /// Records the bounds of the thread's stack,
+    /// which the signal handler must stay within.
+    fn record_stack_bounds(thread: libc::pthread_t) {
+        unsafe {
+            let mut attr: libc::pthread_attr_t = mem::zeroed();
+            if libc::pthread_getattr_np(thread, &mut attr) != 0 {
+                return;
+            }
+            let mut addr = ptr::null_mut();
+            let mut size = 0;
+            if libc::pthread_attr_getstack(&attr, &mut addr, &mut size) == 0 {
+                STACK_LOW.store(addr as usize, Ordering::SeqCst);
+                STACK_HIGH.store(addr as usize + size, Ordering::SeqCst);
+            }
+            libc::pthread_attr_destroy(&mut attr);
+        }
+    }
+
+    extern "C" fn handle_signal(
+        _: libc::c_int,
+        _: *mut libc::siginfo_t,
+        context: *mut libc::c_void,
+    ) {
+        // SAFETY: the kernel passes a valid `ucontext_t` to handlers
+        // installed with `SA_SIGINFO`. A frame is only read after checking
+        // that it's aligned and lies within the game thread's stack, which
+        // stays mapped while the thread is interrupted. Apart from those
+        // reads, the handler only uses atomics, which are async-signal-safe.
+        let generation = REQUESTED.load(Ordering::SeqCst);
+        if CAPTURED.load(Ordering::SeqCst) == generation {
+            // A late signal for a capture that has already
+            // been served; its frames may be being resolved.
+            return;
+        }
+
+        let (pc, mut fp) = unsafe { registers(context as *const libc::ucontext_t) };
+        let low = STACK_LOW.load(Ordering::SeqCst);
+        let high = STACK_HIGH.load(Ordering::SeqCst);
+        let frame_size = 2 * mem::size_of::<usize>();
+
+        FRAMES[0].store(pc, Ordering::SeqCst);
+        let mut count = 1;
+        while count < MAX_FRAMES
+            && fp >= low
+            && fp % mem::align_of::<usize>() == 0
+            && fp.checked_add(frame_size).map_or(false, |end| end <= high)
+        {
+            // Each frame starts with the caller's frame
+            // pointer, followed by the return address.
+            let frame = fp as *const usize;
+            let (caller_fp, return_address) = unsafe { (*frame, *frame.add(1)) };
+            if return_address == 0 {
+                break;
+            }
+            FRAMES[count].store(return_address, Ordering::SeqCst);
+            count += 1;
+
+            // The stack grows down, so callers' frames are at
+            // higher addresses. Anything else isn't a frame chain.
+            if caller_fp <= fp {
+                break;
+            }
+            fp = caller_fp;
+        }
+        FRAME_COUNT.store(count, Ordering::SeqCst);
+        CAPTURED.store(generation, Ordering::SeqCst);
+    }
+
+    /// Returns the instruction and frame pointers of the interrupted code.
+    #[cfg(target_arch = "x86_64")]
+    unsafe fn registers(context: *const libc::ucontext_t) -> (usize, usize) {
+        let registers = &(*context).uc_mcontext.gregs;
+        (
+            registers[libc::REG_RIP as usize] as usize,
+            registers[libc::REG_RBP as usize] as usize,
+        )
+    }
+
+    /// Returns the instruction and frame pointers of the interrupted code.
+    #[cfg(target_arch = "aarch64")]
+    unsafe fn registers(context: *const libc::ucontext_t) -> (usize, usize) {
+        let context = &(*context).uc_mcontext;
+        (context.pc as usize, context.regs[29] as usize)
+    }
+}
+
+#[cfg(not(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+)))]
+mod game_thread {
+    pub struct GameThread;
+
+    impl GameThread {
+        pub fn current() -> Self {
+            GameThread
+        }
+
+        /// Backtraces of other threads are only supported
+        /// on Linux for x86-64 and AArch64.
+        pub fn capture(&self) -> Option<String> {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn warns_repeatedly_until_recovered() {
+        let mut detector = StallDetector::new(WatchdogOptions {
+            warn_after: secs(10),
+            shutdown_after: None,
+        });
+
+        assert_eq!(detector.check(1, secs(0)), Check::default());
+        assert_eq!(detector.check(1, secs(9)), Check::default());
+        let warning = Check {
+            warn: true,
+            ..Default::default()
+        };
+        assert_eq!(detector.check(1, secs(10)), warning);
+        assert_eq!(detector.check(1, secs(11)), Check::default());
+        assert_eq!(detector.check(1, secs(20)), warning);
+        assert_eq!(detector.check(1, secs(1000)), warning);
+
+        assert_eq!(
+            detector.check(2, secs(0)),
+            Check {
+                recovered: true,
+                ..Default::default()
+            }
+        );
+        assert_eq!(detector.check(3, secs(0)), Check::default());
+        assert_eq!(detector.check(3, secs(10)), warning);
+    }
+
+    #[test]
+    fn shuts_down_then_exits() {
+        let mut detector = StallDetector::new(WatchdogOptions {
+            warn_after: secs(10),
+            shutdown_after: Some(secs(60)),
+        });
+
+        assert!(!detector.check(1, secs(59)).hard_timeout);
+        assert!(detector.check(1, secs(60)).hard_timeout);
+        let check = detector.check(1, secs(61));
+        assert!(!check.hard_timeout);
+        assert!(!check.force_exit);
+        assert!(detector.check(1, secs(60) + FORCE_EXIT_DELAY).force_exit);
+    }
+}
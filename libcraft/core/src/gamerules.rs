@@ -1,44 +1,46 @@
 //! Data sourced from: <https://minecraft.gamepedia.com/Game_rule>
 
+use std::fmt;
+
 use serde::{Deserialize, Serialize};
 
 /// All game rules.
-#[derive(Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GameRules {
-    announce_advancements: bool,
-    command_block_output: bool,
-    disable_elytra_movement_check: bool,
-    disable_raids: bool,
-    do_daylight_cycle: bool,
-    do_entity_drops: bool,
-    do_fire_tick: bool,
-    do_insomnia: bool,
-    do_immediate_respawn: bool,
-    do_limited_crafting: bool,
-    do_mob_loot: bool,
-    do_mob_spawning: bool,
-    do_patrol_spawning: bool,
-    do_tile_drops: bool,
-    do_trader_spawning: bool,
-    do_weather_cycle: bool,
-    drowning_damage: bool,
-    fall_damage: bool,
-    fire_damage: bool,
-    forgive_dead_players: bool,
-    keep_inventory: bool,
-    log_admin_commands: bool,
-    max_command_chain_length: u32,
-    max_entity_cramming: u32,
-    mob_griefing: bool,
-    natural_regeneration: bool,
-    random_tick_speed: u32,
-    reduced_debug_info: bool,
-    send_command_feedback: bool,
-    show_death_messages: bool,
-    spawn_radius: u32,
-    spectators_generate_chunks: bool,
-    universal_anger: bool,
+    pub announce_advancements: bool,
+    pub command_block_output: bool,
+    pub disable_elytra_movement_check: bool,
+    pub disable_raids: bool,
+    pub do_daylight_cycle: bool,
+    pub do_entity_drops: bool,
+    pub do_fire_tick: bool,
+    pub do_insomnia: bool,
+    pub do_immediate_respawn: bool,
+    pub do_limited_crafting: bool,
+    pub do_mob_loot: bool,
+    pub do_mob_spawning: bool,
+    pub do_patrol_spawning: bool,
+    pub do_tile_drops: bool,
+    pub do_trader_spawning: bool,
+    pub do_weather_cycle: bool,
+    pub drowning_damage: bool,
+    pub fall_damage: bool,
+    pub fire_damage: bool,
+    pub forgive_dead_players: bool,
+    pub keep_inventory: bool,
+    pub log_admin_commands: bool,
+    pub max_command_chain_length: u32,
+    pub max_entity_cramming: u32,
+    pub mob_griefing: bool,
+    pub natural_regeneration: bool,
+    pub random_tick_speed: u32,
+    pub reduced_debug_info: bool,
+    pub send_command_feedback: bool,
+    pub show_death_messages: bool,
+    pub spawn_radius: u32,
+    pub spectators_generate_chunks: bool,
+    pub universal_anger: bool,
 }
 
 impl Default for GameRules {
@@ -80,3 +82,121 @@ impl Default for GameRules {
         }
     }
 }
+
+/// An error returned when setting a game rule by name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GameRuleError {
+    UnknownRule(String),
+    InvalidValue { rule: String, value: String },
+}
+
+impl fmt::Display for GameRuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameRuleError::UnknownRule(rule) => write!(f, "unknown game rule '{}'", rule),
+            GameRuleError::InvalidValue { rule, value } => {
+                write!(f, "invalid value '{}' for game rule '{}'", value, rule)
+            }
+        }
+    }
+}
+
+impl std::error::Error for GameRuleError {}
+
+/// Implements access to game rules by their vanilla names,
+/// which are used in level.dat and the `/gamerule` command.
+macro_rules! game_rule_names {
+    ($($field:ident: $name:literal),* $(,)?) => {
+        impl GameRules {
+            /// The names of all game rules.
+            pub const NAMES: &'static [&'static str] = &[$($name),*];
+
+            /// Gets the value of a game rule formatted as a string,
+            /// or `None` if there is no rule with the given name.
+            pub fn get(&self, name: &str) -> Option<String> {
+                match name {
+                    $($name => Some(self.$field.to_string()),)*
+                    _ => None,
+                }
+            }
+
+            /// Parses and sets the value of a game rule.
+            pub fn set(&mut self, name: &str, value: &str) -> Result<(), GameRuleError> {
+                match name {
+                    $($name => {
+                        self.$field = value.parse().map_err(|_| GameRuleError::InvalidValue {
+                            rule: name.to_owned(),
+                            value: value.to_owned(),
+                        })?;
+                        Ok(())
+                    })*
+                    _ => Err(GameRuleError::UnknownRule(name.to_owned())),
+                }
+            }
+        }
+    };
+}
+
+game_rule_names! {
+    announce_advancements: "announceAdvancements",
+    command_block_output: "commandBlockOutput",
+    disable_elytra_movement_check: "disableElytraMovementCheck",
+    disable_raids: "disableRaids",
+    do_daylight_cycle: "doDaylightCycle",
+    do_entity_drops: "doEntityDrops",
+    do_fire_tick: "doFireTick",
+    do_insomnia: "doInsomnia",
+    do_immediate_respawn: "doImmediateRespawn",
+    do_limited_crafting: "doLimitedCrafting",
+    do_mob_loot: "doMobLoot",
+    do_mob_spawning: "doMobSpawning",
+    do_patrol_spawning: "doPatrolSpawning",
+    do_tile_drops: "doTileDrops",
+    do_trader_spawning: "doTraderSpawning",
+    do_weather_cycle: "doWeatherCycle",
+    drowning_damage: "drowningDamage",
+    fall_damage: "fallDamage",
+    fire_damage: "fireDamage",
+    forgive_dead_players: "forgiveDeadPlayers",
+    keep_inventory: "keepInventory",
+    log_admin_commands: "logAdminCommands",
+    max_command_chain_length: "maxCommandChainLength",
+    max_entity_cramming: "maxEntityCramming",
+    mob_griefing: "mobGriefing",
+    natural_regeneration: "naturalRegeneration",
+    random_tick_speed: "randomTickSpeed",
+    reduced_debug_info: "reducedDebugInfo",
+    send_command_feedback: "sendCommandFeedback",
+    show_death_messages: "showDeathMessages",
+    spawn_radius: "spawnRadius",
+    spectators_generate_chunks: "spectatorsGenerateChunks",
+    universal_anger: "universalAnger",
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_and_set_by_name() {
+        let mut rules = GameRules::default();
+        assert_eq!(rules.get("keepInventory").as_deref(), Some("false"));
+
+        rules.set("keepInventory", "true").unwrap();
+        assert!(rules.keep_inventory);
+        rules.set("randomTickSpeed", "10").unwrap();
+        assert_eq!(rules.random_tick_speed, 10);
+
+        assert!(rules.set("keepInventory", "10").is_err());
+        assert!(rules.set("notARule", "true").is_err());
+        assert_eq!(rules.get("notARule"), None);
+    }
+
+    #[test]
+    fn names_match_fields() {
+        let rules = GameRules::default();
+        for name in GameRules::NAMES {
+            assert!(rules.get(name).is_some());
+        }
+    }
+}
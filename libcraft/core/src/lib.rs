@@ -16,7 +16,7 @@ pub use consts::*;
 pub use dimension::Dimension;
 pub use entity::EntityKind;
 pub use gamemode::Gamemode;
-pub use gamerules::GameRules;
+pub use gamerules::{GameRuleError, GameRules};
 pub use interaction::InteractionType;
 pub use player::Hand;
 pub use positions::{
@@ -1,7 +1,7 @@
 #![forbid(unsafe_code)]
 #![deny(warnings)]
 
-use crate::{Enchantment, Item};
+use crate::{Enchantment, EnchantmentKind, Item};
 use core::fmt::Display;
 use serde::{Deserialize, Serialize};
 use std::convert::TryInto;
@@ -307,6 +307,13 @@ impl ItemStack {
         self.meta.as_ref().map_or(Some(0), |meta| meta.damage)
     }
 
+    /// Returns the enchantments applied to this `ItemStack`.
+    pub fn enchantments(&self) -> &[Enchantment] {
+        self.meta
+            .as_ref()
+            .map_or(&[], |meta| meta.enchantments.as_slice())
+    }
+
     /// Returns true is the contents of other could be merged with the contents
     /// of self. This does not look at the item count, just the kind.
     /// Items can be merged when they have the same kind, damage, and enchantment.
@@ -424,6 +431,14 @@ impl ItemStackBuilder {
         self
     }
 
+    pub fn enchantment(mut self, kind: EnchantmentKind, level: u32) -> Self {
+        let mut meta = self.meta.unwrap_or_default();
+        meta.enchantments.push(Enchantment::new(kind, level));
+
+        self.meta = Some(meta);
+        self
+    }
+
     /// If damage is some, then its value is applied, else this is a no-op.
     pub fn apply_damage(self, damage: Option<i32>) -> Self {
         match damage {
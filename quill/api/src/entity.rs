@@ -107,6 +107,16 @@ impl Entity {
         }
     }
 
+    /// Determines whether this entity has the given
+    /// permission node, e.g. `myplugin.command.home`.
+    ///
+    /// Nodes are granted in the server's `permissions.toml`.
+    pub fn has_permission(&self, node: &str) -> bool {
+        unsafe {
+            quill_sys::entity_has_permission(self.id.0, node.as_ptr().into(), node.len() as u32)
+        }
+    }
+
     /// Hides the currently visible title for this entity, will do nothing if the there's no title
     pub fn hide_title(&self) {
         self.send_title(&libcraft_text::title::Title::HIDE);
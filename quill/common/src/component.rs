@@ -198,6 +198,7 @@ host_component_enum! {
         CanBuild = 1020,
         Instabreak = 1021,
         Invulnerable = 1022,
+        Ping = 1023,
 
 
     }
@@ -3,7 +3,7 @@
 //! See the [entities module](crate::entities) for entity-specific
 //! components.
 
-use std::fmt::Display;
+use std::{fmt::Display, time::Duration};
 
 use serde::{Deserialize, Serialize};
 use smartstring::{LazyCompact, SmartString};
@@ -284,3 +284,21 @@ impl Sprinting {
     }
 }
 bincode_component_impl!(Sprinting);
+
+/// A player's latency, measured as the round-trip
+/// time of keepalive packets.
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    derive_more::Deref,
+    derive_more::DerefMut,
+)]
+pub struct Ping(pub Duration);
+bincode_component_impl!(Ping);
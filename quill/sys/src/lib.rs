@@ -99,6 +99,11 @@ extern "C" {
     /// Does nothing if the entity does not exist or if it does not have the `Chat` component.
     pub fn entity_send_title(entity: EntityId, title_ptr: Pointer<u8>, title_len: u32);
 
+    /// Determines whether an entity has a permission node.
+    ///
+    /// Returns `false` if the entity does not exist.
+    pub fn entity_has_permission(entity: EntityId, node_ptr: Pointer<u8>, node_len: u32) -> bool;
+
     /// Creates an empty entity builder.
     ///
     /// This builder is used for creating an ecs-entity